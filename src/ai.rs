@@ -0,0 +1,228 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Color, Component, Entity, EventReader, IntoSystemDescriptor, Mesh, Query, Res, ResMut, Resource, Transform, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, LockedAxes, QueryFilter, RapierContext, RigidBody, Velocity};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::floating_origin::{add_origin_shifted, OriginShifted};
+use crate::navmesh::NavMesh;
+
+/// Capsule shaped agents that wander around on their own, steering away from anything a shape
+/// cast ahead of them hits, so there's something moving in the world besides the player. The
+/// wandering comes from a seeded [AiRng], so with fixed time the agents do the same thing every
+/// run. When there's a [NavMesh], agents walk paths across it to random points on it instead of
+/// wandering.
+///
+/// [AiConfig::agent_count] agents are spawned on startup, the `spawn_agents` console command
+/// spawns more.
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<AiConfig>() {
+            app.insert_resource(AiConfig::default());
+        }
+        let seed = app.world.resource::<AiConfig>().seed;
+        add_origin_shifted(app);
+        app
+            .insert_resource(AiRng(StdRng::seed_from_u64(seed)))
+            .add_startup_system(|world: &mut World| {
+                let count = world.resource::<AiConfig>().agent_count;
+                spawn_agents(world, count);
+            })
+            .add_system(shift_agent_paths)
+            .add_system(steer_agents.after(shift_agent_paths))
+            .add_console_command("spawn_agents", "spawns the given number of agents, or one", |world, args| {
+                let count = match args.first() {
+                    Some(count) => match count.parse::<usize>() {
+                        Ok(count) => count,
+                        Err(_) => {
+                            console_print(world, "usage: spawn_agents [count]");
+                            return;
+                        }
+                    },
+                    None => 1
+                };
+                spawn_agents(world, count);
+                console_print(world, format!("spawned {} agents", count));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct AiConfig {
+    pub agent_count: usize,
+    pub seed: u64,
+    /// Agents spawn randomly within this distance of [AiConfig::spawn_center]
+    pub spawn_radius: f32,
+    pub spawn_center: Vec3,
+    pub radius: f32,
+    /// Length of the cylinder between the capsule's caps
+    pub height: f32,
+    pub max_speed: f32,
+    /// Upper limit on how quickly an agent's velocity can change, in units per second squared
+    pub max_force: f32,
+    /// How far ahead the wander circle is
+    pub wander_distance: f32,
+    pub wander_radius: f32,
+    /// How far the point on the wander circle moves each second, in radians
+    pub wander_jitter: f32,
+    /// How far ahead obstacles are looked for, at full speed
+    pub avoid_distance: f32,
+    pub avoid_strength: f32,
+    /// How close to a waypoint of its path an agent has to get to move on to the next
+    pub waypoint_distance: f32
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self {
+            agent_count: 6,
+            seed: 0,
+            spawn_radius: 12.0,
+            spawn_center: Vec3::new(0.0, 2.0, 0.0),
+            radius: 0.3,
+            height: 1.0,
+            max_speed: 2.5,
+            max_force: 4.0,
+            wander_distance: 2.0,
+            wander_radius: 1.0,
+            wander_jitter: 3.0,
+            avoid_distance: 3.0,
+            avoid_strength: 8.0,
+            waypoint_distance: 0.75
+        }
+    }
+}
+
+/// The randomness behind spawning and wandering, replace it to reseed
+#[derive(Resource)]
+pub struct AiRng(pub StdRng);
+
+#[derive(Component)]
+pub struct Agent {
+    /// Horizontal velocity the agent is steering with
+    pub velocity: Vec3,
+    /// Position on the wander circle, in radians
+    pub wander_angle: f32,
+    /// Waypoints left on the [NavMesh], the next first
+    pub path: Vec<Vec3>
+}
+
+/// Spawns `count` agents around [AiConfig::spawn_center]
+pub fn spawn_agents(world: &mut World, count: usize) {
+    let config = world.resource::<AiConfig>().clone();
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape::Capsule {
+        radius: config.radius,
+        depth: config.height,
+        ..default()
+    }.into());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(Color::rgb(0.3, 0.5, 0.9).into());
+
+    for _ in 0..count {
+        let (offset, heading) = {
+            let rng = &mut world.resource_mut::<AiRng>().0;
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let distance = config.spawn_radius * rng.gen::<f32>().sqrt();
+            (Vec3::new(angle.cos(), 0.0, angle.sin()) * distance, rng.gen_range(0.0..std::f32::consts::TAU))
+        };
+        world.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(config.spawn_center + offset),
+                ..default()
+            },
+            RigidBody::Dynamic,
+            Collider::capsule_y(config.height / 2.0, config.radius),
+            // upright, and turned by steering rather than by bumping into things
+            LockedAxes::ROTATION_LOCKED,
+            Velocity::zero(),
+            Agent {
+                velocity: Vec3::new(heading.cos(), 0.0, heading.sin()) * config.max_speed * 0.5,
+                wander_angle: 0.0,
+                path: Vec::new()
+            }
+        ));
+    }
+}
+
+/// Moves the waypoints along with the world when the origin moves
+fn shift_agent_paths(mut shifted: EventReader<OriginShifted>, mut agents: Query<&mut Agent>) {
+    let shift = shifted.iter().map(|shifted| shifted.shift).sum::<Vec3>();
+    if shift == Vec3::ZERO {
+        return;
+    }
+    for mut agent in &mut agents {
+        for waypoint in &mut agent.path {
+            *waypoint += shift;
+        }
+    }
+}
+
+fn steer_agents(
+    time: Res<Time>,
+    config: Res<AiConfig>,
+    mut rng: ResMut<AiRng>,
+    rapier_context: Res<RapierContext>,
+    navmesh: Option<Res<NavMesh>>,
+    mut agents: Query<(Entity, &mut Agent, &mut Transform, &mut Velocity)>
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+    let probe = Collider::ball(config.radius);
+    for (entity, mut agent, mut transform, mut velocity) in &mut agents {
+        let heading = agent.velocity.normalize_or_zero();
+        let heading = if heading == Vec3::ZERO { transform.forward() } else { heading };
+
+        if let Some(navmesh) = &navmesh {
+            if agent.path.is_empty() {
+                if let Some(destination) = navmesh.random_point(&mut rng.0) {
+                    agent.path = navmesh.find_path(transform.translation, destination).unwrap_or_default();
+                }
+            }
+            let position = transform.translation;
+            if let Some(waypoint) = agent.path.first() {
+                if Vec3::new(waypoint.x - position.x, 0.0, waypoint.z - position.z).length() < config.waypoint_distance {
+                    agent.path.remove(0);
+                }
+            }
+        }
+        let desired = match agent.path.first() {
+            Some(waypoint) => Vec3::new(waypoint.x - transform.translation.x, 0.0, waypoint.z - transform.translation.z),
+            None => {
+                agent.wander_angle += rng.0.gen_range(-1.0..=1.0) * config.wander_jitter * delta;
+                heading * config.wander_distance
+                    + Vec3::new(agent.wander_angle.cos(), 0.0, agent.wander_angle.sin()) * config.wander_radius
+            }
+        };
+        let mut steering = desired.normalize_or_zero() * config.max_speed - agent.velocity;
+
+        // looks further ahead the faster it goes
+        let look_ahead = config.avoid_distance * (agent.velocity.length() / config.max_speed).max(0.25);
+        let filter = QueryFilter::default().exclude_sensors().exclude_rigid_body(entity);
+        if let Some((_, toi)) = rapier_context.cast_shape(transform.translation, Quat::IDENTITY, heading, &probe, look_ahead, filter) {
+            let away = Vec3::new(toi.normal1.x, 0.0, toi.normal1.z).normalize_or_zero();
+            // straight into a wall the normal is useless for picking a side, so turn right
+            let away = if away.dot(heading) < -0.99 { heading.cross(Vec3::Y) } else { away };
+            let urgency = 1.0 - toi.toi / look_ahead;
+            steering += away * config.avoid_strength * urgency;
+        }
+
+        let steering = steering.clamp_length_max(config.max_force * delta);
+        agent.velocity = (agent.velocity + steering).clamp_length_max(config.max_speed);
+        // gravity keeps its say over falling
+        velocity.linvel = Vec3::new(agent.velocity.x, velocity.linvel.y, agent.velocity.z);
+        if agent.velocity.length_squared() > 0.01 {
+            let target = transform.translation + agent.velocity;
+            transform.look_at(Vec3::new(target.x, transform.translation.y, target.z), Vec3::Y);
+        }
+    }
+}
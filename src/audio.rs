@@ -0,0 +1,390 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, AssetServer, Handle};
+use bevy::audio::{Audio, AudioSink, AudioSource, PlaybackSettings};
+use bevy::math::Vec3;
+use bevy::prelude::{Commands, Component, Entity, EventReader, GlobalTransform, IntoSystemDescriptor, Query, Res, ResMut, Resource, Transform, TransformBundle, With, Without};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{ActiveEvents, CollisionEvent, QueryFilter, RapierContext, RigidBody, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::RenderTime;
+use crate::free_control::ActiveControl;
+use crate::game_state::LoadingAssets;
+use crate::settings::AddSetting;
+
+/// Audible feedback for physics, impact sounds for dynamic bodies colliding (louder the faster
+/// they hit) and footstep sounds for entities with [Footsteps] walking on the ground.
+///
+/// Sounds are played where they happen, as [SpatialSound]s heard by the [AudioListener], which is
+/// kept on the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]. They get quieter
+/// with distance from it (see [AudioFeedbackConfig::reference_distance]), and louder or quieter
+/// while they play as it moves. Bevy's audio can't pan sounds between speakers yet, so which side a
+/// sound comes from isn't heard.
+///
+/// The master and sound effect volumes are kept in the `audio` section of the settings file, and
+/// set with the `volume` console command.
+///
+/// The sounds are loaded from the paths in [AudioFeedbackConfig], which can be inserted before
+/// adding the plugin to use other sounds, by default `sounds/impact.ogg` and
+/// `sounds/footstep.ogg` in the assets directory.
+pub struct AudioFeedbackPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for AudioFeedbackPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for AudioFeedbackPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<AudioFeedbackConfig>() {
+            app.insert_resource(AudioFeedbackConfig::default());
+        }
+        app
+            .add_setting::<AudioSettings>("audio")
+            .init_resource::<AudioFeedbackSounds>()
+            .add_startup_system(load_sounds)
+            .add_system(attach_listener::<T>)
+            .add_system(add_impact_audio)
+            .add_system(play_impact_sounds)
+            .add_system(track_impact_velocity.after(play_impact_sounds))
+            .add_system(play_footsteps)
+            .add_system(update_spatial_sounds.after(play_impact_sounds).after(play_footsteps))
+            .add_console_command("volume", "sets the master and sound effect volumes, from 0 to 1", |world, args| {
+                let volumes = args.iter()
+                    .map(|arg| arg.parse::<f32>().ok().filter(|volume| (0.0..=1.0).contains(volume)))
+                    .collect::<Option<Vec<_>>>();
+                match volumes.as_deref() {
+                    Some([]) => {
+                        let settings = world.resource::<AudioSettings>().clone();
+                        console_print(world, format!("master {:.2}, sfx {:.2}", settings.master_volume, settings.sfx_volume));
+                    }
+                    Some([master]) => world.resource_mut::<AudioSettings>().master_volume = *master,
+                    Some([master, sfx]) => {
+                        let mut settings = world.resource_mut::<AudioSettings>();
+                        settings.master_volume = *master;
+                        settings.sfx_volume = *sfx;
+                    }
+                    _ => console_print(world, "usage: volume [master] [sfx]")
+                }
+            });
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Scales everything played
+    pub master_volume: f32,
+    /// Scales impacts and footsteps, on top of the master volume
+    pub sfx_volume: f32
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            sfx_volume: 1.0
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn sfx(&self) -> f32 {
+        self.master_volume * self.sfx_volume
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct AudioFeedbackConfig {
+    pub impact_path: String,
+    pub footstep_path: String,
+    /// Relative speed an impact needs to make any sound
+    pub min_impact_speed: f32,
+    /// Relative speed at which impacts play at full volume
+    pub max_impact_speed: f32,
+    pub footstep_volume: f32,
+    /// Sounds closer to the listener than this play at full volume, further away they fall off
+    /// with the inverse of the distance
+    pub reference_distance: f32,
+    /// Sounds further from the listener than this aren't played
+    pub max_distance: f32
+}
+
+impl Default for AudioFeedbackConfig {
+    fn default() -> Self {
+        Self {
+            impact_path: "sounds/impact.ogg".to_string(),
+            footstep_path: "sounds/footstep.ogg".to_string(),
+            min_impact_speed: 1.0,
+            max_impact_speed: 15.0,
+            footstep_volume: 0.4,
+            reference_distance: 4.0,
+            max_distance: 80.0
+        }
+    }
+}
+
+impl AudioFeedbackConfig {
+    /// How loud a sound `distance` from the listener is heard, from 0 to 1
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if distance > self.max_distance {
+            return 0.0;
+        }
+        let reference = self.reference_distance.max(f32::EPSILON);
+        (reference / distance.max(reference)).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Resource, Default)]
+struct AudioFeedbackSounds {
+    impact: Handle<AudioSource>,
+    footstep: Handle<AudioSource>
+}
+
+/// Plays a footstep every `stride` travelled horizontally while something is within
+/// `ground_distance` below the entity. Meant for anything walking, such as a character controller
+#[derive(Component, Clone)]
+pub struct Footsteps {
+    pub stride: f32,
+    pub ground_distance: f32,
+    travelled: f32,
+    last_position: Option<Vec3>
+}
+
+impl Footsteps {
+    pub fn new(stride: f32, ground_distance: f32) -> Self {
+        Self {
+            stride,
+            ground_distance,
+            travelled: 0.0,
+            last_position: None
+        }
+    }
+}
+
+/// Added to dynamic bodies by [AudioFeedbackPlugin], remembers the velocity from before the
+/// physics step since by the time a collision is reported the bodies have already bounced off
+#[derive(Component, Default)]
+pub struct ImpactAudio {
+    last_velocity: Vec3
+}
+
+/// What [SpatialSound]s are heard from, kept on the controlled entity by [AudioFeedbackPlugin]
+#[derive(Component, Default)]
+pub struct AudioListener;
+
+/// A sound playing at the entity's translation, its volume is kept up to date with the distance
+/// to the [AudioListener] until it's done
+#[derive(Component)]
+pub struct SpatialSound {
+    sink: Handle<AudioSink>,
+    /// Before attenuation and the [AudioSettings]
+    pub volume: f32,
+    /// Seconds since it started, for giving up on sounds that never started playing
+    age: f32
+}
+
+/// Sounds that haven't started playing after this many seconds (because their source failed to
+/// load for example) are given up on
+const SPATIAL_SOUND_TIMEOUT: f32 = 10.0;
+
+fn load_sounds(
+    asset_server: Res<AssetServer>,
+    config: Res<AudioFeedbackConfig>,
+    mut sounds: ResMut<AudioFeedbackSounds>,
+    loading: Option<ResMut<LoadingAssets>>
+) {
+    sounds.impact = asset_server.load(config.impact_path.as_str());
+    sounds.footstep = asset_server.load(config.footstep_path.as_str());
+    if let Some(mut loading) = loading {
+        loading.track(sounds.impact.clone_untyped());
+        loading.track(sounds.footstep.clone_untyped());
+    }
+}
+
+fn attach_listener<T: Component>(
+    mut commands: Commands,
+    active: Res<ActiveControl<T>>,
+    listeners: Query<Entity, With<AudioListener>>,
+    controlled: Query<(), With<T>>
+) {
+    let Some(entity) = active.entity.filter(|entity| controlled.contains(*entity)) else {
+        return;
+    };
+    if listeners.contains(entity) {
+        return;
+    }
+    for listener in &listeners {
+        commands.entity(listener).remove::<AudioListener>();
+    }
+    commands.entity(entity).insert(AudioListener);
+}
+
+/// What playing [SpatialSound]s takes, borrowed from the resources of the system playing them
+struct SpatialPlayer<'a> {
+    audio: &'a Audio,
+    sinks: &'a Assets<AudioSink>,
+    config: &'a AudioFeedbackConfig,
+    settings: &'a AudioSettings,
+    listener: Vec3
+}
+
+impl SpatialPlayer<'_> {
+    /// Plays `source` at `position` with `volume`, returning `None` if it's too far from the
+    /// listener to be heard
+    fn play(&self, commands: &mut Commands, source: Handle<AudioSource>, position: Vec3, volume: f32) -> Option<Entity> {
+        let attenuation = self.config.attenuation(position.distance(self.listener));
+        if attenuation <= 0.0 {
+            return None;
+        }
+        let settings = PlaybackSettings::ONCE.with_volume(volume * attenuation * self.settings.sfx());
+        let sink = self.sinks.get_handle(self.audio.play_with_settings(source, settings));
+        Some(commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(position)),
+            SpatialSound { sink, volume, age: 0.0 }
+        )).id())
+    }
+}
+
+fn update_spatial_sounds(
+    mut commands: Commands,
+    time: Res<Time>,
+    render_time: Option<Res<RenderTime>>,
+    config: Res<AudioFeedbackConfig>,
+    settings: Res<AudioSettings>,
+    sinks: Res<Assets<AudioSink>>,
+    listeners: Query<&GlobalTransform, With<AudioListener>>,
+    mut sounds: Query<(Entity, &mut SpatialSound, &GlobalTransform)>
+) {
+    // real time, sounds keep playing while the simulation is paused
+    let delta = render_time.map_or(time.delta_seconds(), |render_time| render_time.delta_seconds());
+    let listener = listeners.iter().next().map(|transform| transform.translation());
+    for (entity, mut sound, transform) in &mut sounds {
+        sound.age += delta;
+        let Some(sink) = sinks.get(&sound.sink) else {
+            // the sink only shows up once the source has loaded and started playing
+            if sound.age > SPATIAL_SOUND_TIMEOUT {
+                commands.entity(entity).despawn();
+            }
+            continue;
+        };
+        if sink.empty() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let attenuation = listener.map_or(1.0, |listener| config.attenuation(transform.translation().distance(listener)));
+        sink.set_volume(sound.volume * attenuation * settings.sfx());
+    }
+}
+
+fn add_impact_audio(mut commands: Commands, bodies: Query<(Entity, &RigidBody, Option<&ActiveEvents>), Without<ImpactAudio>>) {
+    for (entity, body, active_events) in &bodies {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let active_events = active_events.copied().unwrap_or(ActiveEvents::empty());
+        commands.entity(entity).insert((
+            ImpactAudio::default(),
+            active_events | ActiveEvents::COLLISION_EVENTS
+        ));
+    }
+}
+
+fn track_impact_velocity(mut bodies: Query<(&mut ImpactAudio, &Velocity)>) {
+    for (mut impact, velocity) in &mut bodies {
+        if impact.last_velocity != velocity.linvel {
+            impact.last_velocity = velocity.linvel;
+        }
+    }
+}
+
+fn play_impact_sounds(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    sinks: Res<Assets<AudioSink>>,
+    config: Res<AudioFeedbackConfig>,
+    settings: Res<AudioSettings>,
+    sounds: Res<AudioFeedbackSounds>,
+    mut collisions: EventReader<CollisionEvent>,
+    listeners: Query<&GlobalTransform, With<AudioListener>>,
+    bodies: Query<(&ImpactAudio, &GlobalTransform)>
+) {
+    let Some(listener) = listeners.iter().next().map(|transform| transform.translation()) else {
+        collisions.clear();
+        return;
+    };
+    let player = SpatialPlayer {
+        audio: &audio,
+        sinks: &sinks,
+        config: &config,
+        settings: &settings,
+        listener
+    };
+    for collision in collisions.iter() {
+        let CollisionEvent::Started(a, b, _) = *collision else {
+            continue;
+        };
+        // static geometry (or anything else without ImpactAudio) counts as standing still
+        let velocity = |entity| bodies.get(entity).map(|(impact, _)| impact.last_velocity).unwrap_or(Vec3::ZERO);
+        let (a_velocity, b_velocity) = (velocity(a), velocity(b));
+        let speed = (a_velocity - b_velocity).length();
+        if speed < config.min_impact_speed {
+            continue;
+        }
+        // the sound comes from the faster of the two, the other one may well be the ground
+        let faster = if a_velocity.length_squared() >= b_velocity.length_squared() { a } else { b };
+        let Ok((_, transform)) = bodies.get(faster) else {
+            continue;
+        };
+
+        let range = (config.max_impact_speed - config.min_impact_speed).max(f32::EPSILON);
+        let volume = ((speed - config.min_impact_speed) / range).clamp(0.0, 1.0);
+        player.play(&mut commands, sounds.impact.clone(), transform.translation(), volume);
+    }
+}
+
+fn play_footsteps(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    sinks: Res<Assets<AudioSink>>,
+    config: Res<AudioFeedbackConfig>,
+    settings: Res<AudioSettings>,
+    sounds: Res<AudioFeedbackSounds>,
+    rapier_context: Res<RapierContext>,
+    listeners: Query<&GlobalTransform, With<AudioListener>>,
+    mut walkers: Query<(Entity, &GlobalTransform, &mut Footsteps)>
+) {
+    for (entity, transform, mut footsteps) in &mut walkers {
+        let position = transform.translation();
+        let last = footsteps.last_position.replace(position).unwrap_or(position);
+        let grounded = rapier_context
+            .cast_ray(position, Vec3::NEG_Y, footsteps.ground_distance, true, QueryFilter::default().exclude_collider(entity))
+            .is_some();
+        if !grounded {
+            continue;
+        }
+
+        let moved = (position - last) * Vec3::new(1.0, 0.0, 1.0);
+        footsteps.travelled += moved.length();
+        if footsteps.travelled >= footsteps.stride {
+            footsteps.travelled %= footsteps.stride.max(f32::EPSILON);
+            // without a listener, steps are heard as if it was right there
+            let player = SpatialPlayer {
+                audio: &audio,
+                sinks: &sinks,
+                config: &config,
+                settings: &settings,
+                listener: listeners.iter().next().map_or(position, |transform| transform.translation())
+            };
+            player.play(&mut commands, sounds.footstep.clone(), position, config.footstep_volume);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use bevy::app::{App, AppExit, Plugin};
+use bevy::diagnostic::Diagnostics;
+use bevy::ecs::entity::Entities;
+use bevy::log::{error, info};
+use bevy::prelude::{EventWriter, Query, Res, ResMut, Resource, State, With};
+use bevy_rapier3d::prelude::RigidBody;
+use serde::Serialize;
+use crate::fixed_time::{AddTickSystem, FixedTime};
+use crate::game_state::GameState;
+use crate::pool::PhysicsPool;
+use crate::profiler::{FRAME_TIME, RAPIER_STEP};
+use crate::scenario::CurrentScenario;
+
+/// Measures a run of [BenchConfig::ticks] ticks for regression tracking, then writes the metrics
+/// to [BenchConfig::output] and exits. `--bench <ticks>` on the command line starts one, in the
+/// scenario given with `--scenario`, and `--bench-output` says where to write it. Together with
+/// `--headless-steps` (at least as many) it runs without a window.
+///
+/// Every tick of [FixedTime] while [GameState::Running] is a sample of the frame time and the
+/// rapier step, as measured by the [ProfilerPlugin](crate::profiler::ProfilerPlugin), along with
+/// how many entities and rigid bodies there are and the [PhysicsPool]'s active and free entities.
+/// An output ending in `.json` gets the samples with the scenario, tickrate and a summary of the
+/// timings, anything else gets the samples as CSV. The summary (mean, 95th percentile and worst)
+/// is logged either way.
+///
+/// Needs to be added after the [ProfilerPlugin](crate::profiler::ProfilerPlugin).
+pub struct BenchPlugin;
+
+impl Plugin for BenchPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<BenchConfig>() {
+            app.insert_resource(BenchConfig::default());
+        }
+        app
+            .init_resource::<BenchSamples>()
+            // after the rapier step has been measured, and before the checksum dump is flushed on
+            // exit in the last stage
+            .add_tick_system(sample_bench);
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BenchConfig {
+    /// Ticks to measure before exiting, nothing is measured without
+    pub ticks: Option<u64>,
+    /// Where the metrics go, as JSON if it ends in `.json` and CSV otherwise
+    pub output: PathBuf
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            ticks: None,
+            output: "bench.csv".into()
+        }
+    }
+}
+
+/// What one tick measured
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct BenchSample {
+    pub tick: u64,
+    /// Real time since the frame before started, in milliseconds
+    pub frame_ms: f64,
+    /// Time the rapier step took, in milliseconds
+    pub step_ms: f64,
+    pub entities: u32,
+    pub bodies: usize,
+    pub pooled_active: usize,
+    pub pooled_free: usize
+}
+
+#[derive(Resource, Default)]
+struct BenchSamples {
+    samples: Vec<BenchSample>,
+    done: bool
+}
+
+/// Mean, 95th percentile and worst of some timings, in milliseconds
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+struct Summary {
+    mean: f64,
+    p95: f64,
+    max: f64
+}
+
+impl Summary {
+    fn of(values: impl Iterator<Item = f64>) -> Self {
+        let mut values = values.collect::<Vec<_>>();
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(f64::total_cmp);
+        let p95 = ((values.len() - 1) as f64 * 0.95).round() as usize;
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p95: values[p95],
+            max: values[values.len() - 1]
+        }
+    }
+}
+
+/// Everything written to a `.json` output
+#[derive(Serialize)]
+struct BenchReport<'a> {
+    scenario: &'a str,
+    tick_rate: f64,
+    ticks: usize,
+    frame_ms: Summary,
+    step_ms: Summary,
+    samples: &'a [BenchSample]
+}
+
+fn sample_bench(
+    config: Res<BenchConfig>,
+    state: Option<Res<State<GameState>>>,
+    diagnostics: Res<Diagnostics>,
+    entities: &Entities,
+    pool: Option<Res<PhysicsPool>>,
+    fixed_time: Option<Res<FixedTime>>,
+    scenario: Option<Res<CurrentScenario>>,
+    mut samples: ResMut<BenchSamples>,
+    mut exits: EventWriter<AppExit>,
+    bodies: Query<(), With<RigidBody>>
+) {
+    let Some(ticks) = config.ticks else {
+        return;
+    };
+    // loading doesn't count towards the ticks
+    let running = state.map_or(true, |state| *state.current() == GameState::Running);
+    if samples.done || !running {
+        return;
+    }
+    let value = |id| diagnostics.get(id).and_then(|diagnostic| diagnostic.value()).unwrap_or(0.0);
+    let stats = pool.map(|pool| pool.stats).unwrap_or_default();
+    let sample = BenchSample {
+        tick: samples.samples.len() as u64 + 1,
+        frame_ms: value(FRAME_TIME),
+        step_ms: value(RAPIER_STEP),
+        entities: entities.len(),
+        bodies: bodies.iter().count(),
+        pooled_active: stats.active,
+        pooled_free: stats.free
+    };
+    samples.samples.push(sample);
+    if sample.tick < ticks {
+        return;
+    }
+
+    samples.done = true;
+    let frame = Summary::of(samples.samples.iter().map(|sample| sample.frame_ms));
+    let step = Summary::of(samples.samples.iter().map(|sample| sample.step_ms));
+    info!("bench of {} ticks, frame ms mean {:.3} p95 {:.3} max {:.3}", ticks, frame.mean, frame.p95, frame.max);
+    info!("bench of {} ticks, rapier step ms mean {:.3} p95 {:.3} max {:.3}", ticks, step.mean, step.p95, step.max);
+    let scenario = scenario.map_or("none", |scenario| scenario.0.name());
+    let tick_rate = fixed_time.map_or(0.0, |fixed_time| if fixed_time.enabled { fixed_time.tick_rate } else { 0.0 });
+    match write_metrics(&config.output, &samples.samples, scenario, tick_rate, frame, step) {
+        Ok(()) => info!("wrote bench metrics to {}", config.output.display()),
+        Err(e) => error!("failed to write bench metrics {}: {}", config.output.display(), e)
+    }
+    exits.send(AppExit);
+}
+
+/// Writes `samples` as JSON or CSV depending on the extension of `path`, the tickrate is 0 for
+/// real time
+fn write_metrics(path: &Path, samples: &[BenchSample], scenario: &str, tick_rate: f64, frame: Summary, step: Summary) -> io::Result<()> {
+    let json = path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("json"));
+    let out = BufWriter::new(File::create(path)?);
+    if json {
+        let report = BenchReport {
+            scenario,
+            tick_rate,
+            ticks: samples.len(),
+            frame_ms: frame,
+            step_ms: step,
+            samples
+        };
+        serde_json::to_writer_pretty(out, &report)?;
+    } else {
+        let mut writer = csv::Writer::from_writer(out);
+        for sample in samples {
+            writer.serialize(sample)?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
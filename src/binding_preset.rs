@@ -0,0 +1,179 @@
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::asset::{AddAsset, AssetLoader, Assets, AssetServer, HandleUntyped, LoadContext, LoadedAsset};
+use bevy::log::warn;
+use bevy::prelude::{Commands, IntoSystemDescriptor, Res, ResMut, Resource};
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::game_state::LoadingAssets;
+use crate::keybind::{SectionBindings, SectionBindingSystem};
+use crate::settings::AddSetting;
+
+/// Loads binding presets (`.binds.ron`) from a folder of the assets directory (`bindings` by
+/// default), switched between with the `binds_preset` console command (`binds_presets` lists them,
+/// as does [BindingPresets::names] for settings UIs). The preset in use is kept in the
+/// `binding_preset` section of the settings file (see
+/// [SettingsPlugin](crate::settings::SettingsPlugin)).
+///
+/// A preset rebinds actions by section, the preset in use goes into [SectionBindings], so only the
+/// actions of types whose [KeyBindingPlugin](crate::keybind::KeyBindingPlugin) was given a section
+/// with [with_preset_section](crate::keybind::KeyBindingPlugin::with_preset_section) can be
+/// rebound. Each input and action is written the way RON writes it:
+///
+/// ```ron
+/// (
+///     name: "left_handed",
+///     bindings: {
+///         "free_controls": {
+///             "KeyCode(I)": "Forward",
+///             "KeyCode(K)": "Backward",
+///         },
+///     },
+/// )
+/// ```
+///
+/// How the bindings are layered over the ones set without a preset (which is what switching to
+/// `none` goes back to) is up to [SectionBindings]. Presets are applied again whenever their file changes, for which `watch_for_changes` has to be
+/// enabled on Bevy's `AssetPlugin`. Only the presets there at startup are loaded.
+pub struct BindingPresetPlugin {
+    folder: String
+}
+
+impl BindingPresetPlugin {
+    /// Creates a new `BindingPresetPlugin` loading presets from `folder`, relative to the assets
+    /// directory
+    pub fn new(folder: impl Into<String>) -> Self {
+        Self {
+            folder: folder.into()
+        }
+    }
+}
+
+impl Default for BindingPresetPlugin {
+    fn default() -> Self {
+        Self::new("bindings")
+    }
+}
+
+impl Plugin for BindingPresetPlugin {
+    fn build(&self, app: &mut App) {
+        let folder = self.folder.clone();
+        app
+            .add_asset::<BindingPreset>()
+            .init_asset_loader::<BindingPresetLoader>()
+            .add_setting::<BindingPresetSettings>("binding_preset")
+            .init_resource::<SectionBindings>()
+            .add_system_to_stage(CoreStage::PreUpdate, apply_preset.before(SectionBindingSystem))
+            .add_startup_system(move |mut commands: Commands, asset_server: Res<AssetServer>, loading: Option<ResMut<LoadingAssets>>| {
+                let handles = match asset_server.load_folder(folder.as_str()) {
+                    Ok(handles) => handles,
+                    Err(e) => {
+                        warn!("failed to load binding presets from {}: {}", folder, e);
+                        return;
+                    }
+                };
+                if let Some(mut loading) = loading {
+                    for handle in &handles {
+                        loading.track(handle.clone());
+                    }
+                }
+                commands.insert_resource(BindingPresets(handles));
+            })
+            .add_console_command("binds_presets", "lists every binding preset", |world, _| {
+                let names = BindingPresets::names(world.resource::<Assets<BindingPreset>>());
+                if names.is_empty() {
+                    console_print(world, "no binding presets loaded");
+                } else {
+                    console_print(world, names.join(", "));
+                }
+            })
+            .add_console_command("binds_preset", "switches to the named binding preset, or none", |world, args| {
+                let Some(name) = args.first() else {
+                    let line = match &world.resource::<BindingPresetSettings>().preset {
+                        Some(preset) => format!("using the {} preset", preset),
+                        None => "not using a preset".to_string()
+                    };
+                    console_print(world, line);
+                    return;
+                };
+                let preset = match *name {
+                    "none" => None,
+                    name if BindingPresets::names(world.resource::<Assets<BindingPreset>>()).iter().any(|preset| preset == name) => Some(name.to_string()),
+                    name => {
+                        console_print(world, format!("unknown preset `{}`, try `binds_presets`", name));
+                        return;
+                    }
+                };
+                world.resource_mut::<BindingPresetSettings>().preset = preset;
+            });
+    }
+}
+
+/// The presets loaded by [BindingPresetPlugin], kept so they stay loaded
+#[derive(Resource)]
+pub struct BindingPresets(pub Vec<HandleUntyped>);
+
+impl BindingPresets {
+    /// The name of every loaded preset, sorted
+    pub fn names(presets: &Assets<BindingPreset>) -> Vec<String> {
+        let mut names = presets.iter().map(|(_, preset)| preset.name.clone()).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BindingPresetSettings {
+    /// The name of the preset in use
+    pub preset: Option<String>
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "a4d17c39-2f6e-4b08-b5e1-93c2d8e06f7a"]
+pub struct BindingPreset {
+    pub name: String,
+    /// By section, each input bound to an action, both in RON
+    #[serde(default)]
+    pub bindings: HashMap<String, HashMap<String, String>>
+}
+
+#[derive(Default)]
+pub struct BindingPresetLoader;
+
+impl AssetLoader for BindingPresetLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let preset = ron::de::from_bytes::<BindingPreset>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(preset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["binds.ron"]
+    }
+}
+
+/// Puts the bindings of the preset in use into [SectionBindings] whenever the preset changes
+fn apply_preset(
+    settings: Option<Res<BindingPresetSettings>>,
+    presets: Option<Res<Assets<BindingPreset>>>,
+    mut sections: ResMut<SectionBindings>
+) {
+    let (Some(settings), Some(presets)) = (settings, presets) else {
+        return;
+    };
+    // presets changing covers them loading and being edited
+    if !settings.is_changed() && !presets.is_changed() {
+        return;
+    }
+    let bindings = settings.preset.as_ref()
+        .and_then(|name| presets.iter().find(|(_, preset)| preset.name == *name))
+        .map(|(_, preset)| preset.bindings.clone())
+        .unwrap_or_default();
+    if sections.0 != bindings {
+        sections.0 = bindings;
+    }
+}
@@ -0,0 +1,165 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::prelude::{Commands, Component, Entity, IntoSystemDescriptor, Query, Res, ResMut, Resource, Transform, With};
+use bevy::time::Time;
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::free_control::{ActiveControl, apply_intents};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Numbered slots remembering the [Transform] of the entity currently controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]. This plugin can
+/// be initialized in two ways:
+///
+/// * No default bindings [CameraBookmarkPlugin::new]
+/// * 1 to 9 recall a slot, holding either control while pressing them saves into the slot
+///  instead [CameraBookmarkPlugin::default]
+///
+/// Recalling either teleports or, if [CameraBookmarks::fly_duration] is set, flies over to the
+/// saved transform one fixed tick at a time.
+pub struct CameraBookmarkPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<BookmarkControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> CameraBookmarkPlugin<T> {
+    /// Creates a new `CameraBookmarkPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: BookmarkControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for CameraBookmarkPlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        let keys = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+        keys
+            .into_iter()
+            .enumerate()
+            .fold(Self::new(), |plugin, (slot, key)| plugin.bind(key, BookmarkControls::Slot(slot)))
+            .bind(LControl, BookmarkControls::SaveModifier)
+            .bind(RControl, BookmarkControls::SaveModifier)
+    }
+}
+
+impl <T: Component> Plugin for CameraBookmarkPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CameraBookmarks<T>>() {
+            app.insert_resource(CameraBookmarks::<T>::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_system(bookmark_controls::<T>)
+            .add_system(fly_to_bookmark::<T>.after(apply_intents::<T>));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum BookmarkControls {
+    /// Recalls the slot, or saves into it while [BookmarkControls::SaveModifier] is held
+    Slot(usize),
+    SaveModifier
+}
+
+#[derive(Resource)]
+pub struct CameraBookmarks<T> {
+    pub slots: Vec<Option<Transform>>,
+    /// How long flying over to a recalled bookmark takes in seconds, `None` teleports instead
+    pub fly_duration: Option<f32>,
+    pub __phantom: PhantomData<fn(T)>
+}
+
+impl <T> Default for CameraBookmarks<T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![None; 9],
+            fly_duration: Some(0.75),
+            __phantom: default()
+        }
+    }
+}
+
+/// An in progress flight over to a bookmark, removed once it arrives
+#[derive(Component)]
+pub struct BookmarkFlight {
+    pub from: Transform,
+    pub to: Transform,
+    pub elapsed: f32,
+    pub duration: f32
+}
+
+fn bookmark_controls<T: Component>(
+    mut commands: Commands,
+    binds: Res<Input<BookmarkControls>>,
+    active: Res<ActiveControl<T>>,
+    mut bookmarks: ResMut<CameraBookmarks<T>>,
+    mut transforms: Query<&mut Transform, With<T>>
+) {
+    let Some(entity) = active.entity else {
+        return;
+    };
+    let Ok(mut transform) = transforms.get_mut(entity) else {
+        return;
+    };
+
+    for bind in binds.get_just_pressed() {
+        let BookmarkControls::Slot(slot) = *bind else {
+            continue;
+        };
+        if slot >= bookmarks.slots.len() {
+            continue;
+        }
+
+        if binds.pressed(BookmarkControls::SaveModifier) {
+            bookmarks.slots[slot] = Some(*transform);
+            info!("saved camera bookmark {}", slot + 1);
+        } else if let Some(saved) = bookmarks.slots[slot] {
+            match bookmarks.fly_duration {
+                Some(duration) if duration > 0.0 => {
+                    commands.entity(entity).insert(BookmarkFlight {
+                        from: *transform,
+                        to: saved,
+                        elapsed: 0.0,
+                        duration
+                    });
+                }
+                _ => {
+                    *transform = saved;
+                    commands.entity(entity).remove::<BookmarkFlight>();
+                }
+            }
+        }
+    }
+}
+
+fn fly_to_bookmark<T: Component>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flights: Query<(Entity, &mut Transform, &mut BookmarkFlight), With<T>>
+) {
+    for (entity, mut transform, mut flight) in &mut flights {
+        flight.elapsed += time.delta_seconds();
+        let t = (flight.elapsed / flight.duration).min(1.0);
+        // smoothstep, easing in and out of the flight
+        let t = t * t * (3.0 - 2.0 * t);
+
+        transform.translation = flight.from.translation.lerp(flight.to.translation, t);
+        transform.rotation = flight.from.rotation.slerp(flight.to.rotation, t);
+        transform.scale = flight.from.scale.lerp(flight.to.scale, t);
+
+        if flight.elapsed >= flight.duration {
+            commands.entity(entity).remove::<BookmarkFlight>();
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::f32::consts::TAU;
+use std::marker::PhantomData;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::hierarchy::Children;
+use bevy::math::{EulerRot, Quat, Vec3};
+use bevy::prelude::{Commands, Component, Entity, EventReader, GlobalTransform, IntoSystemDescriptor, Query, Res, Resource, Transform, With, Without};
+use bevy::time::Time;
+use bevy::transform::TransformSystem;
+use bevy::utils::{default, HashMap};
+use crate::free_control::{ActiveControl, MoveIntent};
+
+/// Procedural head bob while moving (driven by the [MoveIntent]s of
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]), and camera shake
+/// triggered with [CameraShake] events.
+///
+/// The effects are only ever applied to the [GlobalTransform] after transforms are propagated,
+/// and taken off again at the start of the next frame, so the [Transform] (and anything reading
+/// it, such as physics) never sees them. [CameraEffectsConfig] sets how strong the bob is.
+pub struct CameraEffectsPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for CameraEffectsPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for CameraEffectsPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CameraEffectsConfig<T>>() {
+            app.insert_resource(CameraEffectsConfig::<T>::default());
+        }
+        app
+            .add_event::<CameraShake>()
+            .add_system_to_stage(CoreStage::First, remove_camera_effects::<T>)
+            .add_system(add_camera_effects::<T>)
+            .add_system(update_camera_effects::<T>)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_camera_effects::<T>.after(TransformSystem::TransformPropagate)
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct CameraEffectsConfig<T> {
+    /// How far the camera moves up and down while moving, 0.0 turns head bob off
+    pub bob_amplitude: f32,
+    /// Distance covered by a full up and down of the bob
+    pub bob_stride: f32,
+    /// Fraction of the way to full bob (while moving) or no bob (while still) covered each tick
+    pub bob_smoothing: f32,
+    pub __phantom: PhantomData<fn(T)>
+}
+
+impl <T> Default for CameraEffectsConfig<T> {
+    fn default() -> Self {
+        Self {
+            bob_amplitude: 0.04,
+            bob_stride: 1.5,
+            bob_smoothing: 0.1,
+            __phantom: default()
+        }
+    }
+}
+
+/// Shakes a camera, the shake dies down on its own
+#[derive(Copy, Clone, Debug)]
+pub struct CameraShake {
+    /// The entity to shake, `None` for the [ActiveControl] entity
+    pub entity: Option<Entity>,
+    /// Strongest rotation of the shake, in radians
+    pub amplitude: f32,
+    /// Shakes per second
+    pub frequency: f32,
+    /// How fast the shake dies down, the amplitude is multiplied by `e^(-decay * seconds)`
+    pub decay: f32
+}
+
+/// Effect state of a camera, added to every entity with the marker
+#[derive(Component, Default)]
+pub struct CameraEffects {
+    bob_phase: f32,
+    bob_strength: f32,
+    shakes: Vec<ActiveShake>,
+    /// Global transforms of the entity and its children from before the effects were applied
+    base: Vec<(Entity, GlobalTransform)>
+}
+
+struct ActiveShake {
+    shake: CameraShake,
+    elapsed: f32
+}
+
+impl CameraEffects {
+    /// The offset relative to the camera, from all of the effects combined
+    fn offset(&self, config_amplitude: f32) -> Transform {
+        let bob = self.bob_strength * config_amplitude;
+        let translation = Vec3::new(
+            (self.bob_phase / 2.0).sin() * bob * 0.5,
+            self.bob_phase.sin().abs() * bob,
+            0.0
+        );
+
+        let mut angles = Vec3::ZERO;
+        for active in &self.shakes {
+            let CameraShake { amplitude, frequency, decay, .. } = active.shake;
+            let amplitude = amplitude * (-decay * active.elapsed).exp();
+            let phase = active.elapsed * frequency * TAU;
+            // a few unrelated frequencies per axis keep the shake from looking like a wobble
+            angles += amplitude * Vec3::new(
+                (phase * 1.0).sin() * 0.6 + (phase * 2.3 + 1.0).sin() * 0.4,
+                (phase * 1.1 + 2.0).sin() * 0.6 + (phase * 1.9 + 3.0).sin() * 0.4,
+                (phase * 0.9 + 4.0).sin() * 0.3
+            );
+        }
+
+        Transform {
+            translation,
+            rotation: Quat::from_euler(EulerRot::YXZ, angles.y, angles.x, angles.z),
+            ..default()
+        }
+    }
+}
+
+fn add_camera_effects<T: Component>(mut commands: Commands, cameras: Query<Entity, (With<T>, Without<CameraEffects>)>) {
+    for entity in &cameras {
+        commands.entity(entity).insert(CameraEffects::default());
+    }
+}
+
+fn update_camera_effects<T: Component>(
+    time: Res<Time>,
+    config: Res<CameraEffectsConfig<T>>,
+    active: Res<ActiveControl<T>>,
+    mut move_intents: EventReader<MoveIntent>,
+    mut shakes: EventReader<CameraShake>,
+    mut cameras: Query<(Entity, &mut CameraEffects), With<T>>
+) {
+    let mut moved = HashMap::<Entity, f32>::default();
+    for intent in move_intents.iter() {
+        *moved.entry(intent.entity).or_default() += intent.magnitude;
+    }
+    let delta = time.delta_seconds();
+
+    for (entity, mut effects) in &mut cameras {
+        let distance = moved.get(&entity).copied().unwrap_or(0.0);
+        if distance > 0.0 && config.bob_stride > 0.0 {
+            // a full stride covers a whole period of the (absolute) sine, which is 2 bobs
+            effects.bob_phase = (effects.bob_phase + distance / config.bob_stride * TAU) % (2.0 * TAU);
+        }
+        let target = if distance > 0.0 { 1.0 } else { 0.0 };
+        let smoothing = config.bob_smoothing.clamp(0.0, 1.0);
+        if effects.bob_strength != target {
+            effects.bob_strength += (target - effects.bob_strength) * smoothing;
+            if (effects.bob_strength - target).abs() < 0.001 {
+                effects.bob_strength = target;
+            }
+        }
+
+        if !effects.shakes.is_empty() {
+            for active in &mut effects.shakes {
+                active.elapsed += delta;
+            }
+            // once a shake is down to a hundredth of its strength it's no longer noticeable
+            effects.shakes.retain(|active| (-active.shake.decay * active.elapsed).exp() > 0.01);
+        }
+    }
+
+    for shake in shakes.iter() {
+        let Some(entity) = shake.entity.or(active.entity) else {
+            continue;
+        };
+        if let Ok((_, mut effects)) = cameras.get_mut(entity) {
+            effects.shakes.push(ActiveShake {
+                shake: *shake,
+                elapsed: 0.0
+            });
+        }
+    }
+}
+
+fn apply_camera_effects<T: Component>(
+    config: Res<CameraEffectsConfig<T>>,
+    mut cameras: Query<(Entity, &mut CameraEffects, Option<&Children>), With<T>>,
+    mut globals: Query<&mut GlobalTransform>
+) {
+    for (entity, mut effects, children) in &mut cameras {
+        let offset = effects.offset(config.bob_amplitude);
+        if offset == Transform::IDENTITY {
+            continue;
+        }
+        let Ok(mut global) = globals.get_mut(entity) else {
+            continue;
+        };
+
+        let base = *global;
+        *global = base.mul_transform(offset);
+        let change = global.affine() * base.affine().inverse();
+        effects.base.push((entity, base));
+
+        // children were propagated from the transform without the effects, so they have to be
+        // moved along as well
+        let Some(children) = children else {
+            continue;
+        };
+        for child in children.iter() {
+            if let Ok(mut child_global) = globals.get_mut(*child) {
+                effects.base.push((*child, *child_global));
+                *child_global = GlobalTransform::from(change * child_global.affine());
+            }
+        }
+    }
+}
+
+fn remove_camera_effects<T: Component>(
+    mut cameras: Query<&mut CameraEffects, With<T>>,
+    mut globals: Query<&mut GlobalTransform>
+) {
+    for mut effects in &mut cameras {
+        for (entity, base) in effects.base.drain(..) {
+            if let Ok(mut global) = globals.get_mut(entity) {
+                *global = base;
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::marker::PhantomData;
+use std::str::FromStr;
+use bevy::app::{App, Plugin};
+use bevy::math::{Quat, Vec3};
+use bevy::prelude::{Commands, Component, Entity, Local, Query, Res, ResMut, Resource, Transform, With, Without, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::RenderTime;
+use crate::free_control::{ActiveControl, FreeControlSuspended};
+use crate::picking::PickTarget;
+use crate::selection::Selection;
+use crate::settings::AddSetting;
+
+/// Smoothing for cameras following physics bodies. Bodies only move on ticks of
+/// [FixedTime](crate::fixed_time::FixedTime), and jitter a little when resting or pushed around,
+/// so a camera stuck to one shakes with all of it. With [FollowMode::Spring] the camera is pulled
+/// towards where it should be by a critically damped spring instead (see [FollowSpring]), going by
+/// real time so it keeps moving smoothly between ticks. The mode is kept in the `camera_follow`
+/// section of the settings file, and switched with the `follow_mode` console command.
+///
+/// The [VehicleControlPlugin](crate::vehicle::VehicleControlPlugin) follows vehicles this way, and
+/// the `follow` console command has the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T] follow the first of
+/// the [Selection] (or the [PickTarget]) from behind, until it's run again.
+pub struct CameraFollowPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for CameraFollowPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for CameraFollowPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app
+            .add_setting::<CameraFollowSettings>("camera_follow")
+            .init_resource::<FollowTarget>()
+            .add_system(follow_target::<T>)
+            .add_console_command("follow", "follows the selection, or what's under the crosshair, or stops following", |world, _| {
+                toggle_follow::<T>(world);
+            })
+            .add_console_command("follow_mode", "switches camera following between rigid and spring", |world, args| {
+                let mut settings = world.resource_mut::<CameraFollowSettings>();
+                match args.first().map(|arg| arg.parse::<FollowMode>()) {
+                    None => {
+                        let mode = settings.mode;
+                        console_print(world, format!("following is {}", mode.name()));
+                    }
+                    Some(Ok(mode)) => settings.mode = mode,
+                    Some(Err(())) => console_print(world, "usage: follow_mode [rigid|spring]")
+                }
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum FollowMode {
+    /// Exactly where the followed body is, shaking along with it
+    Rigid,
+    #[default]
+    Spring
+}
+
+impl FollowMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            FollowMode::Rigid => "rigid",
+            FollowMode::Spring => "spring"
+        }
+    }
+}
+
+impl FromStr for FollowMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rigid" => Ok(FollowMode::Rigid),
+            "spring" => Ok(FollowMode::Spring),
+            _ => Err(())
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraFollowSettings {
+    pub mode: FollowMode,
+    /// How quickly the spring catches up, in radians per second, higher is tighter. The camera
+    /// covers most of the way in about 4 divided by this many seconds
+    pub frequency: f32,
+    /// Where the camera sits behind what it follows with the `follow` command, relative to its
+    /// heading
+    pub offset: Vec3
+}
+
+impl Default for CameraFollowSettings {
+    fn default() -> Self {
+        Self {
+            mode: FollowMode::Spring,
+            frequency: 10.0,
+            offset: Vec3::new(0.0, 2.0, 6.0)
+        }
+    }
+}
+
+/// Springs easing a camera's position and the point it looks at, for whoever moves the camera
+/// to keep around (in a [Local] for example)
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FollowSpring {
+    /// Smoothed position and velocity, starting where the camera should be
+    position: Option<(Vec3, Vec3)>,
+    look_at: Option<(Vec3, Vec3)>
+}
+
+impl FollowSpring {
+    /// Starts over from where the camera should be, for when it starts following something else
+    pub fn reset(&mut self) {
+        *self = default();
+    }
+
+    /// Moves towards the `position` and `look_at` point the camera should have, `delta` seconds
+    /// after the last update, returning where the camera goes and what it looks at
+    pub fn update(&mut self, settings: &CameraFollowSettings, position: Vec3, look_at: Vec3, delta: f32) -> (Vec3, Vec3) {
+        if settings.mode == FollowMode::Rigid {
+            self.reset();
+            return (position, look_at);
+        }
+        let frequency = settings.frequency.max(0.0);
+        let position = spring(self.position.get_or_insert((position, Vec3::ZERO)), position, frequency, delta);
+        let look_at = spring(self.look_at.get_or_insert((look_at, Vec3::ZERO)), look_at, frequency, delta);
+        (position, look_at)
+    }
+}
+
+/// Steps a critically damped spring at `state` (its position and velocity) towards `target`, the
+/// closed form, so it doesn't overshoot or blow up however long the step is
+fn spring(state: &mut (Vec3, Vec3), target: Vec3, frequency: f32, delta: f32) -> Vec3 {
+    let (position, velocity) = *state;
+    let offset = position - target;
+    let decay = (-frequency * delta).exp();
+    let change = (velocity + offset * frequency) * delta;
+    *state = (target + (offset + change) * decay, (velocity - change * frequency) * decay);
+    state.0
+}
+
+/// The seconds since the last frame, real ones when there's a [RenderTime] since the camera
+/// can't stand still between ticks
+pub fn frame_delta(time: &Time, render_time: Option<&RenderTime>) -> f32 {
+    render_time.map_or(time.delta_seconds(), |render_time| render_time.delta_seconds())
+}
+
+/// Where the camera sits behind `transform`, looking at it, only going by the heading so it doesn't
+/// roll or tip along with the body
+pub fn behind(transform: &Transform, offset: Vec3) -> (Vec3, Vec3) {
+    let forward = transform.forward();
+    let yaw = Quat::from_rotation_y(f32::atan2(-forward.x, -forward.z));
+    (transform.translation + yaw * offset, transform.translation + Vec3::Y)
+}
+
+/// What the `follow` command follows
+#[derive(Resource, Default)]
+pub struct FollowTarget(pub Option<Entity>);
+
+fn follow_target<T: Component>(
+    mut commands: Commands,
+    time: Res<Time>,
+    render_time: Option<Res<RenderTime>>,
+    settings: Res<CameraFollowSettings>,
+    mut target: ResMut<FollowTarget>,
+    active: Res<ActiveControl<T>>,
+    followed: Query<&Transform, Without<T>>,
+    mut cameras: Query<&mut Transform, With<T>>,
+    mut spring: Local<(Option<Entity>, FollowSpring)>
+) {
+    let Some(entity) = target.0 else {
+        return;
+    };
+    let Some(camera) = active.entity else {
+        return;
+    };
+    let Ok(transform) = followed.get(entity) else {
+        // the followed entity is gone, hand the camera back
+        target.0 = None;
+        commands.entity(camera).remove::<FreeControlSuspended>();
+        return;
+    };
+    let Ok(mut camera) = cameras.get_mut(camera) else {
+        return;
+    };
+    if spring.0 != Some(entity) {
+        *spring = (Some(entity), default());
+    }
+    let (position, look_at) = behind(transform, settings.offset);
+    let delta = frame_delta(&time, render_time.as_deref());
+    let (position, look_at) = spring.1.update(&settings, position, look_at, delta);
+    camera.translation = position;
+    camera.look_at(look_at, Vec3::Y);
+}
+
+/// Starts following the first of the [Selection] (or the [PickTarget] without one) with the
+/// controlled entity, or stops following if it already was
+pub fn toggle_follow<T: Component>(world: &mut World) {
+    let Some(camera) = world.resource::<ActiveControl<T>>().entity else {
+        console_print(world, "nothing controlled to follow with");
+        return;
+    };
+    if world.resource_mut::<FollowTarget>().0.take().is_some() {
+        world.entity_mut(camera).remove::<FreeControlSuspended>();
+        console_print(world, "stopped following");
+        return;
+    }
+    let selected = world.get_resource::<Selection>().and_then(|selection| selection.iter().next());
+    let target = selected.or_else(|| world.get_resource::<PickTarget>().and_then(|target| target.entity));
+    let Some(target) = target.filter(|target| *target != camera) else {
+        console_print(world, "nothing selected or under the crosshair to follow");
+        return;
+    };
+    world.resource_mut::<FollowTarget>().0 = Some(target);
+    world.entity_mut(camera).insert(FreeControlSuspended);
+    console_print(world, format!("following {:?}", target));
+}
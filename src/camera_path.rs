@@ -0,0 +1,262 @@
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::math::{Quat, Vec3};
+use bevy::prelude::{Commands, Component, Entity, IntoSystemDescriptor, Query, Res, ResMut, Resource, Transform, With};
+use bevy::time::Time;
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::free_control::{ActiveControl, cycle_active_control, FreeControlSuspended};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Plays the entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin)
+/// with marker [T] along a Catmull-Rom spline through the points of [CameraPath], for recording
+/// fly-throughs. This plugin can be initialized in two ways:
+///
+/// * No default bindings [CameraPathPlugin::new]
+/// * Insert adds the current transform as a point, Delete clears the path and Home starts or
+///  stops playback [CameraPathPlugin::default]
+///
+/// The free controls are suspended (see [FreeControlSuspended]) while playing. Paths can be kept
+/// in RON files with the `camera_path_save` and `camera_path_load` console commands.
+pub struct CameraPathPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<CameraPathControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> CameraPathPlugin<T> {
+    /// Creates a new `CameraPathPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: CameraPathControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for CameraPathPlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Insert, CameraPathControls::AddPoint)
+            .bind(Delete, CameraPathControls::Clear)
+            .bind(Home, CameraPathControls::TogglePlayback)
+    }
+}
+
+impl <T: Component> Plugin for CameraPathPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CameraPath<T>>() {
+            app.insert_resource(CameraPath::<T>::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_system(camera_path_controls::<T>.after(cycle_active_control::<T>))
+            .add_system(play_camera_path::<T>.after(camera_path_controls::<T>))
+            .add_console_command("camera_path_save", "writes the camera path to the given file", |world, args| {
+                let Some(file) = args.first() else {
+                    console_print(world, "usage: camera_path_save <file>");
+                    return;
+                };
+                let result = world.resource::<CameraPath<T>>().save_points(Path::new(file));
+                match result {
+                    Ok(()) => console_print(world, format!("saved camera path to {}", file)),
+                    Err(e) => console_print(world, format!("failed to save camera path: {}", e))
+                }
+            })
+            .add_console_command("camera_path_load", "replaces the camera path with the one in the given file", |world, args| {
+                let Some(file) = args.first() else {
+                    console_print(world, "usage: camera_path_load <file>");
+                    return;
+                };
+                match CameraPath::<T>::load_points(Path::new(file)) {
+                    Ok(points) => {
+                        console_print(world, format!("loaded {} camera path points", points.len()));
+                        world.resource_mut::<CameraPath<T>>().points = points;
+                    }
+                    Err(e) => console_print(world, format!("failed to load camera path: {}", e))
+                }
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum CameraPathControls {
+    /// Appends the active entity's current transform to [CameraPath::points]
+    AddPoint,
+    Clear,
+    /// Starts playing the path from the first point, or stops if already playing
+    TogglePlayback
+}
+
+#[derive(Resource)]
+pub struct CameraPath<T> {
+    pub points: Vec<Transform>,
+    /// How many points are passed each second during playback
+    pub speed: f32,
+    /// Starts over from the first point once the end is reached, instead of stopping
+    pub looping: bool,
+    pub __phantom: PhantomData<fn(T)>
+}
+
+impl <T> Default for CameraPath<T> {
+    fn default() -> Self {
+        Self {
+            points: Vec::new(),
+            speed: 0.5,
+            looping: false,
+            __phantom: default()
+        }
+    }
+}
+
+impl <T> CameraPath<T> {
+    /// The transform at `t` along the path, where every whole number of `t` is one of the points
+    pub fn sample(&self, t: f32) -> Option<Transform> {
+        let last = self.points.len().checked_sub(1)?;
+        let t = t.clamp(0.0, last as f32);
+        let index = (t.floor() as usize).min(last.saturating_sub(1));
+        let local = t - index as f32;
+
+        // the end points are repeated, so the curve still passes through the first and last point
+        let point = |i: isize| self.points[i.clamp(0, last as isize) as usize];
+        let i = index as isize;
+        let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+
+        Some(Transform {
+            translation: catmull_rom(p0.translation, p1.translation, p2.translation, p3.translation, local),
+            rotation: p1.rotation.slerp(p2.rotation, local),
+            scale: p1.scale.lerp(p2.scale, local)
+        })
+    }
+
+    /// Reads the points of a file written by [CameraPath::save_points]
+    pub fn load_points(path: &Path) -> Result<Vec<Transform>, String> {
+        let ron = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let points = ron::from_str::<Vec<SavedPathPoint>>(&ron).map_err(|e| e.to_string())?;
+        Ok(points.into_iter().map(Into::into).collect())
+    }
+
+    /// Writes the points as a RON list of translations and rotations
+    pub fn save_points(&self, path: &Path) -> Result<(), String> {
+        let points = self.points.iter().map(SavedPathPoint::from).collect::<Vec<_>>();
+        let ron = ron::ser::to_string_pretty(&points, default()).map_err(|e| e.to_string())?;
+        fs::write(path, ron).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedPathPoint {
+    translation: [f32; 3],
+    rotation: [f32; 4]
+}
+
+impl From<&Transform> for SavedPathPoint {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array()
+        }
+    }
+}
+
+impl From<SavedPathPoint> for Transform {
+    fn from(point: SavedPathPoint) -> Self {
+        Transform::from_translation(Vec3::from_array(point.translation))
+            .with_rotation(Quat::from_array(point.rotation))
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Progress along [CameraPath] of an entity being played along it
+#[derive(Component)]
+pub struct CameraPathPlayback {
+    pub t: f32
+}
+
+fn camera_path_controls<T: Component>(
+    mut commands: Commands,
+    binds: Res<Input<CameraPathControls>>,
+    active: Res<ActiveControl<T>>,
+    mut path: ResMut<CameraPath<T>>,
+    entities: Query<(&Transform, Option<&CameraPathPlayback>), With<T>>
+) {
+    let Some(entity) = active.entity else {
+        return;
+    };
+    let Ok((transform, playback)) = entities.get(entity) else {
+        return;
+    };
+
+    if binds.just_pressed(CameraPathControls::AddPoint) {
+        path.points.push(*transform);
+        info!("added camera path point {}", path.points.len());
+    }
+    if binds.just_pressed(CameraPathControls::Clear) {
+        path.points.clear();
+        info!("cleared camera path");
+    }
+    if binds.just_pressed(CameraPathControls::TogglePlayback) {
+        if playback.is_some() {
+            stop_playback(&mut commands, entity);
+        } else if path.points.len() >= 2 {
+            start_playback(&mut commands, entity);
+        } else {
+            info!("camera path needs at least 2 points to play");
+        }
+    }
+}
+
+fn play_camera_path<T: Component>(
+    mut commands: Commands,
+    time: Res<Time>,
+    path: Res<CameraPath<T>>,
+    mut playing: Query<(Entity, &mut Transform, &mut CameraPathPlayback), With<T>>
+) {
+    let end = path.points.len().saturating_sub(1) as f32;
+    for (entity, mut transform, mut playback) in &mut playing {
+        playback.t += path.speed * time.delta_seconds();
+        if playback.t >= end && path.looping && end > 0.0 {
+            playback.t %= end;
+        }
+
+        match path.sample(playback.t) {
+            Some(sampled) => *transform = sampled,
+            None => stop_playback(&mut commands, entity)
+        }
+        if playback.t >= end && !path.looping {
+            stop_playback(&mut commands, entity);
+        }
+    }
+}
+
+/// Starts playing [CameraPath] on `entity` from the first point
+pub fn start_playback(commands: &mut Commands, entity: Entity) {
+    commands.entity(entity)
+        .insert(CameraPathPlayback { t: 0.0 })
+        .insert(FreeControlSuspended);
+}
+
+fn stop_playback(commands: &mut Commands, entity: Entity) {
+    commands.entity(entity)
+        .remove::<CameraPathPlayback>()
+        .remove::<FreeControlSuspended>();
+}
@@ -0,0 +1,416 @@
+use std::fs;
+use std::marker::PhantomData;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::core_pipeline::core_3d::Camera3dBundle;
+use bevy::hierarchy::BuildChildren;
+use bevy::input::Input;
+use bevy::log::{error, info};
+use bevy::prelude::{Added, Camera, Commands, Component, Entity, Image, IntoSystemDescriptor, Parent, Projection, Query, Res, ResMut, Resource, With, Without, World};
+use bevy::render::camera::RenderTarget;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_asset::{PrepareAssetLabel, RenderAssets};
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext};
+use bevy::render::render_resource::{Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout, Maintain, MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::{RenderApp, RenderStage};
+use bevy::tasks::IoTaskPool;
+use bevy::utils::default;
+use bevy::window::Windows;
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Saves PNG screenshots of what the camera with marker [T] sees, and can record every frame to
+/// disk for encoding into a video later. This plugin can be initialized in two ways:
+///
+/// * No default bindings [CapturePlugin::new]
+/// * F12 for a screenshot, F10 to start/stop recording [CapturePlugin::default]
+///
+/// Since [FixedTimePlugin](crate::fixed_time::FixedTimePlugin) makes every frame a single tick,
+/// recordings always come out at the fixed tick rate no matter how slow capturing makes them.
+///
+/// Bevy can't read back a window's surface, so this adds a second camera as a child of the
+/// marked camera that renders into an image the size of the primary window, it's only active on
+/// frames that are being captured. That image is copied into a buffer after rendering and read
+/// back at the end of the frame, which stalls the frame, so recordings will run slowly.
+pub struct CapturePlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<CaptureControls>,
+    directory: PathBuf,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> CapturePlugin<T> {
+    /// Creates a new `CapturePlugin` saving into `directory`, without any default bindings
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            directory: directory.into(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: CaptureControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for CapturePlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new("captures")
+            .bind(F12, CaptureControls::Screenshot)
+            .bind(F10, CaptureControls::ToggleRecording)
+    }
+}
+
+impl <T: Component> Plugin for CapturePlugin<T> {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_plugin(ExtractResourcePlugin::<CaptureRequest>::default())
+            .insert_resource(CaptureState {
+                directory: self.directory.clone(),
+                recording: None
+            })
+            .insert_resource(CaptureReceiver(receiver))
+            .add_startup_system(create_capture_target)
+            .add_system(attach_capture_camera::<T>)
+            .add_system(capture_controls)
+            .add_system(sync_capture_camera.after(capture_controls))
+            .add_system(save_captured_frames);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(CaptureSender(sender))
+                .init_resource::<CaptureBuffer>()
+                .add_system_to_stage(
+                    RenderStage::Prepare,
+                    prepare_capture_buffer.after(PrepareAssetLabel::AssetPrepare)
+                )
+                .add_system_to_stage(RenderStage::Cleanup, read_capture_buffer);
+
+            let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+            render_graph.add_node(CAPTURE_NODE, CaptureNode);
+            render_graph
+                .add_node_edge(bevy::render::main_graph::node::CAMERA_DRIVER, CAPTURE_NODE)
+                .unwrap();
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum CaptureControls {
+    Screenshot,
+    ToggleRecording
+}
+
+#[derive(Resource)]
+pub struct CaptureState {
+    pub directory: PathBuf,
+    recording: Option<Recording>
+}
+
+impl CaptureState {
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+}
+
+struct Recording {
+    directory: PathBuf,
+    frame: u64
+}
+
+/// The camera rendering into the capture image, a child of the camera being captured
+#[derive(Component)]
+pub struct CaptureCamera;
+
+/// Which image gets captured this frame and where it goes, shared with the render world
+#[derive(Resource, Clone, Default)]
+struct CaptureRequest {
+    image: Handle<Image>,
+    paths: Vec<PathBuf>
+}
+
+impl ExtractResource for CaptureRequest {
+    type Source = Self;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+struct CapturedFrame {
+    paths: Vec<PathBuf>,
+    width: u32,
+    height: u32,
+    data: Vec<u8>
+}
+
+#[derive(Resource)]
+struct CaptureReceiver(Receiver<CapturedFrame>);
+
+#[derive(Resource)]
+struct CaptureSender(Sender<CapturedFrame>);
+
+fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+fn window_size(windows: &Windows) -> Extent3d {
+    let window = windows.primary();
+    Extent3d {
+        width: window.physical_width().max(1),
+        height: window.physical_height().max(1),
+        depth_or_array_layers: 1
+    }
+}
+
+fn create_capture_target(mut commands: Commands, windows: Res<Windows>, mut images: ResMut<Assets<Image>>) {
+    let size = window_size(&windows);
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("capture_image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT
+        },
+        ..default()
+    };
+    image.resize(size);
+
+    commands.insert_resource(CaptureRequest {
+        image: images.add(image),
+        paths: Vec::new()
+    });
+}
+
+fn attach_capture_camera<T: Component>(
+    mut commands: Commands,
+    request: Res<CaptureRequest>,
+    cameras: Query<Entity, (With<T>, Added<Camera>)>
+) {
+    for entity in &cameras {
+        let capture_camera = commands.spawn(Camera3dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(request.image.clone()),
+                is_active: false,
+                priority: -1,
+                ..default()
+            },
+            ..default()
+        })
+            .insert(CaptureCamera)
+            .id();
+        commands.entity(entity).add_child(capture_camera);
+    }
+}
+
+fn capture_controls(
+    binds: Res<Input<CaptureControls>>,
+    mut state: ResMut<CaptureState>,
+    mut request: ResMut<CaptureRequest>
+) {
+    request.paths.clear();
+
+    if binds.just_pressed(CaptureControls::ToggleRecording) {
+        if let Some(recording) = state.recording.take() {
+            info!("stopped recording after {} frames, saved to {}", recording.frame, recording.directory.display());
+        } else {
+            let directory = state.directory.join(format!("recording_{}", timestamp()));
+            match fs::create_dir_all(&directory) {
+                Ok(()) => {
+                    info!("started recording to {}", directory.display());
+                    state.recording = Some(Recording { directory, frame: 0 });
+                }
+                Err(e) => error!("failed to start recording to {}: {}", directory.display(), e)
+            }
+        }
+    }
+
+    if let Some(recording) = &mut state.recording {
+        request.paths.push(recording.directory.join(format!("frame_{:06}.png", recording.frame)));
+        recording.frame += 1;
+    }
+
+    if binds.just_pressed(CaptureControls::Screenshot) {
+        match fs::create_dir_all(&state.directory) {
+            Ok(()) => request.paths.push(state.directory.join(format!("screenshot_{}.png", timestamp()))),
+            Err(e) => error!("failed to save screenshot to {}: {}", state.directory.display(), e)
+        }
+    }
+}
+
+/// Keeps the capture camera's projection and image matching the captured camera and window,
+/// and only activates it on frames that are being captured
+fn sync_capture_camera(
+    windows: Res<Windows>,
+    request: Res<CaptureRequest>,
+    mut images: ResMut<Assets<Image>>,
+    parents: Query<&Projection, Without<CaptureCamera>>,
+    mut capture_cameras: Query<(&Parent, &mut Camera, &mut Projection), With<CaptureCamera>>
+) {
+    let capturing = !request.paths.is_empty();
+    if capturing {
+        let size = window_size(&windows);
+        if let Some(image) = images.get_mut(&request.image) {
+            if image.texture_descriptor.size != size {
+                image.resize(size);
+            }
+        }
+    }
+
+    for (parent, mut camera, mut projection) in &mut capture_cameras {
+        if camera.is_active != capturing {
+            camera.is_active = capturing;
+        }
+        if let Ok(parent_projection) = parents.get(parent.get()) {
+            *projection = parent_projection.clone();
+        }
+    }
+}
+
+fn save_captured_frames(receiver: Res<CaptureReceiver>) {
+    for frame in receiver.0.try_iter() {
+        IoTaskPool::get().spawn(async move {
+            let Some(image) = image::RgbaImage::from_raw(frame.width, frame.height, frame.data) else {
+                return;
+            };
+            for path in frame.paths {
+                if let Err(e) = image.save(&path) {
+                    error!("failed to save capture to {}: {}", path.display(), e);
+                }
+            }
+        }).detach();
+    }
+}
+
+const CAPTURE_NODE: &str = "capture";
+
+struct PendingCapture {
+    buffer: Buffer,
+    paths: Vec<PathBuf>,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32
+}
+
+#[derive(Resource, Default)]
+struct CaptureBuffer(Option<PendingCapture>);
+
+fn prepare_capture_buffer(
+    request: Option<Res<CaptureRequest>>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    mut capture_buffer: ResMut<CaptureBuffer>
+) {
+    capture_buffer.0 = None;
+    let Some(request) = request.filter(|request| !request.paths.is_empty()) else {
+        return;
+    };
+    let Some(image) = images.get(&request.image) else {
+        return;
+    };
+
+    let width = image.size.x as u32;
+    let height = image.size.y as u32;
+    // rows copied out of a texture have to be aligned to 256 bytes
+    let padded_bytes_per_row = (width * 4 + 255) / 256 * 256;
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("capture_buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    });
+
+    capture_buffer.0 = Some(PendingCapture {
+        buffer,
+        paths: request.paths.clone(),
+        width,
+        height,
+        padded_bytes_per_row
+    });
+}
+
+struct CaptureNode;
+
+impl Node for CaptureNode {
+    fn run(&self, _graph: &mut RenderGraphContext, render_context: &mut RenderContext, world: &World) -> Result<(), NodeRunError> {
+        let Some(pending) = &world.resource::<CaptureBuffer>().0 else {
+            return Ok(());
+        };
+        let request = world.resource::<CaptureRequest>();
+        let Some(image) = world.resource::<RenderAssets<Image>>().get(&request.image) else {
+            return Ok(());
+        };
+
+        render_context.command_encoder.copy_texture_to_buffer(
+            image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &pending.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(pending.padded_bytes_per_row),
+                    rows_per_image: None
+                }
+            },
+            Extent3d {
+                width: pending.width,
+                height: pending.height,
+                depth_or_array_layers: 1
+            }
+        );
+        Ok(())
+    }
+}
+
+fn read_capture_buffer(
+    render_device: Res<RenderDevice>,
+    sender: Res<CaptureSender>,
+    mut capture_buffer: ResMut<CaptureBuffer>
+) {
+    let Some(pending) = capture_buffer.0.take() else {
+        return;
+    };
+
+    let slice = pending.buffer.slice(..);
+    let (map_sender, map_receiver) = crossbeam_channel::bounded(1);
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = map_sender.send(result);
+    });
+    render_device.poll(Maintain::Wait);
+    if !matches!(map_receiver.recv(), Ok(Ok(()))) {
+        error!("failed to read back captured frame");
+        return;
+    }
+
+    let row_bytes = (pending.width * 4) as usize;
+    let mut data = Vec::with_capacity(row_bytes * pending.height as usize);
+    {
+        let mapped = slice.get_mapped_range();
+        for row in mapped.chunks(pending.padded_bytes_per_row as usize) {
+            data.extend_from_slice(&row[..row_bytes]);
+        }
+    }
+    pending.buffer.unmap();
+
+    let _ = sender.0.send(CapturedFrame {
+        paths: pending.paths,
+        width: pending.width,
+        height: pending.height,
+        data
+    });
+}
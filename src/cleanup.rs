@@ -0,0 +1,169 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::prelude::{Commands, Component, ComputedVisibility, Entity, GlobalTransform, Query, Res, ResMut, Resource, With, Without};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::RigidBody;
+use serde::Deserialize;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::destruction::Fragment;
+use crate::fixed_time::AddTickSystem;
+use crate::floating_origin::FloatingOrigin;
+use crate::free_control::ActiveControl;
+use crate::pool::{PhysicsPool, Pooled};
+use crate::shooter::Projectile;
+
+/// Gets rid of dynamic bodies that are lost, ones further than [CleanupPolicy::max_distance] from
+/// the entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with
+/// marker [T], or below [CleanupPolicy::kill_plane], so cubes knocked off the edge don't fall
+/// forever. Pooled bodies (projectiles, debris) go back to the [PhysicsPool], anything else is
+/// despawned.
+///
+/// Bodies past the distance are only cleaned up once they're out of view, so nothing disappears in
+/// front of the camera. The kill plane goes by absolute height, [FloatingOrigin::offset] included.
+/// Bodies are only checked on ticks, since they don't move in between.
+///
+/// The policy comes from [CleanupConfig], unless the body has its own [Cleanup], which prefabs
+/// get from their `cleanup` (see [PrefabPlugin](crate::prefab::PrefabPlugin)). The
+/// `cleanup_distance` and `kill_plane` console commands change the default policy, and `cleanup`
+/// prints it along with how many bodies were cleaned up.
+pub struct CleanupPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for CleanupPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for CleanupPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CleanupConfig>() {
+            app.insert_resource(CleanupConfig::default());
+        }
+        app
+            .init_resource::<PhysicsPool>()
+            .init_resource::<CleanupStats>()
+            .add_tick_system(clean_up_bodies::<T>)
+            .add_console_command("cleanup", "prints the cleanup policy and how many bodies it removed", |world, _| {
+                let policy = world.resource::<CleanupConfig>().policy;
+                let removed = world.resource::<CleanupStats>().removed;
+                console_print(world, format!(
+                    "max distance {}, kill plane {}, {} cleaned up",
+                    format_limit(policy.max_distance), format_limit(policy.kill_plane), removed
+                ));
+            })
+            .add_console_command("cleanup_distance", "sets how far from the camera bodies are cleaned up, or off", |world, args| {
+                match parse_limit(args.first().copied()) {
+                    Some(limit) => world.resource_mut::<CleanupConfig>().policy.max_distance = limit.filter(|distance| *distance > 0.0),
+                    None => console_print(world, "usage: cleanup_distance <distance|off>")
+                }
+            })
+            .add_console_command("kill_plane", "sets the height below which bodies are cleaned up, or off", |world, args| {
+                match parse_limit(args.first().copied()) {
+                    Some(limit) => world.resource_mut::<CleanupConfig>().policy.kill_plane = limit,
+                    None => console_print(world, "usage: kill_plane <height|off>")
+                }
+            });
+    }
+}
+
+/// `Some(None)` for off
+fn parse_limit(arg: Option<&str>) -> Option<Option<f32>> {
+    match arg {
+        Some("off") => Some(None),
+        Some(limit) => limit.parse::<f32>().ok().map(Some),
+        None => None
+    }
+}
+
+fn format_limit(limit: Option<f32>) -> String {
+    limit.map_or_else(|| "off".to_string(), |limit| format!("{:.1}", limit))
+}
+
+#[derive(Resource, Clone)]
+pub struct CleanupConfig {
+    /// For bodies without their own [Cleanup]
+    pub policy: CleanupPolicy
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            policy: CleanupPolicy::default()
+        }
+    }
+}
+
+/// When a body is lost, either limit can be turned off with `None`, and a body with both turned
+/// off is never cleaned up
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct CleanupPolicy {
+    /// Distance from the controlled entity
+    pub max_distance: Option<f32>,
+    /// Absolute height
+    pub kill_plane: Option<f32>
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        Self {
+            max_distance: Some(500.0),
+            kill_plane: Some(-50.0)
+        }
+    }
+}
+
+/// Cleans a body up by its own policy instead of [CleanupConfig::policy]
+#[derive(Component, Copy, Clone, Debug)]
+pub struct Cleanup(pub CleanupPolicy);
+
+#[derive(Resource, Default)]
+pub struct CleanupStats {
+    /// Bodies cleaned up so far, pooled ones included
+    pub removed: usize
+}
+
+fn clean_up_bodies<T: Component>(
+    mut commands: Commands,
+    config: Res<CleanupConfig>,
+    origin: Option<Res<FloatingOrigin>>,
+    active: Res<ActiveControl<T>>,
+    mut pool: ResMut<PhysicsPool>,
+    mut stats: ResMut<CleanupStats>,
+    cameras: Query<&GlobalTransform, With<T>>,
+    bodies: Query<(Entity, &RigidBody, &GlobalTransform, Option<&Cleanup>, Option<&ComputedVisibility>, Option<&Pooled>), Without<T>>
+) {
+    let camera = active.entity
+        .and_then(|entity| cameras.get(entity).ok())
+        .map(|transform| transform.translation());
+    let height_offset = origin.map_or(0.0, |origin| origin.offset.y as f32);
+    for (entity, body, transform, cleanup, visibility, pooled) in &bodies {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let policy = cleanup.map_or(config.policy, |cleanup| cleanup.0);
+        let position = transform.translation();
+        let below = policy.kill_plane.map_or(false, |kill_plane| position.y + height_offset < kill_plane);
+        let far = match (policy.max_distance, camera) {
+            (Some(max_distance), Some(camera)) => position.distance(camera) > max_distance
+                && !visibility.map_or(false, |visibility| visibility.is_visible()),
+            _ => false
+        };
+        if !below && !far {
+            continue;
+        }
+        stats.removed += 1;
+        match pooled {
+            Some(pooled) => {
+                pool.release(&mut commands, entity, pooled);
+                commands.entity(entity).remove::<(Projectile, Fragment)>();
+            }
+            None => commands.entity(entity).despawn_recursive()
+        }
+    }
+}
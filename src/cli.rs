@@ -0,0 +1,236 @@
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::process;
+use bevy::app::{App, AppExit, Plugin};
+use bevy::ecs::event::Events;
+use bevy::log::{error, info};
+use bevy::prelude::{Commands, Component, Local, Res, Resource, State, World};
+use bevy::utils::default;
+use crate::bench::BenchConfig;
+use crate::camera_path::{start_playback, CameraPath};
+use crate::determinism::DeterminismConfig;
+use crate::fixed_time::FixedTime;
+use crate::free_control::ActiveControl;
+use crate::game_state::GameState;
+use crate::save::{load_world, SaveConfig};
+use crate::scenario::{Scenario, ScenarioConfig};
+
+const USAGE: &str = "\
+usage: bevy_playground [options]
+
+  --fullscreen                start in borderless fullscreen
+  --scene <file>              load a saved scene on startup, saving goes back to it
+  --scenario <name>           start in a scenario: playground (the default), empty,
+                              physics-stack, terrain or vehicle-test
+  --tickrate <ticks>          fixed time ticks per second, 60 by default
+  --headless-steps <n>        run n ticks without a window, then exit
+  --replay <file>             play a camera path saved with camera_path_save on startup
+  --checksum-dump <file>      write each tick's physics checksum to a file
+  --checksum-compare <file>   warn when the checksums stop matching a dumped run
+  --bench <ticks>             measure a run of this many ticks, write the metrics and exit
+  --bench-output <file>       where to write the metrics, JSON if it ends in .json and
+                              CSV otherwise, bench.csv by default
+  --help                      print this";
+
+/// Options given on the command line
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CliArgs {
+    pub fullscreen: bool,
+    pub scene: Option<PathBuf>,
+    pub scenario: Option<Scenario>,
+    pub tickrate: Option<f64>,
+    pub headless_steps: Option<u64>,
+    pub replay: Option<PathBuf>,
+    pub checksum_dump: Option<PathBuf>,
+    pub checksum_compare: Option<PathBuf>,
+    pub bench: Option<u64>,
+    pub bench_output: Option<PathBuf>
+}
+
+impl CliArgs {
+    /// Parses the arguments of this process, printing the usage and exiting if they're invalid
+    /// or `--help` was given
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("{}\n\n{}", e, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    /// Parses `args` (not including the program name), `None` when `--help` is one of them
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--fullscreen" => parsed.fullscreen = true,
+                "--scene" => parsed.scene = Some(value()?.into()),
+                "--scenario" => {
+                    let scenario = value()?;
+                    parsed.scenario = Some(scenario
+                        .parse::<Scenario>()
+                        .map_err(|_| format!("unknown scenario {}", scenario))?);
+                }
+                "--tickrate" => {
+                    let tickrate = value()?;
+                    parsed.tickrate = Some(tickrate
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|tickrate| *tickrate > 0.0)
+                        .ok_or_else(|| format!("invalid tickrate {}", tickrate))?);
+                }
+                "--headless-steps" => {
+                    let steps = value()?;
+                    parsed.headless_steps = Some(steps
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid step count {}", steps))?);
+                }
+                "--replay" => parsed.replay = Some(value()?.into()),
+                "--checksum-dump" => parsed.checksum_dump = Some(value()?.into()),
+                "--checksum-compare" => parsed.checksum_compare = Some(value()?.into()),
+                "--bench" => {
+                    let ticks = value()?;
+                    parsed.bench = Some(ticks
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|ticks| *ticks > 0)
+                        .ok_or_else(|| format!("invalid tick count {}", ticks))?);
+                }
+                "--bench-output" => parsed.bench_output = Some(value()?.into()),
+                "--help" | "-h" => return Ok(None),
+                _ => return Err(format!("unknown argument {}", arg))
+            }
+        }
+        Ok(Some(parsed))
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless_steps.is_some()
+    }
+}
+
+/// Feeds [CliArgs] into the rest of the playground, the tickrate into [FixedTime], the scene
+/// into the [SavePlugin](crate::save::SavePlugin), the scenario into the [ScenarioConfig], the
+/// replay into the [CameraPath] of the entity controlled with marker [T], the checksum files
+/// into the [DeterminismConfig] and the bench into the [BenchConfig]. Needs to be added before the
+/// [FixedTimePlugin](crate::fixed_time::FixedTimePlugin), the
+/// [DeterminismPlugin](crate::determinism::DeterminismPlugin), the
+/// [ScenarioPlugin](crate::scenario::ScenarioPlugin) and the
+/// [BenchPlugin](crate::bench::BenchPlugin).
+///
+/// The window mode has to be given to Bevy's `WindowPlugin` and headless runs need Bevy's winit
+/// and rendering left out, so `--fullscreen` and `--headless-steps` are only partly handled
+/// here, see `main`. For headless runs this replaces the app's runner with one that updates
+/// until the given number of ticks has run in [GameState::Running].
+pub struct CliPlugin<T: Component> {
+    args: CliArgs,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> CliPlugin<T> {
+    pub fn new(args: CliArgs) -> Self {
+        Self {
+            args,
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for CliPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.args.clone());
+        if let Some(tick_rate) = self.args.tickrate {
+            app.insert_resource(FixedTime {
+                tick_rate,
+                ..default()
+            });
+        }
+        if self.args.checksum_dump.is_some() || self.args.checksum_compare.is_some() {
+            app.insert_resource(DeterminismConfig {
+                dump: self.args.checksum_dump.clone(),
+                compare: self.args.checksum_compare.clone()
+            });
+        }
+        if let Some(startup) = self.args.scenario {
+            app.insert_resource(ScenarioConfig {
+                startup,
+                ..default()
+            });
+        }
+        if self.args.bench.is_some() {
+            let mut config = BenchConfig {
+                ticks: self.args.bench,
+                ..default()
+            };
+            if let Some(output) = &self.args.bench_output {
+                config.output = output.clone();
+            }
+            app.insert_resource(config);
+        }
+        if self.args.scene.is_some() {
+            app.add_startup_system(load_cli_scene);
+        }
+        if let Some(replay) = &self.args.replay {
+            match CameraPath::<T>::load_points(replay) {
+                Ok(points) => {
+                    app
+                        .insert_resource(CameraPath::<T> { points, ..default() })
+                        .add_system(start_replay::<T>);
+                }
+                Err(e) => error!("failed to load replay {}: {}", replay.display(), e)
+            }
+        }
+        if let Some(steps) = self.args.headless_steps {
+            app.set_runner(move |app| run_headless(app, steps));
+        }
+    }
+}
+
+fn load_cli_scene(world: &mut World) {
+    let Some(scene) = world.resource::<CliArgs>().scene.clone() else {
+        return;
+    };
+    world.resource_mut::<SaveConfig>().path = scene;
+    load_world(world);
+}
+
+fn start_replay<T: Component>(
+    mut commands: Commands,
+    state: Option<Res<State<GameState>>>,
+    active: Res<ActiveControl<T>>,
+    mut started: Local<bool>
+) {
+    if *started || state.map_or(false, |state| *state.current() != GameState::Running) {
+        return;
+    }
+    if let Some(entity) = active.entity {
+        start_playback(&mut commands, entity);
+        *started = true;
+    }
+}
+
+fn run_headless(mut app: App, steps: u64) {
+    let mut ran = 0;
+    while ran < steps {
+        app.update();
+        if app.world.get_resource::<Events<AppExit>>().map_or(false, |exits| !exits.is_empty()) {
+            break;
+        }
+        // loading doesn't count towards the steps
+        let running = app.world
+            .get_resource::<State<GameState>>()
+            .map_or(true, |state| *state.current() == GameState::Running);
+        if running {
+            ran += 1;
+        }
+    }
+    info!("ran {} headless steps", ran);
+}
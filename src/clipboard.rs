@@ -0,0 +1,288 @@
+use std::any::TypeId;
+use bevy::app::{App, Plugin};
+use bevy::asset::Handle;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{AppTypeRegistry, Children, Entity, Mesh, Parent, ReflectComponent, Resource, SpatialBundle, Transform, World};
+use bevy::reflect::{Reflect, TypeRegistry};
+use bevy_rapier3d::prelude::{Collider, ColliderMassProperties, RigidBody};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::edit_history::record_spawned;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::PickTarget;
+use crate::placement::SurfaceAlignment;
+use crate::save::Saved;
+use crate::selection::Selection;
+
+/// Copying and pasting entities. Copying takes the [Selection], or the [PickTarget] when nothing
+/// is selected, capturing every reflected component along with the rapier body and collider (which
+/// aren't reflectable) and the mesh and material. Pasting puts the copies where the picking ray
+/// hits, [ClipboardConfig::paste_offset] off the surface, keeping how they were laid out around
+/// each other (turned onto the surface, if the [SurfaceAlignment] says so), and selects them. This
+/// plugin can be initialized in two ways:
+///
+/// * No default bindings [ClipboardPlugin::new]
+/// * K copies, O pastes and U duplicates (copies and pastes right next to the originals)
+///  [ClipboardPlugin::default]
+///
+/// Only [Saved] entities are copied. Prefabs stay marked as the prefab they came from since
+/// [PrefabInstance](crate::prefab::PrefabInstance) is reflected, and each paste is a single edit
+/// for the [EditHistoryPlugin](crate::edit_history::EditHistoryPlugin). Also registers the `copy`
+/// and `paste` console commands.
+pub struct ClipboardPlugin {
+    key_bindings: KeyBindingPlugin<ClipboardControls>
+}
+
+impl ClipboardPlugin {
+    /// Creates a new `ClipboardPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("clipboard")
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: ClipboardControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for ClipboardPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(K, ClipboardControls::Copy)
+            .bind(O, ClipboardControls::Paste)
+            .bind(U, ClipboardControls::Duplicate)
+    }
+}
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ClipboardConfig>() {
+            app.insert_resource(ClipboardConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Clipboard>()
+            .add_system(clipboard_controls)
+            .add_console_command("copy", "copies the selection, or what's under the crosshair", |world, _| {
+                let copied = copy(world);
+                console_print(world, format!("copied {} entities", copied));
+            })
+            .add_console_command("paste", "pastes the copied entities under the crosshair", |world, _| {
+                let pasted = paste_at_target(world);
+                console_print(world, format!("pasted {} entities", pasted));
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum ClipboardControls {
+    Copy,
+    Paste,
+    /// Copies, then pastes [ClipboardConfig::duplicate_offset] away from the originals
+    Duplicate
+}
+
+#[derive(Resource, Clone)]
+pub struct ClipboardConfig {
+    /// How far off the surface pasted entities are put, so they don't end up inside it
+    pub paste_offset: f32,
+    /// How far duplicates are from the originals
+    pub duplicate_offset: Vec3
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            paste_offset: 0.5,
+            duplicate_offset: Vec3::new(0.5, 0.0, 0.5)
+        }
+    }
+}
+
+/// A copied entity
+struct ClipboardEntry {
+    transform: Transform,
+    /// Every reflected component, besides the hierarchy and asset handles
+    components: Vec<Box<dyn Reflect>>,
+    mesh: Option<Handle<Mesh>>,
+    material: Option<Handle<StandardMaterial>>,
+    body: Option<RigidBody>,
+    collider: Option<Collider>,
+    mass: Option<ColliderMassProperties>
+}
+
+/// The copied entities
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    entries: Vec<ClipboardEntry>,
+    /// The middle of the copied entities, pasting puts it where the entities are pasted
+    center: Vec3
+}
+
+impl Clipboard {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+fn clipboard_controls(world: &mut World) {
+    let binds = world.resource::<Input<ClipboardControls>>();
+    let (copy_pressed, paste_pressed, duplicate_pressed) = (
+        binds.just_pressed(ClipboardControls::Copy),
+        binds.just_pressed(ClipboardControls::Paste),
+        binds.just_pressed(ClipboardControls::Duplicate)
+    );
+    if copy_pressed {
+        info!("copied {} entities", copy(world));
+    }
+    if paste_pressed {
+        paste_at_target(world);
+    }
+    if duplicate_pressed && copy(world) > 0 {
+        let clipboard = world.resource::<Clipboard>();
+        let at = clipboard.center + world.resource::<ClipboardConfig>().duplicate_offset;
+        paste(world, at);
+    }
+}
+
+/// Copies the [Selection], or the [PickTarget] without one, returning how many entities were
+/// copied. Nothing to copy leaves the clipboard as it was
+pub fn copy(world: &mut World) -> usize {
+    let selected = world.get_resource::<Selection>()
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| selection.iter().collect::<Vec<_>>());
+    let entities = selected
+        .or_else(|| world.get_resource::<PickTarget>().and_then(|target| target.entity).map(|entity| vec![entity]))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entity| world.get::<Saved>(*entity).is_some())
+        .collect::<Vec<_>>();
+    if entities.is_empty() {
+        return 0;
+    }
+
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+    let entries = entities.into_iter()
+        .filter_map(|entity| capture(world, &registry, entity))
+        .collect::<Vec<_>>();
+    let center = entries.iter().map(|entry| entry.transform.translation).sum::<Vec3>() / entries.len().max(1) as f32;
+    let copied = entries.len();
+    world.insert_resource(Clipboard { entries, center });
+    copied
+}
+
+fn capture(world: &World, registry: &TypeRegistry, entity: Entity) -> Option<ClipboardEntry> {
+    let entity_ref = world.get_entity(entity)?;
+    // the copy gets its own place in the hierarchy (none), and the handles are copied as they are
+    let skipped = [
+        TypeId::of::<Parent>(),
+        TypeId::of::<Children>(),
+        TypeId::of::<Handle<Mesh>>(),
+        TypeId::of::<Handle<StandardMaterial>>()
+    ];
+    let components = entity_ref.archetype().components()
+        .filter_map(|id| world.components().get_info(id)?.type_id())
+        .filter(|type_id| !skipped.contains(type_id))
+        .filter_map(|type_id| registry.get(type_id)?.data::<ReflectComponent>())
+        .filter_map(|reflect| reflect.reflect(world, entity))
+        .map(|component| component.clone_value())
+        .collect();
+    Some(ClipboardEntry {
+        transform: entity_ref.get::<Transform>().copied().unwrap_or_default(),
+        components,
+        mesh: entity_ref.get::<Handle<Mesh>>().cloned(),
+        material: entity_ref.get::<Handle<StandardMaterial>>().cloned(),
+        body: entity_ref.get::<RigidBody>().copied(),
+        collider: entity_ref.get::<Collider>().cloned(),
+        mass: entity_ref.get::<ColliderMassProperties>().copied()
+    })
+}
+
+/// Pastes where the [PickTarget] ray hits, or a bit along it when it doesn't hit anything,
+/// returning how many entities were pasted
+pub fn paste_at_target(world: &mut World) -> usize {
+    let offset = world.resource::<ClipboardConfig>().paste_offset;
+    let alignment = world.get_resource::<SurfaceAlignment>().copied().unwrap_or_default();
+    let (at, rotation) = world.get_resource::<PickTarget>()
+        .map(|target| match target.entity {
+            Some(_) => (target.point + target.normal * offset, alignment.rotation(target.normal)),
+            None => (target.ray_origin + target.ray_direction * 5.0, Quat::IDENTITY)
+        })
+        .unwrap_or_default();
+    paste_turned(world, at, rotation)
+}
+
+/// Pastes the copied entities with their middle at `at`, selecting them and recording it as one
+/// edit. Returns how many entities were pasted
+pub fn paste(world: &mut World, at: Vec3) -> usize {
+    paste_turned(world, at, Quat::IDENTITY)
+}
+
+/// Like [paste], with everything turned by `rotation` around the middle
+pub fn paste_turned(world: &mut World, at: Vec3, rotation: Quat) -> usize {
+    // taken out to spawn from it while changing the world
+    let Some(clipboard) = world.remove_resource::<Clipboard>() else {
+        return 0;
+    };
+    let registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = registry.read();
+
+    let mut pasted = Vec::new();
+    for entry in &clipboard.entries {
+        let transform = Transform {
+            translation: at + rotation * (entry.transform.translation - clipboard.center),
+            rotation: rotation * entry.transform.rotation,
+            ..entry.transform
+        };
+        let entity = world.spawn(SpatialBundle::from_transform(transform)).id();
+        for component in &entry.components {
+            let Some(reflect) = registry.get_with_name(component.type_name()).and_then(|registration| registration.data::<ReflectComponent>()) else {
+                continue;
+            };
+            reflect.insert(world, entity, &**component);
+        }
+        let mut entity = world.entity_mut(entity);
+        // the reflected transform is the original's
+        entity.insert(transform);
+        if let Some(mesh) = &entry.mesh {
+            entity.insert(mesh.clone());
+        }
+        if let Some(material) = &entry.material {
+            entity.insert(material.clone());
+        }
+        if let Some(body) = entry.body {
+            entity.insert(body);
+        }
+        if let Some(collider) = &entry.collider {
+            entity.insert(collider.clone());
+        }
+        if let Some(mass) = entry.mass {
+            entity.insert(mass);
+        }
+        pasted.push(entity.id());
+    }
+    drop(registry);
+    world.insert_resource(clipboard);
+
+    if let Some(mut selection) = world.get_resource_mut::<Selection>() {
+        selection.clear();
+        for entity in &pasted {
+            selection.select(*entity);
+        }
+    }
+    let count = pasted.len();
+    record_spawned(world, pasted);
+    count
+}
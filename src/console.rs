@@ -0,0 +1,402 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use bevy::app::{App, Plugin};
+use bevy::asset::AssetServer;
+use bevy::hierarchy::BuildChildren;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::prelude::{Color, Commands, Component, EventReader, IntoSystemDescriptor, KeyCode, Mut, NodeBundle, Query, Res, ResMut, Resource, Style, TextBundle, Visibility, With, World};
+use bevy::text::{Text, TextStyle};
+use bevy::ui::{PositionType, Size, UiRect, Val};
+use bevy::utils::{default, HashMap};
+use bevy::window::ReceivedCharacter;
+use serde::{Deserialize, Serialize};
+use crate::hud::HudConfig;
+use crate::keybind::{InputCapture, KeyBindingPlugin, KeyBindings, RawInput};
+
+/// A text console for running commands, other plugins register commands through
+/// [AddConsoleCommand::add_console_command]. This plugin can be initialized in two ways:
+///
+/// * No default bindings [ConsolePlugin::new]
+/// * The grave key (`) opens and closes the console [ConsolePlugin::default]
+///
+/// Commands can also be bound to inputs directly with [ConsolePlugin::bind_command], for example
+/// binding F5 to `save`, which runs the command whenever the input is pressed as if it was typed.
+/// [bind_console_command] does the same while running. The command lines run while
+/// [ConsoleState::start_recording] is in effect are recorded, which is what
+/// [ConsoleMacroPlugin](crate::console_macro::ConsoleMacroPlugin) builds on.
+///
+/// While open the console holds the [InputCapture], so typing doesn't also trigger bindings.
+pub struct ConsolePlugin {
+    key_bindings: KeyBindingPlugin<ConsoleControls>,
+    command_bindings: KeyBindingPlugin<CommandBind>,
+    bound_commands: Vec<String>
+}
+
+impl ConsolePlugin {
+    /// Creates a new `ConsolePlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            // the toggle has to keep working while the console holds the input capture
+            key_bindings: KeyBindingPlugin::default().ignore_capture(),
+            command_bindings: KeyBindingPlugin::default(),
+            bound_commands: Vec::new()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: ConsoleControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+
+    /// Runs `command` (a full command line, including arguments) whenever `input` is pressed
+    pub fn bind_command(mut self, input: impl Into<RawInput>, command: impl Into<String>) -> Self {
+        let command = command.into();
+        let index = match self.bound_commands.iter().position(|bound| *bound == command) {
+            Some(index) => index,
+            None => {
+                self.bound_commands.push(command);
+                self.bound_commands.len() - 1
+            }
+        };
+        self.command_bindings = self.command_bindings.bind(input, CommandBind(index));
+        self
+    }
+}
+
+impl Default for ConsolePlugin {
+    fn default() -> Self {
+        Self::new().bind(KeyCode::Grave, ConsoleControls::Toggle)
+    }
+}
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_plugin(self.command_bindings.clone())
+            .init_resource::<ConsoleCommands>()
+            .init_resource::<ConsoleState>()
+            .insert_resource(BoundCommands(self.bound_commands.clone()))
+            .add_startup_system(spawn_console)
+            .add_system(console_input)
+            .add_system(dispatch_bound_commands)
+            .add_system(run_console_commands.after(console_input).after(dispatch_bound_commands))
+            .add_system(update_console.after(run_console_commands))
+            .add_console_command("help", "lists every command", |world, _| {
+                let mut help = world
+                    .resource::<ConsoleCommands>()
+                    .commands
+                    .iter()
+                    .map(|(name, command)| format!("{} - {}", name, command.help))
+                    .collect::<Vec<_>>();
+                help.sort();
+                for line in help {
+                    console_print(world, line);
+                }
+            })
+            .add_console_command("echo", "prints its arguments", |world, args| {
+                console_print(world, args.join(" "));
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum ConsoleControls {
+    /// Opens or closes the console
+    Toggle
+}
+
+/// An action standing in for a bound command line, the index into the plugin's bound commands.
+/// Bindings need `Copy` actions, which a command line can't be
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CommandBind(usize);
+
+#[derive(Resource)]
+struct BoundCommands(Vec<String>);
+
+type CommandFn = Arc<dyn Fn(&mut World, &[&str]) + Send + Sync>;
+
+struct ConsoleCommand {
+    help: String,
+    run: CommandFn
+}
+
+/// Every registered command, by name
+#[derive(Resource, Default)]
+pub struct ConsoleCommands {
+    commands: HashMap<String, ConsoleCommand>
+}
+
+impl ConsoleCommands {
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    /// Registers a command while running, replacing any with the same name, see
+    /// [AddConsoleCommand::add_console_command]
+    pub fn insert(&mut self, name: &str, help: &str, run: impl Fn(&mut World, &[&str]) + Send + Sync + 'static) {
+        self.commands.insert(name.to_string(), ConsoleCommand {
+            help: help.to_string(),
+            run: Arc::new(run)
+        });
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.commands.remove(name).is_some()
+    }
+}
+
+pub trait AddConsoleCommand {
+    /// Registers a console command, `run` gets the world and the arguments following the name.
+    /// This works whether or not the [ConsolePlugin] is added yet (or at all)
+    fn add_console_command(
+        &mut self,
+        name: &str,
+        help: &str,
+        run: impl Fn(&mut World, &[&str]) + Send + Sync + 'static
+    ) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(
+        &mut self,
+        name: &str,
+        help: &str,
+        run: impl Fn(&mut World, &[&str]) + Send + Sync + 'static
+    ) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        self.world.resource_mut::<ConsoleCommands>().insert(name, help, run);
+        self
+    }
+}
+
+/// The console's open state, typed line, output and queued command lines
+#[derive(Resource, Default)]
+pub struct ConsoleState {
+    pub open: bool,
+    pub input: String,
+    pub log: VecDeque<String>,
+    queue: Vec<String>,
+    recording: Option<Vec<String>>,
+    left_out: bool
+}
+
+const LOG_LINES: usize = 12;
+
+impl ConsoleState {
+    /// Queues a command line to run at the end of this frame's console systems
+    pub fn run(&mut self, command: impl Into<String>) {
+        self.queue.push(command.into());
+    }
+
+    /// Starts recording the queued command lines that run, over again if it already was.
+    /// Lines of unknown commands and of commands starting or stopping the recording are left out,
+    /// as are those of commands calling [ConsoleState::leave_out]
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stops recording, returning the lines recorded if it was
+    pub fn stop_recording(&mut self) -> Option<Vec<String>> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Keeps the line of the command running right now out of the recording
+    pub fn leave_out(&mut self) {
+        self.left_out = true;
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        info!("console: {}", line);
+        self.log.push_back(line);
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+}
+
+/// Prints a line to the console, for use inside of commands
+pub fn console_print(world: &mut World, line: impl Into<String>) {
+    if let Some(mut state) = world.get_resource_mut::<ConsoleState>() {
+        state.print(line);
+    } else {
+        info!("console: {}", line.into());
+    }
+}
+
+#[derive(Component)]
+struct ConsolePanel;
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn spawn_console(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(0.0),
+                bottom: Val::Px(0.0),
+                ..default()
+            },
+            size: Size::new(Val::Percent(100.0), Val::Auto),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.75).into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(ConsolePanel)
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section("", TextStyle {
+                font: asset_server.load(hud_config.font_path.as_str()),
+                font_size: hud_config.font_size,
+                color: Color::WHITE
+            }))
+                .insert(ConsoleText);
+        });
+}
+
+fn console_input(
+    binds: Res<Input<ConsoleControls>>,
+    key_codes: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut capture: ResMut<InputCapture>,
+    mut state: ResMut<ConsoleState>
+) {
+    if binds.just_pressed(ConsoleControls::Toggle) {
+        state.open = !state.open;
+        capture.set("console", state.open);
+        // the toggle key itself comes in as a character too
+        characters.clear();
+        return;
+    }
+    if !state.open {
+        characters.clear();
+        return;
+    }
+
+    for character in characters.iter() {
+        if !character.char.is_control() {
+            state.input.push(character.char);
+        }
+    }
+    if key_codes.just_pressed(KeyCode::Back) {
+        state.input.pop();
+    }
+    if key_codes.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut state.input);
+        if !line.trim().is_empty() {
+            state.print(format!("> {}", line));
+            state.run(line);
+        }
+    }
+}
+
+fn dispatch_bound_commands(
+    binds: Res<Input<CommandBind>>,
+    bound: Res<BoundCommands>,
+    mut state: ResMut<ConsoleState>
+) {
+    for CommandBind(index) in binds.get_just_pressed() {
+        if let Some(command) = bound.0.get(*index) {
+            state.run(command.clone());
+        }
+    }
+}
+
+/// Runs every queued command line
+pub fn run_console_commands(world: &mut World) {
+    // checked first so the state isn't marked as changed every frame
+    if world.resource::<ConsoleState>().queue.is_empty() {
+        return;
+    }
+    let queue = std::mem::take(&mut world.resource_mut::<ConsoleState>().queue);
+    for line in queue {
+        let was_recording = world.resource::<ConsoleState>().is_recording();
+        let known = run_console_line(world, &line);
+        let mut state = world.resource_mut::<ConsoleState>();
+        let left_out = std::mem::take(&mut state.left_out);
+        // a line starting the recording isn't part of it, nor is one stopping it
+        if known && was_recording && !left_out {
+            if let Some(recording) = &mut state.recording {
+                recording.push(line);
+            }
+        }
+    }
+}
+
+/// Runs a command line right away instead of queueing it, returning whether the command exists
+pub fn run_console_line(world: &mut World, line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return false;
+    };
+    let args = words.collect::<Vec<_>>();
+
+    // cloned out so the command is free to use the registry itself (like help does)
+    let run = world.resource_scope(|_, commands: Mut<ConsoleCommands>| {
+        commands.commands.get(name).map(|command| command.run.clone())
+    });
+    match run {
+        Some(run) => {
+            run(world, &args);
+            true
+        }
+        None => {
+            console_print(world, format!("unknown command `{}`, try `help`", name));
+            false
+        }
+    }
+}
+
+/// Like [ConsolePlugin::bind_command] while running, replacing whatever `input` was bound to. Does
+/// nothing without a [ConsolePlugin]
+pub fn bind_console_command(world: &mut World, input: RawInput, command: impl Into<String>) {
+    let command = command.into();
+    let Some(mut bound) = world.get_resource_mut::<BoundCommands>() else {
+        return;
+    };
+    let index = match bound.0.iter().position(|bound| *bound == command) {
+        Some(index) => index,
+        None => {
+            bound.0.push(command);
+            bound.0.len() - 1
+        }
+    };
+    world.resource_mut::<KeyBindings<CommandBind>>().rebind(input, CommandBind(index));
+}
+
+fn update_console(
+    state: Res<ConsoleState>,
+    mut panels: Query<&mut Visibility, With<ConsolePanel>>,
+    mut texts: Query<&mut Text, With<ConsoleText>>
+) {
+    if !state.is_changed() {
+        return;
+    }
+    for mut visibility in &mut panels {
+        visibility.is_visible = state.open;
+    }
+
+    let mut value = state.log.iter().cloned().collect::<Vec<_>>().join("\n");
+    if !value.is_empty() {
+        value.push('\n');
+    }
+    value += &format!("> {}_", state.input);
+    for mut text in &mut texts {
+        text.sections[0].value = value.clone();
+    }
+}
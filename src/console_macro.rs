@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use bevy::app::{App, Plugin};
+use bevy::prelude::{Resource, World};
+use serde::{Deserialize, Serialize};
+use crate::console::{bind_console_command, console_print, run_console_line, AddConsoleCommand, ConsoleState};
+use crate::keybind::RawInput;
+use crate::settings::AddSetting;
+
+/// Named sequences of console command lines, recorded from the console and played back with one
+/// command, or one input, so scene setups made of many commands can be done over again quickly.
+///
+/// `macro_record <name>` starts recording what the console runs, `macro_stop` stops and keeps it,
+/// and `macro_play <name>` runs every line of it in order, right away. `macro_bind <input> <name>`
+/// binds an input (written the way RON writes it, like `KeyCode(F6)`) to playing it, through
+/// [bind_console_command]. `macros` lists every macro and `macro_delete <name>` forgets one.
+///
+/// Macros and their bindings are kept in the `macros` section of the settings file (see
+/// [SettingsPlugin](crate::settings::SettingsPlugin)), bindings are made again on startup. Playing
+/// a macro from inside itself is refused, so one can't play itself forever.
+pub struct ConsoleMacroPlugin;
+
+impl Plugin for ConsoleMacroPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_setting::<ConsoleMacros>("macros")
+            .init_resource::<MacroState>()
+            .add_startup_system(bind_macros)
+            .add_console_command("macro_record", "starts recording the commands run into a macro", |world, args| {
+                let Some(name) = args.first() else {
+                    console_print(world, "usage: macro_record <name>");
+                    return;
+                };
+                if world.resource::<ConsoleState>().is_recording() {
+                    world.resource_mut::<ConsoleState>().leave_out();
+                    console_print(world, "already recording, stop with `macro_stop` first");
+                    return;
+                }
+                world.resource_mut::<ConsoleState>().start_recording();
+                world.resource_mut::<MacroState>().recording = Some(name.to_string());
+                console_print(world, format!("recording `{}`, stop with `macro_stop`", name));
+            })
+            .add_console_command("macro_stop", "stops recording and keeps the macro", |world, _| {
+                let recorded = world.resource_mut::<ConsoleState>().stop_recording();
+                let name = world.resource_mut::<MacroState>().recording.take();
+                let (Some(lines), Some(name)) = (recorded, name) else {
+                    console_print(world, "not recording");
+                    return;
+                };
+                console_print(world, format!("recorded {} commands into `{}`", lines.len(), name));
+                world.resource_mut::<ConsoleMacros>().macros.insert(name, lines);
+            })
+            .add_console_command("macro_play", "runs every command of a macro", |world, args| {
+                let Some(name) = args.first() else {
+                    console_print(world, "usage: macro_play <name>");
+                    return;
+                };
+                play_macro(world, name);
+            })
+            .add_console_command("macro_bind", "binds an input, like KeyCode(F6), to playing a macro", |world, args| {
+                world.resource_mut::<ConsoleState>().leave_out();
+                let (Some(input), Some(name)) = (args.first(), args.get(1)) else {
+                    console_print(world, "usage: macro_bind <input> <name>");
+                    return;
+                };
+                let Ok(input) = ron::from_str::<RawInput>(input) else {
+                    console_print(world, format!("`{}` isn't an input, they're written like KeyCode(F6)", input));
+                    return;
+                };
+                bind_console_command(world, input, format!("macro_play {}", name));
+                world.resource_mut::<ConsoleMacros>().binds.insert(name.to_string(), input);
+                console_print(world, format!("bound {:?} to `{}`", input, name));
+            })
+            .add_console_command("macros", "lists every macro and what it's bound to", |world, _| {
+                world.resource_mut::<ConsoleState>().leave_out();
+                let macros = world.resource::<ConsoleMacros>().clone();
+                if macros.macros.is_empty() {
+                    console_print(world, "no macros recorded yet");
+                }
+                for (name, lines) in &macros.macros {
+                    let bound = macros.binds.get(name).map_or_else(String::new, |input| format!(", bound to {:?}", input));
+                    console_print(world, format!("{}: {} commands{}", name, lines.len(), bound));
+                }
+            })
+            .add_console_command("macro_delete", "forgets a macro", |world, args| {
+                world.resource_mut::<ConsoleState>().leave_out();
+                let Some(name) = args.first() else {
+                    console_print(world, "usage: macro_delete <name>");
+                    return;
+                };
+                let mut macros = world.resource_mut::<ConsoleMacros>();
+                // the binding stays until restarting, playing a macro that's gone just says so
+                macros.binds.remove(*name);
+                if macros.macros.remove(*name).is_some() {
+                    console_print(world, format!("deleted `{}`", name));
+                } else {
+                    console_print(world, format!("no macro named `{}`", name));
+                }
+            });
+    }
+}
+
+/// Recorded macros by name, and the inputs bound to playing them
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsoleMacros {
+    pub macros: BTreeMap<String, Vec<String>>,
+    pub binds: BTreeMap<String, RawInput>
+}
+
+#[derive(Resource, Default)]
+struct MacroState {
+    /// Name of the macro being recorded
+    recording: Option<String>,
+    /// Macros playing right now, innermost last
+    playing: Vec<String>
+}
+
+/// Runs every line of the macro `name` right away
+pub fn play_macro(world: &mut World, name: &str) {
+    let Some(lines) = world.resource::<ConsoleMacros>().macros.get(name).cloned() else {
+        console_print(world, format!("no macro named `{}`", name));
+        return;
+    };
+    if world.resource::<MacroState>().playing.iter().any(|playing| playing == name) {
+        console_print(world, format!("`{}` plays itself, stopped there", name));
+        return;
+    }
+    world.resource_mut::<MacroState>().playing.push(name.to_string());
+    for line in lines {
+        run_console_line(world, &line);
+    }
+    world.resource_mut::<MacroState>().playing.pop();
+}
+
+fn bind_macros(world: &mut World) {
+    let binds = world.resource::<ConsoleMacros>().binds.clone();
+    for (name, input) in binds {
+        bind_console_command(world, input, format!("macro_play {}", name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::KeyCode;
+    use crate::keybind::RawInput;
+    use crate::settings::round_trip;
+    use super::ConsoleMacros;
+
+    #[test]
+    fn macros_and_their_binds_are_saved() {
+        let mut macros = ConsoleMacros::default();
+        macros.macros.insert("reset".to_string(), vec!["rewind 5".to_string(), "fps_limit off".to_string()]);
+        macros.binds.insert("reset".to_string(), RawInput::KeyCode(KeyCode::F5));
+        assert_eq!(round_trip("macros", &macros), Some(macros));
+    }
+}
@@ -1,11 +1,17 @@
+use std::time::{Duration, Instant};
 use bevy::app::{App, Plugin};
 use bevy::ecs::schedule::ShouldRun;
+use bevy::input::Input;
+use bevy::input::gamepad::GamepadButton;
+use bevy::input::mouse::{MouseMotion, MouseWheel};
 use bevy::log::info;
 use bevy::math::Vec2;
-use bevy::prelude::{EventReader, Local, Res, ResMut, Resource, State, SystemSet, Window};
+use bevy::prelude::{EventReader, EventWriter, IntoSystemDescriptor, KeyCode, Local, MouseButton, Reflect, Res, ResMut, Resource, State, SystemSet, Window};
 use bevy::window::{CursorGrabMode, WindowFocused, Windows};
+use crate::game_state::GameState;
+use crate::window_control::{add_window_mode_changed, WindowModeChanged};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Resource)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Resource, Reflect)]
 pub enum CursorGrab {
     Active,
     Inactive
@@ -29,35 +35,88 @@ impl CursorGrab {
     }
 }
 
+/// Sent by [cursor_grab] whenever it actually grabs or releases the cursor, which (unlike
+/// [CursorGrab] changing) includes focus changes
+#[derive(Copy, Clone, Debug)]
+pub struct CursorGrabChanged {
+    pub grabbed: bool
+}
+
 /// A plugin to handle cursor grabbing, or locking the cursor to the center of the window.
 /// This functionality is initialized as inactive, use the Resource `CursorGrab` to switch between
 /// active and inactive.
 ///
 /// See [cursor_grab] for details on how this works.
+///
+/// Some compositors drop the lock without telling anyone, [CursorGrabConfig::aggressive] works
+/// around that by grabbing the cursor again (and centering it) every
+/// [CursorGrabConfig::aggressive_interval] while active and focused.
+///
+/// With [CursorGrabConfig::idle_timeout] set, going that long without any input while grabbed
+/// releases the cursor (and pauses, see [CursorGrabConfig::pause_on_idle]), so a playground left
+/// running doesn't hold on to the cursor. The next click grabs it again and unpauses.
 pub struct CursorGrabPlugin;
 
 impl Plugin for CursorGrabPlugin {
     fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CursorGrabConfig>() {
+            app.insert_resource(CursorGrabConfig::default());
+        }
+        add_window_mode_changed(app);
         app
             .insert_resource(CursorGrab::Inactive)
-            .add_system(cursor_grab);
+            .register_type::<CursorGrab>()
+            .add_event::<CursorGrabChanged>()
+            .add_system(cursor_grab)
+            .add_system(reassert_cursor_grab.after(cursor_grab))
+            .add_system(release_when_idle.before(cursor_grab));
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct CursorGrabConfig {
+    /// Keeps grabbing the cursor while [CursorGrab] is active, instead of only when it changes
+    pub aggressive: bool,
+    /// How often the cursor is grabbed again in aggressive mode, zero for every frame. This is
+    /// real time, not Bevy's [Time](bevy::time::Time)
+    pub aggressive_interval: Duration,
+    /// How long the cursor stays grabbed without any input, `None` to keep it grabbed. Real time
+    /// as well
+    pub idle_timeout: Option<Duration>,
+    /// Whether releasing after [CursorGrabConfig::idle_timeout] also pauses, through the
+    /// [GameStatePlugin](crate::game_state::GameStatePlugin) if there is one
+    pub pause_on_idle: bool
+}
+
+impl Default for CursorGrabConfig {
+    fn default() -> Self {
+        Self {
+            aggressive: false,
+            aggressive_interval: Duration::ZERO,
+            idle_timeout: None,
+            pause_on_idle: true
+        }
     }
 }
 
-/// The sole system for [CursorGrabPlugin].
+/// The main system for [CursorGrabPlugin].
 ///
 /// A couple things to note about how the grabbing works (assuming that CursorGrab is Active):
 ///  if focus on the window is just lost, the cursor is released.
 ///  if focus on the window is just gained, the cursor is grabbed.
 /// These above two allow alt-tabbing to work properly, otherwise some platforms will just keep
 ///  bringing the application back into view (preventing alt tabbing)
+///  if the window mode is changed (see [WindowModeChanged]), the cursor is grabbed again, since
+///  some platforms release it when switching modes.
 pub fn cursor_grab(
     cursor_grab: Res<CursorGrab>,
     mut focus_events: EventReader<WindowFocused>,
+    mut mode_events: EventReader<WindowModeChanged>,
+    mut grab_changed: EventWriter<CursorGrabChanged>,
     mut windows: ResMut<Windows>
 ) {
     // todo needs to handle multiple windows, going to wait until Bevy updates to having Windows as Entities
-    let release = |window: &mut Window| {
+    let release = |window: &mut Window, grab_changed: &mut EventWriter<CursorGrabChanged>| {
         window.set_cursor_grab_mode(CursorGrabMode::None);
         // some platforms will not actually lock the cursor, and in those cases this will at least
         // provide the illusion that the mouse stays centered.
@@ -66,26 +125,31 @@ pub fn cursor_grab(
         // ended up leaving the cursor.
         window.set_cursor_position(Vec2::new(window.width() / 2.0, window.height() / 2.0));
         window.set_cursor_visibility(true);
+        grab_changed.send(CursorGrabChanged { grabbed: false });
     };
-    let grab = |window: &mut Window| {
+    let grab = |window: &mut Window, grab_changed: &mut EventWriter<CursorGrabChanged>| {
         window.set_cursor_grab_mode(CursorGrabMode::Locked);
         window.set_cursor_position(Vec2::new(window.width() / 2.0, window.height() / 2.0));
         window.set_cursor_visibility(false);
+        grab_changed.send(CursorGrabChanged { grabbed: true });
     };
 
-    let window = windows.primary_mut();
+    let Some(window) = windows.get_primary_mut() else {
+        return;
+    };
+    let mode_changed = mode_events.iter().count() > 0;
 
     match *cursor_grab {
         CursorGrab::Active => {
-            if cursor_grab.is_changed() && window.is_focused() {
-                grab(window);
+            if (cursor_grab.is_changed() || mode_changed) && window.is_focused() {
+                grab(window, &mut grab_changed);
                 return;
             }
         }
         CursorGrab::Inactive => {
             if cursor_grab.is_changed() {
                 if window.cursor_grab_mode() != CursorGrabMode::None {
-                    release(window);
+                    release(window, &mut grab_changed);
                 }
             }
             return;
@@ -100,9 +164,182 @@ pub fn cursor_grab(
 
     if focus_changed {
         if window.is_focused() {
-            grab(window);
+            grab(window, &mut grab_changed);
         } else {
-            release(window);
+            release(window, &mut grab_changed);
         }
     }
 }
+
+/// Grabs and centers the cursor again while [CursorGrabConfig::aggressive] is on, without sending
+/// [CursorGrabChanged] since as far as anything else knows it never stopped being grabbed
+pub fn reassert_cursor_grab(
+    config: Res<CursorGrabConfig>,
+    cursor_grab: Res<CursorGrab>,
+    mut windows: ResMut<Windows>,
+    mut last: Local<Option<Instant>>
+) {
+    if !config.aggressive || cursor_grab.is_inactive() {
+        return;
+    }
+    let Some(window) = windows.get_primary_mut() else {
+        return;
+    };
+    // releasing on focus loss is still up to cursor_grab
+    if !window.is_focused() {
+        return;
+    }
+    let now = Instant::now();
+    if last.map_or(false, |last| now - last < config.aggressive_interval) {
+        return;
+    }
+    *last = Some(now);
+    window.set_cursor_grab_mode(CursorGrabMode::Locked);
+    window.set_cursor_position(Vec2::new(window.width() / 2.0, window.height() / 2.0));
+    window.set_cursor_visibility(false);
+}
+
+#[derive(Default)]
+pub struct IdleState {
+    last_input: Option<Instant>,
+    /// Set once the timeout released the cursor, along with whether it paused as well
+    released: Option<bool>
+}
+
+/// Releases the cursor after [CursorGrabConfig::idle_timeout] without input, and grabs it again
+/// on the next click
+pub fn release_when_idle(
+    config: Res<CursorGrabConfig>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut motion_events: EventReader<MouseMotion>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut cursor_grab: ResMut<CursorGrab>,
+    game_state: Option<ResMut<State<GameState>>>,
+    mut idle: Local<IdleState>
+) {
+    let input = keys.get_pressed().next().is_some()
+        || mouse_buttons.get_pressed().next().is_some()
+        || gamepad_buttons.get_pressed().next().is_some()
+        || motion_events.iter().count() > 0
+        || wheel_events.iter().count() > 0;
+    let Some(timeout) = config.idle_timeout else {
+        idle.released = None;
+        return;
+    };
+    let now = Instant::now();
+
+    if let Some(paused) = idle.released {
+        if mouse_buttons.get_just_pressed().next().is_some() {
+            idle.released = None;
+            idle.last_input = Some(now);
+            match game_state {
+                // going back to running grabs the cursor on its own
+                Some(mut game_state) if paused => {
+                    let _ = game_state.set(GameState::Running);
+                }
+                _ => cursor_grab.activate()
+            }
+        }
+        return;
+    }
+    if cursor_grab.is_inactive() || input {
+        idle.last_input = Some(now);
+        return;
+    }
+    let last_input = *idle.last_input.get_or_insert(now);
+    if now - last_input < timeout {
+        return;
+    }
+
+    info!("releasing the cursor after {:.0} seconds without input", timeout.as_secs_f32());
+    let paused = match game_state {
+        Some(mut game_state) if config.pause_on_idle && *game_state.current() == GameState::Running => {
+            game_state.set(GameState::Paused).is_ok()
+        }
+        _ => false
+    };
+    if !paused {
+        cursor_grab.deactivate();
+    }
+    idle.released = Some(paused);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::event::Events;
+    use bevy::prelude::{Component, KeyCode, MouseButton};
+    use bevy::window::{CursorGrabMode, WindowFocused, WindowId, Windows};
+    use crate::free_control::FreeControlPlugin;
+    use crate::test_support::{headless_app, MockInput};
+    use super::{CursorGrab, CursorGrabPlugin};
+
+    #[derive(Component)]
+    struct TestCam;
+
+    fn grab_mode(app: &App) -> CursorGrabMode {
+        app.world.resource::<Windows>().primary().cursor_grab_mode()
+    }
+
+    /// Focuses or unfocuses the primary window the way winit reports it
+    fn set_focus(app: &mut App, focused: bool) {
+        app.world.resource_mut::<Windows>().primary_mut().update_focused_status_from_backend(focused);
+        app.world.resource_mut::<Events<WindowFocused>>().send(WindowFocused { id: WindowId::primary(), focused });
+        app.update();
+    }
+
+    #[test]
+    fn follows_the_resource() {
+        let mut app = headless_app();
+        app.add_plugin(CursorGrabPlugin);
+        app.update();
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+
+        app.world.resource_mut::<CursorGrab>().activate();
+        app.update();
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+        assert!(!app.world.resource::<Windows>().primary().cursor_visible());
+
+        app.world.resource_mut::<CursorGrab>().deactivate();
+        app.update();
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+        assert!(app.world.resource::<Windows>().primary().cursor_visible());
+    }
+
+    #[test]
+    fn grab_bindings_lock_and_unlock() {
+        let mut app = headless_app();
+        app
+            .add_plugin(FreeControlPlugin::<TestCam>::new().with_grab_bindings())
+            .add_plugin(CursorGrabPlugin);
+        MockInput::new()
+            .tap(MouseButton::Left)
+            .run(&mut app);
+        assert!(app.world.resource::<CursorGrab>().is_active());
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+
+        MockInput::new()
+            .tap(KeyCode::Escape)
+            .run(&mut app);
+        assert!(app.world.resource::<CursorGrab>().is_inactive());
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+    }
+
+    #[test]
+    fn releases_while_unfocused() {
+        let mut app = headless_app();
+        app.add_plugin(CursorGrabPlugin);
+        app.world.resource_mut::<CursorGrab>().activate();
+        app.update();
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+
+        set_focus(&mut app, false);
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+        // still active, so it's grabbed again on coming back
+        assert!(app.world.resource::<CursorGrab>().is_active());
+        set_focus(&mut app, true);
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+    }
+}
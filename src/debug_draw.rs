@@ -0,0 +1,242 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::BuildChildren;
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{Added, AlphaMode, Color, Commands, Component, Entity, GlobalTransform, KeyCode, Mesh, Query, Res, ResMut, Resource, Transform, Visibility, With, Without};
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::free_control::ActiveControl;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Draws a ground grid following the camera (so it never seems to end), the world axes at the
+/// origin, and small axes on every entity with the marker [T]. This plugin can be initialized
+/// in two ways:
+///
+/// * No default bindings [DebugDrawPlugin::new]
+/// * F3 toggles the drawing [DebugDrawPlugin::default]
+///
+/// The grid follows the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]. [DebugDraw] holds
+/// the settings, X is red, Y is green and Z is blue.
+pub struct DebugDrawPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<DebugDrawControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> DebugDrawPlugin<T> {
+    /// Creates a new `DebugDrawPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: DebugDrawControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for DebugDrawPlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(KeyCode::F3, DebugDrawControls::Toggle)
+    }
+}
+
+impl <T: Component> Plugin for DebugDrawPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<DebugDraw>() {
+            app.insert_resource(DebugDraw::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_startup_system(spawn_debug_draw)
+            .add_system(debug_draw_controls)
+            .add_system(add_entity_axes::<T>)
+            .add_system(follow_grid::<T>)
+            .add_system(show_debug_draw);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum DebugDrawControls {
+    Toggle
+}
+
+#[derive(Resource, Clone)]
+pub struct DebugDraw {
+    pub enabled: bool,
+    /// Whether entities with the marker get axes as well
+    pub entity_axes: bool,
+    /// Distance between grid lines, only read when the grid is spawned
+    pub grid_spacing: f32,
+    /// Number of grid lines going each way from the center, only read when the grid is spawned
+    pub grid_extent: u32
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            entity_axes: true,
+            grid_spacing: 1.0,
+            grid_extent: 100
+        }
+    }
+}
+
+#[derive(Component)]
+struct DebugGrid;
+
+/// Any part of the debug drawing, shown and hidden together, other modules can add it to their
+/// own debug visuals
+#[derive(Component)]
+pub struct DebugDrawn;
+
+/// Marks entities that already got their axes
+#[derive(Component)]
+struct HasDebugAxes;
+
+#[derive(Resource)]
+struct DebugAxes([(Handle<Mesh>, Handle<StandardMaterial>); 3]);
+
+/// A mesh of the given lines, to be drawn with an [unlit] material
+pub fn line_mesh(lines: impl IntoIterator<Item = (Vec3, Vec3)>) -> Mesh {
+    let positions = lines
+        .into_iter()
+        .flat_map(|(a, b)| [a.to_array(), b.to_array()])
+        .collect::<Vec<_>>();
+    let count = positions.len();
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    // the pbr pipeline wants these, even when unlit
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; count]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; count]);
+    mesh.set_indices(Some(Indices::U32((0..count as u32).collect())));
+    mesh
+}
+
+pub fn unlit(color: Color) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color,
+        unlit: true,
+        alpha_mode: if color.a() < 1.0 { AlphaMode::Blend } else { default() },
+        ..default()
+    }
+}
+
+fn spawn_debug_draw(
+    mut commands: Commands,
+    config: Res<DebugDraw>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    let extent = config.grid_extent as f32 * config.grid_spacing;
+    let grid = (-(config.grid_extent as i32)..=config.grid_extent as i32)
+        .flat_map(|i| {
+            let offset = i as f32 * config.grid_spacing;
+            [
+                (Vec3::new(offset, 0.0, -extent), Vec3::new(offset, 0.0, extent)),
+                (Vec3::new(-extent, 0.0, offset), Vec3::new(extent, 0.0, offset))
+            ]
+        });
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(line_mesh(grid)),
+        material: materials.add(unlit(Color::rgba(1.0, 1.0, 1.0, 0.15))),
+        visibility: Visibility { is_visible: config.enabled },
+        ..default()
+    })
+        .insert((DebugGrid, DebugDrawn, NotShadowCaster));
+
+    let axes = [(Vec3::X, Color::RED), (Vec3::Y, Color::GREEN), (Vec3::Z, Color::BLUE)]
+        .map(|(axis, color)| (meshes.add(line_mesh([(Vec3::ZERO, axis)])), materials.add(unlit(color))));
+
+    // the world axes are long enough to see the origin from anywhere
+    for (mesh, material) in axes.clone() {
+        commands.spawn(PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_scale(Vec3::splat(extent)),
+            visibility: Visibility { is_visible: config.enabled },
+            ..default()
+        })
+            .insert((DebugDrawn, NotShadowCaster));
+    }
+    commands.insert_resource(DebugAxes(axes));
+}
+
+fn add_entity_axes<T: Component>(
+    mut commands: Commands,
+    config: Res<DebugDraw>,
+    axes: Option<Res<DebugAxes>>,
+    entities: Query<Entity, (With<T>, Without<HasDebugAxes>)>
+) {
+    let Some(axes) = axes else {
+        return;
+    };
+    if !config.entity_axes {
+        return;
+    }
+    for entity in &entities {
+        commands.entity(entity)
+            .insert(HasDebugAxes)
+            .with_children(|parent| {
+                for (mesh, material) in axes.0.clone() {
+                    parent.spawn(PbrBundle {
+                        mesh,
+                        material,
+                        visibility: Visibility { is_visible: config.enabled },
+                        ..default()
+                    })
+                        .insert((DebugDrawn, NotShadowCaster));
+                }
+            });
+    }
+}
+
+fn debug_draw_controls(binds: Res<Input<DebugDrawControls>>, mut config: ResMut<DebugDraw>) {
+    if binds.just_pressed(DebugDrawControls::Toggle) {
+        config.enabled = !config.enabled;
+    }
+}
+
+fn follow_grid<T: Component>(
+    config: Res<DebugDraw>,
+    active: Res<ActiveControl<T>>,
+    followed: Query<&GlobalTransform, With<T>>,
+    mut grids: Query<&mut Transform, With<DebugGrid>>
+) {
+    if !config.enabled {
+        return;
+    }
+    let Some(followed) = active.entity.and_then(|entity| followed.get(entity).ok()) else {
+        return;
+    };
+    // snapped to the spacing, moving the grid by whole cells looks like it's standing still
+    let position = followed.translation();
+    let spacing = config.grid_spacing.max(f32::EPSILON);
+    let snapped = Vec3::new((position.x / spacing).round() * spacing, 0.0, (position.z / spacing).round() * spacing);
+    for mut transform in &mut grids {
+        if transform.translation != snapped {
+            transform.translation = snapped;
+        }
+    }
+}
+
+fn show_debug_draw(
+    config: Res<DebugDraw>,
+    added: Query<(), Added<DebugDrawn>>,
+    mut drawn: Query<&mut Visibility, With<DebugDrawn>>
+) {
+    if !config.is_changed() && added.is_empty() {
+        return;
+    }
+    for mut visibility in &mut drawn {
+        visibility.is_visible = config.enabled;
+    }
+}
@@ -0,0 +1,337 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{Commands, Component, Entity, EventReader, GlobalTransform, Mesh, Query, Res, ResMut, Resource, Transform, With, Without, World};
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::time::Time;
+use bevy::utils::HashSet;
+use bevy_rapier3d::prelude::{ActiveEvents, Collider, ContactForceEvent, ContactForceEventThreshold, RigidBody, Velocity};
+use rand::Rng;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::picking::PickTarget;
+use crate::pool::{PhysicsPool, Pooled};
+use crate::save::SavedShape;
+use crate::selection::Selection;
+
+/// Breaks [Destructible] bodies into fragments when something hits them hard enough, going by
+/// rapier's contact force events. The fragments are either the ones the [Destructible] lists, or
+/// convex pieces cut out of the body's own shape, which are made once for each shape and piece
+/// count and reused after. Fragments come from the [PhysicsPool] and go back to it after
+/// [DestructionConfig::fragment_lifetime], so shattering a lot of things doesn't keep spawning
+/// entities.
+///
+/// Only bodies whose collider is a cuboid, ball or capsule (anything [SavedShape] describes) can
+/// be cut up at runtime. The `destructible` console command toggles a [Destructible] on the
+/// [Selection], or on the [PickTarget] without one, taking how many pieces to break into.
+pub struct DestructionPlugin;
+
+impl Plugin for DestructionPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<DestructionConfig>() {
+            app.insert_resource(DestructionConfig::default());
+        }
+        app
+            .init_resource::<FragmentCache>()
+            .add_system(add_contact_force_events)
+            .add_system(fracture)
+            .add_system(expire_fragments)
+            .add_console_command("destructible", "toggles breaking on impact for the selection, or what's under the crosshair", |world, args| {
+                let pieces = match args.first() {
+                    Some(arg) => arg.parse::<usize>().ok().filter(|pieces| *pieces > 1),
+                    None => Some(world.resource::<DestructionConfig>().pieces)
+                };
+                let Some(pieces) = pieces else {
+                    console_print(world, "usage: destructible [pieces]");
+                    return;
+                };
+                let (added, removed) = toggle_destructible(world, pieces);
+                console_print(world, format!("made {} destructible, {} not", added, removed));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct DestructionConfig {
+    /// Pieces bodies break into, for destructibles made with the console
+    pub pieces: usize,
+    /// Contact force breaking bodies, for destructibles made with the console
+    pub threshold: f32,
+    /// Seconds fragments stay around before going back to the pool
+    pub fragment_lifetime: f32,
+    /// Speed fragments fly away from the middle with, on top of the body's own
+    pub scatter: f32
+}
+
+impl Default for DestructionConfig {
+    fn default() -> Self {
+        Self {
+            pieces: 8,
+            threshold: 400.0,
+            fragment_lifetime: 6.0,
+            scatter: 2.0
+        }
+    }
+}
+
+/// What a [Destructible] breaks into
+#[derive(Clone, Debug)]
+pub enum Fragments {
+    /// These pieces, placed relative to the body
+    Authored(Vec<(Transform, SavedShape)>),
+    /// This many convex pieces cut out of the body's collider
+    Generated(usize)
+}
+
+/// Breaks when a contact force of at least `threshold` pushes on it
+#[derive(Component, Clone, Debug)]
+pub struct Destructible {
+    pub threshold: f32,
+    pub fragments: Fragments
+}
+
+/// A piece of a broken [Destructible]
+#[derive(Component)]
+pub struct Fragment {
+    pub age: f32
+}
+
+/// A fragment relative to the middle of the body it came from
+#[derive(Clone)]
+struct FragmentPiece {
+    offset: Vec3,
+    mesh: Handle<Mesh>,
+    collider: Collider
+}
+
+/// Generated pieces for each shape and piece count, sharing meshes is what lets the pool reuse
+/// fragments
+#[derive(Resource, Default)]
+struct FragmentCache {
+    generated: Vec<(SavedShape, usize, Vec<FragmentPiece>)>,
+    authored: Vec<(SavedShape, FragmentPiece)>
+}
+
+impl FragmentCache {
+    fn generated(&mut self, meshes: &mut Assets<Mesh>, shape: SavedShape, pieces: usize) -> Vec<FragmentPiece> {
+        if let Some((_, _, cached)) = self.generated.iter().find(|(cached, count, _)| *cached == shape && *count == pieces) {
+            return cached.clone();
+        }
+        let generated = cut_pieces(meshes, shape, pieces);
+        self.generated.push((shape, pieces, generated.clone()));
+        generated
+    }
+
+    fn authored(&mut self, meshes: &mut Assets<Mesh>, transform: Transform, shape: SavedShape) -> (Transform, FragmentPiece) {
+        let piece = match self.authored.iter().find(|(cached, _)| *cached == shape) {
+            Some((_, piece)) => piece.clone(),
+            None => {
+                let piece = FragmentPiece {
+                    offset: Vec3::ZERO,
+                    mesh: meshes.add(shape.mesh()),
+                    collider: shape.collider()
+                };
+                self.authored.push((shape, piece.clone()));
+                piece
+            }
+        };
+        (transform, piece)
+    }
+}
+
+/// Cuts `shape` into up to `pieces` convex pieces, each the part of the shape closer to one of
+/// `pieces` random points than to the others. Those parts are convex, so the hull of the grid
+/// points inside each is as well, and the pieces never overlap
+fn cut_pieces(meshes: &mut Assets<Mesh>, shape: SavedShape, pieces: usize) -> Vec<FragmentPiece> {
+    const RESOLUTION: usize = 10;
+
+    let collider = shape.collider();
+    let aabb = collider.raw.compute_local_aabb();
+    let (min, max): (Vec3, Vec3) = (aabb.mins.into(), aabb.maxs.into());
+    let inside = |point: Vec3| collider.contains_point(Vec3::ZERO, Quat::IDENTITY, point);
+
+    let mut rng = rand::thread_rng();
+    let seeds = (0..pieces * 20)
+        .map(|_| Vec3::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y), rng.gen_range(min.z..=max.z)))
+        .filter(|point| inside(*point))
+        .take(pieces)
+        .collect::<Vec<_>>();
+    let mut cells = vec![Vec::new(); seeds.len()];
+    for x in 0..=RESOLUTION {
+        for y in 0..=RESOLUTION {
+            for z in 0..=RESOLUTION {
+                let t = Vec3::new(x as f32, y as f32, z as f32) / RESOLUTION as f32;
+                let point = min + (max - min) * t;
+                if !inside(point) {
+                    continue;
+                }
+                let nearest = (0..seeds.len()).min_by(|a, b| {
+                    seeds[*a].distance_squared(point).total_cmp(&seeds[*b].distance_squared(point))
+                });
+                if let Some(nearest) = nearest {
+                    cells[nearest].push(point);
+                }
+            }
+        }
+    }
+
+    cells.into_iter()
+        // flat cells don't have a hull
+        .filter(|points| points.len() >= 4)
+        .filter_map(|points| {
+            let offset = points.iter().copied().sum::<Vec3>() / points.len() as f32;
+            let points = points.iter().map(|point| *point - offset).collect::<Vec<_>>();
+            let collider = Collider::convex_hull(&points)?;
+            let mesh = meshes.add(hull_mesh(&collider)?);
+            Some(FragmentPiece { offset, mesh, collider })
+        })
+        .collect()
+}
+
+/// A flat shaded mesh of a convex hull collider
+fn hull_mesh(collider: &Collider) -> Option<Mesh> {
+    let (vertices, triangles) = collider.as_convex_polyhedron()?.raw.to_trimesh();
+    let mut positions = Vec::with_capacity(triangles.len() * 3);
+    let mut normals = Vec::with_capacity(triangles.len() * 3);
+    for [a, b, c] in triangles {
+        let corners: [Vec3; 3] = [vertices[a as usize].into(), vertices[b as usize].into(), vertices[c as usize].into()];
+        let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]).normalize_or_zero();
+        for corner in corners {
+            positions.push(corner.to_array());
+            normals.push(normal.to_array());
+        }
+    }
+    let count = positions.len();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; count]);
+    mesh.set_indices(Some(Indices::U32((0..count as u32).collect())));
+    Some(mesh)
+}
+
+fn add_contact_force_events(
+    mut commands: Commands,
+    destructibles: Query<(Entity, &Destructible, Option<&ActiveEvents>, Option<&ContactForceEventThreshold>)>
+) {
+    for (entity, destructible, active_events, threshold) in &destructibles {
+        let active_events = active_events.copied().unwrap_or(ActiveEvents::empty());
+        if !active_events.contains(ActiveEvents::CONTACT_FORCE_EVENTS) {
+            commands.entity(entity).insert(active_events | ActiveEvents::CONTACT_FORCE_EVENTS);
+        }
+        if threshold.map_or(true, |threshold| threshold.0 != destructible.threshold) {
+            commands.entity(entity).insert(ContactForceEventThreshold(destructible.threshold));
+        }
+    }
+}
+
+fn fracture(
+    mut commands: Commands,
+    config: Res<DestructionConfig>,
+    mut cache: ResMut<FragmentCache>,
+    mut pool: ResMut<PhysicsPool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut contact_forces: EventReader<ContactForceEvent>,
+    destructibles: Query<(&Destructible, &Collider, &GlobalTransform, Option<&Velocity>, Option<&Handle<StandardMaterial>>)>
+) {
+    let mut broken = HashSet::default();
+    for event in contact_forces.iter() {
+        for entity in [event.collider1, event.collider2] {
+            let Ok((destructible, collider, transform, velocity, material)) = destructibles.get(entity) else {
+                continue;
+            };
+            if event.total_force_magnitude < destructible.threshold || !broken.insert(entity) {
+                continue;
+            }
+            let transform = transform.compute_transform();
+            let pieces = match &destructible.fragments {
+                Fragments::Authored(pieces) => pieces.iter()
+                    .map(|(piece_transform, shape)| cache.authored(&mut meshes, transform.mul_transform(*piece_transform), *shape))
+                    .collect::<Vec<_>>(),
+                Fragments::Generated(count) => {
+                    // the collider is already scaled, so the pieces are cut to size
+                    let Some(shape) = SavedShape::from_collider(collider) else {
+                        continue;
+                    };
+                    cache.generated(&mut meshes, shape, *count)
+                        .into_iter()
+                        .map(|piece| {
+                            let translation = transform.translation + transform.rotation * piece.offset;
+                            (Transform::from_translation(translation).with_rotation(transform.rotation), piece)
+                        })
+                        .collect()
+                }
+            };
+            let velocity = velocity.copied().unwrap_or_default();
+            let material = material.cloned().unwrap_or_default();
+            for (piece_transform, piece) in pieces {
+                let outward = (piece_transform.translation - transform.translation).normalize_or_zero();
+                pool.acquire(&mut commands, piece.mesh, material.clone(), piece_transform)
+                    .insert((
+                        RigidBody::Dynamic,
+                        piece.collider,
+                        Velocity {
+                            linvel: velocity.linvel + outward * config.scatter,
+                            angvel: velocity.angvel
+                        },
+                        Fragment { age: 0.0 }
+                    ));
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn expire_fragments(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<DestructionConfig>,
+    mut pool: ResMut<PhysicsPool>,
+    mut fragments: Query<(Entity, &mut Fragment, Option<&Pooled>)>
+) {
+    for (entity, mut fragment, pooled) in &mut fragments {
+        fragment.age += time.delta_seconds();
+        if fragment.age < config.fragment_lifetime {
+            continue;
+        }
+        match pooled {
+            Some(pooled) => {
+                pool.release(&mut commands, entity, pooled);
+                commands.entity(entity).remove::<Fragment>();
+            }
+            None => commands.entity(entity).despawn_recursive()
+        }
+    }
+}
+
+/// Toggles a [Destructible] breaking into `pieces` on the [Selection], or the [PickTarget] without
+/// one, returning how many were added and removed. Fixed bodies can be made destructible as well,
+/// their pieces fall
+pub fn toggle_destructible(world: &mut World, pieces: usize) -> (usize, usize) {
+    let selected = world.get_resource::<Selection>()
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| selection.iter().collect::<Vec<_>>());
+    let entities = selected
+        .or_else(|| world.get_resource::<PickTarget>().and_then(|target| target.entity).map(|entity| vec![entity]))
+        .unwrap_or_default();
+    let threshold = world.resource::<DestructionConfig>().threshold;
+    let (mut added, mut removed) = (0, 0);
+    for entity in entities {
+        let breakable = world.query_filtered::<(), (With<Collider>, Without<Fragment>)>().get(world, entity).is_ok();
+        let Some(mut entity) = world.get_entity_mut(entity) else {
+            continue;
+        };
+        if entity.contains::<Destructible>() {
+            entity.remove::<Destructible>();
+            removed += 1;
+        } else if breakable {
+            entity.insert(Destructible {
+                threshold,
+                fragments: Fragments::Generated(pieces)
+            });
+            added += 1;
+        }
+    }
+    (added, removed)
+}
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use bevy::app::{App, AppExit, CoreStage, Plugin};
+use bevy::log::{error, info, warn};
+use bevy::prelude::{Entity, EventReader, Query, ResMut, Resource, Transform, With};
+use bevy_rapier3d::prelude::RigidBody;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::AddTickSystem;
+
+/// Keeps a rolling checksum of every rigid body's [Transform], updated once per tick of
+/// [FixedTime](crate::fixed_time::FixedTime), for catching changes that make the physics stop
+/// being deterministic. Two runs of the same scene with the same input should end up with the same
+/// checksum on every tick.
+///
+/// The checksums can be written to a file with [DeterminismConfig::dump] (or `--checksum-dump`),
+/// and compared against a file from an earlier run with [DeterminismConfig::compare] (or
+/// `--checksum-compare`), which warns about the first tick that doesn't match. Together with
+/// `--headless-steps` this makes for a quick check that nothing changed. Also registers the
+/// `checksum`, `checksum_dump` and `checksum_compare` console commands.
+pub struct DeterminismPlugin;
+
+impl Plugin for DeterminismPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<DeterminismConfig>() {
+            app.insert_resource(DeterminismConfig::default());
+        }
+        let config = app.world.resource::<DeterminismConfig>().clone();
+        let mut checksum = Checksum::default();
+        if let Some(path) = &config.dump {
+            match File::create(path) {
+                Ok(file) => checksum.dump = Some(BufWriter::new(file)),
+                Err(e) => error!("failed to create checksum dump {}: {}", path.display(), e)
+            }
+        }
+        if let Some(path) = &config.compare {
+            if let Err(e) = checksum.compare_with(path) {
+                error!("failed to load checksums {}: {}", path.display(), e);
+            }
+        }
+        app
+            .insert_resource(checksum)
+            .add_tick_system(update_checksum)
+            .add_system_to_stage(CoreStage::Last, flush_checksum_dump)
+            .add_console_command("checksum", "prints the tick and world checksum", |world, _| {
+                let checksum = world.resource::<Checksum>();
+                let line = match checksum.diverged_at {
+                    Some(tick) => format!("tick {} checksum {:016x}, diverged at tick {}", checksum.tick, checksum.value, tick),
+                    None => format!("tick {} checksum {:016x}", checksum.tick, checksum.value)
+                };
+                console_print(world, line);
+            })
+            .add_console_command("checksum_dump", "writes every tick's checksum so far to a file", |world, args| {
+                let Some(path) = args.first() else {
+                    console_print(world, "usage: checksum_dump <file>");
+                    return;
+                };
+                let line = match world.resource::<Checksum>().write_history(path) {
+                    Ok(()) => format!("wrote {} checksums to {}", world.resource::<Checksum>().history.len(), path),
+                    Err(e) => format!("failed to write {}: {}", path, e)
+                };
+                console_print(world, line);
+            })
+            .add_console_command("checksum_compare", "compares checksums against a file from an earlier run", |world, args| {
+                let Some(path) = args.first() else {
+                    console_print(world, "usage: checksum_compare <file>");
+                    return;
+                };
+                let mut checksum = world.resource_mut::<Checksum>();
+                let line = match checksum.compare_with(path) {
+                    Ok(()) => match checksum.diverged_at {
+                        Some(tick) => format!("diverged at tick {}", tick),
+                        None => format!("matching so far, {} checksums loaded", checksum.expected.len())
+                    },
+                    Err(e) => format!("failed to load {}: {}", path, e)
+                };
+                console_print(world, line);
+            });
+    }
+}
+
+/// Insert before adding the [DeterminismPlugin] to change it
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DeterminismConfig {
+    /// Where to write each tick's checksum as it's made
+    pub dump: Option<PathBuf>,
+    /// A dump from an earlier run to compare the checksums with
+    pub compare: Option<PathBuf>
+}
+
+/// The rolling checksum, along with every earlier tick's
+#[derive(Resource, Default)]
+pub struct Checksum {
+    /// Ticks checksummed so far
+    pub tick: u64,
+    /// The latest checksum, made from the one before it and this tick's transforms
+    pub value: u64,
+    /// The first tick that didn't match the compared checksums
+    pub diverged_at: Option<u64>,
+    history: Vec<u64>,
+    expected: Vec<u64>,
+    dump: Option<BufWriter<File>>
+}
+
+impl Checksum {
+    /// Compares with the checksums in `path` from here on, and with those already made
+    fn compare_with(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut expected = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            // written as "<tick> <checksum>", the line number is the tick anyways
+            let Some(value) = line.split_whitespace().nth(1) else {
+                continue;
+            };
+            let value = u64::from_str_radix(value, 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("invalid checksum {}", value)))?;
+            expected.push(value);
+        }
+        self.expected = expected;
+        self.diverged_at = self.history.iter()
+            .zip(&self.expected)
+            .position(|(value, expected)| value != expected)
+            .map(|tick| tick as u64 + 1);
+        Ok(())
+    }
+
+    fn write_history(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for (tick, value) in self.history.iter().enumerate() {
+            writeln!(writer, "{} {:016x}", tick + 1, value)?;
+        }
+        writer.flush()
+    }
+}
+
+/// FNV-1a, it has to hash the same on every run and platform, which the std hasher doesn't promise
+fn hash(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn update_checksum(
+    mut checksum: ResMut<Checksum>,
+    bodies: Query<(Entity, &Transform), With<RigidBody>>
+) {
+    // the query's order depends on archetypes, entities are spawned in the same order each run
+    let mut bodies = bodies.iter().collect::<Vec<_>>();
+    bodies.sort_by_key(|(entity, _)| *entity);
+    let mut value = hash(0xcbf29ce484222325, &checksum.value.to_le_bytes());
+    for (_, transform) in bodies {
+        let Transform { translation, rotation, scale } = transform;
+        for float in translation.to_array().into_iter().chain(rotation.to_array()).chain(scale.to_array()) {
+            value = hash(value, &float.to_bits().to_le_bytes());
+        }
+    }
+
+    let checksum = &mut *checksum;
+    checksum.tick += 1;
+    checksum.value = value;
+    checksum.history.push(value);
+    if let Some(dump) = &mut checksum.dump {
+        if let Err(e) = writeln!(dump, "{} {:016x}", checksum.tick, value) {
+            error!("failed to write checksum: {}", e);
+            checksum.dump = None;
+        }
+    }
+    if checksum.diverged_at.is_none() {
+        if let Some(expected) = checksum.expected.get(checksum.tick as usize - 1) {
+            if *expected != value {
+                warn!("checksums diverged at tick {}, expected {:016x} but got {:016x}", checksum.tick, expected, value);
+                checksum.diverged_at = Some(checksum.tick);
+            }
+        }
+    }
+}
+
+fn flush_checksum_dump(mut exits: EventReader<AppExit>, mut checksum: ResMut<Checksum>) {
+    if exits.iter().next().is_none() {
+        return;
+    }
+    let checksum = &mut *checksum;
+    if let Some(dump) = &mut checksum.dump {
+        match dump.flush() {
+            Ok(()) => info!("dumped {} checksums", checksum.tick),
+            Err(e) => error!("failed to write checksums: {}", e)
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::Handle;
+use bevy::ecs::system::EntityCommands;
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::math::Vec3;
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{Bundle, Commands, Entity, EventReader, Mesh, Parent, Query, ResMut, Resource, SpatialBundle, Transform, With, World};
+use bevy_rapier3d::prelude::{Collider, RigidBody, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::console::AddConsoleCommand;
+use crate::floating_origin::{add_origin_shifted, OriginShifted};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::save::{Saved, SavedPbr};
+use crate::selection::Selection;
+use crate::tags::Tags;
+
+/// Undo and redo for edits made to the sandbox, anything spawning, despawning or moving entities
+/// through [EditCommands] is recorded into the [EditHistory]. This plugin can be initialized in
+/// two ways:
+///
+/// * No default bindings [EditHistoryPlugin::new]
+/// * Ctrl+Z undoes and Ctrl+Y redoes [EditHistoryPlugin::default]
+///
+/// Also registers the `undo` and `redo` console commands. Despawned entities are brought back
+/// with their transform, mesh, material and rapier components, but not their children.
+pub struct EditHistoryPlugin {
+    key_bindings: KeyBindingPlugin<EditHistoryControls>
+}
+
+impl EditHistoryPlugin {
+    /// Creates a new `EditHistoryPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: EditHistoryControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for EditHistoryPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Z, EditHistoryControls::Undo)
+            .bind(Y, EditHistoryControls::Redo)
+            .bind(LControl, EditHistoryControls::Modifier)
+            .bind(RControl, EditHistoryControls::Modifier)
+    }
+}
+
+impl Plugin for EditHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        add_origin_shifted(app);
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<EditHistory>()
+            .add_system(edit_history_controls)
+            .add_system(shift_edit_history)
+            .add_console_command("undo", "undoes the last edit", |world, _| {
+                undo(world);
+            })
+            .add_console_command("redo", "redoes the last undone edit", |world, _| {
+                redo(world);
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum EditHistoryControls {
+    /// Undoes the last edit while [EditHistoryControls::Modifier] is held
+    Undo,
+    /// Redoes the last undone edit while [EditHistoryControls::Modifier] is held
+    Redo,
+    Modifier
+}
+
+/// Everything needed to bring a despawned entity back
+#[derive(Clone)]
+struct EntitySnapshot {
+    transform: Transform,
+    mesh: Option<Handle<Mesh>>,
+    material: Option<Handle<StandardMaterial>>,
+    body: Option<RigidBody>,
+    collider: Option<Collider>,
+    velocity: Option<Velocity>,
+    saved: bool,
+    saved_pbr: Option<SavedPbr>,
+    tags: Option<Tags>
+}
+
+impl EntitySnapshot {
+    fn take(world: &World, entity: Entity) -> Option<Self> {
+        let entity = world.get_entity(entity)?;
+        Some(Self {
+            transform: entity.get::<Transform>().copied().unwrap_or_default(),
+            mesh: entity.get::<Handle<Mesh>>().cloned(),
+            material: entity.get::<Handle<StandardMaterial>>().cloned(),
+            body: entity.get::<RigidBody>().copied(),
+            collider: entity.get::<Collider>().cloned(),
+            velocity: entity.get::<Velocity>().copied(),
+            saved: entity.contains::<Saved>(),
+            saved_pbr: entity.get::<SavedPbr>().map(|pbr| SavedPbr { shape: pbr.shape, color: pbr.color }),
+            tags: entity.get::<Tags>().cloned()
+        })
+    }
+
+    fn restore(self, world: &mut World) -> Entity {
+        let mut entity = world.spawn(SpatialBundle::from_transform(self.transform));
+        if let Some(mesh) = self.mesh {
+            entity.insert(mesh);
+        }
+        if let Some(material) = self.material {
+            entity.insert(material);
+        }
+        if let Some(body) = self.body {
+            entity.insert(body);
+        }
+        if let Some(collider) = self.collider {
+            entity.insert(collider);
+        }
+        if let Some(velocity) = self.velocity {
+            entity.insert(velocity);
+        }
+        if self.saved {
+            entity.insert(Saved);
+        }
+        if let Some(saved_pbr) = self.saved_pbr {
+            entity.insert(saved_pbr);
+        }
+        if let Some(tags) = self.tags {
+            entity.insert(tags);
+        }
+        entity.id()
+    }
+}
+
+/// A step that reverts an edit, applying it gives back the step reverting it in turn
+#[derive(Clone)]
+enum EditStep {
+    Remove(Entity),
+    /// `entity` is the entity as it was before being removed, kept to fix up older steps
+    Restore { entity: Entity, snapshot: Box<EntitySnapshot> },
+    SetTransform(Entity, Transform),
+    /// Several steps making up one edit, like moving a whole [Selection]
+    Group(Vec<EditStep>)
+}
+
+impl EditStep {
+    /// Applies the step, returning its inverse, or `None` if the entity is gone
+    fn apply(self, world: &mut World, history: &mut EditHistory) -> Option<EditStep> {
+        match self {
+            EditStep::Remove(entity) => {
+                let snapshot = EntitySnapshot::take(world, entity)?;
+                world.entity_mut(entity).despawn_recursive();
+                Some(EditStep::Restore { entity, snapshot: Box::new(snapshot) })
+            }
+            EditStep::Restore { entity, snapshot } => {
+                let restored = snapshot.restore(world);
+                // the entity comes back under a new id, older steps still refer to the old one
+                history.remap(entity, restored);
+                if let Some(mut selection) = world.get_resource_mut::<Selection>() {
+                    selection.replace(entity, restored);
+                }
+                Some(EditStep::Remove(restored))
+            }
+            EditStep::SetTransform(entity, transform) => {
+                let mut current = world.get_mut::<Transform>(entity)?;
+                let previous = *current;
+                *current = transform;
+                Some(EditStep::SetTransform(entity, previous))
+            }
+            EditStep::Group(steps) => {
+                // reverting the steps has to happen in the opposite order
+                let mut inverses = steps.into_iter()
+                    .filter_map(|step| step.apply(world, history))
+                    .collect::<Vec<_>>();
+                inverses.reverse();
+                (!inverses.is_empty()).then_some(EditStep::Group(inverses))
+            }
+        }
+    }
+
+    /// Moves the transforms of root entities by `shift`, children move along with their parent
+    fn shift(&mut self, shift: Vec3, is_child: &impl Fn(Entity) -> bool) {
+        match self {
+            EditStep::Remove(_) => {}
+            // restored without a parent
+            EditStep::Restore { snapshot, .. } => snapshot.transform.translation += shift,
+            EditStep::SetTransform(entity, transform) => {
+                if !is_child(*entity) {
+                    transform.translation += shift;
+                }
+            }
+            EditStep::Group(steps) => {
+                for step in steps {
+                    step.shift(shift, is_child);
+                }
+            }
+        }
+    }
+
+    fn remap(&mut self, old: Entity, new: Entity) {
+        let entity = match self {
+            EditStep::Remove(entity) => entity,
+            EditStep::Restore { entity, .. } => entity,
+            EditStep::SetTransform(entity, _) => entity,
+            EditStep::Group(steps) => {
+                for step in steps {
+                    step.remap(old, new);
+                }
+                return;
+            }
+        };
+        if *entity == old {
+            *entity = new;
+        }
+    }
+}
+
+/// The recorded edits, newest last
+#[derive(Resource)]
+pub struct EditHistory {
+    undo: Vec<EditStep>,
+    redo: Vec<EditStep>,
+    /// How many edits can be undone, the oldest are forgotten first
+    pub limit: usize
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: 100
+        }
+    }
+}
+
+impl EditHistory {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn record(&mut self, step: EditStep) {
+        self.redo.clear();
+        self.undo.push(step);
+        if self.undo.len() > self.limit {
+            let excess = self.undo.len() - self.limit;
+            self.undo.drain(..excess);
+        }
+    }
+
+    fn remap(&mut self, old: Entity, new: Entity) {
+        for step in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            step.remap(old, new);
+        }
+    }
+}
+
+/// Keeps the recorded transforms lined up with the world when the origin moves
+fn shift_edit_history(mut shifted: EventReader<OriginShifted>, mut history: ResMut<EditHistory>, children: Query<(), With<Parent>>) {
+    let history = &mut *history;
+    for OriginShifted { shift } in shifted.iter() {
+        let is_child = |entity| children.contains(entity);
+        for step in history.undo.iter_mut().chain(history.redo.iter_mut()) {
+            step.shift(*shift, &is_child);
+        }
+    }
+}
+
+fn record(world: &mut World, step: EditStep) {
+    if let Some(mut history) = world.get_resource_mut::<EditHistory>() {
+        history.record(step);
+    }
+}
+
+/// Records `entities` as spawned by a single edit, for tools spawning straight into the world
+/// instead of through [EditCommands]
+pub fn record_spawned(world: &mut World, entities: Vec<Entity>) {
+    let mut steps = entities.into_iter().map(EditStep::Remove).collect::<Vec<_>>();
+    match steps.len() {
+        0 => {}
+        1 => record(world, steps.remove(0)),
+        _ => record(world, EditStep::Group(steps))
+    }
+}
+
+/// Undoes the last edit, returning whether there was one to undo
+pub fn undo(world: &mut World) -> bool {
+    step_history(world, true)
+}
+
+/// Redoes the last undone edit, returning whether there was one to redo
+pub fn redo(world: &mut World) -> bool {
+    step_history(world, false)
+}
+
+fn step_history(world: &mut World, undo: bool) -> bool {
+    // taken out so the step can remap the rest of the history while changing the world
+    let Some(mut history) = world.remove_resource::<EditHistory>() else {
+        return false;
+    };
+    let mut stepped = false;
+    // steps for entities that are gone by now are skipped
+    while let Some(step) = if undo { history.undo.pop() } else { history.redo.pop() } {
+        if let Some(inverse) = step.apply(world, &mut history) {
+            if undo { history.redo.push(inverse) } else { history.undo.push(inverse) }
+            stepped = true;
+            break;
+        }
+    }
+    world.insert_resource(history);
+    if !stepped {
+        info!("nothing to {}", if undo { "undo" } else { "redo" });
+    }
+    stepped
+}
+
+fn edit_history_controls(world: &mut World) {
+    let binds = world.resource::<Input<EditHistoryControls>>();
+    if !binds.pressed(EditHistoryControls::Modifier) {
+        return;
+    }
+    let (undo_pressed, redo_pressed) = (binds.just_pressed(EditHistoryControls::Undo), binds.just_pressed(EditHistoryControls::Redo));
+    if undo_pressed {
+        undo(world);
+    }
+    if redo_pressed {
+        redo(world);
+    }
+}
+
+/// Versions of the usual spawning, despawning and moving commands that get recorded into the
+/// [EditHistory], for tools editing the sandbox. Without an [EditHistoryPlugin] these act like
+/// the plain commands
+pub trait EditCommands<'w, 's> {
+    fn spawn_recorded<'a>(&'a mut self, bundle: impl Bundle) -> EntityCommands<'w, 's, 'a>;
+
+    /// Despawns `entity` recursively, undoing only brings back the entity itself
+    fn despawn_recorded(&mut self, entity: Entity);
+
+    fn move_recorded(&mut self, entity: Entity, transform: Transform);
+
+    /// Like [EditCommands::despawn_recorded], undone all at once
+    fn despawn_recorded_group(&mut self, entities: Vec<Entity>);
+
+    /// Like [EditCommands::move_recorded], undone all at once
+    fn move_recorded_group(&mut self, moves: Vec<(Entity, Transform)>);
+}
+
+impl <'w, 's> EditCommands<'w, 's> for Commands<'w, 's> {
+    fn spawn_recorded<'a>(&'a mut self, bundle: impl Bundle) -> EntityCommands<'w, 's, 'a> {
+        let entity = self.spawn(bundle).id();
+        self.add(move |world: &mut World| record(world, EditStep::Remove(entity)));
+        self.entity(entity)
+    }
+
+    fn despawn_recorded(&mut self, entity: Entity) {
+        self.add(move |world: &mut World| {
+            let Some(snapshot) = EntitySnapshot::take(world, entity) else {
+                return;
+            };
+            world.entity_mut(entity).despawn_recursive();
+            record(world, EditStep::Restore { entity, snapshot: Box::new(snapshot) });
+        });
+    }
+
+    fn move_recorded(&mut self, entity: Entity, transform: Transform) {
+        self.add(move |world: &mut World| {
+            let Some(mut current) = world.get_mut::<Transform>(entity) else {
+                return;
+            };
+            let previous = *current;
+            *current = transform;
+            record(world, EditStep::SetTransform(entity, previous));
+        });
+    }
+
+    fn despawn_recorded_group(&mut self, entities: Vec<Entity>) {
+        self.add(move |world: &mut World| {
+            let mut steps = Vec::new();
+            for entity in entities {
+                // already gone if it was a child of an earlier one
+                let Some(snapshot) = EntitySnapshot::take(world, entity) else {
+                    continue;
+                };
+                world.entity_mut(entity).despawn_recursive();
+                steps.push(EditStep::Restore { entity, snapshot: Box::new(snapshot) });
+            }
+            if !steps.is_empty() {
+                record(world, EditStep::Group(steps));
+            }
+        });
+    }
+
+    fn move_recorded_group(&mut self, moves: Vec<(Entity, Transform)>) {
+        self.add(move |world: &mut World| {
+            let mut steps = Vec::new();
+            for (entity, transform) in moves {
+                let Some(mut current) = world.get_mut::<Transform>(entity) else {
+                    continue;
+                };
+                steps.push(EditStep::SetTransform(entity, *current));
+                *current = transform;
+            }
+            if !steps.is_empty() {
+                record(world, EditStep::Group(steps));
+            }
+        });
+    }
+}
@@ -0,0 +1,239 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{AddAsset, AssetEvent, AssetLoader, Assets, AssetServer, Handle, LoadContext, LoadedAsset};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::log::info;
+use bevy::math::{EulerRot, Quat, Vec3};
+use bevy::pbr::{DirectionalLight, DirectionalLightBundle, PbrBundle, PointLight, PointLightBundle, StandardMaterial};
+use bevy::prelude::{Color, Commands, Component, Entity, EventReader, Mesh, Query, Res, ResMut, Resource, Transform, With};
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, default};
+use bevy_rapier3d::prelude::RigidBody;
+use serde::Deserialize;
+use crate::game_state::LoadingAssets;
+use crate::save::{SavedBodyKind, SavedShape};
+
+/// Builds the playground environment from a RON asset (`.env.ron`) instead of hardcoding it,
+/// anything spawned from the file is despawned and rebuilt whenever the asset changes.
+///
+/// For changes to be picked up while running, `watch_for_changes` has to be enabled on Bevy's
+/// `AssetPlugin`.
+pub struct EnvironmentPlugin {
+    path: String
+}
+
+impl EnvironmentPlugin {
+    /// Creates a new `EnvironmentPlugin` loading from `path`, relative to the assets directory
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into()
+        }
+    }
+}
+
+impl Default for EnvironmentPlugin {
+    fn default() -> Self {
+        Self::new("playground.env.ron")
+    }
+}
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone();
+        app
+            .add_asset::<EnvironmentDefinition>()
+            .init_asset_loader::<EnvironmentLoader>()
+            .add_startup_system(move |mut commands: Commands, asset_server: Res<AssetServer>, loading: Option<ResMut<LoadingAssets>>| {
+                let handle = asset_server.load(path.as_str());
+                if let Some(mut loading) = loading {
+                    loading.track(handle.clone_untyped());
+                }
+                commands.insert_resource(EnvironmentHandle(handle));
+            })
+            .add_system(spawn_environment);
+    }
+}
+
+/// The environment currently in use by [EnvironmentPlugin], replacing the handle swaps the
+/// environment once the new one is loaded.
+#[derive(Resource)]
+pub struct EnvironmentHandle(pub Handle<EnvironmentDefinition>);
+
+/// Marks all entities spawned from an [EnvironmentDefinition]
+#[derive(Component)]
+pub struct EnvironmentEntity;
+
+#[derive(Deserialize, TypeUuid, Default)]
+#[uuid = "5f6c1e2a-93a4-4d0b-8d27-35c0a1f9b6e4"]
+#[serde(default)]
+pub struct EnvironmentDefinition {
+    pub objects: Vec<EnvironmentObject>,
+    pub lights: Vec<EnvironmentLight>
+}
+
+#[derive(Deserialize)]
+pub struct EnvironmentObject {
+    pub shape: SavedShape,
+    #[serde(default = "default_color")]
+    pub color: Color,
+    #[serde(default)]
+    pub transform: EnvironmentTransform,
+    /// The rapier body of the object, objects without a body don't get a collider either
+    #[serde(default)]
+    pub body: Option<SavedBodyKind>
+}
+
+fn default_color() -> Color {
+    Color::rgb(0.6, 0.6, 0.6)
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct EnvironmentTransform {
+    pub translation: Vec3,
+    /// Euler angles in degrees, applied in YXZ order
+    pub rotation: Vec3,
+    pub scale: Vec3
+}
+
+impl Default for EnvironmentTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: Vec3::ONE
+        }
+    }
+}
+
+impl From<&EnvironmentTransform> for Transform {
+    fn from(transform: &EnvironmentTransform) -> Self {
+        let Vec3 { x, y, z } = transform.rotation;
+        Transform {
+            translation: transform.translation,
+            rotation: Quat::from_euler(EulerRot::YXZ, y.to_radians(), x.to_radians(), z.to_radians()),
+            scale: transform.scale
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub enum EnvironmentLight {
+    Directional {
+        illuminance: f32,
+        #[serde(default = "default_light_color")]
+        color: Color,
+        #[serde(default)]
+        shadows: bool,
+        translation: Vec3,
+        looking_at: Vec3
+    },
+    Point {
+        intensity: f32,
+        range: f32,
+        #[serde(default = "default_light_color")]
+        color: Color,
+        #[serde(default)]
+        shadows: bool,
+        translation: Vec3
+    }
+}
+
+fn default_light_color() -> Color {
+    Color::WHITE
+}
+
+#[derive(Default)]
+pub struct EnvironmentLoader;
+
+impl AssetLoader for EnvironmentLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definition = ron::de::from_bytes::<EnvironmentDefinition>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definition));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["env.ron"]
+    }
+}
+
+fn spawn_environment(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<EnvironmentDefinition>>,
+    environment: Option<Res<EnvironmentHandle>>,
+    definitions: Res<Assets<EnvironmentDefinition>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    spawned: Query<Entity, With<EnvironmentEntity>>
+) {
+    let Some(environment) = environment else {
+        return;
+    };
+
+    let changed = asset_events
+        .iter()
+        .filter(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => *handle == environment.0,
+            AssetEvent::Removed { .. } => false
+        })
+        .count() > 0;
+    if !changed && !environment.is_changed() {
+        return;
+    }
+    let Some(definition) = definitions.get(&environment.0) else {
+        return;
+    };
+
+    for entity in &spawned {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for object in &definition.objects {
+        let mut entity = commands.spawn(PbrBundle {
+            mesh: meshes.add(object.shape.mesh()),
+            material: materials.add(object.color.into()),
+            transform: (&object.transform).into(),
+            ..default()
+        });
+        entity.insert(EnvironmentEntity);
+        if let Some(body) = object.body {
+            entity.insert((RigidBody::from(body), object.shape.collider()));
+        }
+    }
+
+    for light in &definition.lights {
+        match *light {
+            EnvironmentLight::Directional { illuminance, color, shadows, translation, looking_at } => {
+                commands.spawn(DirectionalLightBundle {
+                    directional_light: DirectionalLight {
+                        illuminance,
+                        color,
+                        shadows_enabled: shadows,
+                        ..default()
+                    },
+                    transform: Transform::from_translation(translation).looking_at(looking_at, Vec3::Y),
+                    ..default()
+                })
+                    .insert(EnvironmentEntity);
+            }
+            EnvironmentLight::Point { intensity, range, color, shadows, translation } => {
+                commands.spawn(PointLightBundle {
+                    point_light: PointLight {
+                        intensity,
+                        range,
+                        color,
+                        shadows_enabled: shadows,
+                        ..default()
+                    },
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                })
+                    .insert(EnvironmentEntity);
+            }
+        }
+    }
+
+    info!("spawned environment with {} objects and {} lights", definition.objects.len(), definition.lights.len());
+}
@@ -0,0 +1,179 @@
+use std::str::FromStr;
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::prelude::{Commands, Entity, GlobalTransform, IntoSystemDescriptor, MouseButton, Query, Res, Resource};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{ExternalImpulse, RigidBody};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::{PickSystem, PickTarget};
+use crate::settings::AddSetting;
+
+/// An explosion brush, pushing every dynamic body within [ExplosionSettings::radius] of where the
+/// [PickTarget] ray hits away from that point, weaker further out as the [Falloff] says. This
+/// plugin can be initialized in two ways:
+///
+/// * No default bindings [ExplosionPlugin::new]
+/// * The middle mouse button sets off an explosion [ExplosionPlugin::default]
+///
+/// The settings are kept in the `explosion` section of the settings file (see
+/// [SettingsPlugin](crate::settings::SettingsPlugin)) and changed with the `explosion` console
+/// command, which prints them when given no arguments. Needs a
+/// [PickingPlugin](crate::picking::PickingPlugin).
+pub struct ExplosionPlugin {
+    key_bindings: KeyBindingPlugin<ExplosionControls>
+}
+
+impl ExplosionPlugin {
+    /// Creates a new `ExplosionPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: ExplosionControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for ExplosionPlugin {
+    fn default() -> Self {
+        Self::new().bind(MouseButton::Middle, ExplosionControls::Explode)
+    }
+}
+
+impl Plugin for ExplosionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_setting::<ExplosionSettings>("explosion")
+            .add_system(explode.after(PickSystem))
+            .add_console_command("explosion", "sets the explosion radius, impulse and falloff", |world, args| {
+                let mut settings = world.resource_mut::<ExplosionSettings>();
+                if args.is_empty() {
+                    let (radius, impulse, falloff) = (settings.radius, settings.impulse, settings.falloff);
+                    console_print(world, format!("radius {}, impulse {}, falloff {}", radius, impulse, falloff.name()));
+                    return;
+                }
+                let radius = args[0].parse::<f32>().ok().filter(|radius| *radius > 0.0);
+                let impulse = args.get(1).map_or(Some(settings.impulse), |arg| arg.parse::<f32>().ok());
+                let falloff = args.get(2).map_or(Some(settings.falloff), |arg| arg.parse::<Falloff>().ok());
+                match (radius, impulse, falloff) {
+                    (Some(radius), Some(impulse), Some(falloff)) => {
+                        settings.radius = radius;
+                        settings.impulse = impulse;
+                        settings.falloff = falloff;
+                    }
+                    _ => console_print(world, "usage: explosion [radius [impulse [constant|linear|quadratic|smooth]]]")
+                }
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum ExplosionControls {
+    Explode
+}
+
+/// How the strength of an explosion drops off towards its edge
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Falloff {
+    /// Full strength all the way to the edge
+    Constant,
+    #[default]
+    Linear,
+    Quadratic,
+    /// Smoothstep, stays strong near the middle and fades out softly
+    Smooth
+}
+
+impl Falloff {
+    /// The strength at `distance` (from 0 at the middle to 1 at the edge), from 1 down to 0
+    pub fn factor(self, distance: f32) -> f32 {
+        let remaining = (1.0 - distance).clamp(0.0, 1.0);
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => remaining,
+            Falloff::Quadratic => remaining * remaining,
+            Falloff::Smooth => remaining * remaining * (3.0 - 2.0 * remaining)
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Falloff::Constant => "constant",
+            Falloff::Linear => "linear",
+            Falloff::Quadratic => "quadratic",
+            Falloff::Smooth => "smooth"
+        }
+    }
+}
+
+impl FromStr for Falloff {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Falloff::Constant, Falloff::Linear, Falloff::Quadratic, Falloff::Smooth]
+            .into_iter()
+            .find(|falloff| falloff.name().eq_ignore_ascii_case(s))
+            .ok_or(())
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplosionSettings {
+    pub radius: f32,
+    /// The impulse given to a body right at the middle
+    pub impulse: f32,
+    pub falloff: Falloff,
+    /// How much the push leans upwards, 0 pushes straight away from the middle
+    pub lift: f32
+}
+
+impl Default for ExplosionSettings {
+    fn default() -> Self {
+        Self {
+            radius: 5.0,
+            impulse: 20.0,
+            falloff: Falloff::Linear,
+            lift: 0.3
+        }
+    }
+}
+
+fn explode(
+    mut commands: Commands,
+    binds: Res<Input<ExplosionControls>>,
+    settings: Res<ExplosionSettings>,
+    target: Res<PickTarget>,
+    mut bodies: Query<(Entity, &GlobalTransform, &RigidBody, Option<&mut ExternalImpulse>)>
+) {
+    if !binds.just_pressed(ExplosionControls::Explode) || target.entity.is_none() || settings.radius <= 0.0 {
+        return;
+    }
+    let center = target.point;
+    for (entity, transform, body, impulse) in &mut bodies {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let offset = transform.translation() - center;
+        let distance = offset.length();
+        if distance > settings.radius {
+            continue;
+        }
+        // straight up for anything right at the middle
+        let direction = (offset.normalize_or_zero() + Vec3::Y * settings.lift).try_normalize().unwrap_or(Vec3::Y);
+        let push = direction * settings.impulse * settings.falloff.factor(distance / settings.radius);
+        match impulse {
+            Some(mut impulse) => impulse.impulse += push,
+            None => {
+                commands.entity(entity).insert(ExternalImpulse { impulse: push, ..default() });
+            }
+        }
+    }
+}
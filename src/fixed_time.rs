@@ -1,23 +1,293 @@
-use std::time::Duration;
-use bevy::app::{App, Plugin};
-use bevy::prelude::{Res, ResMut};
+use std::time::{Duration, Instant};
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::prelude::{IntoSystemDescriptor, Local, Res, ResMut, Resource};
 use bevy::time::{Time, TimeUpdateStrategy};
+use bevy_rapier3d::plugin::RapierConfiguration;
+use serde::{Deserialize, Serialize};
+use crate::keybind::{KeyBindingPlugin, RawInput};
 
-/// Ensures that each tick of Bevy's Time is 1/60 seconds after the last, irregardless of
-/// actual time passed (which should be roughly the same).
+/// Ensures that each tick of Bevy's Time is 1/60 seconds (or 1 / [FixedTime::tick_rate]) after the
+/// last, irregardless of actual time passed (which should be roughly the same).
 ///
 /// The main reason for doing this is to keep Rapier physics deterministic, and to keep anything
 /// else in the world from looking wonky next to anything controlled by those physics
 /// (as opposed telling Rapier to use TimestepMode::Interpolated since getting time from Rapier
 /// isn't very straightforward like it is with Bevy)
-pub struct FixedTimePlugin;
+///
+/// The [FixedTime] resource can switch back to real time, for comparing the two, by default F4
+/// toggles it (use [FixedTimePlugin::new] for no default bindings).
+///
+/// Holding slow motion, by default left alt, ramps [FixedTime::time_scale] down to
+/// [FixedTime::slow_motion_scale] and back up to 1 on release, at the rate
+/// [FixedTime::slow_motion_ramp] gives. The scale shrinks how far each tick moves Time rather than
+/// how often things get to tick, so Rapier (left on its variable timestep) still steps once every
+/// rendered frame, just by less, and the slowdown looks as smooth as normal speed without any
+/// interpolating. Slow motion only does anything while fixed time is enabled.
+///
+/// Frame stepping (toggled by semicolon) holds Time still, only letting a single tick through each
+/// time step (apostrophe) is pressed, or [FixedTime::step_repeat_rate] ticks a second once it's
+/// been held for [FixedTime::step_repeat_delay]. Rapier is only stepped on those ticks. Anything
+/// that should keep going while the world is stepping, like the camera's
+/// [free_controls](crate::free_control::free_controls), goes by the real time in [RenderTime]
+/// instead.
+pub struct FixedTimePlugin {
+    key_bindings: KeyBindingPlugin<FixedTimeControls>
+}
+
+impl FixedTimePlugin {
+    /// Creates a new `FixedTimePlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("fixed_time")
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: FixedTimeControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for FixedTimePlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(F4, FixedTimeControls::Toggle)
+            .bind(LAlt, FixedTimeControls::SlowMotion)
+            .bind(Semicolon, FixedTimeControls::ToggleFrameStep)
+            .bind(Apostrophe, FixedTimeControls::Step)
+    }
+}
 
 impl Plugin for FixedTimePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(fixed_time_step);
+        if !app.world.contains_resource::<FixedTime>() {
+            app.insert_resource(FixedTime::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<RenderTime>()
+            .init_resource::<FrameStep>()
+            .add_system_to_stage(CoreStage::First, update_render_time)
+            .add_system_to_stage(CoreStage::PreUpdate, step_physics)
+            .add_system(fixed_time_controls)
+            .add_system(slow_motion)
+            .add_system(frame_step.after(fixed_time_controls))
+            .add_system(fixed_time_step.after(slow_motion).after(frame_step));
     }
 }
 
-fn fixed_time_step(time: Res<Time>, mut time_update_strategy: ResMut<TimeUpdateStrategy>) {
-    *time_update_strategy = TimeUpdateStrategy::ManualInstant(time.last_update().unwrap() + Duration::from_secs_f64(1.0 / 60.0));
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum FixedTimeControls {
+    /// Switches between fixed and real time
+    Toggle,
+    /// Slows time down while held
+    SlowMotion,
+    /// Switches frame stepping on and off
+    ToggleFrameStep,
+    /// Lets a single tick through while frame stepping, repeating while held
+    Step
+}
+
+#[derive(Resource)]
+pub struct FixedTime {
+    /// When disabled, Bevy's Time goes back to following real time
+    pub enabled: bool,
+    /// Ticks per second, insert [FixedTime] before adding the plugin to change it
+    pub tick_rate: f64,
+    /// Stops Time from advancing at all, and Rapier from stepping, set by the
+    /// [GameStatePlugin](crate::game_state::GameStatePlugin) outside of running
+    pub paused: bool,
+    /// How much of a tick each tick moves Time along, ramped by slow motion
+    pub time_scale: f64,
+    /// The time scale slow motion ramps down to
+    pub slow_motion_scale: f64,
+    /// How many seconds (of unscaled ticks) the time scale takes to ramp by 1, 0 switches straight
+    /// away
+    pub slow_motion_ramp: f64,
+    /// Holds Time still besides the ticks let through by [FixedTimeControls::Step], even when
+    /// fixed time is disabled
+    pub frame_step: bool,
+    /// Seconds step has to be held before it starts repeating
+    pub step_repeat_delay: f64,
+    /// Ticks per second let through while step is held
+    pub step_repeat_rate: f64
+}
+
+impl Default for FixedTime {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tick_rate: 60.0,
+            paused: false,
+            time_scale: 1.0,
+            slow_motion_scale: 0.2,
+            slow_motion_ramp: 0.3,
+            frame_step: false,
+            step_repeat_delay: 0.4,
+            step_repeat_rate: 10.0
+        }
+    }
+}
+
+/// Run criteria for systems that should only run on frames Time moved, which are the ticks of
+/// [FixedTime], it stands still while paused or frame stepping
+pub fn ticked(time: Res<Time>) -> ShouldRun {
+    if time.delta().is_zero() {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+pub trait AddTickSystem {
+    /// Adds `system` to [CoreStage::PostUpdate], after rapier has written the tick's results back,
+    /// running only on frames that got a tick (see [ticked])
+    fn add_tick_system<Params>(&mut self, system: impl IntoSystemDescriptor<Params>) -> &mut Self;
+}
+
+impl AddTickSystem for App {
+    fn add_tick_system<Params>(&mut self, system: impl IntoSystemDescriptor<Params>) -> &mut Self {
+        self.add_system_to_stage(CoreStage::PostUpdate, system.with_run_criteria(ticked))
+    }
+}
+
+/// Real time between the last two frames, unlike Time it keeps going while [FixedTime] is slowed
+/// down, paused or frame stepping
+#[derive(Resource, Default)]
+pub struct RenderTime {
+    delta: Duration,
+    last_update: Option<Instant>
+}
+
+impl RenderTime {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
+/// Frame stepping's progress, Time is only moved a tick on the frame after one is let through
+#[derive(Resource, Default)]
+struct FrameStep {
+    /// Whether the next frame gets a tick
+    next: bool,
+    /// How long step has been held, in real time
+    held: f64,
+    /// Repeated ticks built up while step is held, one is let through each time this reaches 1
+    repeat: f64
+}
+
+fn fixed_time_controls(binds: Res<Input<FixedTimeControls>>, mut fixed_time: ResMut<FixedTime>) {
+    if binds.just_pressed(FixedTimeControls::Toggle) {
+        fixed_time.enabled = !fixed_time.enabled;
+        info!("fixed time {}", if fixed_time.enabled { "enabled" } else { "disabled" });
+    }
+    if binds.just_pressed(FixedTimeControls::ToggleFrameStep) {
+        fixed_time.frame_step = !fixed_time.frame_step;
+        info!("frame stepping {}", if fixed_time.frame_step { "enabled" } else { "disabled" });
+    }
+}
+
+fn update_render_time(mut render_time: ResMut<RenderTime>) {
+    let now = Instant::now();
+    if let Some(last_update) = render_time.last_update {
+        render_time.delta = now - last_update;
+    }
+    render_time.last_update = Some(now);
+}
+
+fn frame_step(
+    binds: Res<Input<FixedTimeControls>>,
+    render_time: Res<RenderTime>,
+    fixed_time: Res<FixedTime>,
+    mut step: ResMut<FrameStep>
+) {
+    step.next = false;
+    if !fixed_time.frame_step || !binds.pressed(FixedTimeControls::Step) {
+        step.held = 0.0;
+        step.repeat = 0.0;
+        return;
+    }
+    if binds.just_pressed(FixedTimeControls::Step) {
+        step.next = true;
+        return;
+    }
+    let delta = render_time.delta().as_secs_f64();
+    step.held += delta;
+    if step.held >= fixed_time.step_repeat_delay {
+        step.repeat += fixed_time.step_repeat_rate * delta;
+        if step.repeat >= 1.0 {
+            // at most a tick a frame, so a long frame doesn't build up ticks to catch up on
+            step.repeat = (step.repeat - 1.0).min(1.0);
+            step.next = true;
+        }
+    }
+}
+
+/// Only lets Rapier step on frames that got a tick while frame stepping, and not at all while
+/// paused, Time standing still would otherwise have it step by nothing. This is the only thing
+/// setting [RapierConfiguration::physics_pipeline_active]
+fn step_physics(
+    fixed_time: Res<FixedTime>,
+    step: Res<FrameStep>,
+    rapier_config: Option<ResMut<RapierConfiguration>>
+) {
+    let Some(mut rapier_config) = rapier_config else {
+        return;
+    };
+    let active = !fixed_time.paused && (!fixed_time.frame_step || step.next);
+    if rapier_config.physics_pipeline_active != active {
+        rapier_config.physics_pipeline_active = active;
+    }
+}
+
+fn slow_motion(binds: Res<Input<FixedTimeControls>>, mut fixed_time: ResMut<FixedTime>) {
+    let target = if binds.pressed(FixedTimeControls::SlowMotion) { fixed_time.slow_motion_scale } else { 1.0 };
+    if fixed_time.time_scale == target {
+        return;
+    }
+    let ramp = fixed_time.slow_motion_ramp;
+    fixed_time.time_scale = if ramp <= 0.0 {
+        target
+    } else {
+        let step = 1.0 / (ramp * fixed_time.tick_rate);
+        let difference = target - fixed_time.time_scale;
+        fixed_time.time_scale + difference.clamp(-step, step)
+    };
+}
+
+fn fixed_time_step(
+    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
+    step: Res<FrameStep>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    mut rebased: Local<bool>
+) {
+    let manual = fixed_time.paused || fixed_time.frame_step || fixed_time.enabled;
+    if manual {
+        *rebased = false;
+    }
+    if fixed_time.paused || (fixed_time.frame_step && !step.next) {
+        *time_update_strategy = TimeUpdateStrategy::ManualInstant(time.last_update().unwrap());
+    } else if fixed_time.frame_step {
+        // exactly one tick, slow motion or not
+        *time_update_strategy = TimeUpdateStrategy::ManualInstant(time.last_update().unwrap() + Duration::from_secs_f64(1.0 / fixed_time.tick_rate));
+    } else if fixed_time.enabled {
+        *time_update_strategy = TimeUpdateStrategy::ManualInstant(time.last_update().unwrap() + Duration::from_secs_f64(fixed_time.time_scale / fixed_time.tick_rate));
+    } else if !*rebased && matches!(*time_update_strategy, TimeUpdateStrategy::ManualInstant(_)) {
+        // Time has been going by ticks (or not at all), so it's first put back on real time, the
+        // frame after it can follow real time from there
+        *time_update_strategy = TimeUpdateStrategy::ManualInstant(Instant::now());
+        *rebased = true;
+    } else if !matches!(*time_update_strategy, TimeUpdateStrategy::Automatic) {
+        *time_update_strategy = TimeUpdateStrategy::Automatic;
+    }
 }
@@ -0,0 +1,140 @@
+use std::marker::PhantomData;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::log::info;
+use bevy::math::{DVec3, Vec3};
+use bevy::ecs::event::Events;
+use bevy::prelude::{Component, EventWriter, Parent, Query, Res, ResMut, Resource, Transform, Without};
+use bevy::utils::default;
+use crate::camera_bookmark::{BookmarkFlight, CameraBookmarks};
+use crate::camera_path::CameraPath;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::free_control::ActiveControl;
+
+/// Keeps the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T] near the origin,
+/// so flying very far doesn't run into f32 precision jitter. Once it gets further than
+/// [FloatingOrigin::threshold] from the origin, every root entity is moved by the same amount to
+/// bring it back (which rapier picks up like any other change to a body's transform, velocities
+/// are kept), and the [CameraBookmarks] and [CameraPath] of [T] are moved along.
+///
+/// The position of the world's origin is tracked in [FloatingOrigin::offset], anything that
+/// depends on absolute position (like the [TerrainPlugin](crate::terrain::TerrainPlugin)) needs
+/// to add it in, everything else can listen for [OriginShifted]. The
+/// [RewindBuffer](crate::rewind::RewindBuffer), the [EditHistory](crate::edit_history::EditHistory),
+/// trails, and the [NavMesh](crate::navmesh::NavMesh) along with the paths of agents on it move
+/// with the world that way. The `origin` console command prints where the controlled entity really is.
+pub struct FloatingOriginPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for FloatingOriginPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for FloatingOriginPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<FloatingOrigin>() {
+            app.insert_resource(FloatingOrigin::default());
+        }
+        add_origin_shifted(app);
+        app
+            // before Update, so everything that frame (rapier included) sees the world shifted
+            .add_system_to_stage(CoreStage::PreUpdate, rebase_origin::<T>)
+            .add_console_command("origin", "prints the absolute position of the controlled entity", |world, _| {
+                let offset = world.resource::<FloatingOrigin>().offset;
+                let translation = world
+                    .resource::<ActiveControl<T>>()
+                    .entity
+                    .and_then(|entity| world.get::<Transform>(entity))
+                    .map(|transform| transform.translation);
+                let line = match translation {
+                    Some(translation) => format!("at {} (offset {})", format_position(offset + translation.as_dvec3()), format_position(offset)),
+                    None => format!("nothing controlled (offset {})", format_position(offset))
+                };
+                console_print(world, line);
+            });
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct FloatingOrigin {
+    /// How far from the origin the controlled entity can get before the world is shifted back
+    pub threshold: f32,
+    /// Absolute position of the current origin, the sum of every shift so far (negated)
+    pub offset: DVec3
+}
+
+impl Default for FloatingOrigin {
+    fn default() -> Self {
+        Self {
+            threshold: 1000.0,
+            offset: DVec3::ZERO
+        }
+    }
+}
+
+/// Sent when the world has been shifted, `shift` has been added to every root translation
+#[derive(Copy, Clone, Debug)]
+pub struct OriginShifted {
+    pub shift: Vec3
+}
+
+/// Adds the [OriginShifted] event for a plugin listening for it, unless it's already there. Adding
+/// it again would clear it before every listener saw it, since each time it's added it's also
+/// updated again every frame
+pub fn add_origin_shifted(app: &mut App) {
+    if !app.world.contains_resource::<Events<OriginShifted>>() {
+        app.add_event::<OriginShifted>();
+    }
+}
+
+fn rebase_origin<T: Component>(
+    active: Res<ActiveControl<T>>,
+    mut origin: ResMut<FloatingOrigin>,
+    bookmarks: Option<ResMut<CameraBookmarks<T>>>,
+    path: Option<ResMut<CameraPath<T>>>,
+    mut shifted: EventWriter<OriginShifted>,
+    mut roots: Query<&mut Transform, Without<Parent>>,
+    mut flights: Query<&mut BookmarkFlight>
+) {
+    let Some(translation) = active.entity
+        .and_then(|entity| roots.get(entity).ok())
+        .map(|transform| transform.translation) else {
+        return;
+    };
+    if translation.length() <= origin.threshold {
+        return;
+    }
+    // whole units, so anything snapped to a unit grid (like the debug grid) stays lined up
+    let shift = -translation.round();
+
+    for mut transform in &mut roots {
+        transform.translation += shift;
+    }
+    for mut flight in &mut flights {
+        flight.from.translation += shift;
+        flight.to.translation += shift;
+    }
+    if let Some(mut bookmarks) = bookmarks {
+        for slot in bookmarks.slots.iter_mut().flatten() {
+            slot.translation += shift;
+        }
+    }
+    if let Some(mut path) = path {
+        for point in &mut path.points {
+            point.translation += shift;
+        }
+    }
+
+    origin.offset -= shift.as_dvec3();
+    shifted.send(OriginShifted { shift });
+    info!("shifted the origin to {}", format_position(origin.offset));
+}
+
+fn format_position(position: DVec3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", position.x, position.y, position.z)
+}
@@ -0,0 +1,276 @@
+use std::str::FromStr;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::math::Vec3;
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{Changed, Color, Commands, Component, Entity, GlobalTransform, IntoSystemDescriptor, Mesh, Query, Res, ResMut, Resource, Transform, With, Without, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{ExternalImpulse, RigidBody};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::debug_draw::{line_mesh, unlit, DebugDrawn};
+use crate::fixed_time::ticked;
+use crate::picking::PickTarget;
+use crate::placement::{Placement, PlacementConfig};
+
+/// Spheres pushing dynamic bodies around, the kinds being [ForceFieldKind]s, along with a
+/// [ForceFieldConfig::global_wind] blowing everywhere. Every tick of
+/// [FixedTime](crate::fixed_time::FixedTime) each body in a field gets an impulse worth the tick's
+/// force, so heavier bodies are pushed around less. Fields are drawn as arrows with the rest of the
+/// debug drawing (see [DebugDrawPlugin](crate::debug_draw::DebugDrawPlugin)).
+///
+/// The `field` console command spawns one where the crosshair points, snapped to the grid while
+/// snapping is on in the [PlacementPlugin](crate::placement::PlacementPlugin), with wind blowing
+/// the way the camera looks. `field_clear` removes them all, and `wind` sets the global wind.
+pub struct ForceFieldPlugin;
+
+impl Plugin for ForceFieldPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ForceFieldConfig>() {
+            app.insert_resource(ForceFieldConfig::default());
+        }
+        app
+            .add_system(apply_force_fields.with_run_criteria(ticked))
+            .add_system(draw_force_fields)
+            .add_console_command("field", "spawns a wind, attract, repel or vortex field at the crosshair", |world, args| {
+                let (Some(kind), Some(strength), Some(radius)) = (
+                    args.first().and_then(|arg| arg.parse::<FieldKindName>().ok()),
+                    args.get(1).map_or(Some(10.0), |arg| arg.parse::<f32>().ok()),
+                    args.get(2).map_or(Some(4.0), |arg| arg.parse::<f32>().ok().filter(|radius| *radius > 0.0))
+                ) else {
+                    console_print(world, "usage: field <wind|attract|repel|vortex> [strength] [radius]");
+                    return;
+                };
+                let Some(target) = world.get_resource::<PickTarget>() else {
+                    return;
+                };
+                let mut point = match target.entity {
+                    // the middle of the field a radius away from the surface, except for vortices
+                    // which spin things lying on it
+                    Some(_) if kind == FieldKindName::Vortex => target.point,
+                    Some(_) => target.point + target.normal * radius,
+                    None => target.ray_origin + target.ray_direction * radius * 2.0
+                };
+                let look = target.ray_direction;
+                let kind = match kind {
+                    FieldKindName::Wind => ForceFieldKind::Wind { direction: look },
+                    FieldKindName::Attract => ForceFieldKind::Attractor,
+                    FieldKindName::Repel => ForceFieldKind::Repulsor,
+                    FieldKindName::Vortex => ForceFieldKind::Vortex { axis: Vec3::Y }
+                };
+                let snap = world.get_resource::<Placement>().map_or(false, |placement| placement.snap);
+                let grid_size = world.get_resource::<PlacementConfig>().map_or(0.0, |config| config.grid_size);
+                if snap && grid_size > 0.0 {
+                    point = (point / grid_size).round() * grid_size;
+                }
+                spawn_force_field(world, point, ForceField { kind, strength, radius });
+                console_print(world, format!("spawned a field at {:.1}, {:.1}, {:.1}", point.x, point.y, point.z));
+            })
+            .add_console_command("field_clear", "removes every force field", |world, _| {
+                let fields = world.query_filtered::<Entity, With<ForceField>>().iter(world).collect::<Vec<_>>();
+                for field in &fields {
+                    world.despawn(*field);
+                }
+                console_print(world, format!("removed {} fields", fields.len()));
+            })
+            .add_console_command("wind", "sets the wind blowing everywhere, as x y z", |world, args| {
+                let wind = match args {
+                    [] => None,
+                    [x, y, z] => match (x.parse(), y.parse(), z.parse()) {
+                        (Ok(x), Ok(y), Ok(z)) => Some(Vec3::new(x, y, z)),
+                        _ => {
+                            console_print(world, "usage: wind [x y z]");
+                            return;
+                        }
+                    },
+                    _ => {
+                        console_print(world, "usage: wind [x y z]");
+                        return;
+                    }
+                };
+                match wind {
+                    Some(wind) => world.resource_mut::<ForceFieldConfig>().global_wind = wind,
+                    None => {
+                        let wind = world.resource::<ForceFieldConfig>().global_wind;
+                        console_print(world, format!("wind is {:.1}, {:.1}, {:.1}", wind.x, wind.y, wind.z));
+                    }
+                }
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct ForceFieldConfig {
+    /// Force on every dynamic body, wherever it is
+    pub global_wind: Vec3,
+    /// Distance between the arrows fields are drawn with, read when a field is spawned or changed
+    pub arrow_spacing: f32
+}
+
+impl Default for ForceFieldConfig {
+    fn default() -> Self {
+        Self {
+            global_wind: Vec3::ZERO,
+            arrow_spacing: 1.5
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ForceFieldKind {
+    /// Pushes the same way everywhere in the field
+    Wind { direction: Vec3 },
+    /// Pulls towards the middle, stronger closer in
+    Attractor,
+    /// Pushes away from the middle, stronger closer in
+    Repulsor,
+    /// Spins around `axis` through the middle, pulling in a little so bodies keep circling
+    Vortex { axis: Vec3 }
+}
+
+/// The names the `field` command takes
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum FieldKindName {
+    Wind,
+    Attract,
+    Repel,
+    Vortex
+}
+
+impl FromStr for FieldKindName {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wind" => Ok(FieldKindName::Wind),
+            "attract" => Ok(FieldKindName::Attract),
+            "repel" => Ok(FieldKindName::Repel),
+            "vortex" => Ok(FieldKindName::Vortex),
+            _ => Err(())
+        }
+    }
+}
+
+/// A sphere of `radius` around the entity's translation
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ForceField {
+    pub kind: ForceFieldKind,
+    pub strength: f32,
+    pub radius: f32
+}
+
+impl ForceField {
+    /// The force on a body `offset` from the middle of the field, zero outside of it
+    pub fn force_at(&self, offset: Vec3) -> Vec3 {
+        let distance = offset.length();
+        if distance > self.radius {
+            return Vec3::ZERO;
+        }
+        let falloff = 1.0 - distance / self.radius;
+        let direction = match self.kind {
+            ForceFieldKind::Wind { direction } => return direction.normalize_or_zero() * self.strength,
+            ForceFieldKind::Attractor => -offset.normalize_or_zero(),
+            ForceFieldKind::Repulsor => offset.normalize_or_zero(),
+            ForceFieldKind::Vortex { axis } => {
+                let axis = axis.normalize_or_zero();
+                // only the part of the offset around the axis, bodies above or below spin as well
+                let around = offset - axis * offset.dot(axis);
+                axis.cross(around).normalize_or_zero() - around.normalize_or_zero() * 0.3
+            }
+        };
+        direction * self.strength * falloff
+    }
+
+    fn color(&self) -> Color {
+        match self.kind {
+            ForceFieldKind::Wind { .. } => Color::rgb(0.6, 0.9, 1.0),
+            ForceFieldKind::Attractor => Color::rgb(0.4, 1.0, 0.4),
+            ForceFieldKind::Repulsor => Color::rgb(1.0, 0.4, 0.4),
+            ForceFieldKind::Vortex { .. } => Color::rgb(0.8, 0.5, 1.0)
+        }
+    }
+}
+
+/// Spawns `field` with its middle at `center`, drawn once the debug drawing is shown
+pub fn spawn_force_field(world: &mut World, center: Vec3, field: ForceField) -> Entity {
+    // the arrows are filled in by draw_force_fields
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(line_mesh(std::iter::empty()));
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(unlit(field.color()));
+    world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(center),
+            ..default()
+        },
+        NotShadowCaster,
+        DebugDrawn,
+        field
+    )).id()
+}
+
+fn apply_force_fields(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<ForceFieldConfig>,
+    fields: Query<(&ForceField, &GlobalTransform)>,
+    mut bodies: Query<(Entity, &RigidBody, &GlobalTransform, Option<&mut ExternalImpulse>), Without<ForceField>>
+) {
+    let delta = time.delta_seconds();
+    if fields.is_empty() && config.global_wind == Vec3::ZERO {
+        return;
+    }
+    for (entity, body, transform, impulse) in &mut bodies {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let position = transform.translation();
+        let force = fields.iter()
+            .map(|(field, field_transform)| field.force_at(position - field_transform.translation()))
+            .fold(config.global_wind, |total, force| total + force);
+        if force == Vec3::ZERO {
+            continue;
+        }
+        let push = force * delta;
+        match impulse {
+            Some(mut impulse) => impulse.impulse += push,
+            None => {
+                commands.entity(entity).insert(ExternalImpulse { impulse: push, ..default() });
+            }
+        }
+    }
+}
+
+/// Rebuilds the arrows of fields that changed, an arrow on each point of a grid inside the field
+/// pointing the way bodies there are pushed
+fn draw_force_fields(
+    config: Res<ForceFieldConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    fields: Query<(&ForceField, &Handle<Mesh>), Changed<ForceField>>
+) {
+    let spacing = config.arrow_spacing.max(0.1);
+    for (field, mesh) in &fields {
+        let Some(mesh) = meshes.get_mut(mesh) else {
+            continue;
+        };
+        let steps = (field.radius / spacing).floor() as i32;
+        let mut lines = Vec::new();
+        for x in -steps..=steps {
+            for y in -steps..=steps {
+                for z in -steps..=steps {
+                    let point = Vec3::new(x as f32, y as f32, z as f32) * spacing;
+                    let direction = field.force_at(point).normalize_or_zero();
+                    if point.length() > field.radius || direction == Vec3::ZERO {
+                        continue;
+                    }
+                    let tip = point + direction * spacing * 0.6;
+                    // two barbs, on a side that isn't along the arrow
+                    let side = direction.any_orthogonal_vector() * spacing * 0.12;
+                    let back = tip - direction * spacing * 0.2;
+                    lines.extend([(point, tip), (tip, back + side), (tip, back - side)]);
+                }
+            }
+        }
+        *mesh = line_mesh(lines);
+    }
+}
@@ -0,0 +1,182 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::log::info;
+use bevy::prelude::{EventWriter, Local, Res, ResMut, Resource};
+use bevy::window::{PresentMode, Windows};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::settings::AddSetting;
+use crate::window_control::{add_window_mode_changed, WindowModeChanged};
+
+/// Caps the frame rate by sleeping at the end of each frame (spinning for the last bit, since
+/// sleeping isn't very precise), and sets the [PresentMode] of the primary window. Both are in
+/// [FrameLimit], stored in the `frame_limit` section of the settings file (see
+/// [SettingsPlugin](crate::settings::SettingsPlugin)) and changed with the `fps_limit` and
+/// `present_mode` console commands.
+///
+/// Changing the present mode sends a
+/// [WindowModeChanged](crate::window_control::WindowModeChanged) event.
+///
+/// The frame time actually achieved is measured into [FrameStats], which the
+/// [HudPlugin](crate::hud::HudPlugin) shows. Bevy's [Time](bevy::time::Time) can't be used for
+/// this while the [FixedTimePlugin](crate::fixed_time::FixedTimePlugin) is in use.
+pub struct FrameLimitPlugin;
+
+impl Plugin for FrameLimitPlugin {
+    fn build(&self, app: &mut App) {
+        add_window_mode_changed(app);
+        app
+            .add_setting::<FrameLimit>("frame_limit")
+            .init_resource::<FrameStats>()
+            .add_system(apply_present_mode)
+            .add_system_to_stage(CoreStage::Last, limit_frame_rate)
+            .add_console_command("fps_limit", "caps the frame rate, or removes the cap with off", |world, args| {
+                let limit = match args.first().copied() {
+                    Some("off") => Some(None),
+                    Some(fps) => fps.parse::<f32>().ok().filter(|fps| *fps > 0.0).map(Some),
+                    None => None
+                };
+                match limit {
+                    Some(limit) => world.resource_mut::<FrameLimit>().target_fps = limit,
+                    None => console_print(world, "usage: fps_limit <fps|off>")
+                }
+            })
+            .add_console_command("present_mode", "sets the present mode, one of fifo, mailbox, immediate, auto_vsync or auto_no_vsync", |world, args| {
+                let mode = match args.first().copied() {
+                    Some("fifo") => Some(PresentModeSetting::Fifo),
+                    Some("mailbox") => Some(PresentModeSetting::Mailbox),
+                    Some("immediate") => Some(PresentModeSetting::Immediate),
+                    Some("auto_vsync") => Some(PresentModeSetting::AutoVsync),
+                    Some("auto_no_vsync") => Some(PresentModeSetting::AutoNoVsync),
+                    _ => None
+                };
+                match mode {
+                    Some(mode) => world.resource_mut::<FrameLimit>().present_mode = mode,
+                    None => console_print(world, "usage: present_mode <fifo|mailbox|immediate|auto_vsync|auto_no_vsync>")
+                }
+            });
+    }
+}
+
+/// [PresentMode] as stored in the settings file
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum PresentModeSetting {
+    #[default]
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    Mailbox,
+    Immediate
+}
+
+impl From<PresentModeSetting> for PresentMode {
+    fn from(mode: PresentModeSetting) -> Self {
+        match mode {
+            PresentModeSetting::AutoVsync => PresentMode::AutoVsync,
+            PresentModeSetting::AutoNoVsync => PresentMode::AutoNoVsync,
+            PresentModeSetting::Fifo => PresentMode::Fifo,
+            PresentModeSetting::Mailbox => PresentMode::Mailbox,
+            PresentModeSetting::Immediate => PresentMode::Immediate
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameLimit {
+    /// `None` runs as fast as the present mode allows
+    pub target_fps: Option<f32>,
+    pub present_mode: PresentModeSetting,
+    /// How long before the end of a frame to stop sleeping and spin instead
+    pub spin: Duration
+}
+
+impl Default for FrameLimit {
+    fn default() -> Self {
+        Self {
+            target_fps: None,
+            present_mode: PresentModeSetting::default(),
+            spin: Duration::from_millis(1)
+        }
+    }
+}
+
+/// Real time between frames, smoothed over a few frames
+#[derive(Resource, Default, Debug)]
+pub struct FrameStats {
+    pub frame_time: Duration
+}
+
+impl FrameStats {
+    pub fn fps(&self) -> f32 {
+        let seconds = self.frame_time.as_secs_f32();
+        if seconds > 0.0 { 1.0 / seconds } else { 0.0 }
+    }
+}
+
+/// How much each new frame counts towards [FrameStats::frame_time]
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+
+fn apply_present_mode(limit: Res<FrameLimit>, mut windows: ResMut<Windows>, mut mode_changed: EventWriter<WindowModeChanged>) {
+    if !limit.is_changed() {
+        return;
+    }
+    let Some(window) = windows.get_primary_mut() else {
+        return;
+    };
+    let present_mode: PresentMode = limit.present_mode.into();
+    if window.present_mode() != present_mode {
+        window.set_present_mode(present_mode);
+        info!("present mode set to {:?}", present_mode);
+        mode_changed.send(WindowModeChanged {
+            mode: window.mode(),
+            present_mode
+        });
+    }
+}
+
+fn limit_frame_rate(limit: Res<FrameLimit>, mut stats: ResMut<FrameStats>, mut last_frame: Local<Option<Instant>>) {
+    if let (Some(last), Some(fps)) = (*last_frame, limit.target_fps) {
+        let deadline = last + Duration::from_secs_f32(1.0 / fps);
+        let sleep_until = deadline.checked_sub(limit.spin).unwrap_or(deadline);
+        let now = Instant::now();
+        if sleep_until > now {
+            thread::sleep(sleep_until - now);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    let now = Instant::now();
+    if let Some(last) = *last_frame {
+        let frame_time = now - last;
+        stats.frame_time = if stats.frame_time.is_zero() {
+            frame_time
+        } else {
+            stats.frame_time.mul_f32(1.0 - FRAME_TIME_SMOOTHING) + frame_time.mul_f32(FRAME_TIME_SMOOTHING)
+        };
+    }
+    *last_frame = Some(now);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::settings::round_trip;
+    use super::{FrameLimit, PresentModeSetting};
+
+    #[test]
+    fn present_mode_is_saved() {
+        let limit = FrameLimit {
+            target_fps: Some(144.0),
+            present_mode: PresentModeSetting::Mailbox,
+            spin: Duration::from_micros(500)
+        };
+        let stored = round_trip("frame_limit", &limit).expect("frame_limit didn't load");
+        assert_eq!(stored.target_fps, Some(144.0));
+        assert_eq!(stored.present_mode, PresentModeSetting::Mailbox);
+        assert_eq!(stored.spin, limit.spin);
+    }
+}
@@ -1,27 +1,66 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::str::FromStr;
 use std::f32::consts::{PI, TAU};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
-use bevy::app::{App, Plugin};
-use bevy::input::Input;
-use bevy::input::mouse::MouseMotion;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::input::{Axis, Input};
 use bevy::math::{Quat, Vec2, Vec3};
-use bevy::prelude::{Component, EventReader, Query, Res, ResMut, Resource, Transform, With};
-use bevy::utils::default;
-use bevy::window::{CursorGrabMode, Windows};
+use bevy::ecs::event::Events;
+use bevy::prelude::{Commands, Component, Entity, EventReader, EventWriter, IntoSystemDescriptor, KeyCode, Local, PerspectiveProjection, Projection, Query, Reflect, ReflectResource, Res, ResMut, Resource, SystemLabel, Transform, With, Without};
+use bevy::time::Time;
+use bevy::utils::{default, HashMap};
+use bevy::window::{CursorGrabMode, WindowDescriptor, Windows};
+use bevy_rapier3d::prelude::{Collider, GravityScale, ImpulseJoint, LockedAxes, QueryFilter, RapierContext, RigidBody, Velocity};
 use serde::{Deserialize, Serialize};
-use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::cursor_grab::{cursor_grab, CursorGrab};
+use crate::fixed_time::RenderTime;
+use crate::game_state::running;
+use crate::keybind::{
+    AxisResponse, AxisValues, BindModifier, DeadZone, DisplayName, InputCapture, InputDevice,
+    KeyBindingPlugin, KeyBindings, LastInputDevice, LookDelta, PlayerDevice, PlayerInput,
+    PlayerSlot, RawAxis, RawInput, RawInputSystem, ResponseCurve, SectionBindingSystem, TouchRegion,
+    WheelDirection
+};
+use crate::settings::AddSetting;
+use crate::ui_mode::UiMode;
 
 /// Adds free-moving controls to 3D objects, specifically all entities with the component
 /// [Transform] and the provided generic [T]. This plugin can be initialized in two ways:
 ///
 /// * No default bindings [FreeControlPlugin::new]
-/// * regular WASD controls, left shift for down, space for up [FreeControlPlugin::default]
+/// * regular WASD controls (the [BindingProfile::Wasd] profile, see
+///  [FreeControlPlugin::with_profile] for others), left shift for down, space for up, tab to cycle
+///  the controlled entity, the scroll wheel to zoom, the left stick of a gamepad to move and the
+///  right one to look around, and for touch screens a virtual joystick on the left half of the
+///  window for movement and dragging on the right half to look around
+///  [FreeControlPlugin::default]
 ///
 /// The [FreeControlConfig] resource can be used to control the speed and sensitivity of the
-/// entities
+/// entities. Only a single entity is controlled at a time, which one is kept in the
+/// [ActiveControl] resource and can be switched with [FreeControls::CycleTarget]
+///
+/// Looking around goes by the mouse or the stick axes depending on the
+/// [LastInputDevice](crate::keybind::LastInputDevice), whichever was used last.
+///
+/// Adding [FreeControlSuspended] to an entity stops it from being moved by the controls, for
+/// anything else taking over the entity for a while.
+///
+/// The controls don't move entities directly, they send [LookIntent] and [MoveIntent] events
+/// which [apply_intents] applies, see [FreeControlPlugin::with_manual_intents] for handling them
+/// some other way.
+///
+/// [FreeControlPlugin::with_grab_bindings] additionally lets the controls grab and release the
+/// cursor through the [CursorGrab] resource, and [FreeControlPlugin::with_collision] keeps the
+/// entities from flying through walls.
 pub struct FreeControlPlugin<T: Component> {
     key_bindings: KeyBindingPlugin<FreeControls<T>>,
+    grab_bindings: bool,
+    collision: Option<f32>,
+    movement: MovementMode,
+    profile: Option<BindingProfile>,
     __phantom: PhantomData<fn(T)>
 }
 
@@ -29,7 +68,11 @@ impl <T: Component> FreeControlPlugin<T> {
     /// Creates a new `FreeControlPlugin`, without any default bindings
     pub fn new() -> Self {
         Self {
-            key_bindings: KeyBindingPlugin::default(),
+            key_bindings: KeyBindingPlugin::default().with_preset_section("free_controls"),
+            grab_bindings: false,
+            collision: None,
+            movement: MovementMode::Transform,
+            profile: None,
             __phantom: default()
         }
     }
@@ -38,31 +81,129 @@ impl <T: Component> FreeControlPlugin<T> {
         self.key_bindings = self.key_bindings.bind(input, bind);
         self
     }
+
+    pub fn bind_axis(mut self, axis: RawAxis, bind: FreeControls<T>) -> Self {
+        self.key_bindings = self.key_bindings.bind_axis(axis, bind);
+        self
+    }
+
+    pub fn axis_response(mut self, axis: RawAxis, response: AxisResponse) -> Self {
+        self.key_bindings = self.key_bindings.axis_response(axis, response);
+        self
+    }
+
+    pub fn modifier(mut self, input: impl Into<RawInput>, modifier: BindModifier) -> Self {
+        self.key_bindings = self.key_bindings.modifier(input, modifier);
+        self
+    }
+
+    /// Sets the inversion and scale of the provided `axis`, for example
+    /// `BindModifier::INVERTED` on the axis bound to [FreeControls::PitchAxis] inverts looking up
+    /// and down
+    pub fn axis_modifier(mut self, axis: RawAxis, modifier: BindModifier) -> Self {
+        self.key_bindings = self.key_bindings.axis_modifier(axis, modifier);
+        self
+    }
+
+    /// Binds the movement keys of `profile`, and lets the `controls` section of the settings file
+    /// (see [SettingsPlugin](crate::settings::SettingsPlugin)) and the `controls_profile` console
+    /// command switch to another [BindingProfile] later on. `profile` is only the default, a
+    /// profile stored in the settings wins
+    pub fn with_profile(mut self, profile: BindingProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Only takes input from the device [PlayerInput](crate::keybind::PlayerInput) assigns to the
+    /// player `slot`, for giving each camera its own controls with a marker for each player
+    pub fn for_player(mut self, slot: usize) -> Self {
+        self.key_bindings = self.key_bindings.for_player(slot);
+        self
+    }
+
+    /// Makes [FreeControls::Lock] and [FreeControls::Unlock] activate and deactivate the
+    /// [CursorGrab] resource, binding them to left click and escape. Requires the
+    /// [CursorGrabPlugin](crate::cursor_grab::CursorGrabPlugin)
+    pub fn with_grab_bindings(mut self) -> Self {
+        use bevy::prelude::MouseButton;
+
+        self.grab_bindings = true;
+        self
+            .bind(MouseButton::Left, FreeControls::Lock)
+            .bind(KeyCode::Escape, FreeControls::Unlock)
+    }
+
+    /// Gives the controlled entities a ball [Collider] of `radius`, and has their movement slide
+    /// along other colliders instead of passing through them. Requires the
+    /// [RapierPhysicsPlugin](bevy_rapier3d::plugin::RapierPhysicsPlugin)
+    pub fn with_collision(mut self, radius: f32) -> Self {
+        self.collision = Some(radius);
+        self
+    }
+
+    /// Leaves [LookIntent]s and [MoveIntent]s for other systems to act on, instead of applying
+    /// them to the [Transform] with [apply_intents]
+    pub fn with_manual_intents(mut self) -> Self {
+        self.movement = MovementMode::Manual;
+        self
+    }
+
+    /// Moves the controlled entities through rapier instead of setting their [Transform], giving
+    /// them a dynamic [RigidBody] and a ball [Collider] of `radius` so they push other bodies
+    /// around and are blocked by them. Requires the
+    /// [RapierPhysicsPlugin](bevy_rapier3d::plugin::RapierPhysicsPlugin)
+    pub fn with_velocity_movement(mut self, radius: f32) -> Self {
+        self.movement = MovementMode::Velocity;
+        self.collision = Some(radius);
+        self
+    }
 }
 
 impl <T: Component> Clone for FreeControlPlugin<T> {
     fn clone(&self) -> Self {
         Self {
             key_bindings: self.key_bindings.clone(),
+            grab_bindings: self.grab_bindings,
+            collision: self.collision,
+            movement: self.movement,
+            profile: self.profile,
             __phantom: self.__phantom
         }
     }
 }
 
+const STICK_RESPONSE: AxisResponse = AxisResponse {
+    dead_zone: DeadZone::Radial(0.15),
+    curve: ResponseCurve::Power(2.0)
+};
+
 impl <T: Component> Default for FreeControlPlugin<T> {
     fn default() -> Self {
-        use bevy::prelude::KeyCode::*;
+        use bevy::input::gamepad::GamepadAxisType;
 
-        let key_bindings = KeyBindingPlugin::default()
-            .bind(W, FreeControls::Forward)
-            .bind(S, FreeControls::Backward)
-            .bind(A, FreeControls::Left)
-            .bind(D, FreeControls::Right)
-            .bind(LShift, FreeControls::Down)
-            .bind(Space, FreeControls::Up);
+        // the keyboard bindings come from the profile
+        let key_bindings = KeyBindingPlugin::default().with_preset_section("free_controls")
+            .bind(WheelDirection::Up, FreeControls::ZoomIn)
+            .bind(WheelDirection::Down, FreeControls::ZoomOut)
+            .bind_axis(RawAxis::TouchStickX(TouchRegion::Left), FreeControls::StrafeAxis)
+            .bind_axis(RawAxis::TouchStickY(TouchRegion::Left), FreeControls::ForwardAxis)
+            .bind_axis(RawAxis::TouchDragX(TouchRegion::Right), FreeControls::YawAxis)
+            .bind_axis(RawAxis::TouchDragY(TouchRegion::Right), FreeControls::PitchAxis)
+            .bind_axis(RawAxis::GamepadAxis(GamepadAxisType::LeftStickX), FreeControls::StrafeAxis)
+            .bind_axis(RawAxis::GamepadAxis(GamepadAxisType::LeftStickY), FreeControls::ForwardAxis)
+            .bind_axis(RawAxis::GamepadAxis(GamepadAxisType::RightStickX), FreeControls::StickYawAxis)
+            .bind_axis(RawAxis::GamepadAxis(GamepadAxisType::RightStickY), FreeControls::StickPitchAxis)
+            .axis_response(RawAxis::GamepadAxis(GamepadAxisType::LeftStickX), STICK_RESPONSE)
+            .axis_response(RawAxis::GamepadAxis(GamepadAxisType::LeftStickY), STICK_RESPONSE)
+            .axis_response(RawAxis::GamepadAxis(GamepadAxisType::RightStickX), STICK_RESPONSE)
+            .axis_response(RawAxis::GamepadAxis(GamepadAxisType::RightStickY), STICK_RESPONSE);
 
         Self {
             key_bindings,
+            grab_bindings: false,
+            collision: None,
+            movement: MovementMode::Transform,
+            profile: Some(BindingProfile::Wasd),
             __phantom: default()
         }
     }
@@ -72,15 +213,71 @@ impl <T: Component> Plugin for FreeControlPlugin<T> {
     fn build(&self, app: &mut App) {
         app
             .add_plugin(self.key_bindings.clone())
-            .add_system(free_controls::<T>);
+            .register_type::<FreeControlConfig<T>>()
+            .register_type::<FreeControls<T>>()
+            .insert_resource(ActiveControl::<T>::default())
+            .add_system(cycle_active_control::<T>)
+            .add_system(free_controls::<T>.with_run_criteria(running).label(FreeControlSystem).after(cycle_active_control::<T>));
+        // a second plugin with another marker shares the events, adding them again would clear
+        // them before every consumer saw them
+        if !app.world.contains_resource::<Events<MoveIntent>>() {
+            app
+                .add_event::<LookIntent>()
+                .add_event::<MoveIntent>();
+        }
+        match self.movement {
+            MovementMode::Transform => {
+                app.add_system(apply_intents::<T>.after(FreeControlSystem));
+            }
+            MovementMode::Velocity => {
+                app
+                    .add_system(add_velocity_bodies::<T>)
+                    .add_system(apply_intents_to_velocity::<T>.after(FreeControlSystem));
+            }
+            MovementMode::Manual => {}
+        }
         if !app.world.contains_resource::<FreeControlConfig<T>>() {
             app.insert_resource(FreeControlConfig::<T>::default());
         }
+        if let Some(profile) = self.profile {
+            // every marker shares the one profile setting
+            if !app.world.contains_resource::<ControlProfileSettings>() {
+                app
+                    .insert_resource(ControlProfileSettings { profile })
+                    .add_setting::<ControlProfileSettings>("controls")
+                    .add_console_command("controls_profile", "switches the movement keys to wasd, esdf, arrows or left_handed", |world, args| {
+                        let mut settings = world.resource_mut::<ControlProfileSettings>();
+                        match args.first().map(|arg| arg.parse::<BindingProfile>()) {
+                            None => {
+                                let profile = settings.profile;
+                                console_print(world, format!("using the {} profile", profile.name()));
+                            }
+                            Some(Ok(profile)) => settings.profile = profile,
+                            Some(Err(())) => console_print(world, "usage: controls_profile [wasd|esdf|arrows|left_handed]")
+                        }
+                    });
+            }
+            app.add_system_to_stage(CoreStage::PreUpdate, apply_binding_profile::<T>.before(SectionBindingSystem).before(RawInputSystem));
+        }
+        if self.grab_bindings {
+            app.add_system(grab_controls::<T>.with_run_criteria(running).before(cursor_grab));
+        }
+        if let Some(radius) = self.collision {
+            app
+                .insert_resource(FreeControlCollision::<T> { radius, __phantom: default() })
+                .add_system(add_collision_colliders::<T>);
+        }
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
-pub enum FreeControls<T> {
+/// Label of the system turning inputs into [LookIntent]s and [MoveIntent]s, for every marker, along
+/// with the systems adjusting those intents before they're applied (like swimming slower)
+#[derive(SystemLabel)]
+pub struct FreeControlSystem;
+
+// the 'static bound is only there for Reflect, the marker itself is never stored
+#[derive(Default, Serialize, Deserialize, Reflect)]
+pub enum FreeControls<T: 'static> {
     #[default]
     Forward,
     Backward,
@@ -88,12 +285,210 @@ pub enum FreeControls<T> {
     Right,
     Up,
     Down,
+    /// Moves control over to the next entity with the marker
+    CycleTarget,
+    /// Activates [CursorGrab], see [FreeControlPlugin::with_grab_bindings]
+    Lock,
+    /// Deactivates [CursorGrab], see [FreeControlPlugin::with_grab_bindings]
+    Unlock,
+    /// Axis moving right when positive and left when negative, from -1.0 to 1.0
+    StrafeAxis,
+    /// Axis moving forward when positive and backward when negative, from -1.0 to 1.0
+    ForwardAxis,
+    /// Axis treated like horizontal mouse motion
+    YawAxis,
+    /// Axis treated like vertical mouse motion
+    PitchAxis,
+    /// Axis turning right when positive and left when negative, at
+    /// [FreeControlConfig::stick_yaw_speed] at full deflection, from -1.0 to 1.0
+    StickYawAxis,
+    /// Axis looking up when positive and down when negative, at
+    /// [FreeControlConfig::stick_pitch_speed] at full deflection, from -1.0 to 1.0
+    StickPitchAxis,
+    /// Narrows the field of view of entities with a perspective [Projection]
+    ZoomIn,
+    ZoomOut,
     #[allow(non_camel_case_types)]
-    __phantom(PhantomData<fn(T)>)
+    __phantom(#[reflect(ignore)] PhantomData<fn(T)>)
 }
 
+impl <T: 'static> DisplayName for FreeControls<T> {
+    fn display_name(&self) -> Cow<'static, str> {
+        use FreeControls::*;
+
+        match self {
+            Forward => "Move Forward",
+            Backward => "Move Backward",
+            Left => "Move Left",
+            Right => "Move Right",
+            Up => "Move Up",
+            Down => "Move Down",
+            CycleTarget => "Next Target",
+            Lock => "Grab Cursor",
+            Unlock => "Release Cursor",
+            StrafeAxis => "Strafe",
+            ForwardAxis => "Move",
+            YawAxis | StickYawAxis => "Turn",
+            PitchAxis | StickPitchAxis => "Look Up and Down",
+            ZoomIn => "Zoom In",
+            ZoomOut => "Zoom Out",
+            __phantom(_) => unreachable!()
+        }.into()
+    }
+}
+
+/// The entity currently receiving input from [FreeControlPlugin], if this is `None` or the entity
+/// loses the marker, the first entity with the marker is picked
+#[derive(Resource)]
+pub struct ActiveControl<T> {
+    pub entity: Option<Entity>,
+    pub __phantom: PhantomData<fn(T)>
+}
+
+impl <T> Default for ActiveControl<T> {
+    fn default() -> Self {
+        Self {
+            entity: None,
+            __phantom: default()
+        }
+    }
+}
+
+/// Sent by [free_controls] for the active entity whenever the controls turn it, in radians
+#[derive(Copy, Clone, Debug)]
+pub struct LookIntent {
+    pub entity: Entity,
+    /// Rotation around the global y axis
+    pub yaw: f32,
+    /// Rotation around the local x axis
+    pub pitch: f32
+}
+
+/// Sent by [free_controls] for the active entity whenever the controls move it
+#[derive(Copy, Clone, Debug)]
+pub struct MoveIntent {
+    pub entity: Entity,
+    /// Normalized, in world space
+    pub direction: Vec3,
+    /// How far to move this tick
+    pub magnitude: f32
+}
+
+/// A full set of keyboard movement bindings, see [FreeControlPlugin::with_profile]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum BindingProfile {
+    /// W, A, S and D, space for up, left shift for down and tab to cycle
+    #[default]
+    Wasd,
+    /// WASD moved over by one key so the fingers rest on the bump of F, and A is within reach
+    /// for down
+    Esdf,
+    /// The arrow keys, right shift for up, right control for down and enter to cycle
+    Arrows,
+    /// I, J, K and L for holding the mouse in the left hand, right shift for down and U to cycle
+    LeftHanded
+}
+
+impl BindingProfile {
+    pub const ALL: [BindingProfile; 4] = [BindingProfile::Wasd, BindingProfile::Esdf, BindingProfile::Arrows, BindingProfile::LeftHanded];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BindingProfile::Wasd => "wasd",
+            BindingProfile::Esdf => "esdf",
+            BindingProfile::Arrows => "arrows",
+            BindingProfile::LeftHanded => "left_handed"
+        }
+    }
+
+    /// Keys for forward, backward, left, right, up, down and cycling the target, in that order
+    pub fn keys(self) -> [KeyCode; 7] {
+        use KeyCode::*;
+
+        match self {
+            BindingProfile::Wasd => [W, S, A, D, Space, LShift, Tab],
+            BindingProfile::Esdf => [E, D, S, F, Space, A, Tab],
+            BindingProfile::Arrows => [Up, Down, Left, Right, RShift, RControl, Return],
+            BindingProfile::LeftHanded => [I, K, J, L, Space, RShift, U]
+        }
+    }
+}
+
+impl FromStr for BindingProfile {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BindingProfile::ALL
+            .into_iter()
+            .find(|profile| profile.name() == name)
+            .ok_or(())
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlProfileSettings {
+    pub profile: BindingProfile
+}
+
+/// Swaps the keys of the previous profile for the ones of the current one whenever the setting
+/// changes, inputs rebound by hand to something else are left alone
+fn apply_binding_profile<T: Component>(
+    settings: Res<ControlProfileSettings>,
+    mut binds: ResMut<KeyBindings<FreeControls<T>>>,
+    mut applied: Local<Option<BindingProfile>>
+) {
+    if *applied == Some(settings.profile) {
+        return;
+    }
+    let actions = [
+        FreeControls::Forward,
+        FreeControls::Backward,
+        FreeControls::Left,
+        FreeControls::Right,
+        FreeControls::Up,
+        FreeControls::Down,
+        FreeControls::CycleTarget
+    ];
+    if let Some(previous) = *applied {
+        for (key, action) in previous.keys().into_iter().zip(actions) {
+            if binds.inputs(action).any(|input| input == RawInput::KeyCode(key)) {
+                binds.clear_bind(key);
+            }
+        }
+    }
+    for (key, action) in settings.profile.keys().into_iter().zip(actions) {
+        binds.bind(key, action);
+    }
+    *applied = Some(settings.profile);
+}
+
+/// How [LookIntent]s and [MoveIntent]s end up moving the controlled entities
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MovementMode {
+    /// Set the [Transform] directly, see [apply_intents]
+    Transform,
+    /// Set rapier's [Velocity], see [FreeControlPlugin::with_velocity_movement]
+    Velocity,
+    /// Left to other systems, see [FreeControlPlugin::with_manual_intents]
+    Manual
+}
+
+/// Present when [FreeControlPlugin::with_collision] or [FreeControlPlugin::with_velocity_movement]
+/// was used
 #[derive(Resource)]
-pub struct FreeControlConfig<T> {
+pub struct FreeControlCollision<T> {
+    pub radius: f32,
+    pub __phantom: PhantomData<fn(T)>
+}
+
+/// Keeps [free_controls] from moving the entity while present, it stays the [ActiveControl]
+#[derive(Component)]
+pub struct FreeControlSuspended;
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct FreeControlConfig<T: 'static> {
     pub forward_speed: f32,
     pub backward_speed: f32,
     pub left_speed: f32,
@@ -106,10 +501,26 @@ pub struct FreeControlConfig<T> {
     pub up_sensitivity: f32,
     pub down_sensitivity: f32,
 
+    /// Turning speed of [FreeControls::StickYawAxis] at full deflection, in degrees per second
+    pub stick_yaw_speed: f32,
+    /// Turning speed of [FreeControls::StickPitchAxis] at full deflection, in degrees per second
+    pub stick_pitch_speed: f32,
+
+    /// Narrowest field of view zooming in can reach, in radians
+    pub min_fov: f32,
+    /// Widest field of view zooming out can reach, in radians
+    pub max_fov: f32,
+    /// How much the field of view changes each tick zooming is pressed, in radians
+    pub zoom_speed: f32,
+    /// Fraction of the way to the zoomed field of view covered every 60th of a second, 1.0 for no
+    /// smoothing
+    pub zoom_smoothing: f32,
+
+    #[reflect(ignore)]
     pub __phantom: PhantomData<fn(T)>
 }
 
-impl <T> Default for FreeControlConfig<T> {
+impl <T: 'static> Default for FreeControlConfig<T> {
     fn default() -> Self {
         Self {
             forward_speed: 0.5,
@@ -124,66 +535,331 @@ impl <T> Default for FreeControlConfig<T> {
             up_sensitivity: 0.5 * PI,
             down_sensitivity: 0.5 * PI,
 
+            stick_yaw_speed: 180.0,
+            stick_pitch_speed: 120.0,
+
+            min_fov: PI / 18.0,
+            max_fov: PI / 2.0,
+            zoom_speed: PI / 30.0,
+            zoom_smoothing: 0.25,
+
             __phantom: default()
         }
     }
 }
 
+pub fn grab_controls<T: Component>(
+    binds: Res<Input<FreeControls<T>>>,
+    ui_mode: Option<Res<UiMode>>,
+    mut cursor_grab: ResMut<CursorGrab>
+) {
+    // clicking in ui mode is meant for whatever the cursor points at
+    let ui_mode = ui_mode.map_or(false, |ui_mode| ui_mode.active);
+    if binds.just_pressed(FreeControls::Lock) && cursor_grab.is_inactive() && !ui_mode {
+        cursor_grab.activate();
+    }
+    if binds.just_pressed(FreeControls::Unlock) && cursor_grab.is_active() {
+        cursor_grab.deactivate();
+    }
+}
+
+pub fn cycle_active_control<T: Component>(
+    binds: Res<Input<FreeControls<T>>>,
+    mut active: ResMut<ActiveControl<T>>,
+    controllable: Query<Entity, With<T>>
+) {
+    let mut entities = controllable.iter().collect::<Vec<_>>();
+    entities.sort();
+
+    let current = active.entity.and_then(|entity| entities.iter().position(|e| *e == entity));
+    let next = match current {
+        Some(index) if binds.just_pressed(FreeControls::CycleTarget) => Some((index + 1) % entities.len()),
+        Some(index) => Some(index),
+        None if entities.is_empty() => None,
+        None => Some(0)
+    };
+    let next = next.map(|index| entities[index]);
+    if active.entity != next {
+        active.entity = next;
+    }
+}
+
 pub fn free_controls<T: Component>(
-    mut windows: ResMut<Windows>,
-    mut ev_motion: EventReader<MouseMotion>,
+    time: Res<Time>,
+    render_time: Option<Res<RenderTime>>,
+    windows: Option<Res<Windows>>,
+    look_delta: Res<LookDelta>,
     config: Res<FreeControlConfig<T>>,
     binds: Res<Input<FreeControls<T>>>,
-    mut free_control: Query<&mut Transform, With<T>>
+    axes: Res<Axis<FreeControls<T>>>,
+    axis_values: Res<AxisValues<FreeControls<T>>>,
+    active: Res<ActiveControl<T>>,
+    ui_mode: Option<Res<UiMode>>,
+    last_device: Res<LastInputDevice>,
+    players: Res<PlayerInput>,
+    player_slot: Option<Res<PlayerSlot<FreeControls<T>>>>,
+    capture: Res<InputCapture>,
+    mut zoom_target: Local<Option<(Entity, f32)>>,
+    mut look_intents: EventWriter<LookIntent>,
+    mut move_intents: EventWriter<MoveIntent>,
+    mut free_control: Query<(&Transform, Option<&mut Projection>), (With<T>, Without<FreeControlSuspended>)>
 ) {
-    // todo remove forced usage of MouseMotion, likely requires some rewriting of KeyBindingPlugin
     // todo needs to handle multiple windows, going to wait until Bevy updates to having Windows as Entities
-    let window = windows.get_primary_mut().unwrap();
-
-    if matches!(window.cursor_grab_mode(), CursorGrabMode::Locked) {
-        let mut rotation_move = Vec2::ZERO;
-        for motion in ev_motion.iter() {
-            let Vec2 {x, y} = motion.delta;
-            if x < 0.0 {
-                rotation_move.x += x * config.left_sensitivity;
-            } else {
-                rotation_move.x += x * config.right_sensitivity;
+    let window = windows.as_ref().and_then(|windows| windows.get_primary());
+    let grabbed = window.map_or(false, |window| matches!(window.cursor_grab_mode(), CursorGrabMode::Locked));
+    // without a window, looking (which is only injected then) goes by the size one would have
+    let window_size = window.map_or_else(
+        || {
+            let descriptor = WindowDescriptor::default();
+            Vec2::new(descriptor.width, descriptor.height)
+        },
+        |window| Vec2::new(window.width(), window.height())
+    );
+
+    let sensitivity = |Vec2 {x, y}: Vec2| {
+        let x = if x < 0.0 { x * config.left_sensitivity } else { x * config.right_sensitivity };
+        let y = if y < 0.0 { y * config.up_sensitivity } else { y * config.down_sensitivity };
+        Vec2::new(x, y)
+    };
+    let axis = |bind| axes.get(bind).unwrap_or(0.0);
+
+    // mouse and stick look follow whichever was used last, so a mouse bumped while playing on a
+    // gamepad (or a drifting stick) doesn't fight the other, unless the controls belong to a
+    // player with a device of their own
+    let (mouse, gamepad) = match player_slot.map(|slot| players.device(slot.slot)) {
+        Some(device) => (device == Some(PlayerDevice::KeyboardMouse), matches!(device, Some(PlayerDevice::Gamepad(_)))),
+        None => (last_device.0 != InputDevice::Gamepad, last_device.0 == InputDevice::Gamepad)
+    };
+    // the mouse isn't mapped like other input, so the capture has to be checked here as well
+    let mouse = mouse && !capture.is_captured();
+    let mut rotation_move = Vec2::ZERO;
+    if grabbed && mouse {
+        rotation_move += sensitivity(look_delta.mouse);
+    }
+    rotation_move += sensitivity(look_delta.injected);
+    // axes (such as touch drags) don't depend on the cursor being grabbed, since touch platforms
+    // don't have a cursor to grab
+    // read unclamped, since they're distances like the mouse's
+    rotation_move += sensitivity(Vec2::new(axis_values.get(FreeControls::YawAxis), axis_values.get(FreeControls::PitchAxis)));
+    let ui_active = ui_mode.map_or(false, |ui_mode| ui_mode.active);
+    if ui_active {
+        rotation_move = Vec2::ZERO;
+    }
+
+    let controlled = active.entity.and_then(|entity| free_control.get_mut(entity).ok().map(|components| (entity, components)));
+    let Some((entity, (transform, projection))) = controlled else {
+        return;
+    };
+    // zoom easing and sticks go by real time, so they still work while the world is slowed down or
+    // frame stepping
+    let delta = render_time.map_or(time.delta_seconds(), |render_time| render_time.delta_seconds());
+    if let Some(mut projection) = projection {
+        if let Projection::Perspective(PerspectiveProjection { fov, .. }) = *projection {
+            // the target is kept separate from the projection so zooming can ease towards it,
+            // starting over from the current fov whenever the controlled entity changes
+            let target = match *zoom_target {
+                Some((target_entity, target)) if target_entity == entity => target,
+                _ => fov
+            };
+            let mut target = target;
+            if grabbed && binds.pressed(FreeControls::ZoomIn) {
+                target -= config.zoom_speed;
             }
-            if y < 0.0 {
-                rotation_move.y += y * config.up_sensitivity;
-            } else {
-                rotation_move.y += y * config.down_sensitivity;
+            if grabbed && binds.pressed(FreeControls::ZoomOut) {
+                target += config.zoom_speed;
             }
+            let target = target.clamp(config.min_fov, config.max_fov);
+            *zoom_target = Some((entity, target));
+
+            // only touching the projection when zooming keeps it from being marked as changed
+            // every frame, so easing snaps to the target once close, it would never quite get there
+            let difference = target - fov;
+            if difference != 0.0 {
+                let fov = if difference.abs() < 1e-4 {
+                    target
+                } else {
+                    let smoothing = config.zoom_smoothing.clamp(0.0, 1.0);
+                    fov + difference * (1.0 - (1.0 - smoothing).powf(delta * 60.0))
+                };
+                if let Projection::Perspective(perspective) = &mut *projection {
+                    perspective.fov = fov;
+                }
+            }
+        }
+    }
+
+    let mut yaw = -rotation_move.x / window_size.x;
+    let mut pitch = -rotation_move.y / window_size.y;
+    // sticks hold a deflection rather than moving a distance, so they turn at a rate
+    if gamepad && !ui_active {
+        yaw -= axis(FreeControls::StickYawAxis) * config.stick_yaw_speed.to_radians() * delta;
+        pitch += axis(FreeControls::StickPitchAxis) * config.stick_pitch_speed.to_radians() * delta;
+    }
+    if yaw != 0.0 || pitch != 0.0 {
+        look_intents.send(LookIntent { entity, yaw, pitch });
+    }
+
+    // movement is relative to where the entity will be looking once the look is applied
+    let mut transform = *transform;
+    transform.rotation = Quat::from_rotation_y(yaw) * transform.rotation;
+    transform.rotation = transform.rotation * Quat::from_rotation_x(pitch);
+
+    let mut movement = Vec3::ZERO;
+    let mut handle = |input, f: fn(&Transform) -> Vec3, speed| {
+        if grabbed && binds.pressed(input) {
+            movement += f(&transform) * speed;
         }
+    };
 
-        for mut transform in &mut free_control {
-            let yaw = Quat::from_rotation_y(-rotation_move.x / window.width());
-            let pitch = Quat::from_rotation_x(-rotation_move.y / window.height());
-            transform.rotation = yaw * transform.rotation; // rotate around global y axis
-            transform.rotation = transform.rotation * pitch; // rotate around local x axis
+    {
+        use FreeControls::*;
 
-            let mut handle = |input, f: fn(&Transform) -> Vec3, speed| {
-                if binds.pressed(input) {
-                    let delta = f(&transform) * speed;
-                    transform.translation += delta;
-                }
-            };
+        handle(Forward, Transform::forward, config.forward_speed);
+        handle(Backward, Transform::back, config.backward_speed);
+        handle(Left, Transform::left, config.left_speed);
+        handle(Right, Transform::right, config.right_speed);
+        handle(Up, Transform::up, config.up_speed);
+        handle(Down, Transform::down, config.down_speed);
+    }
+
+    let strafe = axis(FreeControls::StrafeAxis);
+    let strafe_speed = if strafe < 0.0 { config.left_speed } else { config.right_speed };
+    let forward = axis(FreeControls::ForwardAxis);
+    let forward_speed = if forward < 0.0 { config.backward_speed } else { config.forward_speed };
+    movement += transform.right() * strafe * strafe_speed + transform.forward() * forward * forward_speed;
+
+    if movement != Vec3::ZERO {
+        move_intents.send(MoveIntent {
+            entity,
+            direction: movement.normalize(),
+            magnitude: movement.length()
+        });
+    }
+}
 
-            {
-                use FreeControls::*;
+/// Applies [LookIntent]s and [MoveIntent]s straight to the [Transform] of entities with the
+/// marker, sliding along colliders when [FreeControlPlugin::with_collision] was used
+pub fn apply_intents<T: Component>(
+    mut look_intents: EventReader<LookIntent>,
+    mut move_intents: EventReader<MoveIntent>,
+    collision: Option<Res<FreeControlCollision<T>>>,
+    rapier_context: Option<Res<RapierContext>>,
+    mut free_control: Query<&mut Transform, With<T>>
+) {
+    for intent in look_intents.iter() {
+        if let Ok(mut transform) = free_control.get_mut(intent.entity) {
+            transform.rotation = Quat::from_rotation_y(intent.yaw) * transform.rotation; // rotate around global y axis
+            transform.rotation = transform.rotation * Quat::from_rotation_x(intent.pitch); // rotate around local x axis
+        }
+    }
 
-                handle(Forward, Transform::forward, config.forward_speed);
-                handle(Backward, Transform::back, config.backward_speed);
-                handle(Left, Transform::left, config.left_speed);
-                handle(Right, Transform::right, config.right_speed);
-                handle(Up, Transform::up, config.up_speed);
-                handle(Down, Transform::down, config.down_speed);
+    for intent in move_intents.iter() {
+        if let Ok(mut transform) = free_control.get_mut(intent.entity) {
+            let movement = intent.direction * intent.magnitude;
+            match (&collision, &rapier_context) {
+                (Some(collision), Some(rapier_context)) => {
+                    transform.translation = slide(rapier_context, intent.entity, transform.translation, movement, collision.radius);
+                }
+                _ => transform.translation += movement
             }
         }
     }
 }
 
-impl <T> FreeControls<T> {
+/// Moves a ball of `radius` by `movement`, sliding along whatever it hits instead of passing
+/// through it
+fn slide(rapier_context: &RapierContext, entity: Entity, mut position: Vec3, mut movement: Vec3, radius: f32) -> Vec3 {
+    // keeps the ball from ending up exactly touching, which would make the next cast start inside
+    const SKIN: f32 = 0.01;
+
+    let shape = Collider::ball(radius);
+    let filter = QueryFilter::default().exclude_collider(entity);
+    // a few iterations are enough for corners, where the ball slides off one surface into another
+    for _ in 0..3 {
+        let hit = rapier_context.cast_shape(position, Quat::IDENTITY, movement, &shape, 1.0, filter);
+        let Some((_, toi)) = hit else {
+            return position + movement;
+        };
+
+        let length = movement.length();
+        let travel = (toi.toi * length - SKIN).max(0.0);
+        position += movement / length * travel;
+
+        let remaining = movement * (1.0 - toi.toi);
+        movement = remaining - toi.normal1 * remaining.dot(toi.normal1);
+        if movement.length_squared() < f32::EPSILON {
+            break;
+        }
+    }
+    position
+}
+
+/// Applies [LookIntent]s to the [Transform] and [MoveIntent]s to the [Velocity] of entities with
+/// the marker, the velocity is zeroed on ticks without any movement so the entity stops right
+/// away like it does when moving by [Transform]. Entities held by an [ImpulseJoint] (like the
+/// [GrapplePlugin](crate::grapple::GrapplePlugin)'s) keep their velocity instead, so they can swing
+pub fn apply_intents_to_velocity<T: Component>(
+    time: Res<Time>,
+    mut look_intents: EventReader<LookIntent>,
+    mut move_intents: EventReader<MoveIntent>,
+    mut free_control: Query<(Entity, &mut Transform, &mut Velocity), With<T>>,
+    jointed: Query<(), With<ImpulseJoint>>
+) {
+    for intent in look_intents.iter() {
+        if let Ok((_, mut transform, _)) = free_control.get_mut(intent.entity) {
+            transform.rotation = Quat::from_rotation_y(intent.yaw) * transform.rotation;
+            transform.rotation = transform.rotation * Quat::from_rotation_x(intent.pitch);
+        }
+    }
+
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+    let mut moved = HashMap::<Entity, Vec3>::default();
+    for intent in move_intents.iter() {
+        *moved.entry(intent.entity).or_default() += intent.direction * intent.magnitude;
+    }
+    for (entity, _, mut velocity) in &mut free_control {
+        if jointed.contains(entity) {
+            continue;
+        }
+        // intents are distances for a single tick
+        let linvel = moved.get(&entity).copied().unwrap_or(Vec3::ZERO) / delta;
+        if velocity.linvel != linvel || velocity.angvel != Vec3::ZERO {
+            velocity.linvel = linvel;
+            velocity.angvel = Vec3::ZERO;
+        }
+    }
+}
+
+pub fn add_velocity_bodies<T: Component>(
+    mut commands: Commands,
+    entities: Query<Entity, (With<T>, Without<RigidBody>)>
+) {
+    for entity in &entities {
+        // rotation is locked so bumping into things doesn't spin the camera, looking around sets
+        // the rotation directly instead
+        commands.entity(entity).insert((
+            RigidBody::Dynamic,
+            Velocity::zero(),
+            GravityScale(0.0),
+            LockedAxes::ROTATION_LOCKED
+        ));
+    }
+}
+
+pub fn add_collision_colliders<T: Component>(
+    mut commands: Commands,
+    collision: Res<FreeControlCollision<T>>,
+    entities: Query<Entity, (With<T>, Without<Collider>)>
+) {
+    for entity in &entities {
+        commands.entity(entity).insert(Collider::ball(collision.radius));
+    }
+}
+
+impl <T: 'static> FreeControls<T> {
     fn to_num(self) -> u32 {
         match self {
             FreeControls::Forward => 0,
@@ -192,7 +868,18 @@ impl <T> FreeControls<T> {
             FreeControls::Right => 3,
             FreeControls::Up => 4,
             FreeControls::Down => 5,
-            FreeControls::__phantom(_) => 6,
+            FreeControls::CycleTarget => 6,
+            FreeControls::Lock => 7,
+            FreeControls::Unlock => 8,
+            FreeControls::StrafeAxis => 9,
+            FreeControls::ForwardAxis => 10,
+            FreeControls::YawAxis => 11,
+            FreeControls::PitchAxis => 12,
+            FreeControls::ZoomIn => 13,
+            FreeControls::ZoomOut => 14,
+            FreeControls::StickYawAxis => 15,
+            FreeControls::StickPitchAxis => 16,
+            FreeControls::__phantom(_) => 17,
         }
     }
 }
@@ -200,21 +887,21 @@ impl <T> FreeControls<T> {
 // I'm forced to manually implement all of these since Rust's derive for them forces T to also have
 // the trait, which is not necessary when there's a PhantomData involved
 
-impl <T> Copy for FreeControls<T> {}
+impl <T: 'static> Copy for FreeControls<T> {}
 
-impl<T> Clone for FreeControls<T> {
+impl <T: 'static> Clone for FreeControls<T> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
-impl <T> PartialOrd for FreeControls<T> {
+impl <T: 'static> PartialOrd for FreeControls<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl <T> Ord for FreeControls<T> {
+impl <T: 'static> Ord for FreeControls<T> {
     fn cmp(&self, other: &Self) -> Ordering {
         let a = self.to_num();
         let b = other.to_num();
@@ -222,7 +909,7 @@ impl <T> Ord for FreeControls<T> {
     }
 }
 
-impl <T> PartialEq for FreeControls<T> {
+impl <T: 'static> PartialEq for FreeControls<T> {
     fn eq(&self, other: &Self) -> bool {
         let a = self.to_num();
         let b = other.to_num();
@@ -230,10 +917,134 @@ impl <T> PartialEq for FreeControls<T> {
     }
 }
 
-impl <T> Eq for FreeControls<T> {}
+impl <T: 'static> Eq for FreeControls<T> {}
 
-impl <T> Hash for FreeControls<T> {
+impl <T: 'static> Hash for FreeControls<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.to_num().hash(state)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::math::{EulerRot, Vec2, Vec3};
+    use bevy::prelude::{Component, Entity, KeyCode, Transform};
+    use bevy::window::{CursorGrabMode, Windows};
+    use crate::keybind::{InputCapture, LookDelta, RawAxis, RawInput, TouchRegion};
+    use crate::settings::round_trip;
+    use crate::test_support::{headless_app, MockInput, WINDOW_SIZE};
+    use super::{BindingProfile, ControlProfileSettings, FreeControlConfig, FreeControlPlugin, FreeControls};
+
+    #[derive(Component)]
+    struct TestCam;
+
+    /// An app controlling a single entity with W bound to forward, with the cursor grabbed
+    fn controlled_app() -> (App, Entity) {
+        let mut app = headless_app();
+        app.add_plugin(FreeControlPlugin::<TestCam>::new().bind(KeyCode::W, FreeControls::Forward));
+        let entity = app.world.spawn((Transform::default(), TestCam)).id();
+        app.world.resource_mut::<Windows>().primary_mut().set_cursor_grab_mode(CursorGrabMode::Locked);
+        (app, entity)
+    }
+
+    fn transform(app: &App, entity: Entity) -> Transform {
+        *app.world.get::<Transform>(entity).unwrap()
+    }
+
+    #[test]
+    fn moves_while_pressed() {
+        let (mut app, entity) = controlled_app();
+        MockInput::new()
+            .press(KeyCode::W)
+            .wait(5)
+            .run(&mut app);
+        let moved = transform(&app, entity).translation;
+        assert!(moved.z < 0.0 && moved.x == 0.0 && moved.y == 0.0, "moved to {:?}", moved);
+
+        MockInput::new()
+            .release(KeyCode::W)
+            .wait(5)
+            .run(&mut app);
+        // the frame of the release doesn't move anymore either
+        assert_eq!(transform(&app, entity).translation, moved);
+    }
+
+    #[test]
+    fn mouse_motion_turns() {
+        let (mut app, entity) = controlled_app();
+        MockInput::new()
+            .mouse_motion(Vec2::new(100.0, 0.0))
+            .run(&mut app);
+        let forward = transform(&app, entity).forward();
+        assert!(forward.x > 0.0, "facing {:?}", forward);
+        assert_eq!(transform(&app, entity).translation, Vec3::ZERO);
+    }
+
+    #[test]
+    fn touch_drags_turn_as_far_as_the_mouse() {
+        let mut app = headless_app();
+        app.add_plugin(FreeControlPlugin::<TestCam>::new().bind_axis(RawAxis::TouchDragX(TouchRegion::Right), FreeControls::YawAxis));
+        let entity = app.world.spawn((Transform::default(), TestCam)).id();
+        MockInput::new()
+            .press(RawInput::Touch(TouchRegion::Right))
+            .wait(1)
+            .touch_drag(TouchRegion::Right, Vec2::new(100.0, 0.0))
+            .run(&mut app);
+        let sensitivity = app.world.resource::<FreeControlConfig<TestCam>>().right_sensitivity;
+        let (yaw, _, _) = transform(&app, entity).rotation.to_euler(EulerRot::YXZ);
+        let expected = -100.0 * sensitivity / WINDOW_SIZE.x;
+        assert!((yaw - expected).abs() < 1e-5, "turned {} instead of {}", yaw, expected);
+    }
+
+    #[test]
+    fn nothing_moves_without_a_grabbed_cursor() {
+        let (mut app, entity) = controlled_app();
+        app.world.resource_mut::<Windows>().primary_mut().set_cursor_grab_mode(CursorGrabMode::None);
+        MockInput::new()
+            .press(KeyCode::W)
+            .mouse_motion(Vec2::new(100.0, 50.0))
+            .wait(5)
+            .run(&mut app);
+        assert_eq!(transform(&app, entity), Transform::default());
+    }
+
+    #[test]
+    fn injected_looks_turn_without_a_window() {
+        let (mut app, entity) = controlled_app();
+        app.world.remove_resource::<Windows>();
+        app.world.resource_mut::<LookDelta>().inject(Vec2::new(100.0, 0.0));
+        app.update();
+        let forward = transform(&app, entity).forward();
+        assert!(forward.x > 0.0, "facing {:?}", forward);
+
+        // only for the one frame
+        app.update();
+        assert_eq!(transform(&app, entity).forward(), forward);
+    }
+
+    #[test]
+    fn the_profile_is_saved() {
+        let settings = ControlProfileSettings { profile: BindingProfile::Esdf };
+        assert_eq!(round_trip("controls", &settings), Some(settings));
+    }
+
+    #[test]
+    fn input_capture_stops_the_controls() {
+        let (mut app, entity) = controlled_app();
+        app.world.resource_mut::<InputCapture>().capture("test");
+        MockInput::new()
+            .press(KeyCode::W)
+            .mouse_motion(Vec2::new(100.0, 50.0))
+            .wait(5)
+            .run(&mut app);
+        assert_eq!(transform(&app, entity), Transform::default());
+
+        // the key is still held once the capture is released
+        app.world.resource_mut::<InputCapture>().release("test");
+        MockInput::new()
+            .wait(5)
+            .run(&mut app);
+        assert!(transform(&app, entity).translation.z < 0.0);
+    }
+}
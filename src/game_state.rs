@@ -0,0 +1,301 @@
+use bevy::app::{App, AppExit, CoreStage, Plugin};
+use bevy::asset::{AssetServer, HandleUntyped, LoadState};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::hierarchy::BuildChildren;
+use bevy::input::Input;
+use bevy::log::{info, warn};
+use bevy::prelude::{ButtonBundle, Changed, Color, Commands, Component, EventWriter, Local, NodeBundle, Query, Res, ResMut, Resource, State, Style, TextBundle, Visibility, With, Without};
+use bevy::text::{Text, TextStyle};
+use bevy::ui::{AlignItems, FlexDirection, Interaction, JustifyContent, PositionType, Size, UiRect, Val};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::cursor_grab::CursorGrab;
+use crate::fixed_time::FixedTime;
+use crate::hud::HudConfig;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::ui_mode::UiMode;
+
+/// Adds the [GameState] of the playground, whether it's loading, running, paused, or in the menu.
+/// Outside of [GameState::Running] the cursor is released and [FixedTime] is paused, which stops
+/// it advancing and rapier stepping, going back to running grabs the cursor again (unless
+/// [UiMode] is active). This plugin can be initialized in two ways:
+///
+/// * No default bindings [GameStatePlugin::new]
+/// * the pause key pauses and escape opens the menu [GameStatePlugin::default]
+///
+/// Anything else that should only run while running can use the [running] run criteria, as the
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) does.
+///
+/// The app starts in [GameState::Loading], and stays there until everything in [LoadingAssets]
+/// has loaded, so the first frames aren't missing anything and the cursor isn't grabbed before
+/// the window is ready.
+pub struct GameStatePlugin {
+    key_bindings: KeyBindingPlugin<GameStateControls>
+}
+
+impl GameStatePlugin {
+    /// Creates a new `GameStatePlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: GameStateControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for GameStatePlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Pause, GameStateControls::TogglePause)
+            .bind(Escape, GameStateControls::ToggleMenu)
+    }
+}
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<LoadingAssets>()
+            .add_state(GameState::Loading)
+            .add_startup_system(spawn_state_ui)
+            .add_system(game_state_controls)
+            .add_system(menu_buttons)
+            // after Update, so anything loaded there has been tracked already
+            .add_system_to_stage(CoreStage::PostUpdate, check_loading)
+            // after Update, so anything changing the cursor grab on the same input has had its turn
+            .add_system_to_stage(CoreStage::PostUpdate, apply_game_state);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum GameStateControls {
+    /// Switches between [GameState::Running] and [GameState::Paused]
+    TogglePause,
+    /// Opens the menu, or closes it back to [GameState::Running]
+    ToggleMenu
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GameState {
+    /// Waiting for [LoadingAssets]
+    Loading,
+    Running,
+    Paused,
+    Menu
+}
+
+/// Run criteria for systems that should only run in [GameState::Running], runs always when
+/// there is no [GameStatePlugin]
+pub fn running(state: Option<Res<State<GameState>>>) -> ShouldRun {
+    match state {
+        Some(state) if *state.current() != GameState::Running => ShouldRun::No,
+        _ => ShouldRun::Yes
+    }
+}
+
+/// Assets [GameState::Loading] waits for, anything loaded on startup can be added with
+/// [LoadingAssets::track]. Failing to load doesn't keep the app from starting.
+#[derive(Resource, Default)]
+pub struct LoadingAssets {
+    handles: Vec<HandleUntyped>
+}
+
+impl LoadingAssets {
+    pub fn track(&mut self, handle: HandleUntyped) {
+        self.handles.push(handle);
+    }
+}
+
+#[derive(Component)]
+struct LoadingText;
+
+#[derive(Component)]
+struct PausedText;
+
+#[derive(Component)]
+struct MenuPanel;
+
+#[derive(Component, Copy, Clone)]
+enum MenuButton {
+    Resume,
+    Quit
+}
+
+fn spawn_state_ui(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    let style = TextStyle {
+        font: asset_server.load(hud_config.font_path.as_str()),
+        font_size: hud_config.font_size * 2.0,
+        color: Color::WHITE
+    };
+
+    commands.spawn(TextBundle::from_section("loading", style.clone())
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(32.0),
+                left: Val::Percent(45.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(LoadingText);
+
+    commands.spawn(TextBundle::from_section("paused", style.clone())
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(32.0),
+                left: Val::Percent(45.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert((PausedText, Visibility { is_visible: false }));
+
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(MenuPanel)
+        .with_children(|panel| {
+            for (button, label) in [(MenuButton::Resume, "resume"), (MenuButton::Quit, "quit")] {
+                panel.spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(200.0), Val::Px(48.0)),
+                        margin: UiRect::all(Val::Px(8.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                    .insert(button)
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(label, style.clone()));
+                    });
+            }
+        });
+}
+
+fn game_state_controls(binds: Res<Input<GameStateControls>>, mut state: ResMut<State<GameState>>) {
+    let next = if binds.just_pressed(GameStateControls::TogglePause) {
+        match state.current() {
+            GameState::Running => Some(GameState::Paused),
+            GameState::Paused => Some(GameState::Running),
+            GameState::Loading | GameState::Menu => None
+        }
+    } else if binds.just_pressed(GameStateControls::ToggleMenu) {
+        match state.current() {
+            GameState::Loading => None,
+            GameState::Menu => Some(GameState::Running),
+            _ => Some(GameState::Menu)
+        }
+    } else {
+        None
+    };
+    if let Some(next) = next {
+        // only fails if the state is already changing this frame
+        let _ = state.set(next);
+    }
+}
+
+fn menu_buttons(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut state: ResMut<State<GameState>>,
+    mut exit: EventWriter<AppExit>
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            MenuButton::Resume => {
+                let _ = state.set(GameState::Running);
+            }
+            MenuButton::Quit => exit.send(AppExit)
+        }
+    }
+}
+
+fn check_loading(
+    asset_server: Res<AssetServer>,
+    loading: Res<LoadingAssets>,
+    mut state: ResMut<State<GameState>>,
+    mut texts: Query<&mut Text, With<LoadingText>>
+) {
+    if *state.current() != GameState::Loading {
+        return;
+    }
+    let mut loaded = 0;
+    for handle in &loading.handles {
+        match asset_server.get_load_state(handle.id) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => {
+                warn!("{:?} failed to load", asset_server.get_handle_path(handle.id));
+                loaded += 1;
+            }
+            _ => {}
+        }
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = format!("loading {}/{}", loaded, loading.handles.len());
+    }
+    if loaded == loading.handles.len() && state.set(GameState::Running).is_ok() {
+        info!("finished loading {} assets", loaded);
+    }
+}
+
+fn apply_game_state(
+    state: Res<State<GameState>>,
+    ui_mode: Option<Res<UiMode>>,
+    mut last: Local<Option<GameState>>,
+    mut cursor_grab: ResMut<CursorGrab>,
+    fixed_time: Option<ResMut<FixedTime>>,
+    mut loading_texts: Query<&mut Visibility, (With<LoadingText>, Without<PausedText>, Without<MenuPanel>)>,
+    mut paused_texts: Query<&mut Visibility, (With<PausedText>, Without<MenuPanel>)>,
+    mut menus: Query<&mut Visibility, With<MenuPanel>>
+) {
+    let current = *state.current();
+    if *last == Some(current) {
+        return;
+    }
+    let was_running = *last == Some(GameState::Running);
+    *last = Some(current);
+
+    let running = current == GameState::Running;
+    if running && !was_running && !ui_mode.map_or(false, |ui_mode| ui_mode.active) {
+        cursor_grab.activate();
+    } else if !running && cursor_grab.is_active() {
+        cursor_grab.deactivate();
+    }
+    if let Some(mut fixed_time) = fixed_time {
+        fixed_time.paused = !running;
+    }
+
+    for mut visibility in &mut loading_texts {
+        visibility.is_visible = current == GameState::Loading;
+    }
+    for mut visibility in &mut paused_texts {
+        visibility.is_visible = current == GameState::Paused;
+    }
+    for mut visibility in &mut menus {
+        visibility.is_visible = current == GameState::Menu;
+    }
+}
@@ -0,0 +1,364 @@
+use std::f32::consts::TAU;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::BuildChildren;
+use bevy::input::Input;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{Color, Commands, Component, Entity, IntoSystemDescriptor, KeyCode, Mesh, MouseButton, Query, Res, ResMut, Resource, SpatialBundle, Transform, Visibility, With, Without};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::debug_draw::{line_mesh, unlit};
+use crate::edit_history::EditCommands;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::{PickSystem, PickTarget};
+use crate::save::Saved;
+use crate::selection::{Selection, SelectionSystem};
+use crate::ui_mode::UiMode;
+
+/// Handles for moving, turning and scaling the most recently selected entity of the
+/// [Selection] with the cursor: arrows along the world axes, rings around them, or arrows along
+/// the entity's own axes for scaling. Dragging a handle edits the entity as it goes, and lets go
+/// with a single edit recorded by the [EditHistoryPlugin](crate::edit_history::EditHistoryPlugin).
+/// This plugin can be initialized in two ways:
+///
+/// * No default bindings [GizmoPlugin::new]
+/// * V switches between moving, turning and scaling, and the left mouse button drags handles
+///  [GizmoPlugin::default]
+///
+/// The gizmo is only there while [UiMode] is active. Grabbing a handle doesn't also select, or
+/// start a selection box. Needs a [SelectionPlugin](crate::selection::SelectionPlugin).
+pub struct GizmoPlugin {
+    key_bindings: KeyBindingPlugin<GizmoControls>
+}
+
+impl GizmoPlugin {
+    /// Creates a new `GizmoPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: GizmoControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for GizmoPlugin {
+    fn default() -> Self {
+        Self::new()
+            .bind(KeyCode::V, GizmoControls::CycleMode)
+            .bind(MouseButton::Left, GizmoControls::Drag)
+    }
+}
+
+impl Plugin for GizmoPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<GizmoConfig>() {
+            app.insert_resource(GizmoConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Gizmo>()
+            .add_startup_system(spawn_gizmo)
+            .add_system(drag_gizmo.after(PickSystem).before(SelectionSystem));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum GizmoControls {
+    /// Switches to the next [GizmoMode]
+    CycleMode,
+    /// Held to drag the handle under the cursor
+    Drag
+}
+
+#[derive(Resource, Clone)]
+pub struct GizmoConfig {
+    /// How big the gizmo is compared to its distance from the camera, keeping it the same size on
+    /// screen
+    pub size: f32,
+    /// How close the cursor has to be to a handle to grab it, compared to the gizmo's size
+    pub tolerance: f32,
+    /// The colors of the X, Y and Z handles
+    pub colors: [Color; 3],
+    /// The color of the handle being hovered or dragged
+    pub highlight: Color
+}
+
+impl Default for GizmoConfig {
+    fn default() -> Self {
+        Self {
+            size: 0.15,
+            tolerance: 0.08,
+            colors: [Color::rgb(0.9, 0.2, 0.2), Color::rgb(0.3, 0.9, 0.3), Color::rgb(0.2, 0.4, 1.0)],
+            highlight: Color::rgb(1.0, 0.9, 0.1)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale
+}
+
+impl GizmoMode {
+    pub fn next(self) -> Self {
+        match self {
+            GizmoMode::Translate => GizmoMode::Rotate,
+            GizmoMode::Rotate => GizmoMode::Scale,
+            GizmoMode::Scale => GizmoMode::Translate
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    /// The axis of the handle under the cursor
+    hovered: Option<usize>,
+    drag: Option<GizmoDrag>
+}
+
+impl Gizmo {
+    /// Whether a handle is being dragged, the click belongs to the gizmo then
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+}
+
+struct GizmoDrag {
+    entity: Entity,
+    axis: usize,
+    /// The transform from before the drag, also what the handles are measured from
+    original: Transform,
+    /// Where along the axis the drag started, or the angle around it when turning
+    start: f32
+}
+
+#[derive(Component)]
+struct GizmoRoot;
+
+#[derive(Component)]
+struct GizmoHandle {
+    mode: GizmoMode,
+    axis: usize,
+    material: Handle<StandardMaterial>
+}
+
+#[derive(Resource)]
+struct GizmoHighlight(Handle<StandardMaterial>);
+
+const RING_SEGMENTS: usize = 48;
+
+fn spawn_gizmo(
+    mut commands: Commands,
+    config: Res<GizmoConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    commands.insert_resource(GizmoHighlight(materials.add(unlit(config.highlight))));
+    let mut handles = Vec::new();
+    for (axis, color) in config.colors.into_iter().enumerate() {
+        let material = materials.add(unlit(color));
+        let direction = Vec3::AXES[axis];
+        let (side, up) = direction.any_orthonormal_pair();
+        let arrow = [
+            (Vec3::ZERO, direction),
+            (direction, direction * 0.85 + side * 0.06),
+            (direction, direction * 0.85 - side * 0.06),
+            (direction, direction * 0.85 + up * 0.06),
+            (direction, direction * 0.85 - up * 0.06)
+        ];
+        let square = [-side - up, side - up, side + up, -side + up].map(|corner| direction + corner * 0.05);
+        let ring = (0..RING_SEGMENTS).map(|segment| {
+            let point = |segment: usize| {
+                let angle = segment as f32 / RING_SEGMENTS as f32 * TAU;
+                side * angle.cos() + up * angle.sin()
+            };
+            (point(segment), point(segment + 1))
+        });
+
+        let shapes = [
+            (GizmoMode::Translate, line_mesh(arrow)),
+            (GizmoMode::Rotate, line_mesh(ring)),
+            (GizmoMode::Scale, line_mesh([(Vec3::ZERO, direction)].into_iter().chain((0..4).map(|i| (square[i], square[(i + 1) % 4])))))
+        ];
+        for (mode, mesh) in shapes {
+            handles.push(commands.spawn((
+                PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: material.clone(),
+                    ..default()
+                },
+                NotShadowCaster,
+                GizmoHandle { mode, axis, material: material.clone() }
+            )).id());
+        }
+    }
+    commands.spawn((SpatialBundle { visibility: Visibility { is_visible: false }, ..default() }, GizmoRoot))
+        .push_children(&handles);
+}
+
+/// The point on the ray closest to the line through `origin` along `axis`, as how far along the
+/// ray and the line it is, along with how far apart they are there
+fn closest_on_axis(target: &PickTarget, origin: Vec3, axis: Vec3) -> Option<(f32, f32, f32)> {
+    let offset = target.ray_origin - origin;
+    let alignment = target.ray_direction.dot(axis);
+    let denominator = 1.0 - alignment * alignment;
+    // looking straight down the axis
+    if denominator < 1e-6 {
+        return None;
+    }
+    let (ray_offset, axis_offset) = (target.ray_direction.dot(offset), axis.dot(offset));
+    let along_ray = (alignment * axis_offset - ray_offset) / denominator;
+    let along_axis = (axis_offset - alignment * ray_offset) / denominator;
+    let distance = (target.ray_origin + target.ray_direction * along_ray).distance(origin + axis * along_axis);
+    (along_ray >= 0.0).then_some((along_ray, along_axis, distance))
+}
+
+/// Where the ray meets the plane through `origin` facing `axis`, as how far along the ray it is
+/// and the point itself
+fn on_plane(target: &PickTarget, origin: Vec3, axis: Vec3) -> Option<(f32, Vec3)> {
+    let facing = target.ray_direction.dot(axis);
+    if facing.abs() < 1e-6 {
+        return None;
+    }
+    let along_ray = (origin - target.ray_origin).dot(axis) / facing;
+    (along_ray >= 0.0).then(|| (along_ray, target.ray_origin + target.ray_direction * along_ray))
+}
+
+/// The angle of `point` around `axis` through `origin`, measured from `reference`
+fn angle_around(origin: Vec3, axis: Vec3, reference: Vec3, point: Vec3) -> f32 {
+    let offset = point - origin;
+    axis.dot(reference.cross(offset)).atan2(reference.dot(offset))
+}
+
+fn axes(mode: GizmoMode, transform: &Transform) -> [Vec3; 3] {
+    match mode {
+        GizmoMode::Scale => Vec3::AXES.map(|axis| transform.rotation * axis),
+        _ => Vec3::AXES
+    }
+}
+
+fn drag_gizmo(
+    mut commands: Commands,
+    binds: Res<Input<GizmoControls>>,
+    config: Res<GizmoConfig>,
+    ui_mode: Option<Res<UiMode>>,
+    target: Res<PickTarget>,
+    selection: Res<Selection>,
+    highlight: Option<Res<GizmoHighlight>>,
+    mut gizmo: ResMut<Gizmo>,
+    mut edited: Query<&mut Transform, (With<Saved>, Without<GizmoRoot>)>,
+    mut roots: Query<(&mut Transform, &mut Visibility), With<GizmoRoot>>,
+    mut handles: Query<(&GizmoHandle, &mut Handle<StandardMaterial>, &mut Visibility), Without<GizmoRoot>>
+) {
+    let gizmo = &mut *gizmo;
+    if binds.just_pressed(GizmoControls::CycleMode) && gizmo.drag.is_none() {
+        gizmo.mode = gizmo.mode.next();
+    }
+    let ui_mode = ui_mode.map_or(false, |ui_mode| ui_mode.active);
+    let selected = selection.last().filter(|entity| ui_mode && edited.contains(*entity));
+
+    if let Some(drag) = &gizmo.drag {
+        let axis = axes(gizmo.mode, &drag.original)[drag.axis];
+        let origin = drag.original.translation;
+        let mut transform = drag.original;
+        match gizmo.mode {
+            GizmoMode::Translate => if let Some((_, along, _)) = closest_on_axis(&target, origin, axis) {
+                transform.translation = origin + axis * (along - drag.start);
+            },
+            GizmoMode::Rotate => if let Some((_, point)) = on_plane(&target, origin, axis) {
+                let reference = Vec3::AXES[(drag.axis + 1) % 3];
+                let angle = angle_around(origin, axis, reference, point) - drag.start;
+                transform.rotation = Quat::from_axis_angle(axis, angle) * drag.original.rotation;
+            },
+            GizmoMode::Scale => if let Some((_, along, _)) = closest_on_axis(&target, origin, axis) {
+                transform.scale[drag.axis] = drag.original.scale[drag.axis] * (along / drag.start).max(0.01);
+            }
+        }
+
+        let released = !binds.pressed(GizmoControls::Drag) || selected != Some(drag.entity);
+        if let Ok(mut current) = edited.get_mut(drag.entity) {
+            if released {
+                // put back so the recorded edit starts from where the drag did
+                *current = drag.original;
+                commands.move_recorded(drag.entity, transform);
+            } else {
+                *current = transform;
+            }
+        }
+        if released {
+            gizmo.drag = None;
+        }
+    }
+
+    let Some((entity, transform)) = selected.and_then(|entity| edited.get(entity).ok().map(|transform| (entity, *transform))) else {
+        for (_, mut visibility) in &mut roots {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+        }
+        gizmo.hovered = None;
+        gizmo.drag = None;
+        return;
+    };
+    let size = target.ray_origin.distance(transform.translation) * config.size;
+
+    if gizmo.drag.is_none() {
+        let tolerance = size * config.tolerance;
+        let origin = transform.translation;
+        gizmo.hovered = axes(gizmo.mode, &transform).into_iter().enumerate().filter_map(|(index, axis)| match gizmo.mode {
+            GizmoMode::Translate | GizmoMode::Scale => closest_on_axis(&target, origin, axis)
+                .filter(|(_, along, distance)| *distance < tolerance && (0.0..=size * 1.1).contains(along))
+                .map(|(along_ray, ..)| (index, along_ray)),
+            GizmoMode::Rotate => on_plane(&target, origin, axis)
+                .filter(|(_, point)| (point.distance(origin) - size).abs() < tolerance)
+                .map(|(along_ray, _)| (index, along_ray))
+        })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index);
+
+        if let (true, Some(axis)) = (binds.just_pressed(GizmoControls::Drag), gizmo.hovered) {
+            let direction = axes(gizmo.mode, &transform)[axis];
+            let start = match gizmo.mode {
+                GizmoMode::Rotate => on_plane(&target, origin, direction)
+                    .map(|(_, point)| angle_around(origin, direction, Vec3::AXES[(axis + 1) % 3], point)),
+                _ => closest_on_axis(&target, origin, direction).map(|(_, along, _)| along)
+            };
+            // scaling is measured against where it started, which can't be the center
+            if let Some(start) = start.filter(|start| gizmo.mode != GizmoMode::Scale || start.abs() > 1e-3) {
+                gizmo.drag = Some(GizmoDrag { entity, axis, original: transform, start });
+            }
+        }
+    }
+
+    for (mut root, mut visibility) in &mut roots {
+        root.translation = transform.translation;
+        root.rotation = if gizmo.mode == GizmoMode::Scale { transform.rotation } else { Quat::IDENTITY };
+        root.scale = Vec3::splat(size);
+        if !visibility.is_visible {
+            visibility.is_visible = true;
+        }
+    }
+    let active_axis = gizmo.drag.as_ref().map(|drag| drag.axis).or(gizmo.hovered);
+    for (handle, mut material, mut visibility) in &mut handles {
+        let shown = handle.mode == gizmo.mode;
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+        let wanted = match (&highlight, active_axis == Some(handle.axis)) {
+            (Some(highlight), true) => &highlight.0,
+            _ => &handle.material
+        };
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+    }
+}
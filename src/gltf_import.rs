@@ -0,0 +1,153 @@
+use std::str::FromStr;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, AssetServer, Handle};
+use bevy::hierarchy::{Children, DespawnRecursiveExt};
+use bevy::log::{info, warn};
+use bevy::prelude::{Commands, Component, Entity, Mesh, Query, Res, Resource, With, Without};
+use bevy::scene::SceneBundle;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, ComputedColliderShape, RigidBody};
+use bevy_rapier3d::rapier::geometry::VHACDParameters;
+use crate::console::{console_print, AddConsoleCommand};
+
+/// Imports glTF scenes as fixed level geometry with the `gltf` console command, giving every mesh
+/// in the scene a collider so it can be flown through and shot at, built the way the
+/// [ColliderStrategy] says. `gltf_clear` removes everything imported.
+///
+/// The file is relative to the assets directory, and its first scene is placed at the origin
+/// (`#Scene1` and so on after the file name picks another).
+pub struct GltfImportPlugin;
+
+impl Plugin for GltfImportPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<GltfImportConfig>() {
+            app.insert_resource(GltfImportConfig::default());
+        }
+        app
+            .add_system(add_scene_colliders)
+            .add_console_command("gltf", "imports a glTF scene with colliders, as trimeshes or convex parts", |world, args| {
+                let Some(path) = args.first() else {
+                    console_print(world, "usage: gltf <file> [trimesh|convex]");
+                    return;
+                };
+                let strategy = match args.get(1) {
+                    Some(arg) => match arg.parse::<ColliderStrategy>() {
+                        Ok(strategy) => strategy,
+                        Err(()) => {
+                            console_print(world, "usage: gltf <file> [trimesh|convex]");
+                            return;
+                        }
+                    },
+                    None => world.resource::<GltfImportConfig>().strategy
+                };
+                let path = if path.contains('#') { path.to_string() } else { format!("{}#Scene0", path) };
+                let scene = world.resource::<AssetServer>().load(path.as_str());
+                world.spawn((
+                    SceneBundle {
+                        scene,
+                        ..default()
+                    },
+                    RigidBody::Fixed,
+                    GltfImport { strategy, found_meshes: false }
+                ));
+                console_print(world, format!("importing {} with {} colliders", path, strategy.name()));
+            })
+            .add_console_command("gltf_clear", "removes every imported glTF scene", |world, _| {
+                let imported = world.query_filtered::<Entity, With<GltfImport>>().iter(world).collect::<Vec<_>>();
+                for entity in &imported {
+                    world.entity_mut(*entity).despawn_recursive();
+                }
+                console_print(world, format!("removed {} scenes", imported.len()));
+            });
+    }
+}
+
+/// How colliders are made from meshes
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ColliderStrategy {
+    /// The exact triangles, only for fixed geometry since rapier can't have trimeshes collide with
+    /// each other properly
+    #[default]
+    TriMesh,
+    /// Split into convex parts, rougher and slower to build, but solid
+    ConvexDecomposition
+}
+
+impl ColliderStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColliderStrategy::TriMesh => "trimesh",
+            ColliderStrategy::ConvexDecomposition => "convex"
+        }
+    }
+
+    fn shape(self) -> ComputedColliderShape {
+        match self {
+            ColliderStrategy::TriMesh => ComputedColliderShape::TriMesh,
+            ColliderStrategy::ConvexDecomposition => ComputedColliderShape::ConvexDecomposition(VHACDParameters::default())
+        }
+    }
+}
+
+impl FromStr for ColliderStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [ColliderStrategy::TriMesh, ColliderStrategy::ConvexDecomposition]
+            .into_iter()
+            .find(|strategy| strategy.name() == s)
+            .ok_or(())
+    }
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct GltfImportConfig {
+    /// Used when the `gltf` command isn't given one
+    pub strategy: ColliderStrategy
+}
+
+/// The root of an imported scene
+#[derive(Component)]
+pub struct GltfImport {
+    pub strategy: ColliderStrategy,
+    /// Set once the scene has spawned and its meshes got colliders
+    found_meshes: bool
+}
+
+/// Gives the meshes of imported scenes colliders as soon as the scene has spawned, the scene
+/// (and so meshes) only spawns once the whole file is loaded
+fn add_scene_colliders(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut imports: Query<(Entity, &mut GltfImport)>,
+    children: Query<&Children>,
+    mesh_entities: Query<&Handle<Mesh>, Without<Collider>>
+) {
+    for (root, mut import) in &mut imports {
+        if import.found_meshes {
+            continue;
+        }
+        let mut pending = vec![root];
+        let mut found = 0;
+        while let Some(entity) = pending.pop() {
+            if let Ok(entity_children) = children.get(entity) {
+                pending.extend(entity_children.iter());
+            }
+            let Some(mesh) = mesh_entities.get(entity).ok().and_then(|handle| meshes.get(handle)) else {
+                continue;
+            };
+            found += 1;
+            match Collider::from_bevy_mesh(mesh, &import.strategy.shape()) {
+                Some(collider) => {
+                    commands.entity(entity).insert(collider);
+                }
+                // not a triangle list, like lines or points
+                None => warn!("couldn't make a collider for a mesh of {:?}", root)
+            }
+        }
+        if found > 0 {
+            import.found_meshes = true;
+            info!("added colliders to {} meshes of an imported scene", found);
+        }
+    }
+}
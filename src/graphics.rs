@@ -0,0 +1,177 @@
+use bevy::app::{App, Plugin};
+use bevy::core_pipeline::bloom::BloomSettings;
+use bevy::core_pipeline::core_3d::Camera3d;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::pbr::{DirectionalLight, DirectionalLightShadowMap};
+use bevy::prelude::{Added, Camera, Commands, Entity, IntoSystemDescriptor, Msaa, Query, Res, ResMut, Resource, With};
+use serde::{Deserialize, Serialize};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::settings::AddSetting;
+
+/// Runtime toggles for rendering options, applied to every 3d camera and [DirectionalLight]
+/// (including ones spawned later) whenever [GraphicsSettings] changes. This plugin can be
+/// initialized in two ways:
+///
+/// * No default bindings [GraphicsSettingsPlugin::new]
+/// * Numpad 1 toggles shadows, numpad 2 cycles MSAA, numpad 3 toggles bloom, numpad 4 toggles
+///  tonemapping and numpad 5 cycles the shadow map resolution [GraphicsSettingsPlugin::default]
+///
+/// The settings are kept in the `graphics` section of the settings file, see
+/// [SettingsPlugin](crate::settings::SettingsPlugin).
+pub struct GraphicsSettingsPlugin {
+    key_bindings: KeyBindingPlugin<GraphicsControls>
+}
+
+impl GraphicsSettingsPlugin {
+    /// Creates a new `GraphicsSettingsPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: GraphicsControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for GraphicsSettingsPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Numpad1, GraphicsControls::ToggleShadows)
+            .bind(Numpad2, GraphicsControls::CycleMsaa)
+            .bind(Numpad3, GraphicsControls::ToggleBloom)
+            .bind(Numpad4, GraphicsControls::ToggleTonemapping)
+            .bind(Numpad5, GraphicsControls::CycleShadowResolution)
+    }
+}
+
+impl Plugin for GraphicsSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_setting::<GraphicsSettings>("graphics")
+            .add_system(graphics_controls)
+            .add_system(apply_graphics_settings.after(graphics_controls));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum GraphicsControls {
+    ToggleShadows,
+    /// Switches between no multisampling and 4x
+    CycleMsaa,
+    ToggleBloom,
+    ToggleTonemapping,
+    /// Goes through [SHADOW_RESOLUTIONS]
+    CycleShadowResolution
+}
+
+/// The shadow map sizes [GraphicsControls::CycleShadowResolution] goes through
+pub const SHADOW_RESOLUTIONS: [usize; 4] = [512, 1024, 2048, 4096];
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// Whether directional lights cast shadows
+    pub shadows: bool,
+    /// Samples per pixel, Bevy only supports 1 and 4
+    pub msaa: u32,
+    /// Bloom needs the camera to render in HDR, which gets switched with it
+    pub bloom: bool,
+    pub tonemapping: bool,
+    pub shadow_map_size: usize,
+    /// Distance from the camera past which [Lod](crate::lod::Lod) meshes switch to their medium one
+    pub lod_medium: f32,
+    /// Distance past which they switch to their low one
+    pub lod_low: f32,
+    /// Distance past which they're hidden, `None` never hides them
+    pub lod_hide: Option<f32>
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            shadows: true,
+            msaa: 4,
+            bloom: false,
+            tonemapping: true,
+            shadow_map_size: 2048,
+            lod_medium: 30.0,
+            lod_low: 80.0,
+            lod_hide: Some(250.0)
+        }
+    }
+}
+
+fn graphics_controls(binds: Res<Input<GraphicsControls>>, mut settings: ResMut<GraphicsSettings>) {
+    if binds.just_pressed(GraphicsControls::ToggleShadows) {
+        settings.shadows = !settings.shadows;
+        info!("shadows {}", if settings.shadows { "enabled" } else { "disabled" });
+    }
+    if binds.just_pressed(GraphicsControls::CycleMsaa) {
+        settings.msaa = if settings.msaa > 1 { 1 } else { 4 };
+        info!("msaa set to {}x", settings.msaa);
+    }
+    if binds.just_pressed(GraphicsControls::ToggleBloom) {
+        settings.bloom = !settings.bloom;
+        info!("bloom {}", if settings.bloom { "enabled" } else { "disabled" });
+    }
+    if binds.just_pressed(GraphicsControls::ToggleTonemapping) {
+        settings.tonemapping = !settings.tonemapping;
+        info!("tonemapping {}", if settings.tonemapping { "enabled" } else { "disabled" });
+    }
+    if binds.just_pressed(GraphicsControls::CycleShadowResolution) {
+        let next = SHADOW_RESOLUTIONS
+            .iter()
+            .position(|size| *size == settings.shadow_map_size)
+            .map_or(0, |index| (index + 1) % SHADOW_RESOLUTIONS.len());
+        settings.shadow_map_size = SHADOW_RESOLUTIONS[next];
+        info!("shadow map resolution set to {}", settings.shadow_map_size);
+    }
+}
+
+fn apply_graphics_settings(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    mut msaa: ResMut<Msaa>,
+    mut shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut cameras: Query<(Entity, &mut Camera, &mut Tonemapping), With<Camera3d>>,
+    mut lights: Query<&mut DirectionalLight>,
+    new_cameras: Query<(), Added<Camera3d>>,
+    new_lights: Query<(), Added<DirectionalLight>>
+) {
+    if !settings.is_changed() && new_cameras.is_empty() && new_lights.is_empty() {
+        return;
+    }
+
+    let samples = if settings.msaa > 1 { 4 } else { 1 };
+    if msaa.samples != samples {
+        msaa.samples = samples;
+    }
+    if shadow_map.size != settings.shadow_map_size {
+        shadow_map.size = settings.shadow_map_size;
+    }
+
+    for (entity, mut camera, mut tonemapping) in &mut cameras {
+        camera.hdr = settings.bloom;
+        *tonemapping = if settings.tonemapping {
+            Tonemapping::Enabled { deband_dither: true }
+        } else {
+            Tonemapping::Disabled
+        };
+        if settings.bloom {
+            commands.entity(entity).insert(BloomSettings::default());
+        } else {
+            commands.entity(entity).remove::<BloomSettings>();
+        }
+    }
+    for mut light in &mut lights {
+        light.shadows_enabled = settings.shadows;
+    }
+}
@@ -0,0 +1,235 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::prelude::{Commands, Component, Entity, GlobalTransform, IntoSystemDescriptor, Query, Res, Resource, Transform, TransformBundle, With};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, GravityScale, ImpulseJoint, LockedAxes, QueryFilter, RapierContext, RigidBody, Sensor, SphericalJointBuilder, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::cursor_grab::CursorGrab;
+use crate::free_control::ActiveControl;
+use crate::game_state::running;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// A grappling hook for the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]. While the bind is
+/// held, whatever the entity looked at when it was pressed (up to [GrappleConfig::max_distance]
+/// away) holds it at that distance, so it swings around the hit point under gravity like on a
+/// taut rope. A dynamic body that was hit gets pulled around by the rope as well. This plugin can
+/// be initialized in two ways:
+///
+/// * No default bindings [GrapplePlugin::new]
+/// * Holding Q grapples [GrapplePlugin::default]
+///
+/// The controlled entity only needs to be a rigid body while attached, anything missing for that
+/// (the body, a collider, locked rotation) is added when grappling and removed on release. Turn
+/// on joint rendering in the [PhysicsDebugPlugin](crate::physics_debug::PhysicsDebugPlugin) to
+/// see the rope.
+///
+/// Rapier doesn't have rope joints yet, so the rope is a small [GrapplePivot] body at the hit
+/// point that's free to rotate but not to move (or is held onto the hit body by a spherical
+/// joint), with the controlled entity jointed to it at the length of the rope.
+pub struct GrapplePlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<GrappleControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> GrapplePlugin<T> {
+    /// Creates a new `GrapplePlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("grapple"),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: GrappleControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for GrapplePlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::KeyCode::Q, GrappleControls::Grapple)
+    }
+}
+
+impl <T: Component> Plugin for GrapplePlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<GrappleConfig>() {
+            app.insert_resource(GrappleConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_system(attach_grapple::<T>.with_run_criteria(running))
+            .add_system(release_grapple::<T>.after(attach_grapple::<T>));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum GrappleControls {
+    /// Attaches when pressed, releases when let go
+    Grapple
+}
+
+#[derive(Resource, Clone)]
+pub struct GrappleConfig {
+    pub max_distance: f32,
+    /// Gravity of the controlled entity while attached
+    pub gravity_scale: f32,
+    /// Radius of the collider given to the controlled entity while attached, if it has none
+    pub radius: f32
+}
+
+impl Default for GrappleConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 50.0,
+            gravity_scale: 1.0,
+            radius: 0.25
+        }
+    }
+}
+
+/// On the controlled entity while it's grappling, remembers what to undo on release
+#[derive(Component)]
+pub struct Grappling {
+    /// The [GrapplePivot] the entity hangs from
+    pub pivot: Entity,
+    /// The dynamic body that was hit, if it was one
+    pub target: Option<Entity>,
+    added_body: bool,
+    added_collider: bool,
+    added_locked_axes: bool,
+    previous_gravity: Option<GravityScale>
+}
+
+/// Keeps grapples from hooking onto the entity, they go right through it
+#[derive(Component)]
+pub struct NoGrapple;
+
+/// The end of the rope at the hit point, despawned on release
+#[derive(Component)]
+pub struct GrapplePivot;
+
+/// Radius of the [GrapplePivot]'s sensor, only there to give the pivot some mass
+const PIVOT_RADIUS: f32 = 0.05;
+
+fn attach_grapple<T: Component>(
+    mut commands: Commands,
+    binds: Res<Input<GrappleControls>>,
+    config: Res<GrappleConfig>,
+    cursor_grab: Res<CursorGrab>,
+    active: Res<ActiveControl<T>>,
+    rapier_context: Res<RapierContext>,
+    controlled: Query<(&Transform, Option<&RigidBody>, Option<&Collider>, Option<&LockedAxes>, Option<&GravityScale>), With<T>>,
+    targets: Query<(&RigidBody, &GlobalTransform)>,
+    no_grapple: Query<(), With<NoGrapple>>
+) {
+    if !binds.just_pressed(GrappleControls::Grapple) || cursor_grab.is_inactive() {
+        return;
+    }
+    let Some(entity) = active.entity else {
+        return;
+    };
+    let Ok((transform, body, collider, locked_axes, gravity)) = controlled.get(entity) else {
+        return;
+    };
+
+    let filter = QueryFilter::default()
+        .exclude_sensors()
+        .exclude_collider(entity)
+        .exclude_rigid_body(entity)
+        .predicate(&|hit| !no_grapple.contains(hit));
+    let Some((hit, toi)) = rapier_context.cast_ray(transform.translation, transform.forward(), config.max_distance, true, filter) else {
+        return;
+    };
+    let point = transform.translation + transform.forward() * toi;
+
+    let mut pivot = commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(point)),
+        RigidBody::Dynamic,
+        Collider::ball(PIVOT_RADIUS),
+        Sensor,
+        GrapplePivot
+    ));
+    let target = match targets.get(hit) {
+        Ok((RigidBody::Dynamic, global_transform)) => {
+            let local = global_transform.compute_matrix().inverse().transform_point3(point);
+            pivot.insert(ImpulseJoint::new(hit, SphericalJointBuilder::new().local_anchor1(local)));
+            Some(hit)
+        }
+        _ => {
+            pivot.insert(LockedAxes::TRANSLATION_LOCKED);
+            None
+        }
+    };
+    let pivot = pivot.id();
+
+    // the pivot starts out unrotated, so the offset is the same in its space
+    let joint = SphericalJointBuilder::new()
+        .local_anchor1(transform.translation - point)
+        .local_anchor2(Vec3::ZERO);
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.insert((
+        ImpulseJoint::new(pivot, joint),
+        GravityScale(config.gravity_scale),
+        Grappling {
+            pivot,
+            target,
+            added_body: body.is_none(),
+            added_collider: collider.is_none(),
+            added_locked_axes: locked_axes.is_none(),
+            previous_gravity: gravity.copied()
+        }
+    ));
+    if body.is_none() {
+        entity_commands.insert((RigidBody::Dynamic, Velocity::zero()));
+    }
+    if collider.is_none() {
+        entity_commands.insert(Collider::ball(config.radius));
+    }
+    if locked_axes.is_none() {
+        // looking around sets the rotation directly, the rope shouldn't spin it
+        entity_commands.insert(LockedAxes::ROTATION_LOCKED);
+    }
+}
+
+fn release_grapple<T: Component>(
+    mut commands: Commands,
+    binds: Res<Input<GrappleControls>>,
+    grappling: Query<(Entity, &Grappling), With<T>>,
+    bodies: Query<(), With<RigidBody>>
+) {
+    for (entity, grapple) in &grappling {
+        // the grappled body going away (being deleted) lets go as well
+        let attached = bodies.contains(grapple.pivot) && grapple.target.map_or(true, |target| bodies.contains(target));
+        if binds.pressed(GrappleControls::Grapple) && attached {
+            continue;
+        }
+        if let Some(pivot) = commands.get_entity(grapple.pivot) {
+            pivot.despawn();
+        }
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.remove::<(ImpulseJoint, Grappling)>();
+        if grapple.added_body {
+            entity_commands.remove::<(RigidBody, Velocity)>();
+        }
+        if grapple.added_collider {
+            entity_commands.remove::<Collider>();
+        }
+        if grapple.added_locked_axes {
+            entity_commands.remove::<LockedAxes>();
+        }
+        match grapple.previous_gravity {
+            Some(gravity) => {
+                entity_commands.insert(gravity);
+            }
+            None => {
+                entity_commands.remove::<GravityScale>();
+            }
+        }
+    }
+}
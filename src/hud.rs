@@ -0,0 +1,228 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::AssetServer;
+use bevy::hierarchy::BuildChildren;
+use bevy::math::Vec3;
+use bevy::prelude::{Color, Commands, Component, EventReader, GlobalTransform, Local, NodeBundle, Query, Res, Resource, Style, TextBundle, Visibility, With};
+use bevy::text::{Text, TextStyle};
+use bevy::time::Time;
+use bevy::ui::{AlignItems, JustifyContent, PositionType, Size, UiRect, Val};
+use bevy::utils::default;
+use crate::cursor_grab::CursorGrabChanged;
+use crate::frame_limit::FrameStats;
+use crate::lap_timer::{format_lap, LapRecords, LapTimer};
+use crate::measure::Measurements;
+use crate::free_control::ActiveControl;
+use crate::physics_settings::PhysicsSettings;
+use crate::pool::PhysicsPool;
+use crate::selection::Selection;
+
+/// Draws a crosshair in the center of the window while the cursor is grabbed (following
+/// [CursorGrabChanged] events), and a small text area in the top left showing the speed of the
+/// entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with
+/// marker [T], along with the [SelectedSpawn], the [FrameStats], the [PhysicsSettings], the
+/// [PhysicsPool] statistics, the latest of the [Measurements], the size of the [Selection] and the
+/// [LapTimer] if there are any.
+///
+/// Bevy doesn't come with a font, the text uses [HudConfig::font_path] from the assets directory.
+pub struct HudPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for HudPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for HudPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<HudConfig>() {
+            app.insert_resource(HudConfig::default());
+        }
+        app
+            .init_resource::<SelectedSpawn>()
+            .add_startup_system(spawn_hud)
+            .add_system(toggle_crosshair)
+            .add_system(update_hud_text::<T>);
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct HudConfig {
+    pub font_path: String,
+    pub font_size: f32,
+    pub crosshair_color: Color
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        Self {
+            font_path: "fonts/FiraMono-Medium.ttf".to_string(),
+            font_size: 18.0,
+            crosshair_color: Color::rgba(1.0, 1.0, 1.0, 0.8)
+        }
+    }
+}
+
+/// Name of whatever would currently be spawned, shown by the HUD, `None` hides the line
+#[derive(Resource, Default)]
+pub struct SelectedSpawn(pub Option<String>);
+
+#[derive(Component)]
+struct Crosshair;
+
+#[derive(Component)]
+struct HudText;
+
+const CROSSHAIR_SIZE: f32 = 12.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+
+fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>, config: Res<HudConfig>) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(Crosshair)
+        .with_children(|root| {
+            root.spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(CROSSHAIR_SIZE), Val::Px(CROSSHAIR_SIZE)),
+                    ..default()
+                },
+                ..default()
+            })
+                .with_children(|cross| {
+                    let offset = (CROSSHAIR_SIZE - CROSSHAIR_THICKNESS) / 2.0;
+                    let bars = [
+                        (Size::new(Val::Px(CROSSHAIR_SIZE), Val::Px(CROSSHAIR_THICKNESS)), UiRect { left: Val::Px(0.0), bottom: Val::Px(offset), ..default() }),
+                        (Size::new(Val::Px(CROSSHAIR_THICKNESS), Val::Px(CROSSHAIR_SIZE)), UiRect { left: Val::Px(offset), bottom: Val::Px(0.0), ..default() })
+                    ];
+                    for (size, position) in bars {
+                        cross.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                size,
+                                position,
+                                ..default()
+                            },
+                            background_color: config.crosshair_color.into(),
+                            ..default()
+                        });
+                    }
+                });
+        });
+
+    commands.spawn(TextBundle::from_section("", TextStyle {
+        font: asset_server.load(config.font_path.as_str()),
+        font_size: config.font_size,
+        color: Color::WHITE
+    })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(8.0),
+                top: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(HudText);
+}
+
+fn toggle_crosshair(mut grab_changed: EventReader<CursorGrabChanged>, mut crosshairs: Query<&mut Visibility, With<Crosshair>>) {
+    let Some(changed) = grab_changed.iter().last() else {
+        return;
+    };
+    for mut visibility in &mut crosshairs {
+        visibility.is_visible = changed.grabbed;
+    }
+}
+
+fn update_hud_text<T: Component>(
+    time: Res<Time>,
+    active: Res<ActiveControl<T>>,
+    selected: Res<SelectedSpawn>,
+    frame_stats: Option<Res<FrameStats>>,
+    physics: Option<Res<PhysicsSettings>>,
+    pool: Option<Res<PhysicsPool>>,
+    measurements: Option<Res<Measurements>>,
+    selection: Option<Res<Selection>>,
+    laps: Option<Res<LapTimer>>,
+    lap_records: Option<Res<LapRecords>>,
+    controlled: Query<&GlobalTransform, With<T>>,
+    mut last_position: Local<Option<Vec3>>,
+    mut texts: Query<&mut Text, With<HudText>>
+) {
+    let position = active.entity
+        .and_then(|entity| controlled.get(entity).ok())
+        .map(|transform| transform.translation());
+    let speed = match (*last_position, position) {
+        (Some(last), Some(position)) if time.delta_seconds() > 0.0 => (position - last).length() / time.delta_seconds(),
+        _ => 0.0
+    };
+    *last_position = position;
+
+    let mut value = format!("speed: {:.1} m/s", speed);
+    if let Some(frame_stats) = frame_stats {
+        value += &format!("\nframe: {:.1} ms ({:.0} fps)", frame_stats.frame_time.as_secs_f32() * 1000.0, frame_stats.fps());
+    }
+    if let Some(physics) = physics {
+        let [x, y, z] = physics.gravity;
+        value += &format!(
+            "\nphysics: gravity {} {} {}, {} iterations, {} substeps, ccd {}",
+            x, y, z, physics.solver_iterations, physics.substeps, if physics.ccd { "on" } else { "off" }
+        );
+    }
+    if let Some(pool) = pool {
+        value += &format!("\npool: {} active, {} free", pool.stats.active, pool.stats.free);
+    }
+    if let Some(measurements) = measurements {
+        if measurements.is_pending() {
+            value += "\nmeasure: mark the second point";
+        } else if let Some(measurement) = measurements.last() {
+            value += &format!(
+                "\nmeasure: {:.2} m, {:.1}° elevation, {:.1}° between surfaces",
+                measurement.distance(), measurement.elevation(), measurement.surface_angle()
+            );
+        } else if measurements.active {
+            value += "\nmeasure: mark the first point";
+        }
+    }
+    if let Some(selection) = selection.filter(|selection| !selection.is_empty()) {
+        value += &format!("\nselected: {}", selection.len());
+    }
+    if let Some(laps) = laps {
+        let best = |course: &str| lap_records.as_ref()
+            .and_then(|records| records.best.get(course))
+            .map_or_else(String::new, |best| format!(", best {}", format_lap(*best)));
+        if let Some(lap) = &laps.running {
+            value += &format!("\nlap `{}`: {}{}", lap.course, format_lap(lap.elapsed), best(&lap.course));
+        }
+        if let Some(lap) = &laps.finished {
+            let time = format_lap(lap.time);
+            value += &if lap.best {
+                format!("\nfinished `{}` in {}, a new best", lap.course, time)
+            } else {
+                format!("\nfinished `{}` in {}{}", lap.course, time, best(&lap.course))
+            };
+        }
+    }
+    if let Some(spawn) = &selected.0 {
+        value += &format!("\nspawn: {}", spawn);
+    }
+    for mut text in &mut texts {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
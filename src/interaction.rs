@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::AssetServer;
+use bevy::hierarchy::Parent;
+use bevy::input::Input;
+use bevy::prelude::{Color, Commands, Component, Entity, EventWriter, GlobalTransform, IntoSystemDescriptor, Query, Res, ResMut, Resource, Style, TextBundle, Visibility, With};
+use bevy::text::{Text, TextStyle};
+use bevy::ui::{PositionType, UiRect, Val};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
+use serde::{Deserialize, Serialize};
+use crate::cursor_grab::CursorGrab;
+use crate::free_control::ActiveControl;
+use crate::game_state::running;
+use crate::hud::HudConfig;
+use crate::keybind::{BindingLocalization, DisplayName, KeyBindingPlugin, KeyBindings, LastInputDevice, RawInput};
+
+/// Lets the entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin)
+/// with marker [T] interact with [Interactable] entities it looks at from within
+/// [InteractionConfig::max_distance]. The one being looked at is the [InteractionTarget], and
+/// gets a prompt near the bottom of the window naming the bind (one from the [LastInputDevice] if
+/// there is one) and [Interactable::prompt].
+/// Pressing the bind sends an [InteractionEvent], which is all there is to it, doors, buttons and
+/// the like read those and do their own thing. This plugin can be initialized in two ways:
+///
+/// * No default bindings [InteractionPlugin::new]
+/// * E interacts [InteractionPlugin::default]
+///
+/// The collider hit can be the [Interactable] or any of its descendants.
+pub struct InteractionPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<InteractionControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> InteractionPlugin<T> {
+    /// Creates a new `InteractionPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("interaction"),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: InteractionControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for InteractionPlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::KeyCode::E, InteractionControls::Interact)
+    }
+}
+
+impl <T: Component> Plugin for InteractionPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<InteractionConfig>() {
+            app.insert_resource(InteractionConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<InteractionTarget>()
+            .add_event::<InteractionEvent>()
+            .add_startup_system(spawn_prompt)
+            .add_system(find_target::<T>)
+            .add_system(interact::<T>.with_run_criteria(running).after(find_target::<T>))
+            .add_system(update_prompt.after(find_target::<T>));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum InteractionControls {
+    Interact
+}
+
+impl DisplayName for InteractionControls {
+    fn display_name(&self) -> Cow<'static, str> {
+        match self {
+            InteractionControls::Interact => "Interact".into()
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct InteractionConfig {
+    pub max_distance: f32
+}
+
+impl Default for InteractionConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 3.0
+        }
+    }
+}
+
+/// Something that can be interacted with
+#[derive(Component, Clone)]
+pub struct Interactable {
+    /// What interacting does, shown after the bind (e.g. "open")
+    pub prompt: String
+}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into()
+        }
+    }
+}
+
+/// The [Interactable] currently looked at, if any
+#[derive(Resource, Default)]
+pub struct InteractionTarget(pub Option<Entity>);
+
+/// Sent when the bind is pressed while looking at an [Interactable]
+pub struct InteractionEvent {
+    /// The [Interactable]
+    pub entity: Entity,
+    /// The controlled entity that interacted
+    pub interactor: Entity
+}
+
+#[derive(Component)]
+struct InteractionPrompt;
+
+fn find_target<T: Component>(
+    config: Res<InteractionConfig>,
+    active: Res<ActiveControl<T>>,
+    rapier_context: Res<RapierContext>,
+    mut target: ResMut<InteractionTarget>,
+    controlled: Query<&GlobalTransform, With<T>>,
+    interactables: Query<(), With<Interactable>>,
+    parents: Query<&Parent>
+) {
+    let found = active.entity
+        .and_then(|entity| Some((entity, controlled.get(entity).ok()?)))
+        .and_then(|(entity, transform)| {
+            let filter = QueryFilter::default()
+                .exclude_collider(entity)
+                .exclude_rigid_body(entity);
+            let (hit, _) = rapier_context.cast_ray(transform.translation(), transform.forward(), config.max_distance, true, filter)?;
+            // the collider may be part of a bigger interactable
+            let mut current = hit;
+            loop {
+                if interactables.contains(current) {
+                    return Some(current);
+                }
+                current = parents.get(current).ok()?.get();
+            }
+        });
+    if target.0 != found {
+        target.0 = found;
+    }
+}
+
+fn interact<T: Component>(
+    binds: Res<Input<InteractionControls>>,
+    cursor_grab: Res<CursorGrab>,
+    active: Res<ActiveControl<T>>,
+    target: Res<InteractionTarget>,
+    mut events: EventWriter<InteractionEvent>
+) {
+    if !binds.just_pressed(InteractionControls::Interact) || cursor_grab.is_inactive() {
+        return;
+    }
+    if let (Some(entity), Some(interactor)) = (target.0, active.entity) {
+        events.send(InteractionEvent { entity, interactor });
+    }
+}
+
+fn spawn_prompt(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    commands.spawn(TextBundle::from_section("", TextStyle {
+        font: asset_server.load(hud_config.font_path.as_str()),
+        font_size: hud_config.font_size,
+        color: Color::WHITE
+    })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Percent(50.0),
+                bottom: Val::Percent(30.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(InteractionPrompt);
+}
+
+fn update_prompt(
+    target: Res<InteractionTarget>,
+    binds: Res<KeyBindings<InteractionControls>>,
+    last_device: Res<LastInputDevice>,
+    localization: Res<BindingLocalization>,
+    interactables: Query<&Interactable>,
+    mut prompts: Query<(&mut Text, &mut Visibility), With<InteractionPrompt>>
+) {
+    let prompt = target.0.and_then(|entity| interactables.get(entity).ok());
+    let value = match prompt {
+        Some(interactable) => {
+            // the prompt names whichever bind fits what the player is currently using
+            let mut inputs = binds.inputs(InteractionControls::Interact).collect::<Vec<_>>();
+            inputs.sort_by_key(|raw_input| raw_input.device() != last_device.0);
+            let bind = inputs.first().map_or("unbound".to_string(), |raw_input| localization.name(raw_input));
+            format!("[{}] {}", bind, interactable.prompt)
+        }
+        None => String::new()
+    };
+    for (mut text, mut visibility) in &mut prompts {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+        let visible = !value.is_empty();
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+}
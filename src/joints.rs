@@ -0,0 +1,379 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, AssetServer, Handle};
+use bevy::hierarchy::{BuildChildren, DespawnRecursiveExt};
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{Added, ButtonBundle, Changed, Color, Commands, Component, Entity, GlobalTransform, IntoSystemDescriptor, Mesh, NodeBundle, Query, Res, ResMut, Resource, Style, TextBundle, Transform, Visibility, With, Without};
+use bevy::text::{Text, TextStyle};
+use bevy::ui::{FlexDirection, Interaction, PositionType, Size, UiRect, Val};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{GenericJointBuilder, ImpulseJoint, JointAxesMask, JointAxis, RigidBody};
+use crate::debug_draw::{line_mesh, unlit, DebugDrawn};
+use crate::hud::HudConfig;
+use crate::selection::{Selection, SelectionSystem};
+use crate::ui_mode::UiMode;
+
+/// A panel for joining the first two bodies of the [Selection] with a rapier joint, shown while
+/// [UiMode] is active and at least two rigid bodies are selected. The first selected body is the
+/// parent, the joint goes halfway between both of them, and the panel's buttons pick the
+/// [JointKind], the axis revolute and prismatic joints move around or along, and how far they can
+/// move (from [JointToolConfig]). Connecting again replaces the joint the second body had.
+///
+/// Every [ImpulseJoint] (not only the ones made here) is drawn as a line between its anchors along
+/// with the rest of the [DebugDrawPlugin](crate::debug_draw::DebugDrawPlugin)'s drawing, and the
+/// [ObjectInspectorPlugin](crate::object_inspector::ObjectInspectorPlugin) mentions joints made
+/// here. Needs a [SelectionPlugin](crate::selection::SelectionPlugin).
+pub struct JointToolPlugin;
+
+impl Plugin for JointToolPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<JointToolConfig>() {
+            app.insert_resource(JointToolConfig::default());
+        }
+        app
+            .init_resource::<JointSettings>()
+            .add_startup_system(spawn_joint_panel)
+            .add_system(joint_buttons.after(SelectionSystem))
+            .add_system(update_joint_panel.after(joint_buttons))
+            .add_system(spawn_joint_lines)
+            .add_system(update_joint_lines);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum JointKind {
+    #[default]
+    Fixed,
+    Revolute,
+    Spherical,
+    Prismatic
+}
+
+impl JointKind {
+    pub fn next(self) -> Self {
+        match self {
+            JointKind::Fixed => JointKind::Revolute,
+            JointKind::Revolute => JointKind::Spherical,
+            JointKind::Spherical => JointKind::Prismatic,
+            JointKind::Prismatic => JointKind::Fixed
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            JointKind::Fixed => "fixed",
+            JointKind::Revolute => "revolute",
+            JointKind::Spherical => "spherical",
+            JointKind::Prismatic => "prismatic"
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            JointKind::Fixed => Color::rgb(0.9, 0.9, 0.9),
+            JointKind::Revolute => Color::rgb(1.0, 0.6, 0.1),
+            JointKind::Spherical => Color::rgb(0.3, 0.8, 1.0),
+            JointKind::Prismatic => Color::rgb(0.8, 0.3, 1.0)
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct JointToolConfig {
+    /// The limits to choose from for revolute joints, in degrees either way, `None` for no limit
+    pub angle_limits: Vec<Option<f32>>,
+    /// The limits to choose from for prismatic joints, in meters either way
+    pub distance_limits: Vec<Option<f32>>
+}
+
+impl Default for JointToolConfig {
+    fn default() -> Self {
+        Self {
+            angle_limits: vec![None, Some(15.0), Some(45.0), Some(90.0)],
+            distance_limits: vec![None, Some(0.25), Some(0.5), Some(1.0)]
+        }
+    }
+}
+
+/// What the next joint made with the panel will be like
+#[derive(Resource, Default, Clone, Debug)]
+pub struct JointSettings {
+    pub kind: JointKind,
+    /// Index into the world axes
+    pub axis: usize,
+    /// Index into [JointToolConfig::angle_limits] or [JointToolConfig::distance_limits]
+    pub limit: usize
+}
+
+impl JointSettings {
+    /// The limit for the current kind, in radians for revolute joints
+    fn limit(&self, config: &JointToolConfig) -> Option<f32> {
+        match self.kind {
+            JointKind::Revolute => config.angle_limits.get(self.limit).copied().flatten().map(f32::to_radians),
+            JointKind::Prismatic => config.distance_limits.get(self.limit).copied().flatten(),
+            _ => None
+        }
+    }
+}
+
+/// The kind of a joint made by the [JointToolPlugin], kept next to its [ImpulseJoint]
+#[derive(Component, Copy, Clone, Debug)]
+pub struct ToolJoint(pub JointKind);
+
+#[derive(Component)]
+struct JointPanel;
+
+#[derive(Component)]
+struct JointText;
+
+#[derive(Component, Copy, Clone)]
+enum JointButton {
+    Kind,
+    Axis,
+    Limit,
+    Connect,
+    Disconnect
+}
+
+/// The line drawn for the [ImpulseJoint] of the entity
+#[derive(Component)]
+struct JointLine(Entity);
+
+#[derive(Resource)]
+struct JointLineMesh(Handle<Mesh>);
+
+fn spawn_joint_panel(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    let text_style = TextStyle {
+        font: asset_server.load(hud_config.font_path.as_str()),
+        font_size: hud_config.font_size,
+        color: Color::WHITE
+    };
+
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                right: Val::Px(8.0),
+                bottom: Val::Px(8.0),
+                ..default()
+            },
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(JointPanel)
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section("", text_style.clone()))
+                .insert(JointText);
+
+            let buttons = [
+                (JointButton::Kind, "next kind"),
+                (JointButton::Axis, "next axis"),
+                (JointButton::Limit, "next limit"),
+                (JointButton::Connect, "connect"),
+                (JointButton::Disconnect, "disconnect")
+            ];
+            for (button, label) in buttons {
+                panel.spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(hud_config.font_size + 8.0)),
+                        margin: UiRect::top(Val::Px(4.0)),
+                        padding: UiRect::horizontal(Val::Px(4.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.3, 0.3, 0.3, 0.8).into(),
+                    ..default()
+                })
+                    .insert(button)
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(label, text_style.clone()));
+                    });
+            }
+        });
+}
+
+/// The first two selected rigid bodies, parent first
+fn selected_pair(selection: &Selection, bodies: &Query<&Transform, With<RigidBody>>) -> Option<(Entity, Entity)> {
+    let mut selected = selection.iter().filter(|entity| bodies.contains(*entity));
+    Some((selected.next()?, selected.next()?))
+}
+
+fn joint_buttons(
+    mut commands: Commands,
+    config: Res<JointToolConfig>,
+    selection: Res<Selection>,
+    mut settings: ResMut<JointSettings>,
+    buttons: Query<(&Interaction, &JointButton), Changed<Interaction>>,
+    bodies: Query<&Transform, With<RigidBody>>
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            JointButton::Kind => {
+                settings.kind = settings.kind.next();
+                settings.limit = 0;
+            }
+            JointButton::Axis => settings.axis = (settings.axis + 1) % 3,
+            JointButton::Limit => {
+                let count = match settings.kind {
+                    JointKind::Revolute => config.angle_limits.len(),
+                    JointKind::Prismatic => config.distance_limits.len(),
+                    _ => 1
+                };
+                settings.limit = (settings.limit + 1) % count.max(1);
+            }
+            JointButton::Connect => {
+                let Some((parent, child)) = selected_pair(&selection, &bodies) else {
+                    continue;
+                };
+                let (Ok(parent_transform), Ok(child_transform)) = (bodies.get(parent), bodies.get(child)) else {
+                    continue;
+                };
+                let joint = build_joint(&settings, &config, parent_transform, child_transform);
+                commands.entity(child).insert((ImpulseJoint::new(parent, joint), ToolJoint(settings.kind)));
+            }
+            JointButton::Disconnect => {
+                if let Some((_, child)) = selected_pair(&selection, &bodies) {
+                    commands.entity(child).remove::<(ImpulseJoint, ToolJoint)>();
+                }
+            }
+        }
+    }
+}
+
+fn build_joint(settings: &JointSettings, config: &JointToolConfig, parent: &Transform, child: &Transform) -> GenericJointBuilder {
+    let anchor = (parent.translation + child.translation) / 2.0;
+    let axis = Vec3::AXES[settings.axis % 3];
+    let local = |transform: &Transform, point: Vec3| transform.rotation.inverse() * (point - transform.translation);
+    let locked = match settings.kind {
+        JointKind::Fixed => JointAxesMask::LOCKED_FIXED_AXES,
+        JointKind::Revolute => JointAxesMask::LOCKED_REVOLUTE_AXES,
+        JointKind::Spherical => JointAxesMask::LOCKED_SPHERICAL_AXES,
+        JointKind::Prismatic => JointAxesMask::LOCKED_PRISMATIC_AXES
+    };
+    // the axis is a world axis, so it's turned into each body's own frame
+    let mut joint = GenericJointBuilder::new(locked)
+        .local_axis1(parent.rotation.inverse() * axis)
+        .local_axis2(child.rotation.inverse() * axis)
+        .local_anchor1(local(parent, anchor))
+        .local_anchor2(local(child, anchor));
+    if let Some(limit) = settings.limit(config) {
+        let limited = if settings.kind == JointKind::Revolute { JointAxis::AngX } else { JointAxis::X };
+        joint = joint.limits(limited, [-limit, limit]);
+    }
+    joint
+}
+
+fn update_joint_panel(
+    config: Res<JointToolConfig>,
+    ui_mode: Option<Res<UiMode>>,
+    selection: Res<Selection>,
+    settings: Res<JointSettings>,
+    bodies: Query<&Transform, With<RigidBody>>,
+    joints: Query<&ToolJoint>,
+    mut panels: Query<&mut Visibility, With<JointPanel>>,
+    mut texts: Query<&mut Text, With<JointText>>
+) {
+    let pair = selected_pair(&selection, &bodies).filter(|_| ui_mode.map_or(false, |ui_mode| ui_mode.active));
+    for mut visibility in &mut panels {
+        if visibility.is_visible != pair.is_some() {
+            visibility.is_visible = pair.is_some();
+        }
+    }
+    let Some((_, child)) = pair else {
+        return;
+    };
+
+    let limit = match (settings.kind, settings.limit(&config)) {
+        (JointKind::Revolute, Some(limit)) => format!("±{:.0}°", limit.to_degrees()),
+        (JointKind::Prismatic, Some(limit)) => format!("±{:.2} m", limit),
+        (JointKind::Revolute | JointKind::Prismatic, None) => "none".to_string(),
+        _ => "-".to_string()
+    };
+    let mut value = format!(
+        "joint: {}\naxis: {}\nlimit: {}",
+        settings.kind.name(), ["x", "y", "z"][settings.axis % 3], limit
+    );
+    if let Ok(ToolJoint(kind)) = joints.get(child) {
+        value += &format!("\nconnected: {}", kind.name());
+    }
+    for mut text in &mut texts {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn spawn_joint_lines(
+    mut commands: Commands,
+    line_mesh_handle: Option<Res<JointLineMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    joints: Query<(Entity, Option<&ToolJoint>), Added<ImpulseJoint>>
+) {
+    if joints.is_empty() {
+        return;
+    }
+    // a unit line along -Z, stretched between the anchors
+    let mesh = match line_mesh_handle {
+        Some(handle) => handle.0.clone(),
+        None => {
+            let handle = meshes.add(line_mesh([(Vec3::ZERO, Vec3::NEG_Z)]));
+            commands.insert_resource(JointLineMesh(handle.clone()));
+            handle
+        }
+    };
+    for (entity, kind) in &joints {
+        let color = kind.map_or(Color::YELLOW, |ToolJoint(kind)| kind.color());
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: materials.add(unlit(color)),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+            NotShadowCaster,
+            DebugDrawn,
+            JointLine(entity)
+        ));
+    }
+}
+
+fn update_joint_lines(
+    mut commands: Commands,
+    joints: Query<(&ImpulseJoint, &GlobalTransform)>,
+    bodies: Query<&GlobalTransform, Without<JointLine>>,
+    mut lines: Query<(Entity, &JointLine, &mut Transform)>
+) {
+    for (line, JointLine(entity), mut transform) in &mut lines {
+        let Ok((joint, child)) = joints.get(*entity) else {
+            // the joint or its entity is gone
+            commands.entity(line).despawn_recursive();
+            continue;
+        };
+        let Ok(parent) = bodies.get(joint.parent) else {
+            continue;
+        };
+        let from = parent.transform_point(joint.data.local_anchor1());
+        let to = child.transform_point(joint.data.local_anchor2());
+        let offset = to - from;
+        let length = offset.length();
+        let rotation = if length > f32::EPSILON {
+            Quat::from_rotation_arc(Vec3::NEG_Z, offset / length)
+        } else {
+            Quat::IDENTITY
+        };
+        *transform = Transform {
+            translation: from,
+            rotation,
+            scale: Vec3::new(1.0, 1.0, length.max(f32::EPSILON))
+        };
+    }
+}
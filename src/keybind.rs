@@ -1,13 +1,49 @@
+use std::any::type_name;
+use std::borrow::Cow;
 use std::hash::Hash;
+use std::marker::PhantomData;
+use std::time::Duration;
 use bevy::app::{App, CoreStage, Plugin};
-use bevy::input::{Input, InputSystem};
-use bevy::prelude::{IntoSystemDescriptor, KeyCode, MouseButton, Res, ResMut, Resource};
-use bevy::utils::HashMap;
+use bevy::ecs::change_detection::DetectChanges;
+use bevy::input::{Axis, Input, InputSystem};
+use bevy::input::gamepad::{Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, Gamepads};
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::input::touch::{Touch, Touches};
+use bevy::log::warn;
+use bevy::math::Vec2;
+use bevy::prelude::{EventReader, EventWriter, IntoSystemDescriptor, KeyCode, Local, MouseButton, Res, ResMut, Resource, SystemLabel};
+use bevy::time::Time;
+use bevy::window::Windows;
+use bevy::utils::{default, HashMap, HashSet};
 use derive_more::{From, TryInto};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct KeyBindingPlugin<T: Send + Sync + Hash + Eq + Clone + Copy + 'static> {
-    binds: KeyBindings<T>
+    binds: KeyBindings<T>,
+    axis_binds: AxisBindings<T>,
+    buffer: Option<Duration>,
+    player: Option<usize>,
+    ignore_capture: bool,
+    /// The section of [SectionBindings] and what registers it, kept as a function since only
+    /// deserializable action types can have one
+    preset_section: Option<(&'static str, fn(&mut App, &'static str))>
+}
+
+// manually implemented, deriving Default would require T to be Default as well
+
+impl <T: Send + Sync + Hash + Eq + Clone + Copy + 'static> Default for KeyBindingPlugin<T> {
+    fn default() -> Self {
+        Self {
+            binds: KeyBindings::default(),
+            axis_binds: AxisBindings::default(),
+            buffer: None,
+            player: None,
+            ignore_capture: false,
+            preset_section: None
+        }
+    }
 }
 
 impl <T: Send + Sync + Hash + Eq + Clone + Copy + 'static> KeyBindingPlugin<T> {
@@ -28,52 +64,847 @@ impl <T: Send + Sync + Hash + Eq + Clone + Copy + 'static> KeyBindingPlugin<T> {
         self.binds.rebind(input, bind);
         self
     }
+
+    /// Binds the provided `axis` to the provided `bind`, the value ends up in `Axis<T>`
+    pub fn bind_axis(mut self, axis: RawAxis, bind: T) -> Self {
+        self.axis_binds.bind(axis, bind);
+        self
+    }
+
+    /// Sets the dead zone and response curve applied to the provided `axis`
+    pub fn axis_response(mut self, axis: RawAxis, response: AxisResponse) -> Self {
+        self.axis_binds.set_response(axis, response);
+        self
+    }
+
+    /// Sets the inversion and scale applied to the provided `input` when it feeds into `Axis<T>`
+    pub fn modifier(mut self, input: impl Into<RawInput>, modifier: BindModifier) -> Self {
+        self.binds.set_modifier(input, modifier);
+        self
+    }
+
+    /// Sets the inversion and scale applied to the provided `axis`, after its [AxisResponse]
+    pub fn axis_modifier(mut self, axis: RawAxis, modifier: BindModifier) -> Self {
+        self.axis_binds.set_modifier(axis, modifier);
+        self
+    }
+
+    /// Adds an [InputBuffer] remembering presses for the provided `window`
+    pub fn with_buffer(mut self, window: Duration) -> Self {
+        self.buffer = Some(window);
+        self
+    }
+
+    /// Only maps input from the device [PlayerInput] assigns to the player `slot`, instead of
+    /// from every device
+    pub fn for_player(mut self, slot: usize) -> Self {
+        self.player = Some(slot);
+        self
+    }
+
+    /// Keeps mapping the keyboard and mouse while [InputCapture] is captured, for the bindings
+    /// of whatever captures it, like the console's toggle
+    pub fn ignore_capture(mut self) -> Self {
+        self.ignore_capture = true;
+        self
+    }
+}
+
+impl <T: Send + Sync + Hash + Eq + Clone + Copy + DeserializeOwned + 'static> KeyBindingPlugin<T> {
+    /// Lets [SectionBindings] (like the ones of binding presets, see
+    /// [BindingPresetPlugin](crate::binding_preset::BindingPresetPlugin)) rebind these actions,
+    /// under `section`
+    pub fn with_preset_section(mut self, section: &'static str) -> Self {
+        self.preset_section = Some((section, add_section_bindings::<T>));
+        self
+    }
 }
 
 impl <T: Send + Sync + Hash + Eq + Clone + Copy + 'static> Plugin for KeyBindingPlugin<T> {
     fn build(&self, app: &mut App) {
-        app
-            .insert_resource(self.binds.clone())
-            .insert_resource(Input::<T>::default())
-            .add_system_to_stage(
-                CoreStage::PreUpdate,
-                map_keybinds::<T>.after(InputSystem)
-            );
+        if !app.world.contains_resource::<TouchStickConfig>() {
+            app.insert_resource(TouchStickConfig::default());
+        }
+        // raw input is read once for every action type, the per type systems only look it up
+        if !app.world.contains_resource::<RawInputState>() {
+            app
+                .init_resource::<RawInputState>()
+                .init_resource::<PlayerInput>()
+                .init_resource::<InputCapture>()
+                .init_resource::<LastInputDevice>()
+                .init_resource::<BindingRegistry>()
+                .init_resource::<BindingLocalization>()
+                .add_event::<InputDeviceChanged>()
+                .add_event::<BindingConflict>()
+                .init_resource::<LookDelta>()
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    read_raw_inputs.label(RawInputSystem).after(InputSystem)
+                )
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    read_look_delta.label(RawInputSystem).after(InputSystem)
+                )
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    detect_input_device.after(RawInputSystem)
+                )
+                .add_system_to_stage(CoreStage::PreUpdate, report_binding_conflicts.label(BindingConflictSystem));
+        }
+
+        // a second plugin for the same action type (e.g. from two plugins sharing action types)
+        // only adds its bindings, the systems are already in place
+        if app.world.contains_resource::<KeyBindings<T>>() {
+            let mut binds = app.world.resource_mut::<KeyBindings<T>>();
+            for (raw_input, bind) in &self.binds.binds {
+                binds.bind(*raw_input, *bind);
+            }
+            for (raw_input, modifier) in &self.binds.modifiers {
+                binds.set_modifier(*raw_input, *modifier);
+            }
+            let mut axis_binds = app.world.resource_mut::<AxisBindings<T>>();
+            for (raw_axis, bind) in &self.axis_binds.binds {
+                axis_binds.bind(*raw_axis, *bind);
+            }
+            for (raw_axis, response) in &self.axis_binds.responses {
+                axis_binds.set_response(*raw_axis, *response);
+            }
+            for (raw_axis, modifier) in &self.axis_binds.modifiers {
+                axis_binds.set_modifier(*raw_axis, *modifier);
+            }
+        } else {
+            app
+                .insert_resource(self.binds.clone())
+                .insert_resource(self.axis_binds.clone())
+                .insert_resource(Input::<T>::default())
+                .insert_resource(Axis::<T>::default())
+                .insert_resource(AxisValues::<T>::default())
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    map_keybinds::<T>.after(RawInputSystem)
+                )
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    map_axes::<T>.after(RawInputSystem)
+                )
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    register_bindings::<T>.before(BindingConflictSystem)
+                );
+        }
+
+        if let Some(slot) = self.player {
+            app.insert_resource(PlayerSlot::<T>::new(slot));
+        }
+        if self.ignore_capture {
+            app.insert_resource(CaptureExempt::<T>(default()));
+        }
+        if let Some((section, add)) = self.preset_section {
+            add(app, section);
+        }
+
+        if let Some(window) = self.buffer {
+            if !app.world.contains_resource::<InputBuffer<T>>() {
+                app.add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    buffer_inputs::<T>.after(map_keybinds::<T>)
+                );
+            }
+            app.insert_resource(InputBuffer::<T>::new(window));
+        }
     }
 }
 
-pub fn map_keybinds<T: Send + Sync + Hash + Eq + Clone + Copy>(
+/// Bindings by section, each input bound to an action, both in RON. The [KeyBindings] of every
+/// action type given a section with [KeyBindingPlugin::with_preset_section] are layered like so:
+/// every action mentioned in its section loses its other inputs, the rest keep the bindings they
+/// have without it, as does everything when the section goes away. Bindings changed by anything
+/// else in the meantime (like the [BindingProfile](crate::free_control::BindingProfile) switching)
+/// count as changes to those, the section is layered over them again. Systems changing bindings in
+/// [CoreStage::PreUpdate] should run before [SectionBindingSystem] for that to happen the same
+/// frame.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct SectionBindings(pub HashMap<String, HashMap<String, String>>);
+
+/// Label of the systems layering [SectionBindings] over [KeyBindings], which run before
+/// [RawInputSystem]
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SectionBindingSystem;
+
+/// The bindings of `T` without its section, and what they were with it when it was last applied
+struct SectionLayer<T> {
+    base: KeyBindings<T>,
+    applied: KeyBindings<T>
+}
+
+impl <T: Eq + Copy> SectionLayer<T> {
+    /// Moves whatever changed in `binds` since the section was applied into the base, returning
+    /// whether anything did
+    fn take_changes(&mut self, binds: &KeyBindings<T>) -> bool {
+        let mut changed = false;
+        for (input, action) in binds.iter() {
+            if self.applied.get(input) != Some(action) {
+                self.base.rebind(input, action);
+                changed = true;
+            }
+        }
+        for (input, _) in self.applied.iter() {
+            if binds.get(input).is_none() {
+                self.base.clear_bind(input);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Layers `section` of [SectionBindings] over the [KeyBindings] of `T`, see
+/// [KeyBindingPlugin::with_preset_section]
+fn add_section_bindings<T: Send + Sync + Hash + Eq + Clone + Copy + DeserializeOwned + 'static>(app: &mut App, section: &'static str) {
+    app
+        .init_resource::<SectionBindings>()
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            (move |sections: Res<SectionBindings>,
+                   mut binds: ResMut<KeyBindings<T>>,
+                   mut layer: Local<Option<SectionLayer<T>>>| {
+                if !sections.is_changed() && !binds.is_changed() {
+                    return;
+                }
+                let bindings = sections.0.get(section);
+                if let Some(layer) = layer.as_mut() {
+                    if !layer.take_changes(&binds) && !sections.is_changed() {
+                        return;
+                    }
+                } else if bindings.is_none() {
+                    return;
+                }
+                let layer = layer.get_or_insert_with(|| SectionLayer { base: binds.clone(), applied: KeyBindings::default() });
+                let mut rebound = layer.base.clone();
+                if let Some(bindings) = bindings {
+                    let bindings = bindings.iter().filter_map(|(input, action)| {
+                        match (ron::from_str::<RawInput>(input), ron::from_str::<T>(action)) {
+                            (Ok(input), Ok(action)) => Some((input, action)),
+                            _ => {
+                                warn!("ignoring binding {} to {} in section {}", input, action, section);
+                                None
+                            }
+                        }
+                    }).collect::<Vec<_>>();
+                    for (_, action) in &bindings {
+                        for input in rebound.inputs(*action).collect::<Vec<_>>() {
+                            rebound.clear_bind(input);
+                        }
+                    }
+                    for (input, action) in bindings {
+                        rebound.rebind(input, action);
+                    }
+                }
+                layer.applied = rebound.clone();
+                *binds = rebound;
+            }).label(SectionBindingSystem).before(RawInputSystem)
+        );
+}
+
+/// Label of [read_raw_inputs], which all action mapping runs after
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawInputSystem;
+
+/// The state of every [RawInput] and [RawAxis] for the current frame, read once and shared by
+/// every [KeyBindingPlugin] so adding more action types doesn't mean reading input again
+#[derive(Resource, Default)]
+pub struct RawInputState {
+    pressed: HashSet<RawInput>,
+    just_released: HashSet<RawInput>,
+    axes: HashMap<RawAxis, f32>,
+    /// The axes of every connected gamepad, by their place in [RawInputState::gamepads]
+    gamepad_axes: HashMap<(usize, GamepadAxisType), f32>,
+    /// Connected gamepads, ordered by id so their indices stay put while others connect
+    gamepads: Vec<Gamepad>,
+    touch_sticks: HashMap<TouchRegion, (Vec2, Vec2)>
+}
+
+impl RawInputState {
+    pub fn pressed(&self, input: impl Into<RawInput>) -> bool {
+        self.pressed.contains(&input.into())
+    }
+
+    pub fn just_released(&self, input: impl Into<RawInput>) -> bool {
+        self.just_released.contains(&input.into())
+    }
+
+    /// The raw value of `axis`, before any [AxisResponse] is applied
+    pub fn axis(&self, axis: RawAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// The virtual joystick of a [TouchRegion], as the position the touch started from and how
+    /// far the stick is deflected, from -1.0 to 1.0 on each axis. `None` while the region isn't
+    /// touched
+    pub fn touch_stick(&self, region: TouchRegion) -> Option<(Vec2, Vec2)> {
+        self.touch_sticks.get(&region).copied()
+    }
+
+    pub fn iter_pressed(&self) -> impl Iterator<Item = &RawInput> {
+        self.pressed.iter()
+    }
+
+    /// The raw value of `axis_type` on the connected gamepad at `index`, in order of their ids.
+    /// [RawAxis::GamepadAxis] is the same as index 0
+    pub fn gamepad_axis(&self, index: usize, axis_type: GamepadAxisType) -> f32 {
+        self.gamepad_axes.get(&(index, axis_type)).copied().unwrap_or(0.0)
+    }
+
+    /// The connected gamepad at `index`, in order of their ids
+    pub fn gamepad(&self, index: usize) -> Option<Gamepad> {
+        self.gamepads.get(index).copied()
+    }
+}
+
+/// How far to look this frame, gathered once so look controls don't each read [MouseMotion]
+/// themselves and can be driven by something else entirely, like a test or a replay through
+/// [LookDelta::inject]
+#[derive(Resource, Default, Debug)]
+pub struct LookDelta {
+    /// Mouse motion in logical pixels
+    pub mouse: Vec2,
+    /// Whatever was injected for this frame, in logical pixels like the mouse. It doesn't come from
+    /// the mouse, so unlike [LookDelta::mouse] it's used without the cursor being grabbed
+    pub injected: Vec2,
+    pending: Vec2
+}
+
+impl LookDelta {
+    /// Adds `delta` to the next frame's [LookDelta::injected], as if the mouse moved that far
+    pub fn inject(&mut self, delta: Vec2) {
+        self.pending += delta;
+    }
+}
+
+pub fn read_look_delta(mut motion_events: EventReader<MouseMotion>, mut look_delta: ResMut<LookDelta>) {
+    let look_delta = &mut *look_delta;
+    look_delta.mouse = motion_events.iter().map(|motion| motion.delta).sum::<Vec2>();
+    look_delta.injected = std::mem::take(&mut look_delta.pending);
+}
+
+/// Set by UI taking keyboard input, like the console while it's open or a focused text field.
+/// While anything holds the capture, [map_keybinds] and [map_axes] stop mapping the keyboard,
+/// mouse and touch screen into actions (except for plugins made with
+/// [KeyBindingPlugin::ignore_capture]), so typing doesn't also fly the camera around.
+/// Gamepads keep working
+#[derive(Resource, Default, Debug)]
+pub struct InputCapture {
+    owners: HashSet<&'static str>
+}
+
+impl InputCapture {
+    /// Captures input for `owner` until it's released by the same owner, captures by different
+    /// owners are independent of each other
+    pub fn capture(&mut self, owner: &'static str) {
+        self.owners.insert(owner);
+    }
+
+    pub fn release(&mut self, owner: &'static str) {
+        self.owners.remove(owner);
+    }
+
+    /// Captures or releases for `owner`
+    pub fn set(&mut self, owner: &'static str, captured: bool) {
+        if captured {
+            self.capture(owner);
+        } else {
+            self.release(owner);
+        }
+    }
+
+    pub fn is_captured(&self) -> bool {
+        !self.owners.is_empty()
+    }
+}
+
+/// Present for action types mapped while [InputCapture] is captured, see
+/// [KeyBindingPlugin::ignore_capture]
+#[derive(Resource)]
+pub struct CaptureExempt<T>(PhantomData<fn(T)>);
+
+/// Which physical device each player slot for local multiplayer uses. [KeyBindingPlugin]s made
+/// [for_player](KeyBindingPlugin::for_player) only map input from the device of their slot, so
+/// for example two [FreeControlPlugin](crate::free_control::FreeControlPlugin)s with different
+/// markers can each give a camera its own controls. Only gamepad axes are told apart by gamepad,
+/// bound [RawInput]s all come from the keyboard, mouse and touch screen.
+///
+/// By default the first slot takes the keyboard and mouse, and the next ones take the gamepads
+/// in order
+#[derive(Resource, Clone, Debug)]
+pub struct PlayerInput {
+    slots: Vec<Option<PlayerDevice>>
+}
+
+impl Default for PlayerInput {
+    fn default() -> Self {
+        Self {
+            slots: vec![
+                Some(PlayerDevice::KeyboardMouse),
+                Some(PlayerDevice::Gamepad(0)),
+                Some(PlayerDevice::Gamepad(1)),
+                Some(PlayerDevice::Gamepad(2))
+            ]
+        }
+    }
+}
+
+impl PlayerInput {
+    /// Gives `slot` the provided `device`, or nothing with `None`, taking it from any other slot
+    /// that had it
+    pub fn assign(&mut self, slot: usize, device: Option<PlayerDevice>) -> &mut Self {
+        if device.is_some() {
+            for assigned in &mut self.slots {
+                if *assigned == device {
+                    *assigned = None;
+                }
+            }
+        }
+        if self.slots.len() <= slot {
+            self.slots.resize(slot + 1, None);
+        }
+        self.slots[slot] = device;
+        self
+    }
+
+    pub fn device(&self, slot: usize) -> Option<PlayerDevice> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    /// The slot using `device`, if any
+    pub fn slot_of(&self, device: PlayerDevice) -> Option<usize> {
+        self.slots.iter().position(|assigned| *assigned == Some(device))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PlayerDevice {
+    /// The keyboard, mouse and touch screen, there's only one of each as far as Bevy is concerned
+    KeyboardMouse,
+    /// The connected gamepad at this index, see [RawInputState::gamepad]
+    Gamepad(usize)
+}
+
+/// The player slot the `T` actions are mapped for, see [KeyBindingPlugin::for_player]
+#[derive(Resource)]
+pub struct PlayerSlot<T> {
+    pub slot: usize,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T> PlayerSlot<T> {
+    pub fn new(slot: usize) -> Self {
+        Self {
+            slot,
+            __phantom: default()
+        }
+    }
+}
+
+/// The device the `T` actions are mapped from, `None` when they aren't scoped to a player and
+/// take every device
+fn scoped_device<T>(slot: Option<&PlayerSlot<T>>, players: &PlayerInput) -> Option<Option<PlayerDevice>> {
+    slot.map(|slot| players.device(slot.slot))
+}
+
+pub fn read_raw_inputs(
     key_codes: Res<Input<KeyCode>>,
     mouse_buttons: Res<Input<MouseButton>>,
-    key_bindings: Res<KeyBindings<T>>,
-    mut binds: ResMut<Input<T>>
+    touches: Res<Touches>,
+    windows: Option<Res<Windows>>,
+    gamepads: Res<Gamepads>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    stick_config: Res<TouchStickConfig>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut state: ResMut<RawInputState>
 ) {
-    binds.clear();
-    for (raw_input, bind) in &key_bindings.binds {
-        match raw_input {
-            RawInput::KeyCode(key_code) => {
-                if key_codes.pressed(*key_code) {
-                    binds.press(*bind);
-                }
-                if key_codes.just_released(*key_code) {
-                    binds.release(*bind);
+    let windows = windows.as_deref();
+    let state = &mut *state;
+    // the wheel has no state of its own, it's released once a frame goes by without scrolling
+    let last_wheel = [WheelDirection::Up, WheelDirection::Down]
+        .into_iter()
+        .filter(|direction| state.pressed(*direction))
+        .collect::<Vec<_>>();
+    state.pressed.clear();
+    state.just_released.clear();
+    state.axes.clear();
+    state.gamepad_axes.clear();
+    state.gamepads.clear();
+    state.touch_sticks.clear();
+
+    state.pressed.extend(key_codes.get_pressed().map(|key_code| RawInput::KeyCode(*key_code)));
+    state.just_released.extend(key_codes.get_just_released().map(|key_code| RawInput::KeyCode(*key_code)));
+    state.pressed.extend(mouse_buttons.get_pressed().map(|button| RawInput::MouseButton(*button)));
+    state.just_released.extend(mouse_buttons.get_just_released().map(|button| RawInput::MouseButton(*button)));
+
+    for wheel in wheel_events.iter() {
+        if wheel.y > 0.0 {
+            state.pressed.insert(RawInput::MouseWheel(WheelDirection::Up));
+        } else if wheel.y < 0.0 {
+            state.pressed.insert(RawInput::MouseWheel(WheelDirection::Down));
+        }
+    }
+    for direction in last_wheel {
+        if !state.pressed(direction) {
+            state.just_released.insert(RawInput::MouseWheel(direction));
+        }
+    }
+
+    for touch in touches.iter() {
+        let region = TouchRegion::of(touch, windows);
+        state.pressed.insert(RawInput::Touch(region));
+        state.touch_sticks.entry(region).or_insert_with(|| {
+            // Bevy reports touch positions with y going up on mobile platforms
+            let offset = (touch.position() - touch.start_position()) / stick_config.radius;
+            (touch.start_position(), offset.clamp_length_max(1.0))
+        });
+        let drag = touch.delta();
+        *state.axes.entry(RawAxis::TouchDragX(region)).or_default() += drag.x;
+        *state.axes.entry(RawAxis::TouchDragY(region)).or_default() += drag.y;
+    }
+    for touch in touches.iter_just_released() {
+        let region = RawInput::Touch(TouchRegion::of(touch, windows));
+        if !state.pressed.contains(&region) {
+            state.just_released.insert(region);
+        }
+    }
+    for (region, (_, stick)) in &state.touch_sticks {
+        state.axes.insert(RawAxis::TouchStickX(*region), stick.x);
+        state.axes.insert(RawAxis::TouchStickY(*region), stick.y);
+    }
+
+    state.gamepads.extend(gamepads.iter());
+    state.gamepads.sort_by_key(|gamepad| gamepad.id);
+    for (index, gamepad) in state.gamepads.iter().enumerate() {
+        use GamepadAxisType::*;
+
+        for axis_type in [LeftStickX, LeftStickY, LeftZ, RightStickX, RightStickY, RightZ] {
+            if let Some(value) = gamepad_axes.get(GamepadAxis::new(*gamepad, axis_type)) {
+                state.gamepad_axes.insert((index, axis_type), value);
+                // unscoped bindings only read the first gamepad
+                if index == 0 {
+                    state.axes.insert(RawAxis::GamepadAxis(axis_type), value);
                 }
             }
-            RawInput::MouseButton(mouse_button) => {
-                if mouse_buttons.pressed(*mouse_button) {
-                    binds.press(*bind);
-                }
-                if mouse_buttons.just_released(*mouse_button) {
-                    binds.release(*bind);
+        }
+    }
+}
+
+/// Every [RawInput] bound in each [KeyBindings] registry, by the name of its action type, kept up
+/// to date as bindings change so settings UIs can warn about an input before binding it. The same
+/// input bound to two actions of the same type can't happen, [KeyBindings::bind] replaces it.
+///
+/// Conflicts are also logged as warnings and sent as [BindingConflict] events when they first come
+/// up, some are on purpose (like the left mouse button locking the cursor and shooting), so nothing
+/// is done about them
+#[derive(Resource, Default)]
+pub struct BindingRegistry {
+    registries: HashMap<&'static str, HashSet<RawInput>>,
+    reported: HashSet<BindingConflict>
+}
+
+impl BindingRegistry {
+    /// The action types with `input` bound, in no particular order
+    pub fn bound_in(&self, input: impl Into<RawInput>) -> impl Iterator<Item = &'static str> + '_ {
+        let input = input.into();
+        self.registries
+            .iter()
+            .filter(move |(_, inputs)| inputs.contains(&input))
+            .map(|(name, _)| *name)
+    }
+
+    /// The action types other than `T` with `input` bound, what binding `input` in
+    /// `KeyBindings<T>` would conflict with
+    pub fn conflicts_with<T: 'static>(&self, input: impl Into<RawInput>) -> impl Iterator<Item = &'static str> + '_ {
+        self.bound_in(input).filter(|name| *name != type_name::<T>())
+    }
+
+    /// Every pair of action types sharing an input
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut conflicts = Vec::new();
+        for (first, inputs) in &self.registries {
+            for (second, other_inputs) in &self.registries {
+                if first >= second {
+                    continue;
                 }
+                conflicts.extend(inputs.intersection(other_inputs).map(|input| BindingConflict {
+                    input: *input,
+                    first: *first,
+                    second: *second
+                }));
             }
         }
+        conflicts
+    }
+}
+
+/// The same [RawInput] bound in the [KeyBindings] of two different action types, named by
+/// [std::any::type_name]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct BindingConflict {
+    pub input: RawInput,
+    pub first: &'static str,
+    pub second: &'static str
+}
+
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BindingConflictSystem;
+
+fn register_bindings<T: Send + Sync + Hash + Eq + Clone + Copy + 'static>(
+    key_bindings: Res<KeyBindings<T>>,
+    mut registry: ResMut<BindingRegistry>
+) {
+    if key_bindings.is_changed() {
+        registry.registries.insert(type_name::<T>(), key_bindings.binds.keys().copied().collect());
+    }
+}
+
+fn report_binding_conflicts(mut registry: ResMut<BindingRegistry>, mut conflicts: EventWriter<BindingConflict>) {
+    if !registry.is_changed() {
+        return;
+    }
+    let current = registry.conflicts();
+    for conflict in &current {
+        if !registry.reported.contains(conflict) {
+            warn!("{:?} is bound in both {} and {}", conflict.input, conflict.first, conflict.second);
+            conflicts.send(*conflict);
+        }
+    }
+    // a conflict that's resolved and comes back is reported again, this alone isn't a change
+    // worth checking again for
+    registry.bypass_change_detection().reported = current.into_iter().collect();
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum InputDevice {
+    #[default]
+    KeyboardMouse,
+    Gamepad,
+    Touch
+}
+
+/// Whichever [InputDevice] was used last, so prompts and controls can follow whatever the player
+/// is holding. [InputDeviceChanged] is sent whenever it changes
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct LastInputDevice(pub InputDevice);
+
+pub struct InputDeviceChanged {
+    pub device: InputDevice
+}
+
+/// Gamepad axes have to be pushed this far to count as using the gamepad, so a resting stick
+/// that drifts a little doesn't take over
+const GAMEPAD_AXIS_THRESHOLD: f32 = 0.3;
+/// Logical pixels the mouse has to move in a frame to count as using it, for the same reason
+const MOUSE_MOTION_THRESHOLD: f32 = 2.0;
+
+pub fn detect_input_device(
+    raw_inputs: Res<RawInputState>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    look_delta: Res<LookDelta>,
+    mut last: ResMut<LastInputDevice>,
+    mut changed: EventWriter<InputDeviceChanged>
+) {
+    let mut used = HashSet::<InputDevice>::default();
+    used.extend(raw_inputs.iter_pressed().map(RawInput::device));
+    if look_delta.mouse.length() > MOUSE_MOTION_THRESHOLD {
+        used.insert(InputDevice::KeyboardMouse);
+    }
+    let gamepad_axis = raw_inputs.axes
+        .iter()
+        .any(|(axis, value)| matches!(axis, RawAxis::GamepadAxis(_)) && value.abs() > GAMEPAD_AXIS_THRESHOLD);
+    if gamepad_axis || gamepad_buttons.get_pressed().next().is_some() {
+        used.insert(InputDevice::Gamepad);
+    }
+    // staying on the current device while it's still in use keeps two devices used at once from
+    // flipping back and forth every frame
+    if used.contains(&last.0) {
+        return;
+    }
+    let next = [InputDevice::Touch, InputDevice::Gamepad, InputDevice::KeyboardMouse]
+        .into_iter()
+        .find(|device| used.contains(device));
+    if let Some(device) = next {
+        last.0 = device;
+        changed.send(InputDeviceChanged { device });
+    }
+}
+
+/// Remembers presses of actions for a short while, so an action pressed slightly too early (for
+/// example jumping right before landing) can still be acted upon once it's possible.
+/// Added by [KeyBindingPlugin::with_buffer].
+#[derive(Resource)]
+pub struct InputBuffer<T> {
+    pub window: Duration,
+    now: Duration,
+    presses: HashMap<T, Duration>
+}
+
+impl <T: Hash + Eq + Copy> InputBuffer<T> {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            now: Duration::ZERO,
+            presses: HashMap::default()
+        }
+    }
+
+    /// Whether `action` was pressed within the buffer window, without consuming it
+    pub fn buffered(&self, action: T) -> bool {
+        self.presses
+            .get(&action)
+            .filter(|pressed| self.now.saturating_sub(**pressed) <= self.window)
+            .is_some()
+    }
+
+    /// Returns whether `action` was pressed within the buffer window, and forgets that press so
+    /// it can only be acted upon once
+    pub fn consume(&mut self, action: T) -> bool {
+        let buffered = self.buffered(action);
+        self.presses.remove(&action);
+        buffered
+    }
+
+    /// Forgets all buffered presses
+    pub fn clear(&mut self) {
+        self.presses.clear();
+    }
+}
+
+pub fn buffer_inputs<T: Send + Sync + Hash + Eq + Clone + Copy>(
+    time: Res<Time>,
+    binds: Res<Input<T>>,
+    mut buffer: ResMut<InputBuffer<T>>
+) {
+    let buffer = &mut *buffer;
+    buffer.now = time.elapsed();
+    for bind in binds.get_just_pressed() {
+        buffer.presses.insert(*bind, buffer.now);
+    }
+    let (now, window) = (buffer.now, buffer.window);
+    buffer.presses.retain(|_, pressed| now.saturating_sub(*pressed) <= window);
+}
+
+/// Which half of the primary window a touch started in
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum TouchRegion {
+    Left,
+    Right
+}
+
+impl TouchRegion {
+    fn of(touch: &Touch, windows: Option<&Windows>) -> Self {
+        let width = windows
+            .and_then(|windows| windows.get_primary())
+            .map(|window| window.width())
+            .unwrap_or(0.0);
+        if touch.start_position().x < width / 2.0 {
+            TouchRegion::Left
+        } else {
+            TouchRegion::Right
+        }
     }
 }
 
-#[derive(Resource, Default, Clone)]
+pub fn map_keybinds<T: Send + Sync + Hash + Eq + Clone + Copy>(
+    raw_inputs: Res<RawInputState>,
+    key_bindings: Res<KeyBindings<T>>,
+    players: Res<PlayerInput>,
+    slot: Option<Res<PlayerSlot<T>>>,
+    capture: Res<InputCapture>,
+    exempt: Option<Res<CaptureExempt<T>>>,
+    mut binds: ResMut<Input<T>>
+) {
+    binds.clear();
+    let scoped_away = scoped_device(slot.as_deref(), &players)
+        .map_or(false, |device| device != Some(PlayerDevice::KeyboardMouse));
+    if scoped_away || (capture.is_captured() && exempt.is_none()) {
+        // the keys are someone (or something) else's, releasing anything still held keeps it
+        // from sticking
+        for bind in binds.get_pressed().copied().collect::<Vec<_>>() {
+            binds.release(bind);
+        }
+        return;
+    }
+    for (raw_input, bind) in &key_bindings.binds {
+        if raw_inputs.pressed(*raw_input) {
+            binds.press(*bind);
+        }
+        if raw_inputs.just_released(*raw_input) {
+            binds.release(*bind);
+        }
+    }
+}
+
+/// Maps [RawAxis] bindings into `Axis<T>` and [AxisValues], axes bound to the same `T` are summed.
+/// Bound [RawInput]s are added in as well, as 1.0 while pressed, so keys can drive axes
+pub fn map_axes<T: Send + Sync + Hash + Eq + Clone + Copy>(
+    raw_inputs: Res<RawInputState>,
+    key_bindings: Res<KeyBindings<T>>,
+    axis_bindings: Res<AxisBindings<T>>,
+    players: Res<PlayerInput>,
+    slot: Option<Res<PlayerSlot<T>>>,
+    capture: Res<InputCapture>,
+    exempt: Option<Res<CaptureExempt<T>>>,
+    mut axes: ResMut<Axis<T>>,
+    mut axis_values: ResMut<AxisValues<T>>
+) {
+    let device = scoped_device(slot.as_deref(), &players);
+    let keyboard = device.map_or(true, |device| device == Some(PlayerDevice::KeyboardMouse))
+        && !(capture.is_captured() && exempt.is_none());
+    let gamepad = match device {
+        None => Some(0),
+        Some(Some(PlayerDevice::Gamepad(index))) => Some(index),
+        Some(_) => None
+    };
+    let mut values = HashMap::<T, f32>::default();
+    for (raw_input, bind) in &key_bindings.binds {
+        let value = if keyboard && raw_inputs.pressed(*raw_input) {
+            key_bindings.modifier(*raw_input).apply(1.0)
+        } else {
+            0.0
+        };
+        *values.entry(*bind).or_default() += value;
+    }
+    for (raw_axis, bind) in &axis_bindings.binds {
+        let value = match (*raw_axis, gamepad) {
+            (RawAxis::GamepadAxis(axis_type), Some(gamepad)) => {
+                let response = axis_bindings.response(*raw_axis);
+                let partner = stick_partner(axis_type)
+                    .map(|partner| raw_inputs.gamepad_axis(gamepad, partner));
+                response.apply(raw_inputs.gamepad_axis(gamepad, axis_type), partner)
+            }
+            (RawAxis::GamepadAxis(_), None) => 0.0,
+            // touch goes with the keyboard and mouse
+            _ if !keyboard => 0.0,
+            _ => raw_inputs.axis(*raw_axis)
+        };
+        let value = axis_bindings.modifier(*raw_axis).apply(value);
+        *values.entry(*bind).or_default() += value;
+    }
+    for (bind, value) in &values {
+        axes.set(*bind, *value);
+    }
+    axis_values.values = values;
+}
+
+#[derive(Resource, Clone)]
 pub struct KeyBindings<T> {
-    binds: HashMap<RawInput, T>
+    binds: HashMap<RawInput, T>,
+    modifiers: HashMap<RawInput, BindModifier>
+}
+
+impl <T> Default for KeyBindings<T> {
+    fn default() -> Self {
+        Self {
+            binds: HashMap::default(),
+            modifiers: HashMap::default()
+        }
+    }
 }
 
 impl <T> KeyBindings<T> {
@@ -94,10 +925,443 @@ impl <T> KeyBindings<T> {
         let raw_input = input.into();
         self.clear_bind(raw_input).bind(raw_input, bind)
     }
+
+    /// Sets the inversion and scale applied to the provided `input`, only affects the value it
+    /// feeds into `Axis<T>`, being pressed is the same either way
+    pub fn set_modifier(&mut self, input: impl Into<RawInput>, modifier: BindModifier) -> &mut Self {
+        self.modifiers.insert(input.into(), modifier);
+        self
+    }
+
+    pub fn modifier(&self, input: impl Into<RawInput>) -> BindModifier {
+        self.modifiers.get(&input.into()).copied().unwrap_or_default()
+    }
+
+    /// What the provided `input` is bound to
+    pub fn get(&self, input: impl Into<RawInput>) -> Option<T> where T: Copy {
+        self.binds.get(&input.into()).copied()
+    }
+
+    /// Every input along with what it's bound to, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (RawInput, T)> + '_ where T: Copy {
+        self.binds.iter().map(|(raw_input, bind)| (*raw_input, *bind))
+    }
+
+    /// Every input bound to the provided `bind`, in no particular order
+    pub fn inputs(&self, bind: T) -> impl Iterator<Item = RawInput> + '_ where T: PartialEq {
+        self.binds
+            .iter()
+            .filter(move |(_, bound)| **bound == bind)
+            .map(|(raw_input, _)| *raw_input)
+    }
+}
+
+/// The values of `Axis<T>` before Bevy clamps them to -1.0..=1.0, for axes that are distances
+/// rather than deflections, like [RawAxis::TouchDragX] in pixels, or that are scaled up by a
+/// [BindModifier]
+#[derive(Resource)]
+pub struct AxisValues<T> {
+    values: HashMap<T, f32>
+}
+
+// manually implemented, deriving Default would require T to be Default as well
+
+impl <T> Default for AxisValues<T> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::default()
+        }
+    }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, From, TryInto)]
+impl <T: Hash + Eq> AxisValues<T> {
+    /// The value of `bind` this frame, 0.0 if nothing bound to it moved
+    pub fn get(&self, bind: T) -> f32 {
+        self.values.get(&bind).copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct AxisBindings<T> {
+    binds: HashMap<RawAxis, T>,
+    responses: HashMap<RawAxis, AxisResponse>,
+    modifiers: HashMap<RawAxis, BindModifier>
+}
+
+impl <T> Default for AxisBindings<T> {
+    fn default() -> Self {
+        Self {
+            binds: HashMap::default(),
+            responses: HashMap::default(),
+            modifiers: HashMap::default()
+        }
+    }
+}
+
+impl <T> AxisBindings<T> {
+    /// Binds the provided `axis` to the provided `bind`
+    pub fn bind(&mut self, axis: RawAxis, bind: T) -> &mut Self {
+        self.binds.insert(axis, bind);
+        self
+    }
+
+    /// Clears the binding to the provided `axis`
+    pub fn clear_bind(&mut self, axis: RawAxis) -> &mut Self {
+        self.binds.remove(&axis);
+        self
+    }
+
+    /// Sets the dead zone and response curve applied to the provided `axis`, these are only
+    /// applied to gamepad axes
+    pub fn set_response(&mut self, axis: RawAxis, response: AxisResponse) -> &mut Self {
+        self.responses.insert(axis, response);
+        self
+    }
+
+    pub fn response(&self, axis: RawAxis) -> AxisResponse {
+        self.responses.get(&axis).copied().unwrap_or_default()
+    }
+
+    /// Sets the inversion and scale applied to the provided `axis`, applied to every kind of axis
+    /// after its response
+    pub fn set_modifier(&mut self, axis: RawAxis, modifier: BindModifier) -> &mut Self {
+        self.modifiers.insert(axis, modifier);
+        self
+    }
+
+    pub fn modifier(&self, axis: RawAxis) -> BindModifier {
+        self.modifiers.get(&axis).copied().unwrap_or_default()
+    }
+}
+
+/// Inversion and scale of a single binding, so for example look inversion can be set on the
+/// binding itself instead of flipping sensitivities in every config using it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BindModifier {
+    pub inverted: bool,
+    pub scale: f32
+}
+
+impl BindModifier {
+    pub const INVERTED: Self = Self {
+        inverted: true,
+        scale: 1.0
+    };
+
+    pub fn scaled(scale: f32) -> Self {
+        Self {
+            scale,
+            ..default()
+        }
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value * self.scale;
+        if self.inverted { -value } else { value }
+    }
+}
+
+impl Default for BindModifier {
+    fn default() -> Self {
+        Self {
+            inverted: false,
+            scale: 1.0
+        }
+    }
+}
+
+/// The other axis of the stick `axis_type` belongs to, used for radial dead zones
+fn stick_partner(axis_type: GamepadAxisType) -> Option<GamepadAxisType> {
+    use GamepadAxisType::*;
+
+    match axis_type {
+        LeftStickX => Some(LeftStickY),
+        LeftStickY => Some(LeftStickX),
+        RightStickX => Some(RightStickY),
+        RightStickY => Some(RightStickX),
+        _ => None
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct AxisResponse {
+    pub dead_zone: DeadZone,
+    pub curve: ResponseCurve
+}
+
+impl AxisResponse {
+    /// Applies the dead zone and curve to `value`, `partner` is the other axis of the same stick,
+    /// which is needed for radial dead zones
+    pub fn apply(&self, value: f32, partner: Option<f32>) -> f32 {
+        let value = match (self.dead_zone, partner) {
+            (DeadZone::None, _) => value,
+            (DeadZone::Axial(dead_zone), _) | (DeadZone::Radial(dead_zone), None) => {
+                rescale(value.abs(), dead_zone) * value.signum()
+            }
+            (DeadZone::Radial(dead_zone), Some(partner)) => {
+                let length = Vec2::new(value, partner).length();
+                if length <= dead_zone {
+                    0.0
+                } else {
+                    value / length * rescale(length.min(1.0), dead_zone)
+                }
+            }
+        };
+        self.curve.apply(value)
+    }
+}
+
+/// Maps `magnitude` from `dead_zone..1.0` to `0.0..1.0`, so there's no jump leaving the dead zone
+fn rescale(magnitude: f32, dead_zone: f32) -> f32 {
+    if magnitude <= dead_zone {
+        0.0
+    } else {
+        ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum DeadZone {
+    #[default]
+    None,
+    /// Ignores the axis while its own value is within the dead zone
+    Axial(f32),
+    /// Ignores the axis while the whole stick is within the dead zone, which keeps diagonals
+    /// smooth. Axes not belonging to a stick fall back to [DeadZone::Axial]
+    Radial(f32)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// Raises the value to the provided exponent, anything above 1.0 gives finer control near
+    /// the center of the stick
+    Power(f32)
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        match *self {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Power(exponent) => value.abs().powf(exponent) * value.signum()
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, From, TryInto, Serialize, Deserialize)]
 pub enum RawInput {
     KeyCode(KeyCode),
-    MouseButton(MouseButton)
+    MouseButton(MouseButton),
+    /// Pressed while a touch that started in the region is held, so tapping acts like a button
+    Touch(TouchRegion),
+    /// Pressed on every frame the wheel scrolls in the direction
+    MouseWheel(WheelDirection)
+}
+
+impl RawInput {
+    /// The device this input comes from
+    pub fn device(&self) -> InputDevice {
+        match self {
+            RawInput::KeyCode(_) | RawInput::MouseButton(_) | RawInput::MouseWheel(_) => InputDevice::KeyboardMouse,
+            RawInput::Touch(_) => InputDevice::Touch
+        }
+    }
+}
+
+/// A human readable name, for controls menus and prompts to show instead of [Debug] formatting.
+/// Names are in English, [BindingLocalization] translates them
+pub trait DisplayName {
+    fn display_name(&self) -> Cow<'static, str>;
+}
+
+impl DisplayName for RawInput {
+    fn display_name(&self) -> Cow<'static, str> {
+        match self {
+            RawInput::KeyCode(key_code) => key_code.display_name(),
+            RawInput::MouseButton(MouseButton::Left) => "Left Click".into(),
+            RawInput::MouseButton(MouseButton::Right) => "Right Click".into(),
+            RawInput::MouseButton(MouseButton::Middle) => "Middle Click".into(),
+            RawInput::MouseButton(MouseButton::Other(button)) => format!("Mouse {}", button).into(),
+            RawInput::Touch(TouchRegion::Left) => "Tap Left".into(),
+            RawInput::Touch(TouchRegion::Right) => "Tap Right".into(),
+            RawInput::MouseWheel(WheelDirection::Up) => "Scroll Up".into(),
+            RawInput::MouseWheel(WheelDirection::Down) => "Scroll Down".into()
+        }
+    }
+}
+
+impl DisplayName for KeyCode {
+    fn display_name(&self) -> Cow<'static, str> {
+        use KeyCode::*;
+
+        match self {
+            Key1 => "1".into(),
+            Key2 => "2".into(),
+            Key3 => "3".into(),
+            Key4 => "4".into(),
+            Key5 => "5".into(),
+            Key6 => "6".into(),
+            Key7 => "7".into(),
+            Key8 => "8".into(),
+            Key9 => "9".into(),
+            Key0 => "0".into(),
+            Escape => "Esc".into(),
+            Back => "Backspace".into(),
+            Return => "Enter".into(),
+            LShift => "Left Shift".into(),
+            RShift => "Right Shift".into(),
+            LControl => "Left Ctrl".into(),
+            RControl => "Right Ctrl".into(),
+            LAlt => "Left Alt".into(),
+            RAlt => "Right Alt".into(),
+            Grave => "`".into(),
+            LBracket => "[".into(),
+            RBracket => "]".into(),
+            Comma => ",".into(),
+            Period => ".".into(),
+            Slash => "/".into(),
+            Semicolon => ";".into(),
+            Minus => "-".into(),
+            Equals => "=".into(),
+            // the rest read well enough as they are, like "W", "Space" or "Numpad8"
+            other => format!("{:?}", other).into()
+        }
+    }
+}
+
+impl DisplayName for RawAxis {
+    fn display_name(&self) -> Cow<'static, str> {
+        let region = |region: &TouchRegion| match region {
+            TouchRegion::Left => "Left",
+            TouchRegion::Right => "Right"
+        };
+        match self {
+            RawAxis::TouchStickX(touch) | RawAxis::TouchStickY(touch) => format!("{} Touch Stick", region(touch)).into(),
+            RawAxis::TouchDragX(touch) | RawAxis::TouchDragY(touch) => format!("Drag {}", region(touch)).into(),
+            RawAxis::GamepadAxis(axis_type) => {
+                use GamepadAxisType::*;
+
+                match axis_type {
+                    LeftStickX | LeftStickY => "Left Stick".into(),
+                    RightStickX | RightStickY => "Right Stick".into(),
+                    LeftZ => "Left Trigger".into(),
+                    RightZ => "Right Trigger".into(),
+                    Other(axis) => format!("Gamepad Axis {}", axis).into()
+                }
+            }
+        }
+    }
+}
+
+/// Hook for translating [DisplayName]s, given the English name and returning the translation, or
+/// `None` to keep the English name. Without a hook every name is kept as it is
+#[derive(Resource, Default)]
+pub struct BindingLocalization {
+    pub translate: Option<Box<dyn Fn(&str) -> Option<String> + Send + Sync>>
+}
+
+impl BindingLocalization {
+    pub fn new(translate: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            translate: Some(Box::new(translate))
+        }
+    }
+
+    /// The translated display name of `item`
+    pub fn name(&self, item: &impl DisplayName) -> String {
+        let name = item.display_name();
+        self.translate
+            .as_ref()
+            .and_then(|translate| translate(&name))
+            .unwrap_or_else(|| name.into_owned())
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum WheelDirection {
+    /// Scrolling away from the user
+    Up,
+    Down
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum RawAxis {
+    /// A virtual joystick centered wherever a touch in the region started, from -1.0 to 1.0
+    /// once the touch is [TouchStickConfig::radius] away from where it started
+    TouchStickX(TouchRegion),
+    TouchStickY(TouchRegion),
+    /// How far touches in the region moved this frame, in logical pixels
+    TouchDragX(TouchRegion),
+    TouchDragY(TouchRegion),
+    /// An axis of the first connected gamepad, shaped by its [AxisResponse]
+    GamepadAxis(GamepadAxisType)
+}
+
+#[derive(Resource, Clone)]
+pub struct TouchStickConfig {
+    /// How far in logical pixels a touch has to move for a virtual joystick to be fully deflected
+    pub radius: f32
+}
+
+impl Default for TouchStickConfig {
+    fn default() -> Self {
+        Self {
+            radius: 75.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::Input;
+    use crate::test_support::{headless_app, MockInput};
+    use bevy::prelude::KeyCode;
+    use super::{KeyBindingPlugin, KeyBindings, RawInput, SectionLayer, TouchRegion};
+
+    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+    enum TestControls {
+        Left,
+        Right
+    }
+
+    #[test]
+    fn touches_press_their_region() {
+        let mut app = headless_app();
+        app.add_plugin(KeyBindingPlugin::default()
+            .bind(RawInput::Touch(TouchRegion::Left), TestControls::Left)
+            .bind(RawInput::Touch(TouchRegion::Right), TestControls::Right));
+        MockInput::new()
+            .press(RawInput::Touch(TouchRegion::Left))
+            .run(&mut app);
+        let binds = app.world.resource::<Input<TestControls>>();
+        assert!(binds.pressed(TestControls::Left));
+        assert!(!binds.pressed(TestControls::Right));
+
+        MockInput::new()
+            .release(RawInput::Touch(TouchRegion::Left))
+            .press(RawInput::Touch(TouchRegion::Right))
+            .run(&mut app);
+        let binds = app.world.resource::<Input<TestControls>>();
+        assert!(binds.just_released(TestControls::Left));
+        assert!(binds.pressed(TestControls::Right));
+    }
+
+    #[test]
+    fn changes_under_a_section_go_to_the_base() {
+        let mut base = KeyBindings::default();
+        base.bind(KeyCode::W, 'w').bind(KeyCode::A, 'a');
+        let mut applied = base.clone();
+        applied.clear_bind(KeyCode::W).bind(KeyCode::I, 'w');
+        let mut layer = SectionLayer { base, applied: applied.clone() };
+        assert!(!layer.take_changes(&applied));
+
+        // like a profile swapping A for J
+        let mut binds = applied;
+        binds.clear_bind(KeyCode::A).bind(KeyCode::J, 'a');
+        assert!(layer.take_changes(&binds));
+        assert_eq!(layer.base.get(KeyCode::W), Some('w'));
+        assert_eq!(layer.base.get(KeyCode::A), None);
+        assert_eq!(layer.base.get(KeyCode::J), Some('a'));
+    }
 }
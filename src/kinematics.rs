@@ -0,0 +1,330 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::hierarchy::{BuildWorldChildren, Children};
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Changed, Color, Component, Entity, EventReader, IntoSystemDescriptor, Mesh, Query, Res, Resource, Transform, With, Without, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, ImpulseJoint, JointAxis, QueryFilter, RapierContext, RevoluteJointBuilder, RigidBody};
+use crate::free_control::ActiveControl;
+use crate::interaction::{Interactable, InteractionEvent};
+
+/// Doors, levers and moving platforms, for trying out joint motors and kinematic bodies, all of
+/// them [Interactable] through the [InteractionPlugin](crate::interaction::InteractionPlugin):
+///
+/// * A [Door] is a dynamic body on a hinge (a revolute joint), its motor swings it open or shut
+/// * A [Lever] flips, and toggles whatever doors and platforms are in [Lever::targets]
+/// * A [MovingPlatform] is a kinematic body looping through its waypoints while active
+///
+/// The entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin)
+/// with marker [T] rides along with any platform it's at most [KinematicsConfig::ride_distance]
+/// above. Like everything else the platforms move by Bevy's [Time], which the
+/// [FixedTimePlugin](crate::fixed_time::FixedTimePlugin) keeps in step with physics.
+///
+/// A demo of all three is spawned at [KinematicsConfig::demo_point] on startup, if it's set.
+pub struct KinematicsPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for KinematicsPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for KinematicsPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<KinematicsConfig>() {
+            app.insert_resource(KinematicsConfig::default());
+        }
+        app
+            .add_startup_system(|world: &mut World| {
+                if let Some(point) = world.resource::<KinematicsConfig>().demo_point {
+                    spawn_kinematics_demo(world, point);
+                }
+            })
+            .add_system(interact_with_kinematics)
+            .add_system(drive_doors.after(interact_with_kinematics))
+            .add_system(animate_levers.after(interact_with_kinematics))
+            .add_system(move_platforms.after(interact_with_kinematics))
+            .add_system(ride_platforms::<T>.after(move_platforms));
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct KinematicsConfig {
+    pub demo_point: Option<Vec3>,
+    /// How far above a platform the controlled entity can be and still ride it
+    pub ride_distance: f32,
+    /// Stiffness of the door motors, the damping is a tenth of it
+    pub door_stiffness: f32,
+    /// How quickly levers flip, in radians per second
+    pub lever_speed: f32
+}
+
+impl Default for KinematicsConfig {
+    fn default() -> Self {
+        Self {
+            demo_point: Some(Vec3::new(-8.0, 0.0, 0.0)),
+            ride_distance: 2.0,
+            door_stiffness: 50.0,
+            lever_speed: 4.0
+        }
+    }
+}
+
+/// A hinged body, its [ImpulseJoint] has to be a revolute joint, whose motor is aimed at
+/// [Door::open_angle] or back at zero
+#[derive(Component)]
+pub struct Door {
+    pub open: bool,
+    /// Radians around the hinge
+    pub open_angle: f32
+}
+
+#[derive(Component)]
+pub struct Lever {
+    pub on: bool,
+    /// [Door]s and [MovingPlatform]s toggled along with the lever
+    pub targets: Vec<Entity>
+}
+
+/// The moving part of a [Lever], a child of it
+#[derive(Component)]
+pub struct LeverHandle;
+
+/// Tilt of a [LeverHandle] to either side
+const LEVER_ANGLE: f32 = 0.6;
+
+#[derive(Component)]
+pub struct MovingPlatform {
+    /// Points the platform moves between, going back to the first after the last
+    pub waypoints: Vec<Vec3>,
+    /// Units per second
+    pub speed: f32,
+    pub active: bool,
+    /// Index of the waypoint being moved towards
+    pub next: usize,
+    /// How far the platform moved on the last frame
+    pub delta: Vec3
+}
+
+impl MovingPlatform {
+    pub fn new(waypoints: Vec<Vec3>, speed: f32) -> Self {
+        Self {
+            waypoints,
+            speed,
+            active: true,
+            next: 0,
+            delta: Vec3::ZERO
+        }
+    }
+}
+
+/// Spawns a door, a platform and a lever moving the platform around `point`
+pub fn spawn_kinematics_demo(world: &mut World, point: Vec3) {
+    let (post_mesh, door_mesh, platform_mesh, base_mesh, handle_mesh) = {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        (
+            meshes.add(shape::Box::new(0.2, 2.4, 0.2).into()),
+            meshes.add(shape::Box::new(1.0, 2.0, 0.1).into()),
+            meshes.add(shape::Box::new(3.0, 0.2, 3.0).into()),
+            meshes.add(shape::Box::new(0.4, 0.2, 0.4).into()),
+            meshes.add(shape::Box::new(0.08, 0.8, 0.08).into())
+        )
+    };
+    let (post_material, door_material, platform_material, lever_material) = {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        (
+            materials.add(Color::rgb(0.3, 0.3, 0.3).into()),
+            materials.add(Color::rgb(0.55, 0.35, 0.2).into()),
+            materials.add(Color::rgb(0.2, 0.5, 0.6).into()),
+            materials.add(Color::rgb(0.8, 0.7, 0.1).into())
+        )
+    };
+
+    let post = world.spawn((
+        PbrBundle {
+            mesh: post_mesh,
+            material: post_material.clone(),
+            transform: Transform::from_translation(point + Vec3::Y * 1.2),
+            ..default()
+        },
+        RigidBody::Fixed,
+        Collider::cuboid(0.1, 1.2, 0.1)
+    )).id();
+    // hinged on its left edge, a little away from the post so they don't rub
+    let hinge = RevoluteJointBuilder::new(Vec3::Y)
+        .local_anchor1(Vec3::ZERO)
+        .local_anchor2(Vec3::new(-0.65, 0.0, 0.0));
+    world.spawn((
+        PbrBundle {
+            mesh: door_mesh,
+            material: door_material,
+            transform: Transform::from_translation(point + Vec3::new(0.65, 1.2, 0.0)),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(0.5, 1.0, 0.05),
+        ImpulseJoint::new(post, hinge),
+        Door {
+            open: false,
+            open_angle: 1.5
+        },
+        Interactable::new("open or close")
+    ));
+
+    let start = point + Vec3::new(0.0, 0.0, 5.0);
+    let platform = world.spawn((
+        PbrBundle {
+            mesh: platform_mesh,
+            material: platform_material,
+            transform: Transform::from_translation(start),
+            ..default()
+        },
+        RigidBody::KinematicPositionBased,
+        Collider::cuboid(1.5, 0.1, 1.5),
+        MovingPlatform {
+            active: false,
+            ..MovingPlatform::new(vec![start + Vec3::new(0.0, 4.0, 0.0), start + Vec3::new(6.0, 4.0, 0.0), start], 1.5)
+        },
+        Interactable::new("start or stop")
+    )).id();
+
+    world.spawn((
+        PbrBundle {
+            mesh: base_mesh,
+            material: post_material,
+            transform: Transform::from_translation(point + Vec3::new(-2.0, 0.1, 3.0)),
+            ..default()
+        },
+        RigidBody::Fixed,
+        Collider::cuboid(0.2, 0.1, 0.2),
+        Lever {
+            on: false,
+            targets: vec![platform]
+        },
+        Interactable::new("pull")
+    ))
+        .with_children(|lever| {
+            lever.spawn((
+                PbrBundle {
+                    mesh: handle_mesh,
+                    material: lever_material,
+                    transform: handle_transform(false),
+                    ..default()
+                },
+                LeverHandle
+            ));
+        });
+}
+
+fn handle_transform(on: bool) -> Transform {
+    let rotation = Quat::from_rotation_x(if on { LEVER_ANGLE } else { -LEVER_ANGLE });
+    // pivoting at the bottom of the handle
+    Transform::from_rotation(rotation).with_translation(rotation * Vec3::Y * 0.4)
+}
+
+fn interact_with_kinematics(
+    mut events: EventReader<InteractionEvent>,
+    mut doors: Query<&mut Door>,
+    mut levers: Query<&mut Lever>,
+    mut platforms: Query<&mut MovingPlatform>
+) {
+    for event in events.iter() {
+        let mut toggled = vec![event.entity];
+        if let Ok(mut lever) = levers.get_mut(event.entity) {
+            lever.on = !lever.on;
+            toggled.extend(lever.targets.iter().copied());
+        }
+        for entity in toggled {
+            if let Ok(mut door) = doors.get_mut(entity) {
+                door.open = !door.open;
+            }
+            if let Ok(mut platform) = platforms.get_mut(entity) {
+                platform.active = !platform.active;
+            }
+        }
+    }
+}
+
+fn drive_doors(config: Res<KinematicsConfig>, mut doors: Query<(&Door, &mut ImpulseJoint), Changed<Door>>) {
+    for (door, mut joint) in &mut doors {
+        let target = if door.open { door.open_angle } else { 0.0 };
+        joint.data.set_motor_position(JointAxis::AngX, target, config.door_stiffness, config.door_stiffness * 0.1);
+    }
+}
+
+fn animate_levers(
+    time: Res<Time>,
+    config: Res<KinematicsConfig>,
+    levers: Query<(&Lever, &Children)>,
+    mut handles: Query<&mut Transform, With<LeverHandle>>
+) {
+    let step = config.lever_speed * time.delta_seconds();
+    for (lever, children) in &levers {
+        let target = handle_transform(lever.on);
+        let mut handles = handles.iter_many_mut(children);
+        while let Some(mut transform) = handles.fetch_next() {
+            let angle = transform.rotation.angle_between(target.rotation);
+            if angle > f32::EPSILON {
+                let rotation = transform.rotation.slerp(target.rotation, (step / angle).min(1.0));
+                *transform = Transform::from_rotation(rotation).with_translation(rotation * Vec3::Y * 0.4);
+            }
+        }
+    }
+}
+
+fn move_platforms(time: Res<Time>, mut platforms: Query<(&mut MovingPlatform, &mut Transform)>) {
+    for (mut platform, mut transform) in &mut platforms {
+        platform.delta = Vec3::ZERO;
+        if !platform.active || platform.waypoints.is_empty() {
+            continue;
+        }
+        let mut remaining = platform.speed * time.delta_seconds();
+        let start = transform.translation;
+        // a short enough waypoint can be passed within a frame
+        for _ in 0..platform.waypoints.len() {
+            let target = platform.waypoints[platform.next % platform.waypoints.len()];
+            let offset = target - transform.translation;
+            let distance = offset.length();
+            if distance > remaining {
+                transform.translation += offset / distance * remaining;
+                break;
+            }
+            transform.translation = target;
+            remaining -= distance;
+            platform.next = (platform.next + 1) % platform.waypoints.len();
+        }
+        platform.delta = transform.translation - start;
+    }
+}
+
+fn ride_platforms<T: Component>(
+    config: Res<KinematicsConfig>,
+    active: Res<ActiveControl<T>>,
+    rapier_context: Res<RapierContext>,
+    mut controlled: Query<&mut Transform, (With<T>, Without<MovingPlatform>)>,
+    platforms: Query<&MovingPlatform>
+) {
+    let Some(entity) = active.entity else {
+        return;
+    };
+    let Ok(mut transform) = controlled.get_mut(entity) else {
+        return;
+    };
+    let filter = QueryFilter::default()
+        .exclude_sensors()
+        .exclude_collider(entity)
+        .exclude_rigid_body(entity);
+    let Some((hit, _)) = rapier_context.cast_ray(transform.translation, -Vec3::Y, config.ride_distance, true, filter) else {
+        return;
+    };
+    if let Ok(platform) = platforms.get(hit) {
+        transform.translation += platform.delta;
+    }
+}
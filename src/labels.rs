@@ -0,0 +1,210 @@
+use std::marker::PhantomData;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::asset::AssetServer;
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::prelude::{Added, Camera, Color, Commands, Component, Entity, GlobalTransform, IntoSystemDescriptor, KeyCode, Name, Query, ReflectComponent, Res, ResMut, Resource, Style, TextBundle, Visibility, With, Without};
+use bevy::reflect::Reflect;
+use bevy::text::{Text, TextStyle};
+use bevy::transform::TransformSystem;
+use bevy::ui::{PositionType, UiRect, Val};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::free_control::ActiveControl;
+use crate::hud::HudConfig;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Floating text above every entity with a [WorldLabel], naming it (the label's own text, its
+/// [Name], or the entity id) and how far it is from the camera controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]. Labels are
+/// projected through that camera every frame, and hidden behind it or beyond
+/// [LabelConfig::max_distance]. This plugin can be initialized in two ways:
+///
+/// * No default bindings [LabelPlugin::new]
+/// * L toggles the labels [LabelPlugin::default]
+///
+/// Placed shapes and prefabs get labeled with what they are.
+pub struct LabelPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<LabelControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> LabelPlugin<T> {
+    /// Creates a new `LabelPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: LabelControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for LabelPlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(KeyCode::L, LabelControls::Toggle)
+    }
+}
+
+impl <T: Component> Plugin for LabelPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<LabelConfig>() {
+            app.insert_resource(LabelConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .register_type::<WorldLabel>()
+            .add_system(label_controls)
+            .add_system(spawn_label_texts)
+            // after the transforms are propagated, so the labels don't trail their entities
+            .add_system_to_stage(CoreStage::PostUpdate, update_labels::<T>.after(TransformSystem::TransformPropagate));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum LabelControls {
+    Toggle
+}
+
+#[derive(Resource, Clone)]
+pub struct LabelConfig {
+    pub enabled: bool,
+    /// Labels further than this from the camera are hidden
+    pub max_distance: f32,
+    pub show_distance: bool,
+    pub font_size: f32,
+    pub color: Color
+}
+
+impl Default for LabelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_distance: 60.0,
+            show_distance: true,
+            font_size: 14.0,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.9)
+        }
+    }
+}
+
+/// Gives the entity a floating label, see [LabelPlugin]. Reflected, so it's kept in scene files
+/// and by copies
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct WorldLabel {
+    /// Shown instead of the [Name] or entity id
+    pub text: Option<String>,
+    /// Where the label floats, relative to the entity's translation
+    pub offset: Vec3
+}
+
+impl WorldLabel {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: Some(text.into()),
+            ..default()
+        }
+    }
+}
+
+impl Default for WorldLabel {
+    fn default() -> Self {
+        Self {
+            text: None,
+            offset: Vec3::Y
+        }
+    }
+}
+
+/// The text node showing the label of the entity
+#[derive(Component)]
+struct LabelText(Entity);
+
+fn label_controls(binds: Res<Input<LabelControls>>, mut config: ResMut<LabelConfig>) {
+    if binds.just_pressed(LabelControls::Toggle) {
+        config.enabled = !config.enabled;
+    }
+}
+
+fn spawn_label_texts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<LabelConfig>,
+    hud_config: Option<Res<HudConfig>>,
+    labeled: Query<Entity, Added<WorldLabel>>
+) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    for entity in &labeled {
+        commands.spawn(TextBundle::from_section("", TextStyle {
+            font: asset_server.load(hud_config.font_path.as_str()),
+            font_size: config.font_size,
+            color: config.color
+        })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            }))
+            .insert((LabelText(entity), Visibility { is_visible: false }));
+    }
+}
+
+fn update_labels<T: Component>(
+    mut commands: Commands,
+    config: Res<LabelConfig>,
+    active: Res<ActiveControl<T>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<T>>,
+    labeled: Query<(&WorldLabel, &GlobalTransform, Option<&Name>), Without<T>>,
+    mut texts: Query<(Entity, &LabelText, &mut Text, &mut Style, &mut Visibility)>
+) {
+    let camera = active.entity.and_then(|entity| cameras.get(entity).ok());
+    for (text_entity, LabelText(entity), mut text, mut style, mut visibility) in &mut texts {
+        let Ok((label, transform, name)) = labeled.get(*entity) else {
+            // the entity or its label is gone
+            commands.entity(text_entity).despawn_recursive();
+            continue;
+        };
+        let point = transform.translation() + label.offset;
+        let projected = camera.filter(|_| config.enabled).and_then(|(camera, camera_transform)| {
+            let distance = camera_transform.translation().distance(point);
+            if distance > config.max_distance {
+                return None;
+            }
+            camera.world_to_viewport(camera_transform, point).map(|position| (position, distance))
+        });
+        let Some((position, distance)) = projected else {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+            continue;
+        };
+
+        let name = match (&label.text, name) {
+            (Some(text), _) => text.clone(),
+            (None, Some(name)) => name.to_string(),
+            (None, None) => format!("{:?}", entity)
+        };
+        let value = if config.show_distance {
+            format!("{} ({:.1} m)", name, distance)
+        } else {
+            name
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+        // viewport coordinates start at the bottom left, like UI positions from the bottom do
+        style.position = UiRect {
+            left: Val::Px(position.x),
+            bottom: Val::Px(position.y),
+            ..default()
+        };
+        if !visibility.is_visible {
+            visibility.is_visible = true;
+        }
+    }
+}
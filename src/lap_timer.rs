@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::log::info;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, AlphaMode, Color, Component, Entity, EventReader, IntoSystemDescriptor, Mesh, Query, Res, ResMut, Resource, Transform, With, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, Sensor};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::ticked;
+use crate::free_control::ActiveControl;
+use crate::picking::PickTarget;
+use crate::placement::{Placement, PlacementConfig};
+use crate::settings::AddSetting;
+use crate::trigger_volume::{TriggerEnter, TriggerVolume};
+
+/// Times runs through obstacle courses made of [LapGate]s, going by the [TriggerEnter] events of
+/// the entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with
+/// marker [T]. Going through a start gate starts the timer for its course (over again if it was
+/// already running), and going through a finish gate of the same course stops it. A gate can be
+/// both, for courses that loop around. The timer runs on the ticks of
+/// [FixedTime](crate::fixed_time::FixedTime), so it stops while paused and runs at the same pace
+/// whatever the frame rate.
+///
+/// The [HudPlugin](crate::hud::HudPlugin) shows the [LapTimer], and the best time of each course is
+/// kept in the `lap_times` section of the settings file. The `lap_gate` console command spawns a
+/// gate where the crosshair points, facing the camera, `lap_times` lists the best times and
+/// `lap_reset` forgets them.
+pub struct LapTimerPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for LapTimerPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for LapTimerPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<LapTimerConfig>() {
+            app.insert_resource(LapTimerConfig::default());
+        }
+        app
+            .add_setting::<LapRecords>("lap_times")
+            .init_resource::<LapTimer>()
+            .add_system(tick_lap_timer.with_run_criteria(ticked))
+            .add_system(pass_lap_gates::<T>.after(tick_lap_timer))
+            .add_console_command("lap_gate", "spawns a start, finish or both gate of a course at the crosshair", |world, args| {
+                let Some(kind) = args.first().and_then(|arg| arg.parse::<LapGateKind>().ok()) else {
+                    console_print(world, "usage: lap_gate <start|finish|both> [course]");
+                    return;
+                };
+                let course = args.get(1).copied().unwrap_or("default");
+                let Some(transform) = gate_at_target(world) else {
+                    console_print(world, "point at something to put the gate on");
+                    return;
+                };
+                spawn_lap_gate(world, transform, LapGate { course: course.to_string(), kind });
+                console_print(world, format!("spawned a {} gate of `{}`", kind.name(), course));
+            })
+            .add_console_command("lap_times", "lists the best time of each course", |world, _| {
+                let records = world.resource::<LapRecords>().best.clone();
+                if records.is_empty() {
+                    console_print(world, "no laps finished yet");
+                }
+                for (course, best) in records {
+                    console_print(world, format!("{}: {}", course, format_lap(best)));
+                }
+            })
+            .add_console_command("lap_reset", "forgets the best time of the given course, or of every course", |world, args| {
+                let mut records = world.resource_mut::<LapRecords>();
+                match args.first() {
+                    Some(course) => {
+                        records.best.remove(*course);
+                    }
+                    None => records.best.clear()
+                }
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct LapTimerConfig {
+    pub gate_half_extents: Vec3
+}
+
+impl Default for LapTimerConfig {
+    fn default() -> Self {
+        Self {
+            gate_half_extents: Vec3::new(3.0, 2.0, 0.2)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LapGateKind {
+    Start,
+    Finish,
+    /// Finishes the running lap and starts the next one
+    Both
+}
+
+impl LapGateKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            LapGateKind::Start => "start",
+            LapGateKind::Finish => "finish",
+            LapGateKind::Both => "both"
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            LapGateKind::Start => Color::rgba(0.2, 1.0, 0.3, 0.2),
+            LapGateKind::Finish => Color::rgba(1.0, 0.2, 0.2, 0.2),
+            LapGateKind::Both => Color::rgba(1.0, 0.9, 0.2, 0.2)
+        }
+    }
+}
+
+impl FromStr for LapGateKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(LapGateKind::Start),
+            "finish" => Ok(LapGateKind::Finish),
+            "both" => Ok(LapGateKind::Both),
+            _ => Err(())
+        }
+    }
+}
+
+/// On a [TriggerVolume] that starts or stops the timer of `course`
+#[derive(Component, Clone, Debug)]
+pub struct LapGate {
+    pub course: String,
+    pub kind: LapGateKind
+}
+
+/// Best times in seconds by course
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LapRecords {
+    pub best: BTreeMap<String, f32>
+}
+
+#[derive(Clone, Debug)]
+pub struct RunningLap {
+    pub course: String,
+    /// Seconds since the start gate
+    pub elapsed: f32
+}
+
+#[derive(Clone, Debug)]
+pub struct FinishedLap {
+    pub course: String,
+    pub time: f32,
+    /// Whether it beat the best time of the course, or was the first lap of it
+    pub best: bool
+}
+
+#[derive(Resource, Default)]
+pub struct LapTimer {
+    pub running: Option<RunningLap>,
+    /// The lap finished last, until the next one starts
+    pub finished: Option<FinishedLap>
+}
+
+/// `seconds` as minutes, seconds and hundredths
+pub fn format_lap(seconds: f32) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:05.2}", minutes, seconds - minutes * 60.0)
+}
+
+/// Where the crosshair points, snapped like placing is, standing on the surface and facing the
+/// camera
+fn gate_at_target(world: &mut World) -> Option<Transform> {
+    let target = world.get_resource::<PickTarget>()?;
+    // only on something, not in mid air
+    target.entity?;
+    let mut point = target.point;
+    let look = target.ray_direction;
+    let snap = world.get_resource::<Placement>().map_or(false, |placement| placement.snap);
+    let grid_size = world.get_resource::<PlacementConfig>().map_or(0.0, |config| config.grid_size);
+    if snap && grid_size > 0.0 {
+        point = (point / grid_size).round() * grid_size;
+    }
+    let half_extents = world.resource::<LapTimerConfig>().gate_half_extents;
+    let rotation = Quat::from_rotation_y(f32::atan2(-look.x, -look.z));
+    Some(Transform::from_translation(point + Vec3::Y * half_extents.y).with_rotation(rotation))
+}
+
+/// Spawns a translucent gate at `transform`, a [TriggerVolume] tagged with the course
+pub fn spawn_lap_gate(world: &mut World, transform: Transform, gate: LapGate) -> Entity {
+    let half_extents = world.resource::<LapTimerConfig>().gate_half_extents;
+    let size = half_extents * 2.0;
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape::Box::new(size.x, size.y, size.z).into());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+        base_color: gate.kind.color(),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    });
+    world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            transform,
+            ..default()
+        },
+        NotShadowCaster,
+        Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+        Sensor,
+        TriggerVolume::new(format!("lap_{}", gate.course)),
+        gate
+    )).id()
+}
+
+fn tick_lap_timer(time: Res<Time>, mut timer: ResMut<LapTimer>) {
+    if let Some(lap) = &mut timer.running {
+        lap.elapsed += time.delta_seconds();
+    }
+}
+
+fn pass_lap_gates<T: Component>(
+    active: Res<ActiveControl<T>>,
+    mut enters: EventReader<TriggerEnter>,
+    mut timer: ResMut<LapTimer>,
+    mut records: ResMut<LapRecords>,
+    gates: Query<&LapGate>,
+    controlled: Query<(), With<T>>
+) {
+    let activator = active.entity.filter(|entity| controlled.contains(*entity));
+    for enter in enters.iter() {
+        if Some(enter.activator) != activator {
+            continue;
+        }
+        let Ok(gate) = gates.get(enter.volume) else {
+            continue;
+        };
+        let finishes = matches!(gate.kind, LapGateKind::Finish | LapGateKind::Both);
+        let on_course = timer.running.as_ref().map_or(false, |lap| lap.course == gate.course);
+        // a finish gate of another course leaves the running lap be
+        let finished = if finishes && on_course { timer.running.take() } else { None };
+        if let Some(lap) = finished {
+            let best = records.best.get(&lap.course).map_or(true, |best| lap.elapsed < *best);
+            if best {
+                records.best.insert(lap.course.clone(), lap.elapsed);
+            }
+            info!("finished `{}` in {}{}", lap.course, format_lap(lap.elapsed), if best { ", a new best" } else { "" });
+            timer.finished = Some(FinishedLap {
+                course: lap.course,
+                time: lap.elapsed,
+                best
+            });
+        }
+        if matches!(gate.kind, LapGateKind::Start | LapGateKind::Both) {
+            timer.running = Some(RunningLap {
+                course: gate.course.clone(),
+                elapsed: 0.0
+            });
+            if gate.kind == LapGateKind::Start {
+                timer.finished = None;
+            }
+        }
+    }
+}
+
@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::Handle;
+use bevy::prelude::{Component, GlobalTransform, Mesh, Query, Res, Visibility, With, Without};
+use bevy::utils::default;
+use crate::free_control::ActiveControl;
+use crate::graphics::GraphicsSettings;
+
+/// Swaps the mesh of entities with a [Lod] for a simpler one the further they are from the entity
+/// controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T],
+/// and hides them past the last distance, so scenes with a lot of things in them (like the ones
+/// the [StressTestPlugin](crate::stress_test::StressTestPlugin) spawns) stay interactive. The
+/// distances are [GraphicsSettings::lod_medium], [GraphicsSettings::lod_low] and
+/// [GraphicsSettings::lod_hide].
+///
+/// Handles and [Visibility] are only written when an entity changes level, so nothing is
+/// re-uploaded for entities that stay put. Something else hiding an entity with a [Lod] gets
+/// overwritten the next time it changes level.
+pub struct LodPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for LodPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for LodPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_lods::<T>);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum LodLevel {
+    #[default]
+    High,
+    Medium,
+    Low,
+    Hidden
+}
+
+/// The meshes an entity is drawn with at each level, a level without one falls back to the one
+/// before it
+#[derive(Component, Clone, Debug)]
+pub struct Lod {
+    pub high: Handle<Mesh>,
+    pub medium: Option<Handle<Mesh>>,
+    pub low: Option<Handle<Mesh>>,
+    level: LodLevel
+}
+
+impl Lod {
+    /// Drawn with `high` at every distance, until it's hidden
+    pub fn new(high: Handle<Mesh>) -> Self {
+        Self {
+            high,
+            medium: None,
+            low: None,
+            level: LodLevel::High
+        }
+    }
+
+    pub fn with_medium(mut self, medium: Handle<Mesh>) -> Self {
+        self.medium = Some(medium);
+        self
+    }
+
+    pub fn with_low(mut self, low: Handle<Mesh>) -> Self {
+        self.low = Some(low);
+        self
+    }
+
+    /// The level it's currently drawn at
+    pub fn level(&self) -> LodLevel {
+        self.level
+    }
+
+    /// The mesh for `level`, the high one for [LodLevel::Hidden]
+    pub fn mesh(&self, level: LodLevel) -> &Handle<Mesh> {
+        match level {
+            LodLevel::High | LodLevel::Hidden => &self.high,
+            LodLevel::Medium => self.medium.as_ref().unwrap_or(&self.high),
+            LodLevel::Low => self.low.as_ref().or(self.medium.as_ref()).unwrap_or(&self.high)
+        }
+    }
+}
+
+impl GraphicsSettings {
+    /// The level something `distance` away from the camera is drawn at
+    pub fn lod_level(&self, distance: f32) -> LodLevel {
+        if self.lod_hide.map_or(false, |hide| distance > hide) {
+            LodLevel::Hidden
+        } else if distance > self.lod_low {
+            LodLevel::Low
+        } else if distance > self.lod_medium {
+            LodLevel::Medium
+        } else {
+            LodLevel::High
+        }
+    }
+}
+
+fn update_lods<T: Component>(
+    settings: Res<GraphicsSettings>,
+    active: Res<ActiveControl<T>>,
+    cameras: Query<&GlobalTransform, With<T>>,
+    mut lods: Query<(&mut Lod, &GlobalTransform, &mut Handle<Mesh>, &mut Visibility), Without<T>>
+) {
+    let Some(camera) = active.entity.and_then(|entity| cameras.get(entity).ok()) else {
+        return;
+    };
+    let camera = camera.translation();
+    for (mut lod, transform, mut mesh, mut visibility) in &mut lods {
+        let level = settings.lod_level(transform.translation().distance(camera));
+        if level == lod.level {
+            continue;
+        }
+        if level != LodLevel::Hidden && *mesh != *lod.mesh(level) {
+            *mesh = lod.mesh(level).clone();
+        }
+        visibility.is_visible = level != LodLevel::Hidden;
+        lod.level = level;
+    }
+}
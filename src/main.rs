@@ -1,87 +1,287 @@
 mod keybind;
+mod ai;
+mod bench;
+mod binding_preset;
+mod cleanup;
+mod cli;
 mod free_control;
+mod gizmo;
+mod gltf_import;
 mod fixed_time;
+mod force_field;
+mod floating_origin;
 mod cursor_grab;
+mod determinism;
+mod destruction;
+mod save;
+mod scenario;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod selection;
+mod environment;
+mod explosion;
+mod terrain;
+mod vehicle;
+mod navmesh;
+mod sky;
+mod skybox;
+mod capture;
+mod clipboard;
+mod window_control;
+mod virtual_joystick;
+mod camera_bookmark;
+mod camera_path;
+mod ui_mode;
+mod camera_effects;
+mod camera_follow;
+mod audio;
+mod hud;
+mod labels;
+mod lap_timer;
+mod picking;
+mod object_inspector;
+mod outliner;
+mod console;
+mod console_macro;
+mod debug_draw;
+mod edit_history;
+mod material_tool;
+mod measure;
+mod minimap;
+mod settings;
+mod graphics;
+mod grapple;
+mod interaction;
+mod joints;
+mod kinematics;
+mod lod;
+mod trigger_volume;
+mod frame_limit;
+mod game_state;
+mod physics_debug;
+mod physics_settings;
+mod placement;
+mod portal;
+mod pool;
+mod prefab;
+mod profiler;
+mod rewind;
+mod rope;
+mod shooter;
+mod stress_test;
+mod tags;
+#[cfg(test)]
+mod test_support;
+mod trail;
+mod water;
 
-use bevy::app::App;
-use bevy::asset::Assets;
+use bevy::app::{App, PluginGroup};
+use bevy::asset::AssetPlugin;
 use bevy::DefaultPlugins;
-use bevy::input::Input;
-use bevy::log::info;
 use bevy::math::Vec3;
-use bevy::pbr::{DirectionalLight, DirectionalLightBundle, PbrBundle, StandardMaterial};
-use bevy::prelude::{Camera3dBundle, Color, Commands, Component, IntoSystemDescriptor, KeyCode, Mesh, MouseButton, Res, ResMut, shape, Transform};
+use bevy::prelude::{Camera3dBundle, Commands, Component, Transform};
+use bevy::render::settings::WgpuSettings;
 use bevy::utils::default;
-use bevy::window::{WindowMode, Windows};
+use bevy::window::{WindowDescriptor, WindowMode, WindowPlugin};
+use bevy::winit::WinitPlugin;
 use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin};
-use crate::cursor_grab::{cursor_grab, CursorGrab, CursorGrabPlugin};
+use crate::ai::AiPlugin;
+use crate::audio::AudioFeedbackPlugin;
+use crate::bench::BenchPlugin;
+use crate::binding_preset::BindingPresetPlugin;
+use crate::camera_bookmark::CameraBookmarkPlugin;
+use crate::camera_effects::CameraEffectsPlugin;
+use crate::camera_follow::CameraFollowPlugin;
+use crate::camera_path::CameraPathPlugin;
+use crate::capture::CapturePlugin;
+use crate::cleanup::CleanupPlugin;
+use crate::cli::{CliArgs, CliPlugin};
+use crate::clipboard::ClipboardPlugin;
+use crate::console::ConsolePlugin;
+use crate::console_macro::ConsoleMacroPlugin;
+use crate::cursor_grab::CursorGrabPlugin;
+use crate::determinism::DeterminismPlugin;
+use crate::destruction::DestructionPlugin;
+use crate::debug_draw::DebugDrawPlugin;
+use crate::edit_history::EditHistoryPlugin;
+use crate::explosion::ExplosionPlugin;
 use crate::fixed_time::FixedTimePlugin;
+use crate::force_field::ForceFieldPlugin;
+use crate::floating_origin::FloatingOriginPlugin;
+use crate::frame_limit::FrameLimitPlugin;
 use crate::free_control::FreeControlPlugin;
+use crate::game_state::GameStatePlugin;
+use crate::gizmo::GizmoPlugin;
+use crate::gltf_import::GltfImportPlugin;
+use crate::graphics::GraphicsSettingsPlugin;
+use crate::grapple::GrapplePlugin;
+use crate::hud::HudPlugin;
+use crate::interaction::InteractionPlugin;
+use crate::joints::JointToolPlugin;
+use crate::kinematics::KinematicsPlugin;
+use crate::labels::LabelPlugin;
+use crate::lap_timer::LapTimerPlugin;
+use crate::lod::LodPlugin;
+use crate::material_tool::MaterialToolPlugin;
+use crate::measure::MeasurePlugin;
+use crate::minimap::MinimapPlugin;
+use crate::navmesh::NavMeshPlugin;
+use crate::object_inspector::ObjectInspectorPlugin;
+use crate::outliner::OutlinerPlugin;
+use crate::physics_debug::PhysicsDebugPlugin;
+use crate::physics_settings::PhysicsSettingsPlugin;
+use crate::picking::PickingPlugin;
+use crate::placement::PlacementPlugin;
+use crate::portal::PortalPlugin;
+use crate::pool::PoolPlugin;
+use crate::prefab::PrefabPlugin;
+use crate::profiler::ProfilerPlugin;
+use crate::rewind::RewindPlugin;
+use crate::rope::RopePlugin;
+use crate::environment::EnvironmentPlugin;
+use crate::save::SavePlugin;
+use crate::scenario::ScenarioPlugin;
+use crate::selection::SelectionPlugin;
+use crate::trail::TrailPlugin;
+use crate::settings::SettingsPlugin;
+use crate::shooter::ShooterPlugin;
+use crate::sky::SkyPlugin;
+use crate::skybox::SkyboxPlugin;
+use crate::stress_test::StressTestPlugin;
+use crate::tags::TagPlugin;
+use crate::terrain::TerrainPlugin;
+use crate::trigger_volume::TriggerVolumePlugin;
+use crate::ui_mode::UiModePlugin;
+use crate::vehicle::VehicleControlPlugin;
+use crate::virtual_joystick::VirtualJoystickPlugin;
+use crate::water::WaterPlugin;
+use crate::window_control::WindowControlPlugin;
 
 fn main() {
+    let args = CliArgs::from_env();
     let mut app = App::new();
+    let mut plugins = DefaultPlugins
+        .set(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        })
+        .set(WindowPlugin {
+            window: WindowDescriptor {
+                mode: if args.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed },
+                ..default()
+            },
+            // there are no windows at all when headless, which would otherwise exit right away
+            exit_on_all_closed: !args.is_headless(),
+            ..default()
+        });
+    if args.is_headless() {
+        // nothing to render to without a window
+        app.insert_resource(WgpuSettings { backends: None, ..default() });
+        plugins = plugins.disable::<WinitPlugin>();
+    }
     app
-        .add_plugins(DefaultPlugins)
+        .add_plugins(plugins)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(FixedTimePlugin)
-        .add_plugin(FreeControlPlugin::<FreeCam>::default())
+        .add_plugin(CliPlugin::<FreeCam>::new(args.clone()))
+        .add_plugin(SettingsPlugin::default())
+        .add_plugin(BindingPresetPlugin::default())
+        .add_plugin(FixedTimePlugin::default())
+        .add_plugin(DeterminismPlugin)
+        .add_plugin(RewindPlugin::default())
+        .add_plugin(FreeControlPlugin::<FreeCam>::default().with_grab_bindings())
         .add_plugin(CursorGrabPlugin)
-        .add_startup_system(setup_camera_and_light)
-        .add_startup_system(setup_environment)
-        .add_system(toggle_cursor_grab.before(cursor_grab))
-        .add_system(toggle_fullscreen);
+        .add_plugin(GameStatePlugin::default())
+        .add_plugin(SavePlugin::<FreeCam>::default())
+        .add_plugin(EnvironmentPlugin::default())
+        .add_plugin(TerrainPlugin::<FreeCam>::default())
+        .add_plugin(SkyPlugin::default())
+        .add_plugin(SkyboxPlugin::<FreeCam>::default())
+        .add_plugin(GraphicsSettingsPlugin::default())
+        .add_plugin(LodPlugin::<FreeCam>::default());
+    // both need a real window, and capturing needs rendering
+    if !args.is_headless() {
+        app
+            .add_plugin(CapturePlugin::<FreeCam>::default())
+            .add_plugin(WindowControlPlugin::default());
+    }
+    app
+        .add_plugin(FrameLimitPlugin)
+        .add_plugin(VirtualJoystickPlugin)
+        .add_plugin(CameraBookmarkPlugin::<FreeCam>::default())
+        .add_plugin(CameraPathPlugin::<FreeCam>::default())
+        .add_plugin(FloatingOriginPlugin::<FreeCam>::default())
+        .add_plugin(UiModePlugin::<FreeCam>::default())
+        .add_plugin(CameraEffectsPlugin::<FreeCam>::default())
+        .add_plugin(CameraFollowPlugin::<FreeCam>::default())
+        .add_plugin(AudioFeedbackPlugin::<FreeCam>::default())
+        .add_plugin(HudPlugin::<FreeCam>::default())
+        .add_plugin(MinimapPlugin::<FreeCam>::default())
+        .add_plugin(PickingPlugin::<FreeCam>::default())
+        .add_plugin(ObjectInspectorPlugin::default())
+        .add_plugin(TagPlugin)
+        .add_plugin(ConsolePlugin::default())
+        .add_plugin(ConsoleMacroPlugin)
+        .add_plugin(DebugDrawPlugin::<FreeCam>::default())
+        .add_plugin(LabelPlugin::<FreeCam>::default())
+        .add_plugin(PhysicsDebugPlugin::default())
+        .add_plugin(PhysicsSettingsPlugin)
+        .add_plugin(StressTestPlugin)
+        .add_plugin(ProfilerPlugin::default())
+        .add_plugin(PrefabPlugin::default())
+        .add_plugin(GltfImportPlugin)
+        .add_plugin(PlacementPlugin::default())
+        .add_plugin(EditHistoryPlugin::default())
+        .add_plugin(MaterialToolPlugin::default())
+        .add_plugin(MeasurePlugin::default())
+        .add_plugin(SelectionPlugin::<FreeCam>::default())
+        .add_plugin(OutlinerPlugin::<FreeCam>::default())
+        .add_plugin(GizmoPlugin::default())
+        .add_plugin(ClipboardPlugin::default())
+        .add_plugin(JointToolPlugin)
+        .add_plugin(PoolPlugin)
+        .add_plugin(CleanupPlugin::<FreeCam>::default())
+        .add_plugin(ExplosionPlugin::default())
+        .add_plugin(DestructionPlugin)
+        .add_plugin(TrailPlugin)
+        .add_plugin(ShooterPlugin::<FreeCam>::default())
+        .add_plugin(GrapplePlugin::<FreeCam>::default())
+        .add_plugin(RopePlugin)
+        .add_plugin(InteractionPlugin::<FreeCam>::default())
+        .add_plugin(KinematicsPlugin::<FreeCam>::default())
+        .add_plugin(TriggerVolumePlugin::<FreeCam>::default())
+        .add_plugin(PortalPlugin::<FreeCam>::default())
+        .add_plugin(LapTimerPlugin::<FreeCam>::default())
+        .add_plugin(WaterPlugin::<FreeCam>::default())
+        .add_plugin(ForceFieldPlugin)
+        .add_plugin(VehicleControlPlugin::<FreeCam>::default())
+        .add_plugin(NavMeshPlugin)
+        .add_plugin(AiPlugin)
+        .add_plugin(ScenarioPlugin::<FreeCam>::default())
+        .add_plugin(BenchPlugin)
+        .add_startup_system(setup_camera);
+    #[cfg(feature = "inspector")]
+    {
+        use bevy_inspector_egui::quick::ResourceInspectorPlugin;
+        use crate::cursor_grab::CursorGrab;
+        use crate::free_control::FreeControlConfig;
+
+        // the inspector windows need the cursor, see UiModePlugin
+        app
+            .add_plugin(ResourceInspectorPlugin::<FreeControlConfig<FreeCam>>::default())
+            .add_plugin(ResourceInspectorPlugin::<CursorGrab>::default());
+    }
+    #[cfg(feature = "scripting")]
+    app.add_plugin(scripting::ScriptingPlugin::default());
     app.run();
 }
 
 #[derive(Component)]
 pub struct FreeCam;
 
-fn setup_camera_and_light(mut commands: Commands) {
+fn setup_camera(mut commands: Commands) {
     commands.spawn(Camera3dBundle {
         transform: Transform::from_xyz(0.0, 5.0, 20.0).looking_at(Vec3::Y * 5.0, Vec3::Y),
         ..default()
     })
         .insert(FreeCam);
-
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            illuminance: 1000.0,
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
-        ..default()
-    });
-}
-
-fn setup_environment(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>, mut cursor_grab: ResMut<CursorGrab>) {
-    let meshes = &mut meshes;
-    commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(shape::Box::new(2.0, 2.0, 2.0).into()),
-            material: materials.add(Color::rgb(0.6, 0.6, 0.6).into()),
-            ..default()
-        });
-    cursor_grab.activate();
-}
-
-fn toggle_cursor_grab(mut cursor_grab: ResMut<CursorGrab>, key_codes: Res<Input<KeyCode>>, mouse_buttons: Res<Input<MouseButton>>) {
-    if mouse_buttons.just_pressed(MouseButton::Left) && cursor_grab.is_inactive() {
-        info!("activated");
-        cursor_grab.activate();
-    }
-    if key_codes.just_pressed(KeyCode::Escape) && cursor_grab.is_active() {
-        info!("deactivated");
-        cursor_grab.deactivate();
-    }
-}
-
-fn toggle_fullscreen(key_codes: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
-    if key_codes.just_pressed(KeyCode::F11) {
-        if !matches!(windows.primary().mode(), WindowMode::BorderlessFullscreen) {
-            windows.primary_mut().set_mode(WindowMode::BorderlessFullscreen);
-        } else {
-            windows.primary_mut().set_mode(WindowMode::Windowed);
-        }
-    }
 }
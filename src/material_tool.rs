@@ -0,0 +1,189 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::input::Input;
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{Color, IntoSystemDescriptor, Query, Res, ResMut, Resource};
+use bevy::utils::{default, HashMap};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::{PickSystem, PickTarget};
+use crate::save::SavedPbr;
+
+/// Changes the material of whatever is under the crosshair (the [PickTarget]), either cycling
+/// through the presets in [MaterialPalette] or picking a random material. This plugin can be
+/// initialized in two ways:
+///
+/// * No default bindings [MaterialToolPlugin::new]
+/// * M cycles through the presets, N randomizes [MaterialToolPlugin::default]
+///
+/// Random materials are picked from a fixed number of steps for each property, every material
+/// is only created once and shared from then on. Needs a
+/// [PickingPlugin](crate::picking::PickingPlugin).
+pub struct MaterialToolPlugin {
+    key_bindings: KeyBindingPlugin<MaterialToolControls>
+}
+
+impl MaterialToolPlugin {
+    /// Creates a new `MaterialToolPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: MaterialToolControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for MaterialToolPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(M, MaterialToolControls::Cycle)
+            .bind(N, MaterialToolControls::Randomize)
+    }
+}
+
+impl Plugin for MaterialToolPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<MaterialPalette>() {
+            app.insert_resource(MaterialPalette::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_system(material_tool.after(PickSystem));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum MaterialToolControls {
+    /// Switches to the next preset in the [MaterialPalette]
+    Cycle,
+    Randomize
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MaterialPreset {
+    pub color: Color,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Color
+}
+
+impl MaterialPreset {
+    fn material(&self) -> StandardMaterial {
+        StandardMaterial {
+            base_color: self.color,
+            metallic: self.metallic,
+            perceptual_roughness: self.roughness,
+            emissive: self.emissive,
+            ..default()
+        }
+    }
+}
+
+/// Steps of a random material, hue, metallic, roughness and whether it glows
+type RandomKey = (u8, u8, u8, bool);
+
+const HUE_STEPS: u8 = 12;
+const SURFACE_STEPS: u8 = 5;
+
+#[derive(Resource)]
+pub struct MaterialPalette {
+    pub presets: Vec<MaterialPreset>,
+    preset_handles: Vec<Handle<StandardMaterial>>,
+    random_handles: HashMap<RandomKey, Handle<StandardMaterial>>
+}
+
+impl MaterialPalette {
+    pub fn new(presets: Vec<MaterialPreset>) -> Self {
+        Self {
+            presets,
+            preset_handles: Vec::new(),
+            random_handles: HashMap::new()
+        }
+    }
+
+    fn preset(&mut self, index: usize, materials: &mut Assets<StandardMaterial>) -> Option<(Handle<StandardMaterial>, Color)> {
+        let preset = *self.presets.get(index)?;
+        // presets added after the first use get their handles lazily
+        while self.preset_handles.len() <= index {
+            let preset = self.presets[self.preset_handles.len()];
+            self.preset_handles.push(materials.add(preset.material()));
+        }
+        Some((self.preset_handles[index].clone(), preset.color))
+    }
+
+    fn random(&mut self, materials: &mut Assets<StandardMaterial>) -> (Handle<StandardMaterial>, Color) {
+        let mut rng = rand::thread_rng();
+        let key = (rng.gen_range(0..HUE_STEPS), rng.gen_range(0..SURFACE_STEPS), rng.gen_range(0..SURFACE_STEPS), rng.gen_bool(0.2));
+        let (hue, metallic, roughness, glowing) = key;
+        let color = Color::hsl(hue as f32 * 360.0 / HUE_STEPS as f32, 0.7, 0.5);
+        let handle = self.random_handles
+            .entry(key)
+            .or_insert_with(|| materials.add(MaterialPreset {
+                color,
+                metallic: metallic as f32 / (SURFACE_STEPS - 1) as f32,
+                roughness: roughness as f32 / (SURFACE_STEPS - 1) as f32,
+                emissive: if glowing { color } else { Color::BLACK }
+            }.material()))
+            .clone();
+        (handle, color)
+    }
+}
+
+impl Default for MaterialPalette {
+    fn default() -> Self {
+        let preset = |color, metallic, roughness, emissive| MaterialPreset { color, metallic, roughness, emissive };
+        Self::new(vec![
+            preset(Color::rgb(0.6, 0.6, 0.6), 0.0, 0.5, Color::BLACK),
+            preset(Color::rgb(0.8, 0.2, 0.2), 0.0, 0.4, Color::BLACK),
+            preset(Color::rgb(0.2, 0.6, 0.3), 0.0, 0.8, Color::BLACK),
+            preset(Color::rgb(0.2, 0.3, 0.8), 0.0, 0.3, Color::BLACK),
+            preset(Color::rgb(0.9, 0.75, 0.3), 1.0, 0.25, Color::BLACK),
+            preset(Color::rgb(0.8, 0.8, 0.85), 1.0, 0.1, Color::BLACK),
+            preset(Color::rgb(1.0, 0.5, 0.1), 0.0, 0.5, Color::rgb(1.0, 0.4, 0.0))
+        ])
+    }
+}
+
+fn material_tool(
+    binds: Res<Input<MaterialToolControls>>,
+    target: Res<PickTarget>,
+    mut palette: ResMut<MaterialPalette>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut targets: Query<(&mut Handle<StandardMaterial>, Option<&mut SavedPbr>)>
+) {
+    let Some(entity) = target.entity else {
+        return;
+    };
+    let Ok((mut material, saved_pbr)) = targets.get_mut(entity) else {
+        return;
+    };
+
+    let changed = if binds.just_pressed(MaterialToolControls::Cycle) {
+        let count = palette.presets.len();
+        // starts at the first preset for materials that aren't one of them
+        let next = palette.preset_handles
+            .iter()
+            .position(|handle| *handle == *material)
+            .map_or(0, |index| index + 1);
+        if count == 0 { None } else { palette.preset(next % count, &mut materials) }
+    } else if binds.just_pressed(MaterialToolControls::Randomize) {
+        Some(palette.random(&mut materials))
+    } else {
+        None
+    };
+
+    if let Some((handle, color)) = changed {
+        *material = handle;
+        // kept in sync so the new color is what gets saved
+        if let Some(mut saved_pbr) = saved_pbr {
+            saved_pbr.color = color;
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::{BuildChildren, DespawnRecursiveExt};
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Color, Commands, Entity, IntoSystemDescriptor, Mesh, MouseButton, Res, ResMut, Resource, SpatialBundle, Transform};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::debug_draw::{line_mesh, unlit};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::{PickSystem, PickTarget};
+
+/// A measuring mode, while active the first click on something marks a point, the second marks
+/// another and measures from the first one to it. The [HudPlugin](crate::hud::HudPlugin) shows the
+/// latest [Measurement], with the distance, the angle of the line above or below the horizon and
+/// the angle between the surfaces at both ends. Measurements stay drawn as markers with a line
+/// between them (using the [DebugDrawPlugin](crate::debug_draw::DebugDrawPlugin)'s lines) until
+/// cleared. This plugin can be initialized in two ways:
+///
+/// * No default bindings [MeasurePlugin::new]
+/// * R toggles measuring, the left mouse button marks points and C clears the measurements
+///  [MeasurePlugin::default]
+///
+/// Needs a [PickingPlugin](crate::picking::PickingPlugin).
+pub struct MeasurePlugin {
+    key_bindings: KeyBindingPlugin<MeasureControls>
+}
+
+impl MeasurePlugin {
+    /// Creates a new `MeasurePlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: MeasureControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for MeasurePlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(R, MeasureControls::Toggle)
+            .bind(MouseButton::Left, MeasureControls::Mark)
+            .bind(C, MeasureControls::Clear)
+    }
+}
+
+impl Plugin for MeasurePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<MeasureConfig>() {
+            app.insert_resource(MeasureConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Measurements>()
+            .add_system(measure.after(PickSystem));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum MeasureControls {
+    /// Enters or leaves measuring mode, leaving drops a half finished measurement
+    Toggle,
+    /// Marks the [PickTarget] as the next end of a measurement
+    Mark,
+    /// Removes every measurement
+    Clear
+}
+
+#[derive(Resource, Clone)]
+pub struct MeasureConfig {
+    pub marker_radius: f32,
+    pub color: Color
+}
+
+impl Default for MeasureConfig {
+    fn default() -> Self {
+        Self {
+            marker_radius: 0.05,
+            color: Color::rgb(1.0, 0.85, 0.1)
+        }
+    }
+}
+
+/// A point on a surface
+#[derive(Copy, Clone, Debug)]
+pub struct MeasurePoint {
+    pub point: Vec3,
+    pub normal: Vec3
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Measurement {
+    pub from: MeasurePoint,
+    pub to: MeasurePoint,
+    /// The markers and the line
+    gizmo: Entity
+}
+
+impl Measurement {
+    pub fn distance(&self) -> f32 {
+        self.from.point.distance(self.to.point)
+    }
+
+    /// Angle of the line above the horizon, negative below it, in degrees
+    pub fn elevation(&self) -> f32 {
+        let offset = self.to.point - self.from.point;
+        let horizontal = Vec3::new(offset.x, 0.0, offset.z).length();
+        offset.y.atan2(horizontal).to_degrees()
+    }
+
+    /// Angle between the surfaces at both ends, in degrees
+    pub fn surface_angle(&self) -> f32 {
+        self.from.normal.angle_between(self.to.normal).to_degrees()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Measurements {
+    pub active: bool,
+    /// The first end of the measurement being made, with its marker
+    pending: Option<(MeasurePoint, Entity)>,
+    done: Vec<Measurement>,
+    assets: Option<(Handle<Mesh>, Handle<StandardMaterial>)>
+}
+
+impl Measurements {
+    /// Whether the first end of a measurement is marked, waiting for the second
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn last(&self) -> Option<&Measurement> {
+        self.done.last()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Measurement> {
+        self.done.iter()
+    }
+}
+
+fn measure(
+    mut commands: Commands,
+    binds: Res<Input<MeasureControls>>,
+    config: Res<MeasureConfig>,
+    target: Res<PickTarget>,
+    mut measurements: ResMut<Measurements>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    let measurements = &mut *measurements;
+    if binds.just_pressed(MeasureControls::Clear) {
+        for measurement in measurements.done.drain(..) {
+            commands.entity(measurement.gizmo).despawn_recursive();
+        }
+    }
+    if binds.just_pressed(MeasureControls::Toggle) {
+        measurements.active = !measurements.active;
+    }
+    if !measurements.active || binds.just_pressed(MeasureControls::Clear) {
+        if let Some((_, marker)) = measurements.pending.take() {
+            commands.entity(marker).despawn_recursive();
+        }
+        return;
+    }
+    if !binds.just_pressed(MeasureControls::Mark) || target.entity.is_none() {
+        return;
+    }
+
+    let (marker_mesh, material) = measurements.assets.get_or_insert_with(|| (
+        meshes.add(shape::UVSphere { radius: config.marker_radius, ..default() }.into()),
+        materials.add(unlit(config.color))
+    )).clone();
+    let point = MeasurePoint {
+        point: target.point,
+        normal: target.normal
+    };
+    let marker = |commands: &mut Commands, at: Vec3| commands.spawn((
+        PbrBundle {
+            mesh: marker_mesh.clone(),
+            material: material.clone(),
+            transform: Transform::from_translation(at),
+            ..default()
+        },
+        NotShadowCaster
+    )).id();
+
+    match measurements.pending.take() {
+        None => {
+            let entity = marker(&mut commands, point.point);
+            measurements.pending = Some((point, entity));
+        }
+        Some((from, from_marker)) => {
+            let to_marker = marker(&mut commands, point.point);
+            let line = commands.spawn((
+                PbrBundle {
+                    mesh: meshes.add(line_mesh([(from.point, point.point)])),
+                    material: material.clone(),
+                    ..default()
+                },
+                NotShadowCaster
+            )).id();
+            let gizmo = commands.spawn(SpatialBundle::default())
+                .push_children(&[from_marker, to_marker, line])
+                .id();
+            measurements.done.push(Measurement { from, to: point, gizmo });
+        }
+    }
+}
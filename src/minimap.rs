@@ -0,0 +1,365 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::core_pipeline::core_3d::{Camera3d, Camera3dBundle};
+use bevy::hierarchy::{BuildChildren, DespawnRecursiveExt};
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::prelude::{Camera, Color, Commands, Component, Entity, GlobalTransform, Image, ImageBundle, IntoSystemDescriptor, NodeBundle, OrthographicProjection, Projection, Query, Res, ResMut, Resource, Style, Transform, Visibility, With, Without, World};
+use bevy::render::camera::{RenderTarget, ScalingMode};
+use bevy::render::render_resource::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+use bevy::ui::{PositionType, Size, UiCameraConfig, UiImage, UiRect, Val};
+use bevy::utils::{default, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::free_control::ActiveControl;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::PickTarget;
+use crate::selection::Selection;
+
+/// A top-down overview in the bottom right corner of the window, rendered by an orthographic
+/// camera looking straight down on the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T], with north (-z)
+/// up. The controlled entity and everything with a [MinimapIcon] are drawn over it as dots. This
+/// plugin can be initialized in two ways:
+///
+/// * No default bindings [MinimapPlugin::new]
+/// * Numpad 0 toggles the minimap, numpad plus and minus zoom in and out [MinimapPlugin::default]
+///
+/// Vehicles get an icon when they're spawned, the `minimap_icon` console command gives one to (or
+/// takes it from) the [Selection], or the [PickTarget] without one.
+pub struct MinimapPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<MinimapControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> MinimapPlugin<T> {
+    /// Creates a new `MinimapPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("minimap"),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: MinimapControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for MinimapPlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Numpad0, MinimapControls::Toggle)
+            .bind(NumpadAdd, MinimapControls::ZoomIn)
+            .bind(NumpadSubtract, MinimapControls::ZoomOut)
+    }
+}
+
+impl <T: Component> Plugin for MinimapPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<MinimapConfig>() {
+            app.insert_resource(MinimapConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_startup_system(spawn_minimap)
+            .add_system(minimap_controls)
+            .add_system(follow_minimap::<T>.after(minimap_controls))
+            .add_system(update_minimap_icons::<T>.after(follow_minimap::<T>))
+            .add_console_command("minimap_icon", "shows the selection, or what's under the crosshair, on the minimap or stops showing it", |world, _| {
+                let (added, removed) = toggle_minimap_icons(world);
+                console_print(world, format!("added {} minimap icons, removed {}", added, removed));
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum MinimapControls {
+    Toggle,
+    /// Shows a smaller area, by [MinimapConfig::zoom_step]
+    ZoomIn,
+    ZoomOut
+}
+
+#[derive(Resource, Clone)]
+pub struct MinimapConfig {
+    /// Width and height in pixels, both of the texture and on screen
+    pub size: u32,
+    /// Half the width of the area shown on startup, in world units
+    pub extent: f32,
+    pub min_extent: f32,
+    pub max_extent: f32,
+    /// Zooming multiplies or divides the extent by this
+    pub zoom_step: f32,
+    /// How far above the controlled entity the camera is, it sees everything below that
+    pub height: f32,
+    pub background: Color,
+    /// Size of the dots in pixels
+    pub icon_size: f32,
+    /// Color of the controlled entity's dot
+    pub camera_color: Color
+}
+
+impl Default for MinimapConfig {
+    fn default() -> Self {
+        Self {
+            size: 200,
+            extent: 40.0,
+            min_extent: 5.0,
+            max_extent: 500.0,
+            zoom_step: 1.5,
+            height: 200.0,
+            background: Color::rgb(0.1, 0.12, 0.15),
+            icon_size: 6.0,
+            camera_color: Color::WHITE
+        }
+    }
+}
+
+/// Draws the entity as a dot on the minimap
+#[derive(Component, Copy, Clone, Debug)]
+pub struct MinimapIcon {
+    pub color: Color
+}
+
+impl Default for MinimapIcon {
+    fn default() -> Self {
+        Self {
+            color: Color::rgb(1.0, 0.8, 0.2)
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Minimap {
+    pub visible: bool,
+    /// Half the width of the area shown, in world units
+    pub extent: f32,
+    camera: Entity,
+    root: Entity,
+    /// The dot drawn for each entity, the controlled one included
+    icons: HashMap<Entity, Entity>
+}
+
+#[derive(Component)]
+pub struct MinimapCamera;
+
+fn spawn_minimap(mut commands: Commands, config: Res<MinimapConfig>, mut images: ResMut<Assets<Image>>) {
+    let size = Extent3d {
+        width: config.size.max(1),
+        height: config.size.max(1),
+        depth_or_array_layers: 1
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("minimap_image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    let camera = commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(image.clone()),
+                // before the main camera, so the texture is ready when the UI is drawn
+                priority: -1,
+                ..default()
+            },
+            camera_3d: Camera3d {
+                clear_color: ClearColorConfig::Custom(config.background),
+                ..default()
+            },
+            projection: Projection::Orthographic(OrthographicProjection {
+                scale: config.extent,
+                scaling_mode: ScalingMode::FixedVertical(2.0),
+                far: config.height * 2.0,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, config.height, 0.0).looking_at(Vec3::ZERO, Vec3::NEG_Z),
+            ..default()
+        },
+        // the UI would be drawn onto the minimap as well otherwise
+        UiCameraConfig { show_ui: false },
+        MinimapCamera
+    )).id();
+
+    let pixels = config.size as f32;
+    let root = commands.spawn(ImageBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                right: Val::Px(8.0),
+                bottom: Val::Px(8.0),
+                ..default()
+            },
+            size: Size::new(Val::Px(pixels), Val::Px(pixels)),
+            ..default()
+        },
+        image: UiImage(image),
+        ..default()
+    }).id();
+
+    commands.insert_resource(Minimap {
+        visible: true,
+        extent: config.extent,
+        camera,
+        root,
+        icons: HashMap::new()
+    });
+}
+
+fn minimap_controls(binds: Res<Input<MinimapControls>>, config: Res<MinimapConfig>, mut minimap: ResMut<Minimap>) {
+    if binds.just_pressed(MinimapControls::Toggle) {
+        minimap.visible = !minimap.visible;
+    }
+    let step = config.zoom_step.max(1.0);
+    if binds.just_pressed(MinimapControls::ZoomIn) {
+        minimap.extent /= step;
+    }
+    if binds.just_pressed(MinimapControls::ZoomOut) {
+        minimap.extent *= step;
+    }
+    let extent = minimap.extent.clamp(config.min_extent, config.max_extent.max(config.min_extent));
+    if minimap.extent != extent {
+        minimap.extent = extent;
+    }
+}
+
+fn follow_minimap<T: Component>(
+    config: Res<MinimapConfig>,
+    minimap: Res<Minimap>,
+    active: Res<ActiveControl<T>>,
+    controlled: Query<&GlobalTransform, With<T>>,
+    mut cameras: Query<(&mut Camera, &mut Transform, &mut Projection), (With<MinimapCamera>, Without<T>)>,
+    mut roots: Query<&mut Visibility>
+) {
+    let Ok((mut camera, mut transform, mut projection)) = cameras.get_mut(minimap.camera) else {
+        return;
+    };
+    if camera.is_active != minimap.visible {
+        camera.is_active = minimap.visible;
+    }
+    if let Ok(mut visibility) = roots.get_mut(minimap.root) {
+        if visibility.is_visible != minimap.visible {
+            visibility.is_visible = minimap.visible;
+        }
+    }
+    if !minimap.visible {
+        return;
+    }
+    if let Projection::Orthographic(projection) = &mut *projection {
+        if projection.scale != minimap.extent {
+            projection.scale = minimap.extent;
+        }
+    }
+    let Some(center) = active.entity.and_then(|entity| controlled.get(entity).ok()).map(|transform| transform.translation()) else {
+        return;
+    };
+    *transform = Transform::from_translation(center + Vec3::Y * config.height).looking_at(center, Vec3::NEG_Z);
+}
+
+/// Adds dots for new icons, moves every dot to where its entity is relative to the middle of the
+/// minimap, and removes dots of entities that are gone or lost their icon
+fn update_minimap_icons<T: Component>(
+    mut commands: Commands,
+    config: Res<MinimapConfig>,
+    mut minimap: ResMut<Minimap>,
+    active: Res<ActiveControl<T>>,
+    controlled: Query<&GlobalTransform, With<T>>,
+    icons: Query<(Entity, &MinimapIcon, &GlobalTransform)>,
+    mut dots: Query<(&mut Style, &mut Visibility)>
+) {
+    let center = active.entity.and_then(|entity| controlled.get(entity).ok().map(|transform| (entity, transform.translation())));
+    let Some((camera, center)) = center.filter(|_| minimap.visible) else {
+        return;
+    };
+    let camera_icon = MinimapIcon { color: config.camera_color };
+    let shown = icons.iter()
+        .map(|(entity, icon, transform)| (entity, *icon, transform.translation()))
+        .filter(|(entity, _, _)| *entity != camera)
+        .chain([(camera, camera_icon, center)])
+        .collect::<Vec<_>>();
+
+    let Minimap { extent, root, icons: dot_entities, .. } = &mut *minimap;
+    let pixels = config.size as f32;
+    let half = config.icon_size / 2.0;
+    for (entity, icon, position) in &shown {
+        // +x is right and +z is down, going by how the camera looks down
+        let offset = (*position - center) / (*extent * 2.0);
+        let (u, v) = (offset.x + 0.5, offset.z + 0.5);
+        let inside = (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v);
+        let position = UiRect {
+            left: Val::Px(u * pixels - half),
+            top: Val::Px(v * pixels - half),
+            ..default()
+        };
+        match dot_entities.get(entity).copied() {
+            Some(dot) => {
+                if let Ok((mut style, mut visibility)) = dots.get_mut(dot) {
+                    style.position = position;
+                    visibility.is_visible = inside;
+                }
+            }
+            None => {
+                let dot = commands.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position,
+                        size: Size::new(Val::Px(config.icon_size), Val::Px(config.icon_size)),
+                        ..default()
+                    },
+                    background_color: icon.color.into(),
+                    visibility: Visibility { is_visible: inside },
+                    ..default()
+                }).id();
+                commands.entity(*root).add_child(dot);
+                dot_entities.insert(*entity, dot);
+            }
+        }
+    }
+    dot_entities.retain(|entity, dot| {
+        let keep = shown.iter().any(|(shown, _, _)| shown == entity);
+        if !keep {
+            commands.entity(*dot).despawn_recursive();
+        }
+        keep
+    });
+}
+
+/// Toggles a [MinimapIcon] on the [Selection], or the [PickTarget] without one, returning how
+/// many were added and removed
+pub fn toggle_minimap_icons(world: &mut World) -> (usize, usize) {
+    let selected = world.get_resource::<Selection>()
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| selection.iter().collect::<Vec<_>>());
+    let entities = selected
+        .or_else(|| world.get_resource::<PickTarget>().and_then(|target| target.entity).map(|entity| vec![entity]))
+        .unwrap_or_default();
+    let (mut added, mut removed) = (0, 0);
+    for entity in entities {
+        let Some(mut entity) = world.get_entity_mut(entity) else {
+            continue;
+        };
+        if entity.contains::<MinimapIcon>() {
+            entity.remove::<MinimapIcon>();
+            removed += 1;
+        } else {
+            entity.insert(MinimapIcon::default());
+            added += 1;
+        }
+    }
+    (added, removed)
+}
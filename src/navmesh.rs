@@ -0,0 +1,385 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::log::info;
+use bevy::math::{IVec2, Quat, Vec2, Vec3};
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{Added, Color, Component, Entity, EventReader, IntoSystemDescriptor, Mesh, Query, ResMut, Resource, With, World};
+use bevy::utils::{default, HashMap};
+use bevy_rapier3d::plugin::RapierConfiguration;
+use bevy_rapier3d::prelude::{Collider, QueryFilter, RapierContext};
+use rand::Rng;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::debug_draw::{line_mesh, unlit, DebugDrawn};
+use crate::environment::EnvironmentEntity;
+use crate::floating_origin::{add_origin_shifted, OriginShifted};
+use crate::terrain::TerrainChunk;
+
+/// Bakes a [NavMesh] of where an agent can walk within [NavMeshConfig::extent] of
+/// [NavMeshConfig::center], from the fixed colliders there (the environment and terrain). It's a
+/// grid of cells at [NavMeshConfig::cell_size], walkable where the ground isn't too steep and an
+/// agent fits standing on it, connected to their neighbours when the step between them is small
+/// enough. [NavMesh::find_path] finds paths across it, which the [AiPlugin](crate::ai::AiPlugin)'s
+/// agents follow.
+///
+/// It's rebaked whenever environment objects or terrain chunks are spawned (once physics has
+/// caught up with them), and by the `navmesh_bake` console command. The walkable cells are shown
+/// by the [DebugDrawPlugin](crate::debug_draw::DebugDrawPlugin).
+pub struct NavMeshPlugin;
+
+impl Plugin for NavMeshPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<NavMeshConfig>() {
+            app.insert_resource(NavMeshConfig::default());
+        }
+        add_origin_shifted(app);
+        app
+            .init_resource::<NavMeshBake>()
+            .add_system(shift_navmesh)
+            .add_system(schedule_bake)
+            .add_system(bake_when_ready.after(schedule_bake))
+            .add_console_command("navmesh_bake", "bakes the navmesh again", |world, _| {
+                bake(world);
+                let walkable = world.get_resource::<NavMesh>().map_or(0, |navmesh| navmesh.walkable.len());
+                console_print(world, format!("baked {} walkable cells", walkable));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct NavMeshConfig {
+    pub center: Vec3,
+    /// Half the width and depth of the baked area
+    pub extent: f32,
+    pub cell_size: f32,
+    /// Steepest walkable ground, in radians
+    pub max_slope: f32,
+    /// Highest step up or down between neighbouring cells
+    pub step_height: f32,
+    pub agent_height: f32,
+    pub agent_radius: f32,
+    /// How far above [NavMeshConfig::center] the ground is looked for from
+    pub ceiling: f32
+}
+
+impl Default for NavMeshConfig {
+    fn default() -> Self {
+        Self {
+            center: Vec3::ZERO,
+            extent: 40.0,
+            cell_size: 1.0,
+            max_slope: 40f32.to_radians(),
+            step_height: 0.5,
+            agent_height: 1.6,
+            agent_radius: 0.3,
+            ceiling: 50.0
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct NavMesh {
+    /// Corner of the grid at the lowest x and z
+    pub origin: Vec2,
+    pub cell_size: f32,
+    pub step_height: f32,
+    pub size: IVec2,
+    /// Ground height of each cell, `None` where it isn't walkable, row major along x
+    pub heights: Vec<Option<f32>>,
+    /// Indices of the walkable cells
+    pub walkable: Vec<usize>
+}
+
+impl NavMesh {
+    /// The cell containing `point`, if it's inside the grid
+    pub fn cell_at(&self, point: Vec3) -> Option<IVec2> {
+        let cell = ((Vec2::new(point.x, point.z) - self.origin) / self.cell_size).floor().as_ivec2();
+        self.contains(cell).then_some(cell)
+    }
+
+    fn contains(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.size.x && cell.y < self.size.y
+    }
+
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.x + cell.y * self.size.x) as usize
+    }
+
+    /// Ground height of a walkable cell
+    pub fn height(&self, cell: IVec2) -> Option<f32> {
+        if self.contains(cell) { self.heights[self.index(cell)] } else { None }
+    }
+
+    /// Center of a cell, on the ground
+    pub fn point(&self, cell: IVec2) -> Option<Vec3> {
+        let height = self.height(cell)?;
+        let center = self.origin + (cell.as_vec2() + 0.5) * self.cell_size;
+        Some(Vec3::new(center.x, height, center.y))
+    }
+
+    /// Whether an agent can walk straight from `from` to the neighbouring cell `to`
+    fn connected(&self, from: IVec2, to: IVec2) -> bool {
+        let (Some(a), Some(b)) = (self.height(from), self.height(to)) else {
+            return false;
+        };
+        if (a - b).abs() > self.step_height {
+            return false;
+        }
+        // diagonals can't cut corners
+        let step = to - from;
+        step.x == 0 || step.y == 0
+            || (self.height(from + IVec2::new(step.x, 0)).is_some() && self.height(from + IVec2::new(0, step.y)).is_some())
+    }
+
+    /// A walkable point picked at random
+    pub fn random_point(&self, rng: &mut impl Rng) -> Option<Vec3> {
+        let index = *self.walkable.get(rng.gen_range(0..self.walkable.len().max(1)))?;
+        let cell = IVec2::new(index as i32 % self.size.x, index as i32 / self.size.x);
+        self.point(cell)
+    }
+
+    /// Points on the ground to walk through from `from` to `to`, the first being the center of the
+    /// cell after the one `from` is in and the last being the center of the cell `to` is in.
+    /// `None` when either is off the mesh or there's no way between them
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.cell_at(from).filter(|cell| self.height(*cell).is_some())?;
+        let goal = self.cell_at(to).filter(|cell| self.height(*cell).is_some())?;
+
+        // a*, over cells
+        let estimate = |cell: IVec2| (cell - goal).as_vec2().length();
+        let mut open = BinaryHeap::new();
+        let mut came_from = HashMap::<IVec2, IVec2>::default();
+        let mut costs = HashMap::<IVec2, f32>::default();
+        open.push(OpenCell { cell: start, priority: estimate(start) });
+        costs.insert(start, 0.0);
+        while let Some(OpenCell { cell, .. }) = open.pop() {
+            if cell == goal {
+                break;
+            }
+            let cost = costs[&cell];
+            for x in -1..=1 {
+                for z in -1..=1 {
+                    let next = cell + IVec2::new(x, z);
+                    if next == cell || !self.connected(cell, next) {
+                        continue;
+                    }
+                    let next_cost = cost + IVec2::new(x, z).as_vec2().length();
+                    if costs.get(&next).map_or(true, |known| next_cost < *known) {
+                        costs.insert(next, next_cost);
+                        came_from.insert(next, cell);
+                        open.push(OpenCell { cell: next, priority: next_cost + estimate(next) });
+                    }
+                }
+            }
+        }
+        if !costs.contains_key(&goal) {
+            return None;
+        }
+
+        let mut cells = vec![goal];
+        while let Some(previous) = came_from.get(cells.last().unwrap()) {
+            cells.push(*previous);
+        }
+        cells.reverse();
+
+        // skips every cell that can be seen past, so paths don't zigzag along the grid
+        let mut path = Vec::new();
+        let mut current = 0;
+        while current + 1 < cells.len() {
+            let mut furthest = current + 1;
+            for candidate in (current + 2..cells.len()).rev() {
+                if self.straight_line(cells[current], cells[candidate]) {
+                    furthest = candidate;
+                    break;
+                }
+            }
+            path.push(self.point(cells[furthest])?);
+            current = furthest;
+        }
+        Some(path)
+    }
+
+    /// Whether walking in a straight line between the centers of two cells stays on the mesh
+    fn straight_line(&self, from: IVec2, to: IVec2) -> bool {
+        let distance = (to - from).as_vec2();
+        let steps = (distance.length() * 2.0).ceil() as i32;
+        let mut previous = from;
+        for step in 1..=steps {
+            let cell = (from.as_vec2() + 0.5 + distance * step as f32 / steps as f32).floor().as_ivec2();
+            if cell != previous && !self.connected(previous, cell) {
+                return false;
+            }
+            previous = cell;
+        }
+        true
+    }
+}
+
+/// Cells to visit in [NavMesh::find_path], cheapest first
+#[derive(PartialEq)]
+struct OpenCell {
+    cell: IVec2,
+    priority: f32
+}
+
+impl Eq for OpenCell {}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, BinaryHeap pops the largest
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+/// Frames left until a pending bake, [RapierContext]'s queries only see new colliders after a
+/// physics step
+#[derive(Resource, Default)]
+struct NavMeshBake {
+    countdown: Option<u32>
+}
+
+/// The debug drawing of the walkable cells
+#[derive(Component)]
+struct NavMeshDrawing;
+
+/// Moves the baked cells and the baked area along with the world when the origin moves, so it
+/// doesn't need baking again
+fn shift_navmesh(mut shifted: EventReader<OriginShifted>, mut config: ResMut<NavMeshConfig>, navmesh: Option<ResMut<NavMesh>>) {
+    let shift = shifted.iter().map(|shifted| shifted.shift).sum::<Vec3>();
+    if shift == Vec3::ZERO {
+        return;
+    }
+    config.center += shift;
+    if let Some(mut navmesh) = navmesh {
+        navmesh.origin += Vec2::new(shift.x, shift.z);
+        for height in navmesh.heights.iter_mut().flatten() {
+            *height += shift.y;
+        }
+    }
+}
+
+fn schedule_bake(
+    mut pending: ResMut<NavMeshBake>,
+    environment: Query<(), Added<EnvironmentEntity>>,
+    terrain: Query<(), Added<TerrainChunk>>
+) {
+    if !environment.is_empty() || !terrain.is_empty() {
+        pending.countdown = Some(2);
+    }
+}
+
+fn bake_when_ready(world: &mut World) {
+    let stepping = world
+        .get_resource::<RapierConfiguration>()
+        .map_or(true, |config| config.physics_pipeline_active);
+    let mut pending = world.resource_mut::<NavMeshBake>();
+    match pending.countdown {
+        Some(0) => pending.countdown = None,
+        Some(countdown) if stepping => {
+            pending.countdown = Some(countdown - 1);
+            return;
+        }
+        _ => return
+    }
+    bake(world);
+}
+
+/// Bakes the [NavMesh] from the colliders currently in the world, replacing the last one
+pub fn bake(world: &mut World) {
+    let config = world.resource::<NavMeshConfig>().clone();
+    let cells = (config.extent * 2.0 / config.cell_size).ceil().max(1.0) as i32;
+    let origin = Vec2::new(config.center.x, config.center.z) - config.extent;
+    let mut navmesh = NavMesh {
+        origin,
+        cell_size: config.cell_size,
+        step_height: config.step_height,
+        size: IVec2::splat(cells),
+        heights: Vec::with_capacity((cells * cells) as usize),
+        walkable: Vec::new()
+    };
+
+    {
+        let rapier_context = world.resource::<RapierContext>();
+        let filter = QueryFilter::only_fixed().exclude_sensors();
+        let min_normal = config.max_slope.cos();
+        let half_height = (config.agent_height / 2.0 - config.agent_radius).max(0.0);
+        let agent = Collider::capsule_y(half_height, config.agent_radius);
+        for z in 0..cells {
+            for x in 0..cells {
+                let center = origin + (Vec2::new(x as f32, z as f32) + 0.5) * config.cell_size;
+                let from = Vec3::new(center.x, config.center.y + config.ceiling, center.y);
+                let ground = rapier_context
+                    .cast_ray_and_get_normal(from, -Vec3::Y, config.ceiling * 2.0, true, filter)
+                    .filter(|(_, hit)| hit.normal.y >= min_normal)
+                    .map(|(_, hit)| hit.point)
+                    // lifted a bit, so the ground itself doesn't count as something in the way
+                    .filter(|point| {
+                        let standing = *point + Vec3::Y * (config.agent_height / 2.0 + config.step_height / 2.0);
+                        rapier_context.intersection_with_shape(standing, Quat::IDENTITY, &agent, filter).is_none()
+                    });
+                if ground.is_some() {
+                    navmesh.walkable.push(navmesh.heights.len());
+                }
+                navmesh.heights.push(ground.map(|point| point.y));
+            }
+        }
+    }
+
+    let drawn = world
+        .query_filtered::<Entity, With<NavMeshDrawing>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in drawn {
+        world.entity_mut(entity).despawn_recursive();
+    }
+    let lines = drawing_lines(&navmesh);
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(line_mesh(lines));
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(unlit(Color::rgba(0.2, 0.9, 0.4, 0.6)));
+    world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            ..default()
+        },
+        NotShadowCaster,
+        NavMeshDrawing,
+        DebugDrawn
+    ));
+
+    info!("baked a navmesh with {} walkable cells", navmesh.walkable.len());
+    world.insert_resource(navmesh);
+}
+
+/// An outline of each walkable cell, slightly inset and above the ground so neighbours are
+/// told apart and the lines aren't hidden in the ground
+fn drawing_lines(navmesh: &NavMesh) -> Vec<(Vec3, Vec3)> {
+    let inset = navmesh.cell_size * 0.1;
+    let mut lines = Vec::with_capacity(navmesh.walkable.len() * 4);
+    for index in &navmesh.walkable {
+        let cell = IVec2::new(*index as i32 % navmesh.size.x, *index as i32 / navmesh.size.x);
+        let Some(height) = navmesh.height(cell) else {
+            continue;
+        };
+        let min = navmesh.origin + cell.as_vec2() * navmesh.cell_size + inset;
+        let max = min + navmesh.cell_size - inset * 2.0;
+        let y = height + 0.05;
+        let corners = [
+            Vec3::new(min.x, y, min.y),
+            Vec3::new(max.x, y, min.y),
+            Vec3::new(max.x, y, max.y),
+            Vec3::new(min.x, y, max.y)
+        ];
+        for i in 0..4 {
+            lines.push((corners[i], corners[(i + 1) % 4]));
+        }
+    }
+    lines
+}
@@ -0,0 +1,255 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{AssetServer, Assets, Handle};
+use bevy::hierarchy::BuildChildren;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::math::EulerRot;
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{ButtonBundle, Changed, Color, Commands, Component, Entity, IntoSystemDescriptor, NodeBundle, Query, Res, ResMut, Resource, Style, TextBundle, Transform, Visibility, With};
+use bevy::text::{Text, TextStyle};
+use bevy::ui::{FlexDirection, Interaction, PositionType, Size, UiRect, Val};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{ImpulseJoint, RigidBody, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::edit_history::EditCommands;
+use crate::hud::HudConfig;
+use crate::joints::ToolJoint;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::{PickSystem, PickTarget};
+use crate::selection::Selection;
+use crate::tags::Tags;
+
+/// A panel showing the [Transform], rapier body, velocity, joint, material and [Tags] of an entity,
+/// with buttons to freeze the body in place or despawn the entity. The entity is the [PickTarget]
+/// at the time [InspectorControls::Inspect] was pressed, so this needs a
+/// [PickingPlugin](crate::picking::PickingPlugin). This plugin can be initialized in two ways:
+///
+/// * No default bindings [ObjectInspectorPlugin::new]
+/// * I inspects whatever is under the crosshair, or closes the panel when nothing is
+///  [ObjectInspectorPlugin::default]
+///
+/// The buttons need a cursor to click, see [UiModePlugin](crate::ui_mode::UiModePlugin). When the
+/// inspected entity is part of the [Selection] the panel says so, and despawning takes the whole
+/// selection with it.
+pub struct ObjectInspectorPlugin {
+    key_bindings: KeyBindingPlugin<InspectorControls>
+}
+
+impl ObjectInspectorPlugin {
+    /// Creates a new `ObjectInspectorPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: InspectorControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for ObjectInspectorPlugin {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::KeyCode::I, InspectorControls::Inspect)
+    }
+}
+
+impl Plugin for ObjectInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Inspected>()
+            .add_startup_system(spawn_inspector)
+            .add_system(inspector_controls.after(PickSystem))
+            .add_system(inspector_buttons.after(inspector_controls))
+            .add_system(update_inspector.after(inspector_buttons));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum InspectorControls {
+    /// Inspects the [PickTarget], or stops inspecting if there is none
+    Inspect
+}
+
+/// The entity shown in the inspector panel
+#[derive(Resource, Default)]
+pub struct Inspected(pub Option<Entity>);
+
+/// A body frozen by the inspector (or the `freeze` console command), made [RigidBody::Fixed] and
+/// remembering what it was before
+#[derive(Component)]
+pub struct Frozen(pub RigidBody);
+
+#[derive(Component)]
+struct InspectorPanel;
+
+#[derive(Component)]
+struct InspectorText;
+
+#[derive(Component, Copy, Clone)]
+enum InspectorButton {
+    Freeze,
+    Despawn
+}
+
+fn spawn_inspector(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    let text_style = TextStyle {
+        font: asset_server.load(hud_config.font_path.as_str()),
+        font_size: hud_config.font_size,
+        color: Color::WHITE
+    };
+
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                right: Val::Px(8.0),
+                top: Val::Px(8.0),
+                ..default()
+            },
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(InspectorPanel)
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section("", text_style.clone()))
+                .insert(InspectorText);
+
+            for (button, label) in [(InspectorButton::Freeze, "freeze / unfreeze"), (InspectorButton::Despawn, "despawn")] {
+                panel.spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Auto, Val::Px(hud_config.font_size + 8.0)),
+                        margin: UiRect::top(Val::Px(4.0)),
+                        padding: UiRect::horizontal(Val::Px(4.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.3, 0.3, 0.3, 0.8).into(),
+                    ..default()
+                })
+                    .insert(button)
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(label, text_style.clone()));
+                    });
+            }
+        });
+}
+
+fn inspector_controls(binds: Res<Input<InspectorControls>>, target: Res<PickTarget>, mut inspected: ResMut<Inspected>) {
+    if binds.just_pressed(InspectorControls::Inspect) {
+        inspected.0 = target.entity;
+    }
+}
+
+fn inspector_buttons(
+    mut commands: Commands,
+    mut inspected: ResMut<Inspected>,
+    selection: Option<Res<Selection>>,
+    buttons: Query<(&Interaction, &InspectorButton), Changed<Interaction>>,
+    bodies: Query<(Option<&RigidBody>, Option<&Frozen>)>
+) {
+    let Some(entity) = inspected.0 else {
+        return;
+    };
+
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            InspectorButton::Freeze => match bodies.get(entity) {
+                Ok((_, Some(Frozen(body)))) => {
+                    commands.entity(entity).insert(*body).remove::<Frozen>();
+                }
+                Ok((Some(body), None)) if *body != RigidBody::Fixed => {
+                    commands.entity(entity).insert((RigidBody::Fixed, Frozen(*body)));
+                }
+                _ => info!("only moving bodies can be frozen")
+            },
+            InspectorButton::Despawn => {
+                match selection.filter(|selection| selection.contains(entity)) {
+                    Some(selection) => commands.despawn_recorded_group(selection.iter().collect()),
+                    None => commands.despawn_recorded(entity)
+                }
+                inspected.0 = None;
+                return;
+            }
+        }
+    }
+}
+
+fn update_inspector(
+    mut inspected: ResMut<Inspected>,
+    materials: Res<Assets<StandardMaterial>>,
+    selection: Option<Res<Selection>>,
+    entities: Query<(&Transform, Option<&RigidBody>, Option<&Velocity>, Option<&Handle<StandardMaterial>>, Option<&Frozen>, Option<&Tags>)>,
+    joints: Query<(&ImpulseJoint, Option<&ToolJoint>)>,
+    mut panels: Query<&mut Visibility, With<InspectorPanel>>,
+    mut texts: Query<&mut Text, With<InspectorText>>
+) {
+    let shown = inspected.0.and_then(|entity| entities.get(entity).ok().map(|components| (entity, components)));
+    // the entity could've been despawned by anything else as well
+    if shown.is_none() && inspected.0.is_some() {
+        inspected.0 = None;
+    }
+    for mut visibility in &mut panels {
+        if visibility.is_visible != shown.is_some() {
+            visibility.is_visible = shown.is_some();
+        }
+    }
+    let Some((entity, (transform, body, velocity, material, frozen, tags))) = shown else {
+        return;
+    };
+
+    let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
+    let mut value = format!(
+        "entity {:?}\nposition: {:.2} {:.2} {:.2}\nrotation: {:.0} {:.0} {:.0}\nscale: {:.2} {:.2} {:.2}",
+        entity,
+        transform.translation.x, transform.translation.y, transform.translation.z,
+        yaw.to_degrees(), pitch.to_degrees(), roll.to_degrees(),
+        transform.scale.x, transform.scale.y, transform.scale.z
+    );
+    match (body, frozen) {
+        (_, Some(Frozen(body))) => value += &format!("\nbody: {:?} (frozen)", body),
+        (Some(body), None) => value += &format!("\nbody: {:?}", body),
+        (None, None) => value += "\nbody: none"
+    }
+    if let Some(velocity) = velocity {
+        value += &format!(
+            "\nvelocity: {:.2} {:.2} {:.2}\nangular: {:.2} {:.2} {:.2}",
+            velocity.linvel.x, velocity.linvel.y, velocity.linvel.z,
+            velocity.angvel.x, velocity.angvel.y, velocity.angvel.z
+        );
+    }
+    if let Ok((joint, kind)) = joints.get(entity) {
+        let kind = kind.map_or("custom", |ToolJoint(kind)| kind.name());
+        value += &format!("\njoint: {} to {:?}", kind, joint.parent);
+    }
+    if let Some(material) = material.and_then(|material| materials.get(material)) {
+        let [r, g, b, a] = material.base_color.as_rgba_f32();
+        value += &format!(
+            "\ncolor: {:.2} {:.2} {:.2} {:.2}\nmetallic: {:.2} roughness: {:.2}",
+            r, g, b, a, material.metallic, material.perceptual_roughness
+        );
+    }
+    if let Some(tags) = tags.filter(|tags| !tags.0.is_empty()) {
+        value += &format!("\ntags: {}", tags.0.join(" "));
+    }
+
+    if let Some(selection) = selection.filter(|selection| selection.contains(entity)) {
+        value += &format!("\nselected ({} in total)", selection.len());
+    }
+
+    for mut text in &mut texts {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
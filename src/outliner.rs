@@ -0,0 +1,301 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::AssetServer;
+use bevy::hierarchy::{BuildChildren, DespawnRecursiveExt};
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::prelude::{BackgroundColor, ButtonBundle, Changed, Color, Commands, Component, Entity, GlobalTransform, IntoSystemDescriptor, Name, NodeBundle, Or, Parent, Query, Res, ResMut, Resource, Style, TextBundle, Transform, Visibility, With};
+use bevy::text::TextStyle;
+use bevy::ui::{FlexDirection, Interaction, PositionType, Size, UiRect, Val};
+use bevy::utils::{default, HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::camera_bookmark::{BookmarkFlight, CameraBookmarks};
+use crate::free_control::ActiveControl;
+use crate::hud::HudConfig;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::labels::WorldLabel;
+use crate::selection::{Selection, SelectionControls, SelectionSystem};
+use crate::tags::Tags;
+
+/// A panel in the bottom left corner listing every entity with a [Name] or [Tags], each under the
+/// closest of its ancestors that's listed as well. Clicking an entry selects the entity, replacing
+/// the [Selection] like clicking it in the world does (or toggling it while
+/// [SelectionControls::Additive] is held), and flies the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T] over to it. This
+/// plugin can be initialized in two ways:
+///
+/// * No default bindings [OutlinerPlugin::new]
+/// * Slash toggles the outliner [OutlinerPlugin::default]
+///
+/// The entries need a cursor to click, see [UiModePlugin](crate::ui_mode::UiModePlugin). Flights
+/// take [CameraBookmarks::fly_duration] like recalling a bookmark does, without a
+/// [CameraBookmarkPlugin](crate::camera_bookmark::CameraBookmarkPlugin) the camera teleports.
+pub struct OutlinerPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<OutlinerControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> OutlinerPlugin<T> {
+    /// Creates a new `OutlinerPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: OutlinerControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for OutlinerPlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::KeyCode::Slash, OutlinerControls::Toggle)
+    }
+}
+
+impl <T: Component> Plugin for OutlinerPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<OutlinerConfig>() {
+            app.insert_resource(OutlinerConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_startup_system(spawn_outliner)
+            .add_system(outliner_controls)
+            .add_system(outliner_clicks::<T>.after(SelectionSystem))
+            .add_system(update_outliner.after(outliner_controls).after(outliner_clicks::<T>));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum OutlinerControls {
+    Toggle
+}
+
+#[derive(Resource, Clone)]
+pub struct OutlinerConfig {
+    /// Entries past this many are left out
+    pub max_entries: usize,
+    /// How far from an entity flying over to it stops, facing it
+    pub fly_distance: f32,
+    pub entry_color: Color,
+    pub selected_color: Color
+}
+
+impl Default for OutlinerConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 30,
+            fly_distance: 6.0,
+            entry_color: Color::rgba(0.3, 0.3, 0.3, 0.8),
+            selected_color: Color::rgba(0.2, 0.45, 0.8, 0.9)
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Outliner {
+    pub visible: bool,
+    root: Entity,
+    /// What the panel shows, each entity with its indented text
+    entries: Vec<(Entity, String)>
+}
+
+#[derive(Resource)]
+struct OutlinerFont(TextStyle);
+
+#[derive(Component)]
+struct OutlinerEntry(Entity);
+
+fn spawn_outliner(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let root = commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(8.0),
+                bottom: Val::Px(8.0),
+                ..default()
+            },
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    }).id();
+    // the font is loaded once here, the entries only clone the handle
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    commands.insert_resource(OutlinerFont(TextStyle {
+        font: asset_server.load(hud_config.font_path.as_str()),
+        font_size: hud_config.font_size,
+        color: Color::WHITE
+    }));
+    commands.insert_resource(Outliner {
+        visible: false,
+        root,
+        entries: Vec::new()
+    });
+}
+
+fn outliner_controls(binds: Res<Input<OutlinerControls>>, mut outliner: ResMut<Outliner>, mut visibilities: Query<&mut Visibility>) {
+    if !binds.just_pressed(OutlinerControls::Toggle) {
+        return;
+    }
+    outliner.visible = !outliner.visible;
+    if let Ok(mut visibility) = visibilities.get_mut(outliner.root) {
+        visibility.is_visible = outliner.visible;
+    }
+}
+
+fn outliner_clicks<T: Component>(
+    mut commands: Commands,
+    config: Res<OutlinerConfig>,
+    active: Res<ActiveControl<T>>,
+    bookmarks: Option<Res<CameraBookmarks<T>>>,
+    selection_binds: Option<Res<Input<SelectionControls>>>,
+    mut selection: Option<ResMut<Selection>>,
+    clicks: Query<(&Interaction, &OutlinerEntry), Changed<Interaction>>,
+    targets: Query<&GlobalTransform>,
+    cameras: Query<&Transform, With<T>>
+) {
+    for (interaction, OutlinerEntry(entity)) in &clicks {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let additive = selection_binds.as_ref().map_or(false, |binds| binds.pressed(SelectionControls::Additive));
+        if let Some(selection) = &mut selection {
+            if additive {
+                selection.toggle(*entity);
+                continue;
+            }
+            selection.clear();
+            selection.select(*entity);
+        }
+
+        let Some((camera_entity, camera)) = active.entity.and_then(|entity| Some((entity, cameras.get(entity).ok()?))) else {
+            continue;
+        };
+        let Ok(target) = targets.get(*entity) else {
+            continue;
+        };
+        let target = target.translation();
+        let direction = (target - camera.translation).try_normalize().unwrap_or(camera.forward());
+        let to = Transform::from_translation(target - direction * config.fly_distance)
+            .looking_at(target, Vec3::Y)
+            .with_scale(camera.scale);
+        match bookmarks.as_ref().and_then(|bookmarks| bookmarks.fly_duration) {
+            Some(duration) if duration > 0.0 => {
+                commands.entity(camera_entity).insert(BookmarkFlight {
+                    from: *camera,
+                    to,
+                    elapsed: 0.0,
+                    duration
+                });
+            }
+            _ => {
+                commands.entity(camera_entity).insert(to).remove::<BookmarkFlight>();
+            }
+        }
+    }
+}
+
+/// The listed entities depth first, each with how deep it is
+fn outline(listed: &HashSet<Entity>, parents: &Query<&Parent>) -> Vec<(Entity, usize)> {
+    let mut children = HashMap::<Option<Entity>, Vec<Entity>>::default();
+    for entity in listed {
+        let mut ancestor = parents.get(*entity).ok().map(|parent| parent.get());
+        while let Some(parent) = ancestor.filter(|parent| !listed.contains(parent)) {
+            ancestor = parents.get(parent).ok().map(|parent| parent.get());
+        }
+        children.entry(ancestor).or_default().push(*entity);
+    }
+    for entities in children.values_mut() {
+        // so the entries don't shuffle around from frame to frame
+        entities.sort();
+    }
+    let mut outline = Vec::new();
+    let mut stack = children.get(&None).map_or_else(Vec::new, |roots| roots.iter().rev().map(|root| (*root, 0)).collect());
+    while let Some((entity, depth)) = stack.pop() {
+        outline.push((entity, depth));
+        if let Some(children) = children.get(&Some(entity)) {
+            stack.extend(children.iter().rev().map(|child| (*child, depth + 1)));
+        }
+    }
+    outline
+}
+
+fn update_outliner(
+    mut commands: Commands,
+    config: Res<OutlinerConfig>,
+    font: Res<OutlinerFont>,
+    selection: Option<Res<Selection>>,
+    mut outliner: ResMut<Outliner>,
+    listed: Query<(Entity, Option<&Name>, Option<&WorldLabel>, Option<&Tags>), Or<(With<Name>, With<Tags>)>>,
+    parents: Query<&Parent>,
+    mut entries: Query<(&OutlinerEntry, &mut BackgroundColor)>
+) {
+    if !outliner.visible {
+        return;
+    }
+    let names = listed.iter()
+        .map(|(entity, name, label, tags)| {
+            let name = name.map(|name| name.to_string())
+                .or_else(|| label.and_then(|label| label.text.clone()))
+                .unwrap_or_else(|| format!("{:?}", entity));
+            match tags.filter(|tags| !tags.0.is_empty()) {
+                Some(tags) => (entity, format!("{} [{}]", name, tags.0.join(" "))),
+                None => (entity, name)
+            }
+        })
+        .collect::<HashMap<_, _>>();
+    let outline = outline(&names.keys().copied().collect(), &parents);
+    let shown = outline.iter()
+        .take(config.max_entries)
+        .map(|(entity, depth)| (*entity, format!("{}{}", "  ".repeat(*depth), names[entity])))
+        .collect::<Vec<_>>();
+
+    if shown != outliner.entries {
+        let hidden = outline.len() - shown.len();
+        let root = outliner.root;
+        commands.entity(root).despawn_descendants();
+        commands.entity(root).with_children(|panel| {
+            if shown.is_empty() {
+                panel.spawn(TextBundle::from_section("nothing named or tagged", font.0.clone()));
+            }
+            for (entity, text) in &shown {
+                panel.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Auto, Val::Px(font.0.font_size + 4.0)),
+                            margin: UiRect::top(Val::Px(2.0)),
+                            padding: UiRect::horizontal(Val::Px(4.0)),
+                            ..default()
+                        },
+                        background_color: config.entry_color.into(),
+                        ..default()
+                    },
+                    OutlinerEntry(*entity)
+                ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(text.clone(), font.0.clone()));
+                    });
+            }
+            if hidden > 0 {
+                panel.spawn(TextBundle::from_section(format!("and {} more", hidden), font.0.clone()));
+            }
+        });
+        outliner.entries = shown;
+    }
+
+    for (OutlinerEntry(entity), mut color) in &mut entries {
+        let selected = selection.as_ref().map_or(false, |selection| selection.contains(*entity));
+        let wanted = if selected { config.selected_color } else { config.entry_color };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::prelude::{KeyCode, Res, ResMut, Resource};
+use bevy::utils::default;
+use bevy_rapier3d::render::{DebugRenderContext, DebugRenderMode, RapierDebugRenderPlugin};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Adds Rapier's debug rendering, starting hidden and toggled at runtime through the
+/// [PhysicsDebug] resource. This plugin can be initialized in two ways:
+///
+/// * No default bindings [PhysicsDebugPlugin::new]
+/// * F2 toggles the debug rendering [PhysicsDebugPlugin::default]
+///
+/// Also registers the `physics_debug` console command, which toggles the rendering without
+/// arguments, or toggles one of the categories (`colliders`, `contacts` or `joints`) when given one.
+pub struct PhysicsDebugPlugin {
+    key_bindings: KeyBindingPlugin<PhysicsDebugControls>
+}
+
+impl PhysicsDebugPlugin {
+    /// Creates a new `PhysicsDebugPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: PhysicsDebugControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for PhysicsDebugPlugin {
+    fn default() -> Self {
+        Self::new().bind(KeyCode::F2, PhysicsDebugControls::Toggle)
+    }
+}
+
+impl Plugin for PhysicsDebugPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PhysicsDebug>() {
+            app.insert_resource(PhysicsDebug::default());
+        }
+        let enabled = app.world.resource::<PhysicsDebug>().enabled;
+        app
+            .add_plugin(RapierDebugRenderPlugin {
+                enabled,
+                ..default()
+            })
+            .add_plugin(self.key_bindings.clone())
+            .add_system(physics_debug_controls)
+            .add_system(sync_physics_debug)
+            .add_console_command("physics_debug", "toggles physics debug rendering, or one of colliders/contacts/joints", |world, args| {
+                let mut debug = world.resource_mut::<PhysicsDebug>();
+                let line = match args.first().copied() {
+                    None => {
+                        debug.enabled = !debug.enabled;
+                        format!("physics debug {}", on_off(debug.enabled))
+                    }
+                    Some("colliders") => {
+                        debug.colliders = !debug.colliders;
+                        format!("collider rendering {}", on_off(debug.colliders))
+                    }
+                    Some("contacts") => {
+                        debug.contacts = !debug.contacts;
+                        format!("contact rendering {}", on_off(debug.contacts))
+                    }
+                    Some("joints") => {
+                        debug.joints = !debug.joints;
+                        format!("joint rendering {}", on_off(debug.joints))
+                    }
+                    Some(other) => format!("unknown category `{}`, expected colliders, contacts or joints", other)
+                };
+                console_print(world, line);
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum PhysicsDebugControls {
+    /// Shows or hides the debug rendering
+    Toggle
+}
+
+/// What Rapier's debug rendering shows, copied over to its [DebugRenderContext] whenever changed
+#[derive(Resource, Clone)]
+pub struct PhysicsDebug {
+    pub enabled: bool,
+    /// Collider shapes and the axes of rigid bodies
+    pub colliders: bool,
+    pub contacts: bool,
+    pub joints: bool
+}
+
+impl Default for PhysicsDebug {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            colliders: true,
+            contacts: false,
+            joints: true
+        }
+    }
+}
+
+impl PhysicsDebug {
+    pub fn mode(&self) -> DebugRenderMode {
+        let mut mode = DebugRenderMode::empty();
+        mode.set(DebugRenderMode::COLLIDER_SHAPES | DebugRenderMode::RIGID_BODY_AXES, self.colliders);
+        mode.set(DebugRenderMode::SOLVER_CONTACTS | DebugRenderMode::CONTACTS, self.contacts);
+        mode.set(DebugRenderMode::JOINTS, self.joints);
+        mode
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "enabled" } else { "disabled" }
+}
+
+fn physics_debug_controls(binds: Res<Input<PhysicsDebugControls>>, mut debug: ResMut<PhysicsDebug>) {
+    if binds.just_pressed(PhysicsDebugControls::Toggle) {
+        debug.enabled = !debug.enabled;
+        info!("physics debug {}", on_off(debug.enabled));
+    }
+}
+
+fn sync_physics_debug(debug: Res<PhysicsDebug>, mut context: ResMut<DebugRenderContext>) {
+    if !debug.is_changed() {
+        return;
+    }
+    context.enabled = debug.enabled;
+    context.pipeline.mode = debug.mode();
+}
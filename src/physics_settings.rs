@@ -0,0 +1,113 @@
+use bevy::app::{App, Plugin};
+use bevy::math::Vec3;
+use bevy::prelude::{Res, ResMut, Resource};
+use bevy_rapier3d::plugin::{RapierConfiguration, RapierContext, TimestepMode};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::settings::AddSetting;
+
+/// Rapier's gravity, solver iterations, CCD and substeps, changed on the fly through
+/// [PhysicsSettings] (applied whenever it changes). They're kept in the `physics` section of the
+/// settings file (see [SettingsPlugin](crate::settings::SettingsPlugin)) and changed with the
+/// `gravity`, `solver_iterations`, `ccd` and `substeps` console commands, each of which prints
+/// the current value when given no arguments. The [HudPlugin](crate::hud::HudPlugin) shows them
+/// as well.
+pub struct PhysicsSettingsPlugin;
+
+impl Plugin for PhysicsSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_setting::<PhysicsSettings>("physics")
+            .add_system(apply_physics_settings)
+            .add_console_command("gravity", "sets the gravity vector, as x y z", |world, args| {
+                let mut settings = world.resource_mut::<PhysicsSettings>();
+                if args.is_empty() {
+                    let [x, y, z] = settings.gravity;
+                    console_print(world, format!("gravity is {} {} {}", x, y, z));
+                    return;
+                }
+                let parsed = args.iter().map(|arg| arg.parse::<f32>()).collect::<Result<Vec<_>, _>>();
+                match parsed.as_deref() {
+                    Ok(&[x, y, z]) => settings.gravity = [x, y, z],
+                    _ => console_print(world, "usage: gravity [x y z]")
+                }
+            })
+            .add_console_command("solver_iterations", "sets how many velocity iterations the solver does", |world, args| {
+                let mut settings = world.resource_mut::<PhysicsSettings>();
+                match args.first().map(|arg| arg.parse::<usize>()) {
+                    None => {
+                        let iterations = settings.solver_iterations;
+                        console_print(world, format!("{} solver iterations", iterations));
+                    }
+                    Some(Ok(iterations)) if iterations > 0 => settings.solver_iterations = iterations,
+                    Some(_) => console_print(world, "usage: solver_iterations [count]")
+                }
+            })
+            .add_console_command("ccd", "turns continuous collision detection on or off", |world, args| {
+                let mut settings = world.resource_mut::<PhysicsSettings>();
+                match args.first().copied() {
+                    None => {
+                        let ccd = settings.ccd;
+                        console_print(world, format!("ccd is {}", if ccd { "on" } else { "off" }));
+                    }
+                    Some("on") => settings.ccd = true,
+                    Some("off") => settings.ccd = false,
+                    Some(_) => console_print(world, "usage: ccd [on|off]")
+                }
+            })
+            .add_console_command("substeps", "sets how many substeps each physics step is split into", |world, args| {
+                let mut settings = world.resource_mut::<PhysicsSettings>();
+                match args.first().map(|arg| arg.parse::<usize>()) {
+                    None => {
+                        let substeps = settings.substeps;
+                        console_print(world, format!("{} substeps", substeps));
+                    }
+                    Some(Ok(substeps)) if substeps > 0 => settings.substeps = substeps,
+                    Some(_) => console_print(world, "usage: substeps [count]")
+                }
+            });
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsSettings {
+    pub gravity: [f32; 3],
+    /// Velocity iterations of the solver, more makes stacks and joints stiffer
+    pub solver_iterations: usize,
+    /// Whether bodies with [Ccd](bevy_rapier3d::prelude::Ccd) get continuous collision detection,
+    /// turned off by giving rapier no CCD substeps
+    pub ccd: bool,
+    /// Substeps of each physics step
+    pub substeps: usize
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            gravity: [0.0, -9.81, 0.0],
+            solver_iterations: 4,
+            ccd: true,
+            substeps: 1
+        }
+    }
+}
+
+fn apply_physics_settings(
+    settings: Res<PhysicsSettings>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut rapier_context: ResMut<RapierContext>
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    rapier_config.gravity = Vec3::from(settings.gravity);
+    match &mut rapier_config.timestep_mode {
+        TimestepMode::Fixed { substeps, .. }
+        | TimestepMode::Variable { substeps, .. }
+        | TimestepMode::Interpolated { substeps, .. } => *substeps = settings.substeps
+    }
+    let parameters = &mut rapier_context.integration_parameters;
+    parameters.max_velocity_iterations = settings.solver_iterations;
+    parameters.max_ccd_substeps = if settings.ccd { 1 } else { 0 };
+}
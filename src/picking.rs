@@ -0,0 +1,119 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::ecs::event::Events;
+use bevy::math::Vec3;
+use bevy::prelude::{Component, Entity, EventReader, GlobalTransform, IntoSystemDescriptor, Query, Res, ResMut, Resource, SystemLabel, With};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
+use crate::free_control::ActiveControl;
+use crate::ui_mode::{CursorRay, UiMode};
+
+/// Keeps [PickTarget] up to date with whatever collider is under the crosshair of the entity
+/// controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T],
+/// or under the cursor while [UiMode] is active.
+///
+/// Requires the [RapierPhysicsPlugin](bevy_rapier3d::plugin::RapierPhysicsPlugin), along with the
+/// [UiModePlugin](crate::ui_mode::UiModePlugin) for picking with the cursor.
+pub struct PickingPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for PickingPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for PickingPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PickingConfig>() {
+            app.insert_resource(PickingConfig::default());
+        }
+        if !app.world.contains_resource::<Events<CursorRay>>() {
+            app.add_event::<CursorRay>();
+        }
+        app
+            .init_resource::<PickTarget>()
+            .add_system(pick::<T>.label(PickSystem));
+    }
+}
+
+/// Label of [pick], systems reading [PickTarget] should run after it
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PickSystem;
+
+#[derive(Resource, Clone)]
+pub struct PickingConfig {
+    /// How far away things can be picked
+    pub max_distance: f32
+}
+
+impl Default for PickingConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 100.0
+        }
+    }
+}
+
+/// The closest collider along the picking ray, `entity` is `None` when nothing was hit
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct PickTarget {
+    pub entity: Option<Entity>,
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub distance: f32,
+    /// The ray that was cast, kept around for anything placing things along it
+    pub ray_origin: Vec3,
+    pub ray_direction: Vec3
+}
+
+fn pick<T: Component>(
+    config: Res<PickingConfig>,
+    active: Res<ActiveControl<T>>,
+    ui_mode: Option<Res<UiMode>>,
+    rapier_context: Res<RapierContext>,
+    mut cursor_rays: EventReader<CursorRay>,
+    cameras: Query<&GlobalTransform, With<T>>,
+    mut target: ResMut<PickTarget>
+) {
+    let Some(camera) = active.entity else {
+        return;
+    };
+    let Ok(camera_transform) = cameras.get(camera) else {
+        return;
+    };
+
+    let cursor_ray = cursor_rays.iter().last();
+    let (origin, direction) = match (ui_mode.map_or(false, |ui_mode| ui_mode.active), cursor_ray) {
+        (true, Some(ray)) => (ray.origin, ray.direction),
+        // the cursor is outside the window, nothing to point at
+        (true, None) => {
+            if target.entity.is_some() {
+                *target = PickTarget::default();
+            }
+            return;
+        }
+        (false, _) => (camera_transform.translation(), camera_transform.forward())
+    };
+
+    let filter = QueryFilter::default().exclude_collider(camera);
+    let hit = rapier_context.cast_ray_and_get_normal(origin, direction, config.max_distance, true, filter);
+    *target = match hit {
+        Some((entity, intersection)) => PickTarget {
+            entity: Some(entity),
+            point: intersection.point,
+            normal: intersection.normal,
+            distance: intersection.toi,
+            ray_origin: origin,
+            ray_direction: direction
+        },
+        None => PickTarget {
+            ray_origin: origin,
+            ray_direction: direction,
+            ..default()
+        }
+    };
+}
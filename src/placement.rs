@@ -0,0 +1,395 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::ecs::event::Events;
+use bevy::input::Input;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{AlphaMode, Color, Commands, Component, IntoSystemDescriptor, Mesh, MouseButton, Query, Res, ResMut, Resource, Transform, Visibility, With};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::RigidBody;
+use serde::{Deserialize, Serialize};
+use crate::console::AddConsoleCommand;
+use crate::edit_history::EditCommands;
+use crate::hud::SelectedSpawn;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::labels::WorldLabel;
+use crate::picking::{PickSystem, PickTarget};
+use crate::prefab::{Prefab, PrefabLibrary, PrefabLibraryHandle, SpawnPrefab};
+use crate::save::{Saved, SavedBodyKind, SavedPbr, SavedShape};
+
+/// A placement mode for building scenes, while active a translucent ghost of the chosen
+/// [PlacementShape] follows the [PickTarget] (resting on whatever is hit, or floating
+/// [PlacementConfig::distance] along the ray otherwise), and confirming spawns the real thing.
+/// This plugin can be initialized in two ways:
+///
+/// * No default bindings [PlacementPlugin::new]
+/// * B toggles placement, [ and ] pick the shape, G toggles grid snapping, \ toggles aligning to
+///  the surface and the left mouse button places [PlacementPlugin::default]
+///
+/// Placed things stand upright, or on the surface they're placed on while the [SurfaceAlignment]
+/// says so, which the [ClipboardPlugin](crate::clipboard::ClipboardPlugin) follows when pasting as
+/// well.
+/// Placed objects are marked [Saved] (described with [SavedPbr]) so they end up in scene files, and
+/// the chosen shape is shown by the HUD through [SelectedSpawn]. Placing can be undone through the
+/// [EditHistoryPlugin](crate::edit_history::EditHistoryPlugin). The `place` console command places a
+/// prefab from the [PrefabPlugin](crate::prefab::PrefabPlugin) instead. Needs a
+/// [PickingPlugin](crate::picking::PickingPlugin).
+pub struct PlacementPlugin {
+    key_bindings: KeyBindingPlugin<PlacementControls>
+}
+
+impl PlacementPlugin {
+    /// Creates a new `PlacementPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("placement")
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: PlacementControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for PlacementPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(B, PlacementControls::Toggle)
+            .bind(RBracket, PlacementControls::NextShape)
+            .bind(LBracket, PlacementControls::PreviousShape)
+            .bind(G, PlacementControls::ToggleSnap)
+            .bind(Backslash, PlacementControls::ToggleAlignment)
+            .bind(MouseButton::Left, PlacementControls::Confirm)
+    }
+}
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PlacementConfig>() {
+            app.insert_resource(PlacementConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Placement>()
+            .init_resource::<SelectedSpawn>()
+            .init_resource::<SurfaceAlignment>()
+            .add_startup_system(spawn_ghost)
+            .add_system(placement_controls)
+            .add_system(update_ghost.after(placement_controls).after(PickSystem))
+            .add_system(place.after(update_ghost))
+            .add_console_command("place", "places the named prefab, or goes back to shapes without a name", |world, args| {
+                let mut placement = world.resource_mut::<Placement>();
+                placement.prefab = args.first().map(|name| name.to_string());
+                placement.active = true;
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum PlacementControls {
+    /// Enters or leaves placement mode
+    Toggle,
+    NextShape,
+    PreviousShape,
+    /// Switches snapping to [PlacementConfig::grid_size] on or off
+    ToggleSnap,
+    /// Switches between the [SurfaceAlignment]s, outside of placement mode as well since pasting
+    /// follows it too
+    ToggleAlignment,
+    /// Spawns the shape where the ghost is
+    Confirm
+}
+
+#[derive(Clone)]
+pub struct PlacementShape {
+    pub name: String,
+    pub shape: SavedShape,
+    pub color: Color
+}
+
+#[derive(Resource, Clone)]
+pub struct PlacementConfig {
+    /// The shapes to choose from, only read at startup
+    pub shapes: Vec<PlacementShape>,
+    /// How far along the ray the ghost floats when nothing is hit
+    pub distance: f32,
+    pub grid_size: f32,
+    pub body: SavedBodyKind
+}
+
+impl Default for PlacementConfig {
+    fn default() -> Self {
+        let shape = |name: &str, shape, color| PlacementShape { name: name.to_string(), shape, color };
+        Self {
+            shapes: vec![
+                shape("cube", SavedShape::Cuboid { half_extents: Vec3::splat(0.5) }, Color::rgb(0.8, 0.4, 0.3)),
+                shape("ball", SavedShape::Ball { radius: 0.5 }, Color::rgb(0.3, 0.5, 0.8)),
+                shape("capsule", SavedShape::Capsule { half_height: 0.5, radius: 0.3 }, Color::rgb(0.4, 0.7, 0.4)),
+                shape("plank", SavedShape::Cuboid { half_extents: Vec3::new(1.0, 0.1, 0.25) }, Color::rgb(0.7, 0.6, 0.4))
+            ],
+            distance: 5.0,
+            grid_size: 0.5,
+            body: SavedBodyKind::Dynamic
+        }
+    }
+}
+
+/// The state of placement mode, `shape` indexes into [PlacementConfig::shapes]
+#[derive(Resource, Default)]
+pub struct Placement {
+    pub active: bool,
+    pub shape: usize,
+    /// Name of a prefab to place instead of the shape, see [PrefabPlugin](crate::prefab::PrefabPlugin)
+    pub prefab: Option<String>,
+    pub snap: bool
+}
+
+/// Which way is up for placed and pasted things
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum SurfaceAlignment {
+    #[default]
+    WorldUp,
+    /// Up is along the normal of the surface they're put on
+    SurfaceNormal
+}
+
+impl SurfaceAlignment {
+    pub fn toggled(self) -> Self {
+        match self {
+            SurfaceAlignment::WorldUp => SurfaceAlignment::SurfaceNormal,
+            SurfaceAlignment::SurfaceNormal => SurfaceAlignment::WorldUp
+        }
+    }
+
+    /// The rotation turning world up into up on a surface facing `normal`, which is no rotation
+    /// at all without a surface (a zero normal)
+    pub fn rotation(self, normal: Vec3) -> Quat {
+        match self {
+            SurfaceAlignment::SurfaceNormal if normal != Vec3::ZERO => Quat::from_rotation_arc(Vec3::Y, normal.normalize()),
+            _ => Quat::IDENTITY
+        }
+    }
+}
+
+#[derive(Component)]
+struct PlacementGhost;
+
+/// Ghost meshes along with their shapes' materials, in the same order as the shapes
+#[derive(Resource)]
+struct PlacementAssets {
+    meshes: Vec<Handle<Mesh>>,
+    materials: Vec<Handle<StandardMaterial>>,
+    /// The ghost mesh of the prefab being placed, and the shape it was built from
+    prefab_mesh: Option<(SavedShape, Handle<Mesh>)>
+}
+
+fn spawn_ghost(
+    mut commands: Commands,
+    config: Res<PlacementConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    let assets = PlacementAssets {
+        meshes: config.shapes.iter().map(|shape| meshes.add(shape.shape.mesh())).collect(),
+        materials: config.shapes.iter().map(|shape| materials.add(shape.color.into())).collect(),
+        prefab_mesh: None
+    };
+    commands.spawn(PbrBundle {
+        mesh: assets.meshes.first().cloned().unwrap_or_default(),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgba(1.0, 1.0, 1.0, 0.35),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert((PlacementGhost, NotShadowCaster));
+    commands.insert_resource(assets);
+}
+
+fn placement_controls(
+    binds: Res<Input<PlacementControls>>,
+    config: Res<PlacementConfig>,
+    mut placement: ResMut<Placement>,
+    mut alignment: ResMut<SurfaceAlignment>,
+    mut selected: ResMut<SelectedSpawn>
+) {
+    if binds.just_pressed(PlacementControls::ToggleAlignment) {
+        *alignment = alignment.toggled();
+    }
+    let count = config.shapes.len();
+    if count == 0 {
+        return;
+    }
+    if binds.just_pressed(PlacementControls::Toggle) {
+        placement.active = !placement.active;
+    }
+    if placement.active {
+        // choosing a shape goes back from placing a prefab
+        if binds.just_pressed(PlacementControls::NextShape) {
+            placement.shape = (placement.shape + 1) % count;
+            placement.prefab = None;
+        }
+        if binds.just_pressed(PlacementControls::PreviousShape) {
+            placement.shape = (placement.shape + count - 1) % count;
+            placement.prefab = None;
+        }
+        if binds.just_pressed(PlacementControls::ToggleSnap) {
+            placement.snap = !placement.snap;
+        }
+    }
+
+    if placement.is_changed() || alignment.is_changed() {
+        selected.0 = placement.active.then(|| {
+            let mut name = placement.prefab.as_ref().unwrap_or(&config.shapes[placement.shape % count].name).clone();
+            if placement.snap {
+                name += " (snapped)";
+            }
+            if *alignment == SurfaceAlignment::SurfaceNormal {
+                name += " (aligned)";
+            }
+            name
+        });
+    }
+}
+
+/// How far `shape` reaches from its center in `direction`, roughly for capsules
+fn extent_along(shape: SavedShape, direction: Vec3) -> f32 {
+    match shape {
+        SavedShape::Cuboid { half_extents } => direction.abs().dot(half_extents),
+        SavedShape::Ball { radius } => radius,
+        SavedShape::Capsule { half_height, radius } => direction.y.abs() * half_height + radius
+    }
+}
+
+fn placement_transform(
+    config: &PlacementConfig,
+    placement: &Placement,
+    alignment: SurfaceAlignment,
+    shape: SavedShape,
+    target: &PickTarget
+) -> Transform {
+    let (point, rotation) = match target.entity {
+        Some(_) => {
+            let rotation = alignment.rotation(target.normal);
+            // resting on the surface instead of halfway inside of it, the shape's own direction
+            // towards the surface is what counts
+            let extent = extent_along(shape, rotation.inverse() * target.normal);
+            (target.point + target.normal * extent, rotation)
+        }
+        None => (target.ray_origin + target.ray_direction * config.distance, Quat::IDENTITY)
+    };
+    let point = if placement.snap && config.grid_size > 0.0 {
+        (point / config.grid_size).round() * config.grid_size
+    } else {
+        point
+    };
+    Transform::from_translation(point).with_rotation(rotation)
+}
+
+/// The prefab being placed, if [Placement::prefab] names one that's loaded
+fn selected_prefab<'a>(
+    placement: &Placement,
+    library: &Option<Res<PrefabLibraryHandle>>,
+    libraries: &'a Assets<PrefabLibrary>
+) -> Option<&'a Prefab> {
+    let name = placement.prefab.as_ref()?;
+    libraries.get(&library.as_ref()?.0)?.get(name)
+}
+
+fn update_ghost(
+    config: Res<PlacementConfig>,
+    placement: Res<Placement>,
+    alignment: Res<SurfaceAlignment>,
+    target: Res<PickTarget>,
+    library: Option<Res<PrefabLibraryHandle>>,
+    libraries: Res<Assets<PrefabLibrary>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    assets: Option<ResMut<PlacementAssets>>,
+    mut ghosts: Query<(&mut Transform, &mut Visibility, &mut Handle<Mesh>), With<PlacementGhost>>
+) {
+    let Some(mut assets) = assets else {
+        return;
+    };
+    let ghost = match (placement.active, &placement.prefab) {
+        (false, _) => None,
+        (true, Some(_)) => selected_prefab(&placement, &library, &libraries).map(|prefab| {
+            // rebuilt whenever the prefab's shape changes, such as when its file is edited
+            let stale = assets.prefab_mesh.as_ref().map_or(true, |(shape, _)| *shape != prefab.shape);
+            if stale {
+                assets.prefab_mesh = Some((prefab.shape, meshes.add(prefab.shape.mesh())));
+            }
+            let handle = assets.prefab_mesh.as_ref().map(|(_, handle)| handle.clone()).unwrap_or_default();
+            (prefab.shape, handle)
+        }),
+        (true, None) => config.shapes
+            .get(placement.shape)
+            .zip(assets.meshes.get(placement.shape))
+            .map(|(shape, handle)| (shape.shape, handle.clone()))
+    };
+    let ghost = ghost.map(|(shape, handle)| (placement_transform(&config, &placement, *alignment, shape, &target), handle));
+
+    for (mut transform, mut visibility, mut mesh) in &mut ghosts {
+        if visibility.is_visible != ghost.is_some() {
+            visibility.is_visible = ghost.is_some();
+        }
+        let Some((placed, handle)) = &ghost else {
+            continue;
+        };
+        transform.translation = placed.translation;
+        transform.rotation = placed.rotation;
+        if *mesh != *handle {
+            *mesh = handle.clone();
+        }
+    }
+}
+
+fn place(
+    mut commands: Commands,
+    binds: Res<Input<PlacementControls>>,
+    config: Res<PlacementConfig>,
+    placement: Res<Placement>,
+    alignment: Res<SurfaceAlignment>,
+    target: Res<PickTarget>,
+    library: Option<Res<PrefabLibraryHandle>>,
+    libraries: Res<Assets<PrefabLibrary>>,
+    prefab_spawns: Option<ResMut<Events<SpawnPrefab>>>,
+    assets: Option<Res<PlacementAssets>>
+) {
+    if !placement.active || !binds.just_pressed(PlacementControls::Confirm) {
+        return;
+    }
+
+    if placement.prefab.is_some() {
+        let (Some(prefab), Some(mut prefab_spawns)) = (selected_prefab(&placement, &library, &libraries), prefab_spawns) else {
+            return;
+        };
+        prefab_spawns.send(SpawnPrefab {
+            name: prefab.name.clone(),
+            transform: placement_transform(&config, &placement, *alignment, prefab.shape, &target)
+        });
+        return;
+    }
+
+    let (Some(assets), Some(shape)) = (assets, config.shapes.get(placement.shape)) else {
+        return;
+    };
+    commands.spawn_recorded(PbrBundle {
+        mesh: assets.meshes[placement.shape].clone(),
+        material: assets.materials[placement.shape].clone(),
+        transform: placement_transform(&config, &placement, *alignment, shape.shape, &target),
+        ..default()
+    })
+        .insert((
+            RigidBody::from(config.body),
+            shape.shape.collider(),
+            Saved,
+            SavedPbr { shape: shape.shape, color: shape.color },
+            WorldLabel::new(shape.name.clone())
+        ));
+}
@@ -0,0 +1,160 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::Handle;
+use bevy::ecs::system::EntityCommands;
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{Commands, Component, Entity, Mesh, Res, ResMut, Resource, Transform, Visibility};
+use bevy::utils::{default, HashMap};
+use bevy_rapier3d::prelude::{ActiveEvents, Ccd, Collider, ColliderMassProperties, ExternalForce, ExternalImpulse, RigidBody, Velocity};
+use crate::console::{console_print, AddConsoleCommand};
+
+/// Keeps short lived physics bodies (projectiles, debris) around after they're done, hidden and
+/// without a body or collider, so the next one spawned with the same mesh and material reuses the
+/// entity instead of spawning a new one. Spawning goes through [PhysicsPool::acquire], and
+/// despawning through [PhysicsPool::release].
+///
+/// At most [PoolConfig::max_free] entities are kept for each mesh and material, anything beyond
+/// that is despawned. The [HudPlugin](crate::hud::HudPlugin) shows the [PoolStats], and the
+/// `pool` console command prints them.
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PoolConfig>() {
+            app.insert_resource(PoolConfig::default());
+        }
+        app
+            .init_resource::<PhysicsPool>()
+            .add_system(trim_pool)
+            .add_console_command("pool", "prints physics pool statistics", |world, _| {
+                let stats = world.resource::<PhysicsPool>().stats;
+                console_print(world, format!(
+                    "{} active, {} free, {} spawned, {} reused",
+                    stats.active, stats.free, stats.spawned, stats.reused
+                ));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct PoolConfig {
+    /// Free entities kept for each mesh and material
+    pub max_free: usize
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_free: 256
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct PoolStats {
+    /// Acquired and not yet released
+    pub active: usize,
+    /// Released and waiting to be reused
+    pub free: usize,
+    /// Entities spawned because there was nothing free to reuse
+    pub spawned: usize,
+    pub reused: usize
+}
+
+type PoolKey = (Handle<Mesh>, Handle<StandardMaterial>);
+
+#[derive(Resource, Default)]
+pub struct PhysicsPool {
+    free: HashMap<PoolKey, Vec<Entity>>,
+    pub stats: PoolStats
+}
+
+/// On every entity from a [PhysicsPool], remembers what it can be reused for
+#[derive(Component)]
+pub struct Pooled {
+    key: PoolKey,
+    free: bool
+}
+
+impl Pooled {
+    /// Whether the entity is released, waiting in the pool
+    pub fn is_free(&self) -> bool {
+        self.free
+    }
+}
+
+impl PhysicsPool {
+    /// Spawns a visible entity with the mesh and material at `transform`, or reuses a released
+    /// one with the same mesh and material. The body, collider and anything else it needs are up
+    /// to the caller
+    pub fn acquire<'w, 's, 'a>(
+        &mut self,
+        commands: &'a mut Commands<'w, 's>,
+        mesh: Handle<Mesh>,
+        material: Handle<StandardMaterial>,
+        transform: Transform
+    ) -> EntityCommands<'w, 's, 'a> {
+        self.stats.active += 1;
+        let key = (mesh, material);
+        let mut reused = None;
+        if let Some(free) = self.free.get_mut(&key) {
+            // something else (like the delete tool) may have despawned it while it was free
+            while let Some(entity) = free.pop() {
+                self.stats.free -= 1;
+                if commands.get_entity(entity).is_some() {
+                    reused = Some(entity);
+                    break;
+                }
+            }
+        }
+        match reused {
+            Some(entity) => {
+                self.stats.reused += 1;
+                let mut entity_commands = commands.entity(entity);
+                entity_commands.insert((transform, Visibility { is_visible: true }, Pooled { key, free: false }));
+                entity_commands
+            }
+            None => {
+                self.stats.spawned += 1;
+                let (mesh, material) = key.clone();
+                commands.spawn((
+                    PbrBundle {
+                        mesh,
+                        material,
+                        transform,
+                        ..default()
+                    },
+                    Pooled { key, free: false }
+                ))
+            }
+        }
+    }
+
+    /// Hides `entity` and takes its physics away, keeping it around for [PhysicsPool::acquire].
+    /// Components only the caller knows about have to be removed by the caller
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity, pooled: &Pooled) {
+        if pooled.free {
+            return;
+        }
+        self.stats.active = self.stats.active.saturating_sub(1);
+        self.stats.free += 1;
+        commands.entity(entity)
+            .remove::<(RigidBody, Collider, ColliderMassProperties, Velocity, ExternalForce, ExternalImpulse, Ccd, ActiveEvents)>()
+            .insert((Visibility { is_visible: false }, Pooled { key: pooled.key.clone(), free: true }));
+        self.free.entry(pooled.key.clone()).or_default().push(entity);
+    }
+}
+
+fn trim_pool(mut commands: Commands, config: Res<PoolConfig>, mut pool: ResMut<PhysicsPool>) {
+    let PhysicsPool { free, stats } = &mut *pool;
+    for entities in free.values_mut() {
+        while entities.len() > config.max_free {
+            let entity = entities.remove(0);
+            stats.free -= 1;
+            if let Some(entity_commands) = commands.get_entity(entity) {
+                entity_commands.despawn_recursive();
+            }
+        }
+    }
+    free.retain(|_, entities| !entities.is_empty());
+}
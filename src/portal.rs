@@ -0,0 +1,219 @@
+use std::f32::consts::PI;
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, AlphaMode, Color, Commands, Component, Entity, GlobalTransform, IntoSystemDescriptor, Mesh, Parent, Query, Res, Resource, Transform, With, Without, World};
+use bevy::transform::TransformSystem;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, RigidBody, Sensor, Velocity};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::AddTickSystem;
+use crate::free_control::ActiveControl;
+use crate::picking::PickTarget;
+use crate::placement::{Placement, PlacementConfig};
+
+/// Pairs of teleporters, anything with a dynamic body (or the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]) that gets inside
+/// one comes out of the other. Where it is in the first one, which way it faces and its velocity
+/// are carried over turned around, so going in through the front of one comes out of the front of
+/// the other, moving away from it.
+///
+/// Bodies are moved after rapier has written back the results of a tick of
+/// [FixedTime](crate::fixed_time::FixedTime), so the next tick starts from the new position. Once
+/// moved they're marked [Teleported], and aren't sent back until they've left the portal they came
+/// out of.
+///
+/// The `portal` console command sets the first of a pair where the crosshair points the first
+/// time, and spawns both the second time, facing the camera. Portals snap to the grid while
+/// snapping is on in the [PlacementPlugin](crate::placement::PlacementPlugin). `portal_clear`
+/// removes every portal.
+pub struct PortalPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for PortalPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for PortalPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PortalConfig>() {
+            app.insert_resource(PortalConfig::default());
+        }
+        app
+            .init_resource::<PortalStart>()
+            .add_tick_system(teleport::<T>.before(TransformSystem::TransformPropagate))
+            .add_console_command("portal", "sets the first of a portal pair at the crosshair, then spawns both", |world, _| {
+                let Some(placed) = portal_at_target(world) else {
+                    console_print(world, "point at something to put the portal on");
+                    return;
+                };
+                let Some(first) = world.resource_mut::<PortalStart>().0.take() else {
+                    world.resource_mut::<PortalStart>().0 = Some(placed);
+                    console_print(world, "portal started, point at where the other end goes and run `portal` again");
+                    return;
+                };
+                spawn_portal_pair(world, first, placed);
+                console_print(world, "spawned a portal pair");
+            })
+            .add_console_command("portal_clear", "removes every portal", |world, _| {
+                world.resource_mut::<PortalStart>().0 = None;
+                let portals = world.query_filtered::<Entity, With<Portal>>().iter(world).collect::<Vec<_>>();
+                for portal in &portals {
+                    world.despawn(*portal);
+                }
+                console_print(world, format!("removed {} portals", portals.len()));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct PortalConfig {
+    pub half_extents: Vec3,
+    /// Of the first and second portal of each pair
+    pub colors: (Color, Color)
+}
+
+impl Default for PortalConfig {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::new(1.0, 1.5, 0.3),
+            colors: (Color::rgba(0.2, 0.5, 1.0, 0.35), Color::rgba(1.0, 0.55, 0.1, 0.35))
+        }
+    }
+}
+
+/// A box around the entity's translation that sends what gets inside to `target`, its front faces
+/// +z
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Portal {
+    pub target: Entity,
+    pub half_extents: Vec3
+}
+
+impl Portal {
+    /// Whether `point` is inside the portal at `transform`
+    pub fn contains(&self, transform: &Transform, point: Vec3) -> bool {
+        let local = transform.rotation.inverse() * (point - transform.translation);
+        local.abs().cmple(self.half_extents).all()
+    }
+}
+
+/// Given to whatever came out of the `portal`, and removed once it's left it
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Teleported {
+    pub portal: Entity
+}
+
+/// The first portal of the pair being made with the console
+#[derive(Resource, Default)]
+struct PortalStart(Option<Transform>);
+
+/// The rotation taking things going into `from` to coming out of `to`, turned around so their
+/// fronts line up
+pub fn portal_turn(from: &Transform, to: &Transform) -> Quat {
+    to.rotation * Quat::from_rotation_y(PI) * from.rotation.inverse()
+}
+
+/// Where the crosshair points, snapped like placing is, standing on the surface and facing the
+/// camera
+fn portal_at_target(world: &mut World) -> Option<Transform> {
+    let target = world.get_resource::<PickTarget>()?;
+    // only on something, not in mid air
+    target.entity?;
+    let mut point = target.point;
+    let look = target.ray_direction;
+    let snap = world.get_resource::<Placement>().map_or(false, |placement| placement.snap);
+    let grid_size = world.get_resource::<PlacementConfig>().map_or(0.0, |config| config.grid_size);
+    if snap && grid_size > 0.0 {
+        point = (point / grid_size).round() * grid_size;
+    }
+    let half_extents = world.resource::<PortalConfig>().half_extents;
+    // -z along where the camera looks, so the front faces it
+    let rotation = Quat::from_rotation_y(f32::atan2(-look.x, -look.z));
+    Some(Transform::from_translation(point + Vec3::Y * half_extents.y).with_rotation(rotation))
+}
+
+/// Spawns two portals leading into each other, returning them
+pub fn spawn_portal_pair(world: &mut World, first: Transform, second: Transform) -> (Entity, Entity) {
+    let config = world.resource::<PortalConfig>().clone();
+    let size = config.half_extents * 2.0;
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape::Box::new(size.x, size.y, size.z).into());
+    let spawn = |world: &mut World, transform: Transform, color: Color| {
+        let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+            base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        world.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material,
+                transform,
+                ..default()
+            },
+            NotShadowCaster,
+            // a sensor, so it can be picked without bodies bumping into it
+            Collider::cuboid(config.half_extents.x, config.half_extents.y, config.half_extents.z),
+            Sensor
+        )).id()
+    };
+    let a = spawn(world, first, config.colors.0);
+    let b = spawn(world, second, config.colors.1);
+    world.entity_mut(a).insert(Portal { target: b, half_extents: config.half_extents });
+    world.entity_mut(b).insert(Portal { target: a, half_extents: config.half_extents });
+    (a, b)
+}
+
+fn teleport<T: Component>(
+    mut commands: Commands,
+    active: Res<ActiveControl<T>>,
+    portals: Query<(Entity, &Portal, &GlobalTransform)>,
+    mut travellers: Query<(Entity, &mut Transform, Option<&RigidBody>, Option<&mut Velocity>, Option<&Teleported>), (Without<Portal>, Without<Parent>)>,
+    controlled: Query<(), With<T>>
+) {
+    if portals.is_empty() {
+        return;
+    }
+    let portals = portals.iter()
+        .map(|(entity, portal, transform)| (entity, *portal, transform.compute_transform()))
+        .collect::<Vec<_>>();
+    for (entity, mut transform, body, velocity, teleported) in &mut travellers {
+        let is_controlled = active.entity == Some(entity) && controlled.contains(entity);
+        if !is_controlled && body != Some(&RigidBody::Dynamic) {
+            continue;
+        }
+        let position = transform.translation;
+        if let Some(teleported) = teleported {
+            let still_inside = portals.iter()
+                .any(|(portal_entity, portal, portal_transform)| *portal_entity == teleported.portal && portal.contains(portal_transform, position));
+            if still_inside {
+                continue;
+            }
+            commands.entity(entity).remove::<Teleported>();
+        }
+        let Some((from, to, target)) = portals.iter()
+            .find(|(_, portal, portal_transform)| portal.contains(portal_transform, position))
+            .and_then(|(_, portal, portal_transform)| {
+                let (_, _, target_transform) = portals.iter().find(|(target, _, _)| *target == portal.target)?;
+                Some((*portal_transform, *target_transform, portal.target))
+            }) else {
+            continue;
+        };
+        let turn = portal_turn(&from, &to);
+        transform.translation = to.translation + turn * (position - from.translation);
+        transform.rotation = turn * transform.rotation;
+        if let Some(mut velocity) = velocity {
+            velocity.linvel = turn * velocity.linvel;
+            velocity.angvel = turn * velocity.angvel;
+        }
+        commands.entity(entity).insert(Teleported { portal: target });
+    }
+}
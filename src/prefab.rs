@@ -0,0 +1,228 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::{AddAsset, AssetLoader, Assets, AssetServer, Handle, LoadContext, LoadedAsset};
+use bevy::ecs::event::Events;
+use bevy::math::Vec3;
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{Color, Commands, Component, EventReader, Mesh, ReflectComponent, Res, ResMut, Resource, Transform, World};
+use bevy::reflect::{Reflect, TypeUuid};
+use bevy::utils::{BoxedFuture, default};
+use bevy_rapier3d::prelude::{ColliderMassProperties, RigidBody, Velocity};
+use serde::Deserialize;
+use crate::cleanup::{Cleanup, CleanupPolicy};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::edit_history::EditCommands;
+use crate::game_state::LoadingAssets;
+use crate::labels::WorldLabel;
+use crate::picking::PickTarget;
+use crate::save::{Saved, SavedBodyKind, SavedPbr, SavedShape};
+
+/// Loads a library of named prefabs from a RON asset (`.prefabs.ron`), spawned by sending
+/// [SpawnPrefab] events or with the `spawn` console command (`prefabs` lists them). Prefabs are
+/// looked up by name whenever they're spawned, so edits to the file apply to everything spawned
+/// afterwards.
+///
+/// Spawned prefabs are marked with [PrefabInstance], which sticks around in scene files and copies.
+///
+/// For changes to be picked up while running, `watch_for_changes` has to be enabled on Bevy's
+/// `AssetPlugin`.
+pub struct PrefabPlugin {
+    path: String
+}
+
+impl PrefabPlugin {
+    /// Creates a new `PrefabPlugin` loading from `path`, relative to the assets directory
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into()
+        }
+    }
+}
+
+impl Default for PrefabPlugin {
+    fn default() -> Self {
+        Self::new("playground.prefabs.ron")
+    }
+}
+
+impl Plugin for PrefabPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone();
+        app
+            .add_asset::<PrefabLibrary>()
+            .init_asset_loader::<PrefabLoader>()
+            .add_event::<SpawnPrefab>()
+            .register_type::<PrefabInstance>()
+            .add_startup_system(move |mut commands: Commands, asset_server: Res<AssetServer>, loading: Option<ResMut<LoadingAssets>>| {
+                let handle = asset_server.load(path.as_str());
+                if let Some(mut loading) = loading {
+                    loading.track(handle.clone_untyped());
+                }
+                commands.insert_resource(PrefabLibraryHandle(handle));
+            })
+            .add_system(spawn_prefabs)
+            .add_console_command("prefabs", "lists every prefab", |world, _| {
+                let names = prefab_library(world)
+                    .map(|library| library.prefabs.iter().map(|prefab| prefab.name.clone()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                if names.is_empty() {
+                    console_print(world, "no prefabs loaded");
+                } else {
+                    console_print(world, names.join(", "));
+                }
+            })
+            .add_console_command("spawn", "spawns the named prefab under the crosshair", |world, args| {
+                let Some(name) = args.first() else {
+                    console_print(world, "usage: spawn <prefab>");
+                    return;
+                };
+                if !prefab_library(world).map_or(false, |library| library.get(name).is_some()) {
+                    console_print(world, format!("unknown prefab `{}`, try `prefabs`", name));
+                    return;
+                }
+                let translation = world
+                    .get_resource::<PickTarget>()
+                    .map(|target| match target.entity {
+                        Some(_) => target.point + target.normal,
+                        None => target.ray_origin + target.ray_direction * 5.0
+                    })
+                    .unwrap_or(Vec3::ZERO);
+                world.resource_mut::<Events<SpawnPrefab>>().send(SpawnPrefab {
+                    name: name.to_string(),
+                    transform: Transform::from_translation(translation)
+                });
+            });
+    }
+}
+
+/// The library currently in use by [PrefabPlugin]
+#[derive(Resource)]
+pub struct PrefabLibraryHandle(pub Handle<PrefabLibrary>);
+
+#[derive(Deserialize, TypeUuid, Default)]
+#[uuid = "0b3e8f5d-6a2c-4e71-9c4b-7d18f2a65e03"]
+#[serde(default)]
+pub struct PrefabLibrary {
+    pub prefabs: Vec<Prefab>
+}
+
+impl PrefabLibrary {
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.iter().find(|prefab| prefab.name == name)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Prefab {
+    pub name: String,
+    pub shape: SavedShape,
+    #[serde(default = "default_color")]
+    pub color: Color,
+    #[serde(default)]
+    pub metallic: f32,
+    #[serde(default = "default_roughness")]
+    pub roughness: f32,
+    /// Prefabs without a body don't get a collider either
+    #[serde(default)]
+    pub body: Option<SavedBodyKind>,
+    /// Overrides the mass rapier computes from the collider
+    #[serde(default)]
+    pub mass: Option<f32>,
+    #[serde(default)]
+    pub linvel: Vec3,
+    #[serde(default)]
+    pub angvel: Vec3,
+    /// Overrides when the body is cleaned up once it's lost, see
+    /// [CleanupPlugin](crate::cleanup::CleanupPlugin)
+    #[serde(default)]
+    pub cleanup: Option<CleanupPolicy>
+}
+
+fn default_color() -> Color {
+    Color::rgb(0.6, 0.6, 0.6)
+}
+
+fn default_roughness() -> f32 {
+    0.5
+}
+
+#[derive(Default)]
+pub struct PrefabLoader;
+
+impl AssetLoader for PrefabLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let library = ron::de::from_bytes::<PrefabLibrary>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(library));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["prefabs.ron"]
+    }
+}
+
+/// Marks an entity spawned from a prefab, with the prefab's name
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct PrefabInstance(pub String);
+
+/// Spawns the prefab called `name`, ignored if there's no such prefab
+pub struct SpawnPrefab {
+    pub name: String,
+    pub transform: Transform
+}
+
+fn prefab_library(world: &World) -> Option<&PrefabLibrary> {
+    let handle = world.get_resource::<PrefabLibraryHandle>()?;
+    world.resource::<Assets<PrefabLibrary>>().get(&handle.0)
+}
+
+fn spawn_prefabs(
+    mut commands: Commands,
+    mut spawns: EventReader<SpawnPrefab>,
+    library: Option<Res<PrefabLibraryHandle>>,
+    libraries: Res<Assets<PrefabLibrary>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    let Some(library) = library.and_then(|library| libraries.get(&library.0)) else {
+        spawns.clear();
+        return;
+    };
+    for spawn in spawns.iter() {
+        let Some(prefab) = library.get(&spawn.name) else {
+            continue;
+        };
+        let mut entity = commands.spawn_recorded(PbrBundle {
+            mesh: meshes.add(prefab.shape.mesh()),
+            material: materials.add(StandardMaterial {
+                base_color: prefab.color,
+                metallic: prefab.metallic,
+                perceptual_roughness: prefab.roughness,
+                ..default()
+            }),
+            transform: spawn.transform,
+            ..default()
+        });
+        entity.insert((
+            Saved,
+            SavedPbr { shape: prefab.shape, color: prefab.color },
+            WorldLabel::new(prefab.name.clone()),
+            PrefabInstance(prefab.name.clone())
+        ));
+        if let Some(body) = prefab.body {
+            entity.insert((
+                RigidBody::from(body),
+                prefab.shape.collider(),
+                Velocity { linvel: prefab.linvel, angvel: prefab.angvel }
+            ));
+            if let Some(mass) = prefab.mass {
+                entity.insert(ColliderMassProperties::Mass(mass));
+            }
+            if let Some(cleanup) = prefab.cleanup {
+                entity.insert(Cleanup(cleanup));
+            }
+        }
+    }
+}
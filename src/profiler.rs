@@ -0,0 +1,283 @@
+use std::time::Instant;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::asset::AssetServer;
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::hierarchy::{BuildChildren, Children};
+use bevy::input::Input;
+use bevy::prelude::{Color, Commands, Component, IntoSystemDescriptor, Local, NodeBundle, Query, Res, ResMut, Resource, StageLabel, Style, SystemStage, TextBundle, Visibility, With};
+use bevy::text::{Text, TextStyle};
+use bevy::ui::{AlignItems, BackgroundColor, PositionType, Size, UiRect, Val};
+use bevy::utils::{default, HashMap};
+use bevy_rapier3d::plugin::PhysicsStages;
+use serde::{Deserialize, Serialize};
+use crate::free_control::FreeControlSystem;
+use crate::hud::HudConfig;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// A rolling graph of real frame times in the bottom left, and the time spent on input mapping
+/// (all of [CoreStage::PreUpdate]), the free controls and the rapier step, measured into Bevy's
+/// [Diagnostics] (see [FRAME_TIME] and [SPANS]) and shown as text above the graph. The two are
+/// toggled separately through [ProfilerOverlay]. This plugin can be initialized in two ways:
+///
+/// * No default bindings [ProfilerPlugin::new]
+/// * Numpad 8 toggles the graph and numpad 9 the timings [ProfilerPlugin::default]
+///
+/// Bevy's own frame time diagnostic goes by [Time](bevy::time::Time), which the
+/// [FixedTimePlugin](crate::fixed_time::FixedTimePlugin) makes useless for this, so the frame time
+/// is measured separately. The free controls timing spans from just before to just after their
+/// system, so anything running in parallel with it can make it look slower than it is.
+///
+/// Has to be added after rapier's plugin, the rapier step is measured around its stage.
+pub struct ProfilerPlugin {
+    key_bindings: KeyBindingPlugin<ProfilerControls>
+}
+
+impl ProfilerPlugin {
+    /// Creates a new `ProfilerPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: ProfilerControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for ProfilerPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Numpad8, ProfilerControls::ToggleGraph)
+            .bind(Numpad9, ProfilerControls::ToggleTimings)
+    }
+}
+
+impl Plugin for ProfilerPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ProfilerOverlay>() {
+            app.insert_resource(ProfilerOverlay::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Diagnostics>()
+            .init_resource::<SpanStarts>()
+            .add_startup_system(register_diagnostics)
+            .add_startup_system(spawn_overlay)
+            .add_system_to_stage(CoreStage::First, measure_frame_time)
+            .add_stage_before(CoreStage::PreUpdate, ProfilerStage::BeforeInput, SystemStage::single(start_span(INPUT)))
+            .add_stage_after(CoreStage::PreUpdate, ProfilerStage::AfterInput, SystemStage::single(end_span(INPUT)))
+            .add_system(start_span(FREE_CONTROLS).before(FreeControlSystem))
+            .add_system(end_span(FREE_CONTROLS).after(FreeControlSystem))
+            .add_stage_before(PhysicsStages::StepSimulation, ProfilerStage::BeforeStep, SystemStage::single(start_span(RAPIER_STEP)))
+            .add_stage_after(PhysicsStages::StepSimulation, ProfilerStage::AfterStep, SystemStage::single(end_span(RAPIER_STEP)))
+            .add_system(profiler_controls)
+            .add_system_to_stage(CoreStage::PostUpdate, update_overlay);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum ProfilerControls {
+    ToggleGraph,
+    ToggleTimings
+}
+
+#[derive(Resource, Clone)]
+pub struct ProfilerOverlay {
+    pub graph: bool,
+    pub timings: bool,
+    /// Frame time at the top of the graph, in milliseconds
+    pub graph_max_ms: f64
+}
+
+impl Default for ProfilerOverlay {
+    fn default() -> Self {
+        Self {
+            graph: false,
+            timings: false,
+            graph_max_ms: 50.0
+        }
+    }
+}
+
+#[derive(StageLabel)]
+enum ProfilerStage {
+    BeforeInput,
+    AfterInput,
+    BeforeStep,
+    AfterStep
+}
+
+/// Real time between the starts of frames, in milliseconds
+pub const FRAME_TIME: DiagnosticId = DiagnosticId::from_u128(0x3f0b_52a1_7c4e_4d8a_9e61_0b2d_8f7a_c501);
+const INPUT: DiagnosticId = DiagnosticId::from_u128(0x3f0b_52a1_7c4e_4d8a_9e61_0b2d_8f7a_c502);
+const FREE_CONTROLS: DiagnosticId = DiagnosticId::from_u128(0x3f0b_52a1_7c4e_4d8a_9e61_0b2d_8f7a_c503);
+/// Time rapier's step took, in milliseconds
+pub const RAPIER_STEP: DiagnosticId = DiagnosticId::from_u128(0x3f0b_52a1_7c4e_4d8a_9e61_0b2d_8f7a_c504);
+
+/// The measured spans and their names, in milliseconds
+pub const SPANS: [(DiagnosticId, &str); 3] = [
+    (INPUT, "input"),
+    (FREE_CONTROLS, "free controls"),
+    (RAPIER_STEP, "rapier step")
+];
+
+/// Frames kept in the history, which is also how many bars the graph has
+const HISTORY: usize = 120;
+const BAR_WIDTH: f32 = 2.0;
+const GRAPH_HEIGHT: f32 = 80.0;
+
+#[derive(Resource, Default)]
+struct SpanStarts(HashMap<DiagnosticId, Instant>);
+
+#[derive(Component)]
+struct FrameGraph;
+
+#[derive(Component)]
+struct TimingsText;
+
+fn register_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(FRAME_TIME, "frame_time", HISTORY).with_suffix("ms"));
+    for (id, name) in SPANS {
+        diagnostics.add(Diagnostic::new(id, name, HISTORY).with_suffix("ms"));
+    }
+}
+
+fn measure_frame_time(mut diagnostics: ResMut<Diagnostics>, mut last: Local<Option<Instant>>) {
+    let now = Instant::now();
+    if let Some(last) = *last {
+        diagnostics.add_measurement(FRAME_TIME, || (now - last).as_secs_f64() * 1000.0);
+    }
+    *last = Some(now);
+}
+
+fn start_span(id: DiagnosticId) -> impl Fn(ResMut<SpanStarts>) + Send + Sync + 'static {
+    move |mut starts: ResMut<SpanStarts>| {
+        starts.0.insert(id, Instant::now());
+    }
+}
+
+fn end_span(id: DiagnosticId) -> impl Fn(ResMut<SpanStarts>, ResMut<Diagnostics>) + Send + Sync + 'static {
+    move |mut starts: ResMut<SpanStarts>, mut diagnostics: ResMut<Diagnostics>| {
+        // the start is skipped along with the system it's before, like the free controls
+        // outside of running
+        if let Some(start) = starts.0.remove(&id) {
+            diagnostics.add_measurement(id, || start.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+fn spawn_overlay(mut commands: Commands, asset_server: Res<AssetServer>, hud_config: Option<Res<HudConfig>>) {
+    let hud_config = hud_config.map(|config| config.clone()).unwrap_or_default();
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(8.0),
+                bottom: Val::Px(8.0),
+                ..default()
+            },
+            size: Size::new(Val::Px(HISTORY as f32 * BAR_WIDTH), Val::Px(GRAPH_HEIGHT)),
+            align_items: AlignItems::FlexEnd,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(FrameGraph)
+        .with_children(|graph| {
+            for _ in 0..HISTORY {
+                graph.spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BAR_WIDTH), Val::Px(0.0)),
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+        });
+
+    commands.spawn(TextBundle::from_section("", TextStyle {
+        font: asset_server.load(hud_config.font_path.as_str()),
+        font_size: hud_config.font_size,
+        color: Color::WHITE
+    })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(8.0),
+                bottom: Val::Px(GRAPH_HEIGHT + 16.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(TimingsText);
+}
+
+fn profiler_controls(binds: Res<Input<ProfilerControls>>, mut overlay: ResMut<ProfilerOverlay>) {
+    if binds.just_pressed(ProfilerControls::ToggleGraph) {
+        overlay.graph = !overlay.graph;
+    }
+    if binds.just_pressed(ProfilerControls::ToggleTimings) {
+        overlay.timings = !overlay.timings;
+    }
+}
+
+fn update_overlay(
+    overlay: Res<ProfilerOverlay>,
+    diagnostics: Res<Diagnostics>,
+    mut graphs: Query<(&mut Visibility, &Children), With<FrameGraph>>,
+    mut bars: Query<(&mut Style, &mut BackgroundColor)>,
+    mut texts: Query<&mut Text, With<TimingsText>>
+) {
+    for (mut visibility, children) in &mut graphs {
+        if visibility.is_visible != overlay.graph {
+            visibility.is_visible = overlay.graph;
+        }
+        if !overlay.graph {
+            continue;
+        }
+        let Some(frame_time) = diagnostics.get(FRAME_TIME) else {
+            continue;
+        };
+        // the newest frame on the right
+        let values = frame_time.values().copied().collect::<Vec<_>>();
+        let padding = HISTORY.saturating_sub(values.len());
+        for (index, child) in children.iter().enumerate() {
+            let Ok((mut style, mut color)) = bars.get_mut(*child) else {
+                continue;
+            };
+            let value = index.checked_sub(padding).and_then(|index| values.get(index)).copied().unwrap_or(0.0);
+            let height = (value / overlay.graph_max_ms).min(1.0) as f32 * GRAPH_HEIGHT;
+            style.size.height = Val::Px(height);
+            *color = if value > 1000.0 / 30.0 {
+                Color::rgb(0.9, 0.2, 0.2)
+            } else if value > 1000.0 / 60.0 + 1.0 {
+                Color::rgb(0.9, 0.8, 0.2)
+            } else {
+                Color::rgb(0.3, 0.9, 0.3)
+            }.into();
+        }
+    }
+
+    let value = if overlay.timings {
+        let mut lines = Vec::new();
+        for (id, name) in [(FRAME_TIME, "frame")].into_iter().chain(SPANS) {
+            if let Some(average) = diagnostics.get(id).and_then(|diagnostic| diagnostic.average()) {
+                lines.push(format!("{}: {:.2} ms", name, average));
+            }
+        }
+        lines.join("\n")
+    } else {
+        String::new()
+    };
+    for mut text in &mut texts {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::prelude::{Entity, EventReader, IntoSystemDescriptor, Query, Res, ResMut, Resource, Transform, World};
+use bevy_rapier3d::prelude::{RigidBody, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::{AddTickSystem, FixedTime};
+use crate::floating_origin::{add_origin_shifted, OriginShifted};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Rewinds the world. Every tick the [Transform] and [Velocity] of each dynamic body are kept in a
+/// ring buffer holding [RewindConfig::history] seconds, and rewinding puts everything back the way
+/// it was [RewindConfig::rewind] seconds ago, carrying on from there. Since [FixedTime] makes
+/// every tick the same length, going back a number of seconds is going back exactly that many
+/// ticks' worth of snapshots. This plugin can be initialized in two ways:
+///
+/// * No default bindings [RewindPlugin::new]
+/// * Minus rewinds [RewindPlugin::default]
+///
+/// Bodies spawned since are left where they are, and despawned ones don't come back. Also
+/// registers the `rewind` console command, which takes how many seconds to go back.
+pub struct RewindPlugin {
+    key_bindings: KeyBindingPlugin<RewindControls>
+}
+
+impl RewindPlugin {
+    /// Creates a new `RewindPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: RewindControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for RewindPlugin {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::KeyCode::Minus, RewindControls::Rewind)
+    }
+}
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<RewindConfig>() {
+            app.insert_resource(RewindConfig::default());
+        }
+        add_origin_shifted(app);
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<RewindBuffer>()
+            .add_system(shift_snapshots)
+            .add_system(rewind_controls.after(shift_snapshots))
+            .add_tick_system(take_snapshot)
+            .add_console_command("rewind", "rewinds the world by some seconds", |world, args| {
+                let seconds = match args.first() {
+                    Some(arg) => arg.parse::<f64>().ok().filter(|seconds| *seconds > 0.0),
+                    None => Some(world.resource::<RewindConfig>().rewind)
+                };
+                let Some(seconds) = seconds else {
+                    console_print(world, "usage: rewind [seconds]");
+                    return;
+                };
+                let rewound = rewind(world, seconds);
+                console_print(world, format!("rewound {} ticks", rewound));
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum RewindControls {
+    Rewind
+}
+
+#[derive(Resource, Clone)]
+pub struct RewindConfig {
+    /// Seconds of snapshots kept, older ones are dropped
+    pub history: f64,
+    /// Seconds [RewindControls::Rewind] goes back
+    pub rewind: f64
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            history: 10.0,
+            rewind: 3.0
+        }
+    }
+}
+
+/// The state of a dynamic body on a tick
+struct BodySnapshot {
+    entity: Entity,
+    transform: Transform,
+    velocity: Option<Velocity>
+}
+
+/// A snapshot for each tick, newest at the back
+#[derive(Resource, Default)]
+pub struct RewindBuffer {
+    snapshots: VecDeque<Vec<BodySnapshot>>
+}
+
+impl RewindBuffer {
+    /// How many ticks can be rewound
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+fn ticks_per_second(world: &World) -> f64 {
+    world.get_resource::<FixedTime>().map_or(60.0, |fixed_time| fixed_time.tick_rate)
+}
+
+fn take_snapshot(
+    config: Res<RewindConfig>,
+    fixed_time: Option<Res<FixedTime>>,
+    mut buffer: ResMut<RewindBuffer>,
+    bodies: Query<(Entity, &RigidBody, &Transform, Option<&Velocity>)>
+) {
+    let snapshot = bodies.iter()
+        .filter(|(_, body, _, _)| **body == RigidBody::Dynamic)
+        .map(|(entity, _, transform, velocity)| BodySnapshot {
+            entity,
+            transform: *transform,
+            velocity: velocity.copied()
+        })
+        .collect();
+    let tick_rate = fixed_time.map_or(60.0, |fixed_time| fixed_time.tick_rate);
+    let capacity = (config.history * tick_rate).ceil().max(1.0) as usize;
+    while buffer.snapshots.len() >= capacity {
+        buffer.snapshots.pop_front();
+    }
+    buffer.snapshots.push_back(snapshot);
+}
+
+/// Moves the buffered snapshots along with the world when the origin moves, so rewinding doesn't
+/// put bodies back where they were before the shift
+fn shift_snapshots(mut shifted: EventReader<OriginShifted>, mut buffer: ResMut<RewindBuffer>) {
+    for OriginShifted { shift } in shifted.iter() {
+        for body in buffer.snapshots.iter_mut().flatten() {
+            body.transform.translation += *shift;
+        }
+    }
+}
+
+fn rewind_controls(world: &mut World) {
+    if world.resource::<Input<RewindControls>>().just_pressed(RewindControls::Rewind) {
+        let seconds = world.resource::<RewindConfig>().rewind;
+        let rewound = rewind(world, seconds);
+        info!("rewound {} ticks", rewound);
+    }
+}
+
+/// Puts every body that's still around back the way it was `seconds` ago (or as far back as the
+/// buffer goes), dropping the snapshots after it. Returns how many ticks were rewound
+pub fn rewind(world: &mut World, seconds: f64) -> usize {
+    let ticks = (seconds * ticks_per_second(world)).round() as usize;
+    let mut buffer = world.resource_mut::<RewindBuffer>();
+    // the oldest snapshot is kept, so it can be rewound to again
+    let ticks = ticks.min(buffer.snapshots.len().saturating_sub(1));
+    if ticks == 0 {
+        return 0;
+    }
+    let keep = buffer.snapshots.len() - ticks;
+    buffer.snapshots.truncate(keep);
+    let Some(snapshot) = buffer.snapshots.back().map(|snapshot| {
+        snapshot.iter()
+            .map(|body| (body.entity, body.transform, body.velocity))
+            .collect::<Vec<_>>()
+    }) else {
+        return 0;
+    };
+
+    for (entity, transform, velocity) in snapshot {
+        let Some(mut entity) = world.get_entity_mut(entity) else {
+            continue;
+        };
+        entity.insert(transform);
+        if let Some(velocity) = velocity {
+            entity.insert(velocity);
+        }
+    }
+    ticks
+}
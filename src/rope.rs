@@ -0,0 +1,263 @@
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Color, Component, Entity, GlobalTransform, Mesh, Resource, Transform, TransformBundle, World};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, ImpulseJoint, JointAxis, RigidBody, SphericalJointBuilder, Velocity};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::grapple::NoGrapple;
+use crate::picking::PickTarget;
+use crate::placement::{Placement, PlacementConfig};
+
+/// Ropes and chains, a line of capsule bodies held together by spherical joints, hanging between
+/// two anchors. The `rope` console command (taking the number of segments and the stiffness) sets
+/// the first anchor where the crosshair points the first time, and spawns the rope up to where it
+/// points the second time. Anchors snap to the grid while snapping is on in the
+/// [PlacementPlugin](crate::placement::PlacementPlugin), and hold onto the dynamic body they're
+/// put on, or stay where they are otherwise. `rope_clear` removes every rope.
+///
+/// The [GrapplePlugin](crate::grapple::GrapplePlugin) can hook onto ropes unless
+/// [RopeConfig::grappleable] is off.
+pub struct RopePlugin;
+
+impl Plugin for RopePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<RopeConfig>() {
+            app.insert_resource(RopeConfig::default());
+        }
+        app
+            .init_resource::<RopeStart>()
+            .add_console_command("rope", "sets the start of a rope at the crosshair, then spawns it to there", |world, args| {
+                let config = world.resource::<RopeConfig>().clone();
+                let (Some(segments), Some(stiffness)) = (
+                    args.first().map_or(Some(config.segments), |arg| arg.parse::<usize>().ok().filter(|segments| *segments > 0)),
+                    args.get(1).map_or(Some(config.stiffness), |arg| arg.parse::<f32>().ok().filter(|stiffness| *stiffness >= 0.0))
+                ) else {
+                    console_print(world, "usage: rope [segments] [stiffness]");
+                    return;
+                };
+                let Some(anchor) = anchor_at_target(world) else {
+                    console_print(world, "point at something to put the rope on");
+                    return;
+                };
+                let Some(start) = world.resource_mut::<RopeStart>().0.take() else {
+                    world.resource_mut::<RopeStart>().0 = Some(anchor);
+                    console_print(world, "rope started, point at its other end and run `rope` again");
+                    return;
+                };
+                let settings = RopeSettings {
+                    segments,
+                    stiffness,
+                    ..config.settings()
+                };
+                spawn_rope(world, start, anchor, settings);
+                console_print(world, format!("spawned a rope of {} segments", segments));
+            })
+            .add_console_command("rope_clear", "removes every rope", |world, _| {
+                world.resource_mut::<RopeStart>().0 = None;
+                let ropes = world.query::<(Entity, &Rope)>()
+                    .iter(world)
+                    .map(|(entity, rope)| (entity, rope.segments.clone(), rope.end_body))
+                    .collect::<Vec<_>>();
+                for (entity, segments, end_body) in &ropes {
+                    if let Some(mut body) = end_body.and_then(|body| world.get_entity_mut(body)) {
+                        body.remove::<ImpulseJoint>();
+                    }
+                    for segment in segments {
+                        if let Some(segment) = world.get_entity_mut(*segment) {
+                            segment.despawn_recursive();
+                        }
+                    }
+                    world.entity_mut(*entity).despawn_recursive();
+                }
+                console_print(world, format!("removed {} ropes", ropes.len()));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct RopeConfig {
+    /// Segments of ropes made with the console when not given
+    pub segments: usize,
+    /// Stiffness of ropes made with the console when not given
+    pub stiffness: f32,
+    pub radius: f32,
+    pub color: Color,
+    /// Whether the [GrapplePlugin](crate::grapple::GrapplePlugin) can hook onto ropes
+    pub grappleable: bool
+}
+
+impl Default for RopeConfig {
+    fn default() -> Self {
+        Self {
+            segments: 12,
+            stiffness: 0.0,
+            radius: 0.08,
+            color: Color::rgb(0.6, 0.45, 0.3),
+            grappleable: true
+        }
+    }
+}
+
+impl RopeConfig {
+    pub fn settings(&self) -> RopeSettings {
+        RopeSettings {
+            segments: self.segments,
+            stiffness: self.stiffness,
+            radius: self.radius,
+            color: self.color,
+            grappleable: self.grappleable
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RopeSettings {
+    pub segments: usize,
+    /// How strongly each joint pulls back towards being straight, 0 for a slack rope and higher
+    /// for something more like a chain of rods
+    pub stiffness: f32,
+    pub radius: f32,
+    pub color: Color,
+    pub grappleable: bool
+}
+
+/// Where a rope ends, on a dynamic `body` or fixed in place without one
+#[derive(Clone, Copy, Debug)]
+pub struct RopeAnchor {
+    pub point: Vec3,
+    pub body: Option<Entity>
+}
+
+/// The start of the rope being made with the console
+#[derive(Resource, Default)]
+struct RopeStart(Option<RopeAnchor>);
+
+/// On the fixed anchor at the start of a rope
+#[derive(Component, Default)]
+pub struct Rope {
+    /// From the start to the end, along with the fixed anchor at the end if there is one
+    pub segments: Vec<Entity>,
+    /// The body the end is jointed to
+    pub end_body: Option<Entity>
+}
+
+#[derive(Component)]
+pub struct RopeSegment;
+
+/// Where the crosshair points, snapped like placing is, holding onto a dynamic body there
+fn anchor_at_target(world: &mut World) -> Option<RopeAnchor> {
+    let target = world.get_resource::<PickTarget>()?;
+    let hit = target.entity?;
+    let mut point = target.point;
+    let snap = world.get_resource::<Placement>().map_or(false, |placement| placement.snap);
+    let grid_size = world.get_resource::<PlacementConfig>().map_or(0.0, |config| config.grid_size);
+    if snap && grid_size > 0.0 {
+        point = (point / grid_size).round() * grid_size;
+    }
+    let body = match world.get::<RigidBody>(hit) {
+        Some(RigidBody::Dynamic) => Some(hit),
+        _ => None
+    };
+    Some(RopeAnchor { point, body })
+}
+
+/// The joint between two segments (or a segment and an anchor), `stiffness` being how much it
+/// resists bending
+fn rope_joint(anchor1: Vec3, anchor2: Vec3, stiffness: f32) -> SphericalJointBuilder {
+    let mut joint = SphericalJointBuilder::new()
+        .local_anchor1(anchor1)
+        .local_anchor2(anchor2)
+        // neighbouring segments touch, they'd push each other apart otherwise
+        .contacts_enabled(false);
+    if stiffness > 0.0 {
+        for axis in [JointAxis::AngX, JointAxis::AngY, JointAxis::AngZ] {
+            joint = joint.motor_position(axis, 0.0, stiffness, stiffness * 0.1);
+        }
+    }
+    joint
+}
+
+/// `point` in the space of `body`
+fn local_point(world: &World, body: Entity, point: Vec3) -> Vec3 {
+    world.get::<GlobalTransform>(body)
+        .map_or(point, |transform| transform.compute_matrix().inverse().transform_point3(point))
+}
+
+/// Spawns a rope of capsules from `start` to `end`, returning the entity with its [Rope]
+pub fn spawn_rope(world: &mut World, start: RopeAnchor, end: RopeAnchor, settings: RopeSettings) -> Entity {
+    let offset = end.point - start.point;
+    let length = offset.length().max(settings.radius * 2.0);
+    let direction = offset.try_normalize().unwrap_or(Vec3::NEG_Y);
+    let segments = settings.segments.max(1);
+    let segment_length = length / segments as f32;
+    // the capsules run along their y axis, reaching from joint to joint
+    let rotation = Quat::from_rotation_arc(Vec3::Y, direction);
+    let half = Vec3::Y * segment_length * 0.5;
+    let half_height = (segment_length * 0.5 - settings.radius).max(0.0);
+
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape::Capsule {
+        radius: settings.radius,
+        depth: half_height * 2.0,
+        ..default()
+    }.into());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(settings.color.into());
+
+    // the first segment holds onto the start, the rope is kept on a fixed anchor there even when
+    // the start is a body, so removing it doesn't touch the body
+    let start_body = start.body.map(|body| (body, local_point(world, body, start.point)));
+    let root = world.spawn((
+        TransformBundle::from_transform(Transform::from_translation(start.point).with_rotation(rotation)),
+        RigidBody::Fixed,
+        Rope::default()
+    )).id();
+    let (mut parent, mut parent_anchor) = start_body.unwrap_or((root, Vec3::ZERO));
+
+    let mut spawned = Vec::with_capacity(segments);
+    for i in 0..segments {
+        let center = start.point + direction * segment_length * (i as f32 + 0.5);
+        let mut segment = world.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(center).with_rotation(rotation),
+                ..default()
+            },
+            RigidBody::Dynamic,
+            Velocity::zero(),
+            Collider::capsule_y(half_height, settings.radius),
+            ImpulseJoint::new(parent, rope_joint(parent_anchor, -half, settings.stiffness)),
+            RopeSegment
+        ));
+        if !settings.grappleable {
+            segment.insert(NoGrapple);
+        }
+        let segment = segment.id();
+        spawned.push(segment);
+        parent = segment;
+        parent_anchor = half;
+    }
+
+    // the end is held by the last segment, on the body if it's free to take a joint
+    let end_body = end.body.filter(|body| !world.entity(*body).contains::<ImpulseJoint>());
+    let end_joint = match end_body {
+        Some(body) => {
+            let local = local_point(world, body, end.point);
+            world.entity_mut(body).insert(ImpulseJoint::new(parent, rope_joint(parent_anchor, local, 0.0)));
+            body
+        }
+        None => world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(end.point).with_rotation(rotation)),
+            RigidBody::Fixed,
+            ImpulseJoint::new(parent, rope_joint(parent_anchor, Vec3::ZERO, 0.0))
+        )).id()
+    };
+    if end_body.is_none() {
+        // despawned along with the rest of the rope
+        spawned.push(end_joint);
+    }
+    world.entity_mut(root).insert(Rope { segments: spawned, end_body });
+    root
+}
@@ -0,0 +1,522 @@
+use std::any::type_name;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::ecs::entity::EntityMap;
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::input::Input;
+use bevy::log::{error, info};
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{Added, AppTypeRegistry, Color, Commands, Component, Entity, FromReflect, Mesh, Query, ReflectComponent, Res, ResMut, Resource, shape, Transform, With, Without, World};
+use bevy::reflect::Reflect;
+use bevy::scene::DynamicSceneBuilder;
+use bevy::scene::serde::SceneDeserializer;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, RigidBody, Velocity};
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+use crate::console::AddConsoleCommand;
+use crate::cursor_grab::CursorGrab;
+use crate::free_control::{ActiveControl, FreeControlConfig};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Writes every entity marked with [Saved] to a `.scn.ron` file and reads it back on a bound
+/// action, so sandbox setups can be persisted between runs.
+///
+/// Asset handles and rapier colliders can't go into a scene file as is, so entities describe
+/// themselves through [SavedPbr], and rapier state is captured into [SavedBody] at save time. The
+/// [FreeControlConfig] of the marker `T` is stored alongside the entities, as is the transform of
+/// the entity controlled with `T` and whether the cursor was grabbed. Which of those are put back
+/// on load is up to the [RestoreOnLoad] policy.
+///
+/// Defaults to saving with F5 and loading with F9, use [SavePlugin::new] for no default bindings.
+/// The `save` and `load` console commands do the same, optionally with another path.
+pub struct SavePlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<SaveControls>,
+    path: PathBuf,
+    restore: RestoreOnLoad,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> SavePlugin<T> {
+    /// Creates a new `SavePlugin` writing to `path`, without any default bindings
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            path: path.into(),
+            restore: RestoreOnLoad::default(),
+            __phantom: default()
+        }
+    }
+
+    /// Sets what loading a scene puts back besides the entities
+    pub fn restore_on_load(mut self, restore: RestoreOnLoad) -> Self {
+        self.restore = restore;
+        self
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: SaveControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for SavePlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new("playground.scn.ron")
+            .bind(F5, SaveControls::Save)
+            .bind(F9, SaveControls::Load)
+    }
+}
+
+impl <T: Component> Plugin for SavePlugin<T> {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(self.key_bindings.clone())
+            .insert_resource(SaveConfig { path: self.path.clone(), restore: self.restore })
+            .register_type::<Saved>()
+            .register_type::<SavedShape>()
+            .register_type::<SavedPbr>()
+            .register_type::<SavedBodyKind>()
+            .register_type::<SavedBody>()
+            .register_type::<SavedFreeControlConfig>()
+            .register_type::<SavedControlState>()
+            .add_system(save_scene::<T>)
+            .add_system(load_scene)
+            .add_system(restore_pbr)
+            .add_system(restore_body)
+            .add_system(restore_free_control_config::<T>)
+            .add_system(restore_control_state::<T>)
+            .add_console_command("save", "saves the scene, optionally to the given path", |world, args| {
+                with_path(world, args, save_world::<T>);
+            })
+            .add_console_command("load", "loads the scene, optionally from the given path", |world, args| {
+                with_path(world, args, load_world);
+            });
+    }
+}
+
+/// Runs `f` with [SaveConfig::path] temporarily set to the first of `args`, if there is one
+fn with_path(world: &mut World, args: &[&str], f: impl FnOnce(&mut World)) {
+    let Some(path) = args.first() else {
+        f(world);
+        return;
+    };
+    let previous = std::mem::replace(&mut world.resource_mut::<SaveConfig>().path, PathBuf::from(path));
+    f(world);
+    world.resource_mut::<SaveConfig>().path = previous;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum SaveControls {
+    Save,
+    Load
+}
+
+#[derive(Resource, Clone)]
+pub struct SaveConfig {
+    pub path: PathBuf,
+    pub restore: RestoreOnLoad
+}
+
+/// What loading a scene restores from the file, anything not restored stays as it was before
+/// loading
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RestoreOnLoad {
+    /// The transform of the controlled entity, off by default so loading doesn't move the camera
+    pub transform: bool,
+    pub free_control_config: bool,
+    pub cursor_grab: bool
+}
+
+impl Default for RestoreOnLoad {
+    fn default() -> Self {
+        Self {
+            transform: false,
+            free_control_config: true,
+            cursor_grab: false
+        }
+    }
+}
+
+/// Marks an entity to be written out by [SavePlugin], loading a scene replaces every entity
+/// with this marker.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Saved;
+
+#[derive(Copy, Clone, PartialEq, Debug, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum SavedShape {
+    Cuboid { half_extents: Vec3 },
+    Ball { radius: f32 },
+    Capsule { half_height: f32, radius: f32 }
+}
+
+impl Default for SavedShape {
+    fn default() -> Self {
+        SavedShape::Cuboid { half_extents: Vec3::ONE }
+    }
+}
+
+impl SavedShape {
+    pub fn mesh(&self) -> Mesh {
+        match *self {
+            SavedShape::Cuboid { half_extents } => {
+                let size = half_extents * 2.0;
+                shape::Box::new(size.x, size.y, size.z).into()
+            }
+            SavedShape::Ball { radius } => shape::UVSphere { radius, ..default() }.into(),
+            SavedShape::Capsule { half_height, radius } => shape::Capsule {
+                radius,
+                depth: half_height * 2.0,
+                ..default()
+            }.into()
+        }
+    }
+
+    pub fn collider(&self) -> Collider {
+        match *self {
+            SavedShape::Cuboid { half_extents } => Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            SavedShape::Ball { radius } => Collider::ball(radius),
+            SavedShape::Capsule { half_height, radius } => Collider::capsule_y(half_height, radius)
+        }
+    }
+
+    /// Describes the provided `collider`, only cuboids, balls and capsules are supported
+    pub fn from_collider(collider: &Collider) -> Option<Self> {
+        if let Some(cuboid) = collider.as_cuboid() {
+            Some(SavedShape::Cuboid { half_extents: cuboid.half_extents() })
+        } else if let Some(ball) = collider.as_ball() {
+            Some(SavedShape::Ball { radius: ball.radius() })
+        } else if let Some(capsule) = collider.as_capsule() {
+            Some(SavedShape::Capsule { half_height: capsule.half_height(), radius: capsule.radius() })
+        } else {
+            None
+        }
+    }
+}
+
+/// Describes the mesh and material of a saved entity, these get rebuilt whenever this is added
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct SavedPbr {
+    pub shape: SavedShape,
+    pub color: Color
+}
+
+#[derive(Copy, Clone, Debug, Default, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum SavedBodyKind {
+    #[default]
+    Dynamic,
+    Fixed,
+    KinematicPositionBased,
+    KinematicVelocityBased
+}
+
+impl From<RigidBody> for SavedBodyKind {
+    fn from(body: RigidBody) -> Self {
+        match body {
+            RigidBody::Dynamic => SavedBodyKind::Dynamic,
+            RigidBody::Fixed => SavedBodyKind::Fixed,
+            RigidBody::KinematicPositionBased => SavedBodyKind::KinematicPositionBased,
+            RigidBody::KinematicVelocityBased => SavedBodyKind::KinematicVelocityBased
+        }
+    }
+}
+
+impl From<SavedBodyKind> for RigidBody {
+    fn from(kind: SavedBodyKind) -> Self {
+        match kind {
+            SavedBodyKind::Dynamic => RigidBody::Dynamic,
+            SavedBodyKind::Fixed => RigidBody::Fixed,
+            SavedBodyKind::KinematicPositionBased => RigidBody::KinematicPositionBased,
+            SavedBodyKind::KinematicVelocityBased => RigidBody::KinematicVelocityBased
+        }
+    }
+}
+
+/// Rapier state of a saved entity, only ever present in scene files, on load it gets replaced by
+/// the actual rapier components.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct SavedBody {
+    pub body: Option<SavedBodyKind>,
+    pub collider: Option<SavedShape>,
+    pub linvel: Vec3,
+    pub angvel: Vec3
+}
+
+/// [FreeControlConfig] as stored in scene files, `marker` is the type name of the marker the
+/// config belongs to.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct SavedFreeControlConfig {
+    pub marker: String,
+
+    pub forward_speed: f32,
+    pub backward_speed: f32,
+    pub left_speed: f32,
+    pub right_speed: f32,
+    pub up_speed: f32,
+    pub down_speed: f32,
+
+    pub left_sensitivity: f32,
+    pub right_sensitivity: f32,
+    pub up_sensitivity: f32,
+    pub down_sensitivity: f32,
+
+    pub stick_yaw_speed: f32,
+    pub stick_pitch_speed: f32,
+
+    pub min_fov: f32,
+    pub max_fov: f32,
+    pub zoom_speed: f32,
+    pub zoom_smoothing: f32
+}
+
+impl SavedFreeControlConfig {
+    fn new<T: 'static>(config: &FreeControlConfig<T>) -> Self {
+        Self {
+            marker: type_name::<T>().to_string(),
+
+            forward_speed: config.forward_speed,
+            backward_speed: config.backward_speed,
+            left_speed: config.left_speed,
+            right_speed: config.right_speed,
+            up_speed: config.up_speed,
+            down_speed: config.down_speed,
+
+            left_sensitivity: config.left_sensitivity,
+            right_sensitivity: config.right_sensitivity,
+            up_sensitivity: config.up_sensitivity,
+            down_sensitivity: config.down_sensitivity,
+
+            stick_yaw_speed: config.stick_yaw_speed,
+            stick_pitch_speed: config.stick_pitch_speed,
+
+            min_fov: config.min_fov,
+            max_fov: config.max_fov,
+            zoom_speed: config.zoom_speed,
+            zoom_smoothing: config.zoom_smoothing
+        }
+    }
+
+    fn apply<T: 'static>(&self, config: &mut FreeControlConfig<T>) {
+        config.forward_speed = self.forward_speed;
+        config.backward_speed = self.backward_speed;
+        config.left_speed = self.left_speed;
+        config.right_speed = self.right_speed;
+        config.up_speed = self.up_speed;
+        config.down_speed = self.down_speed;
+
+        config.left_sensitivity = self.left_sensitivity;
+        config.right_sensitivity = self.right_sensitivity;
+        config.up_sensitivity = self.up_sensitivity;
+        config.down_sensitivity = self.down_sensitivity;
+
+        config.stick_yaw_speed = self.stick_yaw_speed;
+        config.stick_pitch_speed = self.stick_pitch_speed;
+
+        config.min_fov = self.min_fov;
+        config.max_fov = self.max_fov;
+        config.zoom_speed = self.zoom_speed;
+        config.zoom_smoothing = self.zoom_smoothing;
+    }
+}
+
+/// The controlled entity and the cursor as stored in scene files, `marker` is the type name of
+/// the marker of the [ActiveControl]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct SavedControlState {
+    pub marker: String,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub cursor_grabbed: bool
+}
+
+fn save_scene<T: Component>(world: &mut World) {
+    if world.resource::<Input<SaveControls>>().just_pressed(SaveControls::Save) {
+        save_world::<T>(world);
+    }
+}
+
+fn load_scene(world: &mut World) {
+    if world.resource::<Input<SaveControls>>().just_pressed(SaveControls::Load) {
+        load_world(world);
+    }
+}
+
+/// Writes every [Saved] entity to [SaveConfig::path]
+pub fn save_world<T: Component>(world: &mut World) {
+    let mut bodies = world.query_filtered::<(Entity, Option<&RigidBody>, Option<&Collider>, Option<&Velocity>), With<Saved>>();
+    let bodies = bodies
+        .iter(world)
+        .filter(|(_, body, collider, _)| body.is_some() || collider.is_some())
+        .map(|(entity, body, collider, velocity)| {
+            let velocity = velocity.copied().unwrap_or_default();
+            let saved = SavedBody {
+                body: body.map(|body| (*body).into()),
+                collider: collider.and_then(SavedShape::from_collider),
+                linvel: velocity.linvel,
+                angvel: velocity.angvel
+            };
+            (entity, saved)
+        })
+        .collect::<Vec<_>>();
+
+    let config = world
+        .get_resource::<FreeControlConfig<T>>()
+        .map(SavedFreeControlConfig::new);
+    let config_carrier = config.map(|config| world.spawn((Saved, config)).id());
+    let control_state = world
+        .get_resource::<ActiveControl<T>>()
+        .and_then(|active| active.entity)
+        .and_then(|entity| world.get::<Transform>(entity))
+        .map(|transform| SavedControlState {
+            marker: type_name::<T>().to_string(),
+            translation: transform.translation,
+            rotation: transform.rotation,
+            cursor_grabbed: world.get_resource::<CursorGrab>().map_or(false, |grab| grab.is_active())
+        });
+    let state_carrier = control_state.map(|state| world.spawn((Saved, state)).id());
+
+    let entities = world
+        .query_filtered::<Entity, With<Saved>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    let mut builder = DynamicSceneBuilder::from_world(world);
+    builder.extract_entities(entities.into_iter());
+    let mut scene = builder.build();
+
+    // rapier's components aren't reflectable, so they're slotted into the scene by hand
+    for (entity, saved) in bodies {
+        if let Some(dynamic_entity) = scene.entities.iter_mut().find(|e| e.entity == entity.index()) {
+            dynamic_entity.components.push(Box::new(saved));
+        }
+    }
+
+    for carrier in [config_carrier, state_carrier].into_iter().flatten() {
+        world.despawn(carrier);
+    }
+
+    let path = world.resource::<SaveConfig>().path.clone();
+    let result = scene
+        .serialize_ron(world.resource::<AppTypeRegistry>())
+        .map_err(|e| e.to_string())
+        .and_then(|ron| fs::write(&path, ron).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => info!("saved scene to {}", path.display()),
+        Err(e) => error!("failed to save scene to {}: {}", path.display(), e)
+    }
+}
+
+/// Replaces every [Saved] entity with the ones in [SaveConfig::path]
+pub fn load_world(world: &mut World) {
+    let path = world.resource::<SaveConfig>().path.clone();
+    let scene = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|ron| {
+            let type_registry = world.resource::<AppTypeRegistry>().read();
+            let mut deserializer = ron::de::Deserializer::from_str(&ron).map_err(|e| e.to_string())?;
+            SceneDeserializer { type_registry: &type_registry }
+                .deserialize(&mut deserializer)
+                .map_err(|e| e.to_string())
+        });
+    let scene = match scene {
+        Ok(scene) => scene,
+        Err(e) => {
+            error!("failed to load scene from {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let old = world
+        .query_filtered::<Entity, With<Saved>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in old {
+        world.entity_mut(entity).despawn_recursive();
+    }
+
+    match scene.write_to_world(world, &mut EntityMap::default()) {
+        Ok(()) => info!("loaded scene from {}", path.display()),
+        Err(e) => error!("failed to load scene from {}: {}", path.display(), e)
+    }
+}
+
+fn restore_pbr(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    // anything spawned with a mesh already (like placed objects) is only described for saving
+    saved: Query<(Entity, &SavedPbr), (Added<SavedPbr>, Without<Handle<Mesh>>)>
+) {
+    for (entity, pbr) in &saved {
+        commands.entity(entity).insert((
+            meshes.add(pbr.shape.mesh()),
+            materials.add(pbr.color.into())
+        ));
+    }
+}
+
+fn restore_body(mut commands: Commands, saved: Query<(Entity, &SavedBody), Added<SavedBody>>) {
+    for (entity, saved) in &saved {
+        let mut entity = commands.entity(entity);
+        if let Some(body) = saved.body {
+            entity.insert((RigidBody::from(body), Velocity { linvel: saved.linvel, angvel: saved.angvel }));
+        }
+        if let Some(collider) = saved.collider {
+            entity.insert(collider.collider());
+        }
+        entity.remove::<SavedBody>();
+    }
+}
+
+fn restore_free_control_config<T: Component>(
+    mut commands: Commands,
+    save_config: Res<SaveConfig>,
+    mut config: ResMut<FreeControlConfig<T>>,
+    saved: Query<(Entity, &SavedFreeControlConfig), Added<SavedFreeControlConfig>>
+) {
+    for (entity, saved) in &saved {
+        if saved.marker == type_name::<T>() {
+            if save_config.restore.free_control_config {
+                saved.apply(&mut config);
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn restore_control_state<T: Component>(
+    mut commands: Commands,
+    save_config: Res<SaveConfig>,
+    active: Res<ActiveControl<T>>,
+    mut cursor_grab: Option<ResMut<CursorGrab>>,
+    mut controlled: Query<&mut Transform, With<T>>,
+    saved: Query<(Entity, &SavedControlState), Added<SavedControlState>>
+) {
+    for (entity, saved) in &saved {
+        if saved.marker != type_name::<T>() {
+            continue;
+        }
+        commands.entity(entity).despawn();
+        if save_config.restore.transform {
+            if let Some(mut transform) = active.entity.and_then(|entity| controlled.get_mut(entity).ok()) {
+                transform.translation = saved.translation;
+                transform.rotation = saved.rotation;
+            }
+        }
+        if let (true, Some(cursor_grab)) = (save_config.restore.cursor_grab, cursor_grab.as_mut()) {
+            if saved.cursor_grabbed != cursor_grab.is_active() {
+                if saved.cursor_grabbed { cursor_grab.activate() } else { cursor_grab.deactivate() }
+            }
+        }
+    }
+}
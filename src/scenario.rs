@@ -0,0 +1,299 @@
+use std::marker::PhantomData;
+use std::str::FromStr;
+use bevy::app::{App, Plugin, StartupStage};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::log::info;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{Color, Component, Entity, Mesh, Or, Resource, Transform, With, World};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::RigidBody;
+use crate::cli::CliArgs;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::environment::{EnvironmentDefinition, EnvironmentEntity, EnvironmentHandle};
+use crate::save::{Saved, SavedPbr, SavedShape};
+use crate::selection::Selection;
+use crate::tags::Tags;
+use crate::terrain::TerrainConfig;
+use crate::vehicle::{spawn_vehicle, VehicleConfig};
+
+/// Named scenes the playground can start in, built in code so they're the same on every run,
+/// which makes them good for comparing performance between changes. [ScenarioConfig::startup] is
+/// built on startup (`--scenario` on the command line sets it), and the `scenario` console command
+/// switches to another one, clearing away the last one along with everything [Saved]. Without a
+/// name it lists them.
+///
+/// * `playground` is the environment loaded by the
+///  [EnvironmentPlugin](crate::environment::EnvironmentPlugin), with the terrain around it
+/// * `empty` is a bare ground
+/// * `physics-stack` is a pyramid of [ScenarioConfig::stack_size] cubes a side on the ground,
+///  tagged `stack`
+/// * `terrain` is only the terrain of the [TerrainPlugin](crate::terrain::TerrainPlugin)
+/// * `vehicle-test` is a vehicle on the ground in front of a ramp and a wall of boxes tagged
+///  `wall`
+///
+/// Each moves the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T] to where it
+/// overlooks the scene, except on startup with a scene given on the command line, which puts the
+/// camera back itself. Needs to be added after the plugins it builds with: the
+/// [EnvironmentPlugin](crate::environment::EnvironmentPlugin), the
+/// [TerrainPlugin](crate::terrain::TerrainPlugin) and the
+/// [VehicleControlPlugin](crate::vehicle::VehicleControlPlugin).
+pub struct ScenarioPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for ScenarioPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for ScenarioPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ScenarioConfig>() {
+            app.insert_resource(ScenarioConfig::default());
+        }
+        app
+            .init_resource::<CurrentScenario>()
+            // after the camera and the environment handle are there
+            .add_startup_system_to_stage(StartupStage::PostStartup, start_scenario::<T>)
+            .add_console_command("scenario", "switches to the given scenario, or lists them", |world, args| {
+                let Some(arg) = args.first() else {
+                    let current = world.resource::<CurrentScenario>().0;
+                    for scenario in Scenario::ALL {
+                        let marker = if scenario == current { " (current)" } else { "" };
+                        console_print(world, format!("{}{}", scenario.name(), marker));
+                    }
+                    return;
+                };
+                let Ok(scenario) = arg.parse::<Scenario>() else {
+                    console_print(world, format!("unknown scenario `{}`, try `scenario`", arg));
+                    return;
+                };
+                clear_scenario(world);
+                build_scenario::<T>(world, scenario, true);
+                console_print(world, format!("switched to {}", scenario.name()));
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Scenario {
+    #[default]
+    Playground,
+    Empty,
+    PhysicsStack,
+    Terrain,
+    VehicleTest
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 5] = [Scenario::Playground, Scenario::Empty, Scenario::PhysicsStack, Scenario::Terrain, Scenario::VehicleTest];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scenario::Playground => "playground",
+            Scenario::Empty => "empty",
+            Scenario::PhysicsStack => "physics-stack",
+            Scenario::Terrain => "terrain",
+            Scenario::VehicleTest => "vehicle-test"
+        }
+    }
+
+    /// Where the camera starts, overlooking the scene
+    pub fn camera(self) -> Transform {
+        match self {
+            Scenario::Playground | Scenario::Empty => Transform::from_xyz(0.0, 5.0, 20.0).looking_at(Vec3::Y * 5.0, Vec3::Y),
+            Scenario::PhysicsStack => Transform::from_xyz(0.0, 8.0, 25.0).looking_at(Vec3::Y * 3.0, Vec3::Y),
+            Scenario::Terrain => Transform::from_xyz(0.0, 25.0, 60.0).looking_at(Vec3::ZERO, Vec3::Y),
+            Scenario::VehicleTest => Transform::from_xyz(0.0, 6.0, 18.0).looking_at(Vec3::Y, Vec3::Y)
+        }
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scenario::ALL.into_iter().find(|scenario| scenario.name() == s).ok_or(())
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct ScenarioConfig {
+    /// Built on startup
+    pub startup: Scenario,
+    /// Cubes along each side of the bottom layer of `physics-stack`
+    pub stack_size: usize,
+    /// Half the width of the ground scenarios without terrain stand on
+    pub ground_extent: f32
+}
+
+impl Default for ScenarioConfig {
+    fn default() -> Self {
+        Self {
+            startup: Scenario::default(),
+            stack_size: 8,
+            ground_extent: 50.0
+        }
+    }
+}
+
+/// The scenario built last
+#[derive(Resource, Default)]
+pub struct CurrentScenario(pub Scenario);
+
+/// Given to what a scenario spawns that isn't [Saved], so it's cleared away with the scenario
+#[derive(Component)]
+pub struct ScenarioEntity;
+
+/// The [EnvironmentHandle] taken away by scenarios without the environment, for `playground` to
+/// put back
+#[derive(Resource)]
+struct StashedEnvironment(Handle<EnvironmentDefinition>);
+
+fn start_scenario<T: Component>(world: &mut World) {
+    let scenario = world.resource::<ScenarioConfig>().startup;
+    // a scene given on the command line puts the camera back itself
+    let move_camera = world.get_resource::<CliArgs>().map_or(true, |args| args.scene.is_none());
+    build_scenario::<T>(world, scenario, move_camera);
+}
+
+/// Despawns everything the current scenario spawned, the environment and everything [Saved]
+pub fn clear_scenario(world: &mut World) {
+    let entities = world
+        .query_filtered::<Entity, Or<(With<ScenarioEntity>, With<EnvironmentEntity>, With<Saved>)>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in entities {
+        // already gone if it was a child of an earlier one
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+    if let Some(mut selection) = world.get_resource_mut::<Selection>() {
+        selection.clear();
+    }
+    // so `playground` spawns the environment again
+    if let Some(EnvironmentHandle(handle)) = world.remove_resource::<EnvironmentHandle>() {
+        world.insert_resource(StashedEnvironment(handle));
+    }
+}
+
+/// Builds `scenario` on top of whatever is there, see [clear_scenario]
+pub fn build_scenario<T: Component>(world: &mut World, scenario: Scenario, move_camera: bool) {
+    let with_environment = scenario == Scenario::Playground;
+    if with_environment {
+        if let Some(StashedEnvironment(handle)) = world.remove_resource::<StashedEnvironment>() {
+            // spawned again as the handle is inserted
+            world.insert_resource(EnvironmentHandle(handle));
+        }
+    } else if let Some(EnvironmentHandle(handle)) = world.remove_resource::<EnvironmentHandle>() {
+        world.insert_resource(StashedEnvironment(handle));
+    }
+    if let Some(mut terrain) = world.get_resource_mut::<TerrainConfig>() {
+        terrain.enabled = matches!(scenario, Scenario::Playground | Scenario::Terrain);
+    }
+
+    let config = world.resource::<ScenarioConfig>().clone();
+    match scenario {
+        Scenario::Playground | Scenario::Terrain => {}
+        Scenario::Empty => spawn_ground(world, &config),
+        Scenario::PhysicsStack => {
+            spawn_ground(world, &config);
+            spawn_pyramid(world, config.stack_size);
+        }
+        Scenario::VehicleTest => {
+            spawn_ground(world, &config);
+            spawn_vehicle_course(world);
+        }
+    }
+
+    if move_camera {
+        let camera = scenario.camera();
+        for mut transform in world.query_filtered::<&mut Transform, With<T>>().iter_mut(world) {
+            *transform = camera;
+        }
+    }
+    world.resource_mut::<CurrentScenario>().0 = scenario;
+    info!("built scenario {}", scenario.name());
+}
+
+fn spawn_fixed(world: &mut World, shape: SavedShape, color: Color, transform: Transform) {
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape.mesh());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(color.into());
+    world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            transform,
+            ..default()
+        },
+        RigidBody::Fixed,
+        shape.collider(),
+        ScenarioEntity
+    ));
+}
+
+fn spawn_ground(world: &mut World, config: &ScenarioConfig) {
+    let shape = SavedShape::Cuboid { half_extents: Vec3::new(config.ground_extent, 0.5, config.ground_extent) };
+    spawn_fixed(world, shape, Color::rgb(0.35, 0.4, 0.35), Transform::from_xyz(0.0, -0.5, 0.0));
+}
+
+/// Spawns dynamic [Saved] cuboids sharing one mesh and material, centered on `translations`
+fn spawn_boxes(world: &mut World, half_extents: Vec3, color: Color, tag: &str, translations: impl IntoIterator<Item = Vec3>) {
+    let shape = SavedShape::Cuboid { half_extents };
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape.mesh());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(color.into());
+    for translation in translations {
+        world.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(translation),
+                ..default()
+            },
+            RigidBody::Dynamic,
+            shape.collider(),
+            Saved,
+            SavedPbr { shape, color },
+            Tags(vec![tag.to_string()])
+        ));
+    }
+}
+
+fn spawn_pyramid(world: &mut World, size: usize) {
+    let mut translations = Vec::new();
+    for layer in 0..size {
+        let width = size - layer;
+        let start = -(width as f32 - 1.0) / 2.0;
+        for x in 0..width {
+            for z in 0..width {
+                translations.push(Vec3::new(start + x as f32, 0.5 + layer as f32, start + z as f32));
+            }
+        }
+    }
+    spawn_boxes(world, Vec3::splat(0.5), Color::rgb(0.8, 0.55, 0.25), "stack", translations);
+}
+
+fn spawn_vehicle_course(world: &mut World) {
+    // low end towards the vehicle, which faces -z
+    let ramp_angle = 12.0_f32.to_radians();
+    let ramp = Vec3::new(3.0, 0.25, 6.0);
+    spawn_fixed(
+        world,
+        SavedShape::Cuboid { half_extents: ramp },
+        Color::rgb(0.5, 0.5, 0.55),
+        Transform::from_xyz(0.0, ramp.z * ramp_angle.sin(), -25.0).with_rotation(Quat::from_rotation_x(ramp_angle))
+    );
+    let wall = (0..3).flat_map(|row| (0..5).map(move |column| Vec3::new(column as f32 - 2.0, 0.5 + row as f32, -50.0)));
+    spawn_boxes(world, Vec3::splat(0.5), Color::rgb(0.3, 0.5, 0.8), "wall", wall);
+    if world.contains_resource::<VehicleConfig>() {
+        let vehicle = spawn_vehicle(world, Transform::from_xyz(0.0, 1.5, 0.0));
+        world.entity_mut(vehicle).insert(ScenarioEntity);
+    }
+}
@@ -0,0 +1,335 @@
+use std::sync::{Arc, Mutex};
+use bevy::app::{App, Plugin};
+use bevy::asset::{AddAsset, AssetEvent, AssetLoader, Assets, AssetServer, Handle, HandleUntyped, LoadContext, LoadedAsset};
+use bevy::ecs::event::Events;
+use bevy::log::{error, warn};
+use bevy::math::Vec3;
+use bevy::prelude::{Commands, Entity, EventReader, IntoSystemDescriptor, Mut, Res, ResMut, Resource, Transform, World};
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, FuncArgs, Scope, AST};
+use crate::console::{console_print, ConsoleCommands, ConsoleState};
+use crate::game_state::LoadingAssets;
+use crate::interaction::InteractionEvent;
+use crate::prefab::SpawnPrefab;
+use crate::trigger_volume::{TriggerEnter, TriggerExit};
+
+/// Runs [rhai](https://rhai.rs) scripts from a folder of the assets directory (`scripts` by
+/// default), for trying things out without recompiling. Each script is run once when it's loaded,
+/// and again whenever it changes (`watch_for_changes` has to be enabled on Bevy's `AssetPlugin`
+/// for that). Only the scripts there at startup are loaded.
+///
+/// Besides rhai's own, scripts get these functions:
+///
+/// * `command(name, help, function)` registers a console command calling the script's
+///  `function` with an array of the arguments, until the script is changed or unloaded
+/// * `spawn(prefab, x, y, z)` spawns a prefab from the [PrefabPlugin](crate::prefab::PrefabPlugin),
+///  the position given as floats (`1.0` rather than `1`)
+/// * `run(line)` runs a console command line
+/// * `print(text)` prints to the console
+///
+/// And any of these functions a script has are called as things happen, with entities passed as
+/// numbers: `on_interact(entity, interactor)` for each [InteractionEvent], and
+/// `on_trigger_enter(tag, activator, volume)` and `on_trigger_exit(tag, activator, volume)` for
+/// each [TriggerEnter] and [TriggerExit]. Needs the
+/// [InteractionPlugin](crate::interaction::InteractionPlugin) and
+/// [TriggerVolumePlugin](crate::trigger_volume::TriggerVolumePlugin).
+///
+/// Only built with the `scripting` feature.
+pub struct ScriptingPlugin {
+    folder: String
+}
+
+impl ScriptingPlugin {
+    /// Creates a new `ScriptingPlugin` loading scripts from `folder`, relative to the assets
+    /// directory
+    pub fn new(folder: impl Into<String>) -> Self {
+        Self {
+            folder: folder.into()
+        }
+    }
+}
+
+impl Default for ScriptingPlugin {
+    fn default() -> Self {
+        Self::new("scripts")
+    }
+}
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        let folder = self.folder.clone();
+        app
+            .add_asset::<Script>()
+            .init_asset_loader::<ScriptLoader>()
+            .insert_resource(Scripts::new())
+            .init_resource::<ScriptQueue>()
+            .add_startup_system(move |mut commands: Commands, asset_server: Res<AssetServer>, loading: Option<ResMut<LoadingAssets>>| {
+                let handles = match asset_server.load_folder(folder.as_str()) {
+                    Ok(handles) => handles,
+                    Err(e) => {
+                        warn!("failed to load scripts from {}: {}", folder, e);
+                        return;
+                    }
+                };
+                if let Some(mut loading) = loading {
+                    for handle in &handles {
+                        loading.track(handle.clone());
+                    }
+                }
+                commands.insert_resource(ScriptHandles(handles));
+            })
+            .add_system(queue_script_work)
+            .add_system(run_scripts.after(queue_script_work));
+    }
+}
+
+/// The source of a `.rhai` script
+#[derive(TypeUuid)]
+#[uuid = "5f0c2a7e-93d4-4b1e-8a66-1c7b3e9d0f42"]
+pub struct Script {
+    pub source: String
+}
+
+#[derive(Default)]
+pub struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let source = String::from_utf8(bytes.to_vec())?;
+            load_context.set_default_asset(LoadedAsset::new(Script { source }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+/// The scripts loaded by [ScriptingPlugin], kept so they stay loaded
+#[derive(Resource)]
+pub struct ScriptHandles(pub Vec<HandleUntyped>);
+
+/// What a script asked for while running, done once it's finished since scripts can't reach the
+/// world themselves
+enum ScriptAction {
+    Command { name: String, help: String, function: String },
+    Spawn { prefab: String, translation: Vec3 },
+    Run(String),
+    Print(String)
+}
+
+struct LoadedScript {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+    /// The console commands it registered
+    commands: Vec<String>
+}
+
+/// The engine and every loaded script
+#[derive(Resource)]
+pub struct Scripts {
+    engine: Engine,
+    scripts: HashMap<Handle<Script>, LoadedScript>,
+    actions: Arc<Mutex<Vec<ScriptAction>>>
+}
+
+impl Scripts {
+    fn new() -> Self {
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+
+        let push = queue(&actions);
+        engine.register_fn("command", move |name: &str, help: &str, function: &str| push(ScriptAction::Command {
+            name: name.to_string(),
+            help: help.to_string(),
+            function: function.to_string()
+        }));
+        let push = queue(&actions);
+        engine.register_fn("spawn", move |prefab: &str, x: f64, y: f64, z: f64| push(ScriptAction::Spawn {
+            prefab: prefab.to_string(),
+            translation: Vec3::new(x as f32, y as f32, z as f32)
+        }));
+        let push = queue(&actions);
+        engine.register_fn("run", move |line: &str| push(ScriptAction::Run(line.to_string())));
+        let push = queue(&actions);
+        engine.on_print(move |text| push(ScriptAction::Print(text.to_string())));
+
+        Self {
+            engine,
+            scripts: HashMap::default(),
+            actions
+        }
+    }
+
+    /// How many scripts are loaded
+    pub fn len(&self) -> usize {
+        self.scripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+}
+
+/// A function for the engine to queue actions with
+fn queue(actions: &Arc<Mutex<Vec<ScriptAction>>>) -> impl Fn(ScriptAction) + Send + Sync + 'static {
+    let actions = actions.clone();
+    move |action| actions.lock().unwrap().push(action)
+}
+
+/// Script changes and events waiting for [run_scripts]
+#[derive(Resource, Default)]
+struct ScriptQueue {
+    /// Scripts to run again, or unload when there's no source
+    changed: Vec<(Handle<Script>, Option<String>)>,
+    /// Hook functions to call in every script that has them
+    calls: Vec<(&'static str, Array)>
+}
+
+fn entity_arg(entity: Entity) -> Dynamic {
+    Dynamic::from(entity.to_bits() as i64)
+}
+
+fn queue_script_work(
+    scripts: Res<Assets<Script>>,
+    mut queue: ResMut<ScriptQueue>,
+    mut script_events: EventReader<AssetEvent<Script>>,
+    mut interactions: EventReader<InteractionEvent>,
+    mut enters: EventReader<TriggerEnter>,
+    mut exits: EventReader<TriggerExit>
+) {
+    for event in script_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                let source = scripts.get(handle).map(|script| script.source.clone());
+                queue.changed.push((handle.clone_weak(), source));
+            }
+            AssetEvent::Removed { handle } => queue.changed.push((handle.clone_weak(), None))
+        }
+    }
+    for event in interactions.iter() {
+        queue.calls.push(("on_interact", vec![entity_arg(event.entity), entity_arg(event.interactor)]));
+    }
+    for event in enters.iter() {
+        queue.calls.push(("on_trigger_enter", vec![event.tag.clone().into(), entity_arg(event.activator), entity_arg(event.volume)]));
+    }
+    for event in exits.iter() {
+        queue.calls.push(("on_trigger_exit", vec![event.tag.clone().into(), entity_arg(event.activator), entity_arg(event.volume)]));
+    }
+}
+
+fn run_scripts(world: &mut World) {
+    let queue = std::mem::take(&mut *world.resource_mut::<ScriptQueue>());
+    if queue.changed.is_empty() && queue.calls.is_empty() {
+        return;
+    }
+    for (handle, source) in queue.changed {
+        let name = world.resource::<AssetServer>()
+            .get_handle_path(&handle)
+            .map_or_else(|| "script".to_string(), |path| path.path().display().to_string());
+        unload_script(world, &handle);
+        if let Some(source) = source {
+            load_script(world, handle, name, &source);
+        }
+    }
+    for (function, args) in queue.calls {
+        let handles = world.resource::<Scripts>().scripts.keys().cloned().collect::<Vec<_>>();
+        for handle in handles {
+            call_script(world, &handle, function, args.clone());
+        }
+    }
+}
+
+fn unload_script(world: &mut World, handle: &Handle<Script>) {
+    let Some(script) = world.resource_mut::<Scripts>().scripts.remove(handle) else {
+        return;
+    };
+    if let Some(mut commands) = world.get_resource_mut::<ConsoleCommands>() {
+        for command in &script.commands {
+            commands.remove(command);
+        }
+    }
+}
+
+fn load_script(world: &mut World, handle: Handle<Script>, name: String, source: &str) {
+    world.resource_scope(|world, mut scripts: Mut<Scripts>| {
+        let ast = match scripts.engine.compile(source) {
+            Ok(ast) => ast,
+            Err(e) => {
+                error!("failed to compile {}: {}", name, e);
+                return;
+            }
+        };
+        let mut scope = Scope::new();
+        if let Err(e) = scripts.engine.run_ast_with_scope(&mut scope, &ast) {
+            error!("failed to run {}: {}", name, e);
+        }
+        scripts.scripts.insert(handle.clone(), LoadedScript {
+            name,
+            ast,
+            scope,
+            commands: Vec::new()
+        });
+    });
+    apply_actions(world, &handle);
+}
+
+/// Calls `function` in the script, if the script has it
+fn call_script(world: &mut World, handle: &Handle<Script>, function: &str, args: impl FuncArgs) {
+    world.resource_scope(|_, mut scripts: Mut<Scripts>| {
+        let scripts = &mut *scripts;
+        let Some(script) = scripts.scripts.get_mut(handle) else {
+            return;
+        };
+        if !script.ast.iter_functions().any(|metadata| metadata.name == function) {
+            return;
+        }
+        // the top level already ran when the script was loaded
+        let options = CallFnOptions::new().eval_ast(false);
+        if let Err(e) = scripts.engine.call_fn_with_options::<Dynamic>(options, &mut script.scope, &script.ast, function, args) {
+            error!("{} failed in {}: {}", function, script.name, e);
+        }
+    });
+    apply_actions(world, handle);
+}
+
+/// Does what the script `handle` asked for while it ran
+fn apply_actions(world: &mut World, handle: &Handle<Script>) {
+    let actions = std::mem::take(&mut *world.resource::<Scripts>().actions.lock().unwrap());
+    for action in actions {
+        match action {
+            ScriptAction::Command { name, help, function } => {
+                let script = handle.clone_weak();
+                let mut scripts = world.resource_mut::<Scripts>();
+                let Some(loaded) = scripts.scripts.get_mut(handle) else {
+                    continue;
+                };
+                loaded.commands.push(name.clone());
+                let Some(mut commands) = world.get_resource_mut::<ConsoleCommands>() else {
+                    warn!("`{}` wasn't registered, there's no console", name);
+                    continue;
+                };
+                commands.insert(&name, &help, move |world, args| {
+                    let args = args.iter().map(|arg| Dynamic::from(arg.to_string())).collect::<Array>();
+                    call_script(world, &script, &function, (args,));
+                });
+            }
+            ScriptAction::Spawn { prefab, translation } => {
+                world.resource_mut::<Events<SpawnPrefab>>().send(SpawnPrefab {
+                    name: prefab,
+                    transform: Transform::from_translation(translation)
+                });
+            }
+            ScriptAction::Run(line) => {
+                if let Some(mut state) = world.get_resource_mut::<ConsoleState>() {
+                    state.run(line);
+                }
+            }
+            ScriptAction::Print(text) => console_print(world, text)
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::ecs::event::Events;
+use bevy::input::Input;
+use bevy::math::{Quat, Vec2, Vec3};
+use bevy::prelude::{Camera, Color, Commands, Component, Entity, EventReader, GlobalTransform, IntoSystemDescriptor, Local, MouseButton, NodeBundle, Query, Res, ResMut, Resource, Style, SystemLabel, Transform, Visibility, With};
+use bevy::ui::{Interaction, PositionType, Size, UiRect, Val};
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::edit_history::EditCommands;
+use crate::free_control::ActiveControl;
+use crate::gizmo::Gizmo;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::picking::{PickSystem, PickTarget};
+use crate::save::Saved;
+use crate::ui_mode::{CursorRay, UiMode};
+
+/// Selecting several entities at once and editing them together. Clicking selects what's under
+/// the cursor, dragging selects everything inside the box (projected through the camera controlled
+/// through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]), and with
+/// [SelectionControls::Additive] held both add to the [Selection] instead of replacing it. This
+/// plugin can be initialized in two ways:
+///
+/// * No default bindings [SelectionPlugin::new]
+/// * The left mouse button selects, shift adds to the selection, holding T drags it along the
+///  ground, H and J turn it and X deletes it [SelectionPlugin::default]
+///
+/// Selecting needs the cursor so it only happens while [UiMode] is active, the rest works either
+/// way. Clicks on buttons, like the entries of the
+/// [OutlinerPlugin](crate::outliner::OutlinerPlugin), are left to them. Only [Saved] entities (the
+/// ones making up the scene) can be selected. Moving, turning and deleting are recorded as one
+/// edit each for the whole selection, see
+/// [EditHistoryPlugin](crate::edit_history::EditHistoryPlugin). Needs a
+/// [PickingPlugin](crate::picking::PickingPlugin).
+pub struct SelectionPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<SelectionControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> SelectionPlugin<T> {
+    /// Creates a new `SelectionPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("selection"),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: SelectionControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for SelectionPlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(MouseButton::Left, SelectionControls::Select)
+            .bind(LShift, SelectionControls::Additive)
+            .bind(RShift, SelectionControls::Additive)
+            .bind(T, SelectionControls::Move)
+            .bind(H, SelectionControls::TurnLeft)
+            .bind(J, SelectionControls::TurnRight)
+            .bind(X, SelectionControls::Delete)
+    }
+}
+
+impl <T: Component> Plugin for SelectionPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<SelectionConfig>() {
+            app.insert_resource(SelectionConfig::default());
+        }
+        if !app.world.contains_resource::<Events<CursorRay>>() {
+            app.add_event::<CursorRay>();
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<Selection>()
+            .add_startup_system(spawn_selection_box)
+            .add_system(select::<T>.label(SelectionSystem).after(PickSystem))
+            .add_system(edit_selection.after(SelectionSystem));
+    }
+}
+
+/// Label of the system changing the [Selection], anything taking clicks for itself (like the
+/// [GizmoPlugin](crate::gizmo::GizmoPlugin)) should run before it
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SelectionSystem;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum SelectionControls {
+    /// Clicked selects the [PickTarget], dragged selects everything inside the box
+    Select,
+    /// Held to add to the selection instead of replacing it, clicking something already selected
+    /// deselects it
+    Additive,
+    /// Held to drag the selection along the horizontal plane it's on
+    Move,
+    TurnLeft,
+    TurnRight,
+    Delete
+}
+
+#[derive(Resource, Clone)]
+pub struct SelectionConfig {
+    /// How far the cursor has to move, in logical pixels, for a click to become a box
+    pub drag_threshold: f32,
+    /// How far a single turn goes, in degrees
+    pub turn_step: f32,
+    pub box_color: Color
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        Self {
+            drag_threshold: 4.0,
+            turn_step: 15.0,
+            box_color: Color::rgba(0.3, 0.6, 1.0, 0.25)
+        }
+    }
+}
+
+/// The selected entities, oldest first. Despawned entities are kept until the selection changes,
+/// so undoing their removal (which brings them back under a new id, see [Selection::replace])
+/// selects them again
+#[derive(Resource, Default, Debug)]
+pub struct Selection {
+    entities: Vec<Entity>
+}
+
+impl Selection {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter().copied()
+    }
+
+    /// The most recently selected entity
+    pub fn last(&self) -> Option<Entity> {
+        self.entities.last().copied()
+    }
+
+    pub fn select(&mut self, entity: Entity) {
+        if !self.contains(entity) {
+            self.entities.push(entity);
+        }
+    }
+
+    pub fn deselect(&mut self, entity: Entity) {
+        self.entities.retain(|selected| *selected != entity);
+    }
+
+    pub fn toggle(&mut self, entity: Entity) {
+        if self.contains(entity) {
+            self.deselect(entity);
+        } else {
+            self.select(entity);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    /// Swaps `old` for `new` if `old` is selected, for entities coming back under a new id
+    pub fn replace(&mut self, old: Entity, new: Entity) {
+        for entity in &mut self.entities {
+            if *entity == old {
+                *entity = new;
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct SelectionBox;
+
+fn spawn_selection_box(mut commands: Commands, config: Res<SelectionConfig>) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        background_color: config.box_color.into(),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert(SelectionBox);
+}
+
+fn select<T: Component>(
+    binds: Res<Input<SelectionControls>>,
+    config: Res<SelectionConfig>,
+    ui_mode: Option<Res<UiMode>>,
+    target: Res<PickTarget>,
+    active: Res<ActiveControl<T>>,
+    mut cursor_rays: EventReader<CursorRay>,
+    gizmo: Option<Res<Gizmo>>,
+    mut selection: ResMut<Selection>,
+    cameras: Query<(&Camera, &GlobalTransform), With<T>>,
+    selectable: Query<(Entity, &GlobalTransform), With<Saved>>,
+    mut boxes: Query<(&mut Style, &mut Visibility), With<SelectionBox>>,
+    interactions: Query<&Interaction>,
+    mut drag_start: Local<Option<Vec2>>,
+    mut cursor: Local<Option<Vec2>>
+) {
+    if let Some(ray) = cursor_rays.iter().last() {
+        *cursor = Some(ray.cursor);
+    }
+    if !ui_mode.map_or(false, |ui_mode| ui_mode.active) {
+        *drag_start = None;
+        *cursor = None;
+    }
+    // a click grabbing a gizmo handle or on a button doesn't select anything
+    let on_ui = interactions.iter().any(|interaction| *interaction != Interaction::None);
+    if binds.just_pressed(SelectionControls::Select) && !gizmo.map_or(false, |gizmo| gizmo.is_dragging()) && !on_ui {
+        *drag_start = *cursor;
+    }
+    let dragged = match (*drag_start, *cursor) {
+        (Some(start), Some(cursor)) if start.distance(cursor) >= config.drag_threshold => Some((start.min(cursor), start.max(cursor))),
+        _ => None
+    };
+
+    for (mut style, mut visibility) in &mut boxes {
+        let shown = dragged.is_some() && binds.pressed(SelectionControls::Select);
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+        if let (true, Some((min, max))) = (shown, dragged) {
+            // cursor positions start at the bottom left, like UI positions from the bottom do
+            style.position = UiRect {
+                left: Val::Px(min.x),
+                bottom: Val::Px(min.y),
+                ..default()
+            };
+            style.size = Size::new(Val::Px(max.x - min.x), Val::Px(max.y - min.y));
+        }
+    }
+
+    if !binds.just_released(SelectionControls::Select) || drag_start.take().is_none() {
+        return;
+    }
+    let additive = binds.pressed(SelectionControls::Additive);
+    if !additive {
+        selection.clear();
+    }
+    match dragged {
+        Some((min, max)) => {
+            let Some((camera, camera_transform)) = active.entity.and_then(|entity| cameras.get(entity).ok()) else {
+                return;
+            };
+            for (entity, transform) in &selectable {
+                let inside = camera.world_to_viewport(camera_transform, transform.translation())
+                    .map_or(false, |position| position.cmpge(min).all() && position.cmple(max).all());
+                if inside {
+                    selection.select(entity);
+                }
+            }
+        }
+        None => {
+            let Some(entity) = target.entity.filter(|entity| selectable.contains(*entity)) else {
+                return;
+            };
+            if additive {
+                selection.toggle(entity);
+            } else {
+                selection.select(entity);
+            }
+        }
+    }
+}
+
+/// A [SelectionControls::Move] in progress
+struct MoveDrag {
+    /// Where the picking ray first met the plane being dragged along
+    anchor: Vec3,
+    /// The transforms from before the drag, to record the whole move as one edit
+    original: Vec<(Entity, Transform)>
+}
+
+/// Where the picking ray meets the horizontal plane at `height`
+fn ray_on_plane(target: &PickTarget, height: f32) -> Option<Vec3> {
+    if target.ray_direction.y.abs() < 1e-4 {
+        return None;
+    }
+    let distance = (height - target.ray_origin.y) / target.ray_direction.y;
+    (distance >= 0.0).then(|| target.ray_origin + target.ray_direction * distance)
+}
+
+fn edit_selection(
+    mut commands: Commands,
+    binds: Res<Input<SelectionControls>>,
+    config: Res<SelectionConfig>,
+    target: Res<PickTarget>,
+    selection: Res<Selection>,
+    mut transforms: Query<&mut Transform, With<Saved>>,
+    mut drag: Local<Option<MoveDrag>>
+) {
+    if binds.just_pressed(SelectionControls::Move) {
+        let original = selection.iter()
+            .filter_map(|entity| transforms.get(entity).ok().map(|transform| (entity, *transform)))
+            .collect::<Vec<_>>();
+        if !original.is_empty() {
+            let center = original.iter().map(|(_, transform)| transform.translation).sum::<Vec3>() / original.len() as f32;
+            *drag = ray_on_plane(&target, center.y).map(|anchor| MoveDrag { anchor, original });
+        }
+    }
+    if let Some(MoveDrag { anchor, original }) = drag.as_ref() {
+        let offset = ray_on_plane(&target, anchor.y).map(|point| point - *anchor);
+        for (entity, from) in original {
+            if let (Some(offset), Ok(mut transform)) = (offset, transforms.get_mut(*entity)) {
+                transform.translation = from.translation + offset;
+            }
+        }
+        if !binds.pressed(SelectionControls::Move) {
+            let mut moves = Vec::new();
+            for (entity, from) in original {
+                if let Ok(mut transform) = transforms.get_mut(*entity) {
+                    // put back so the recorded move starts from where the drag did
+                    moves.push((*entity, *transform));
+                    *transform = *from;
+                }
+            }
+            commands.move_recorded_group(moves);
+            *drag = None;
+        }
+        return;
+    }
+
+    let turn = match (binds.just_pressed(SelectionControls::TurnLeft), binds.just_pressed(SelectionControls::TurnRight)) {
+        (true, false) => config.turn_step,
+        (false, true) => -config.turn_step,
+        _ => 0.0
+    };
+    if turn != 0.0 {
+        let current = selection.iter()
+            .filter_map(|entity| transforms.get(entity).ok().map(|transform| (entity, *transform)))
+            .collect::<Vec<_>>();
+        if !current.is_empty() {
+            let center = current.iter().map(|(_, transform)| transform.translation).sum::<Vec3>() / current.len() as f32;
+            let rotation = Quat::from_rotation_y(turn.to_radians());
+            commands.move_recorded_group(current.into_iter().map(|(entity, mut transform)| {
+                transform.translation = center + rotation * (transform.translation - center);
+                transform.rotation = rotation * transform.rotation;
+                (entity, transform)
+            }).collect());
+        }
+    }
+
+    if binds.just_pressed(SelectionControls::Delete) && !selection.is_empty() {
+        // kept selected, undoing the delete selects them again
+        commands.despawn_recorded_group(selection.iter().collect());
+    }
+}
+
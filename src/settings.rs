@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::log::{error, warn};
+use bevy::prelude::{Local, Res, ResMut, Resource};
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Persists settings between runs in a RON file, each setting is a resource stored in a section
+/// of the file named when registering it with [AddSetting::add_setting]. Settings are read from
+/// the file when registered and written back whenever the resource changes.
+///
+/// Each section is kept in the file as a RON string of its own, going through `ron::Value` would
+/// lose the variants of any enums in it.
+///
+/// This plugin needs to be added before any plugin registering settings, otherwise those settings
+/// keep their defaults.
+pub struct SettingsPlugin {
+    path: PathBuf
+}
+
+impl SettingsPlugin {
+    /// Creates a new `SettingsPlugin` reading and writing `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into()
+        }
+    }
+}
+
+impl Default for SettingsPlugin {
+    fn default() -> Self {
+        Self::new("settings.ron")
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(SettingsFile::load(self.path.clone()))
+            .add_system_to_stage(CoreStage::Last, write_settings);
+    }
+}
+
+/// The contents of the settings file, each section in RON
+#[derive(Resource)]
+pub struct SettingsFile {
+    pub path: PathBuf,
+    sections: BTreeMap<String, String>,
+    dirty: bool
+}
+
+impl SettingsFile {
+    pub(crate) fn load(path: PathBuf) -> Self {
+        // a missing file just means nothing has been changed yet
+        let sections = match fs::read_to_string(&path) {
+            Ok(ron) => ron::from_str(&ron).unwrap_or_else(|e| {
+                error!("failed to read settings from {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new()
+        };
+        Self {
+            path,
+            sections,
+            dirty: false
+        }
+    }
+
+    /// Reads a section, `None` if it's missing or doesn't match `T`
+    pub fn get<T: DeserializeOwned>(&self, section: &str) -> Option<T> {
+        let ron = self.sections.get(section)?;
+        ron::from_str::<T>(ron)
+            .map_err(|e| warn!("ignoring settings section {}: {}", section, e))
+            .ok()
+    }
+
+    pub fn set<T: Serialize>(&mut self, section: &str, setting: &T) {
+        match ron::to_string(setting) {
+            Ok(ron) => {
+                if self.sections.get(section) != Some(&ron) {
+                    self.sections.insert(section.to_string(), ron);
+                    self.dirty = true;
+                }
+            }
+            Err(e) => error!("failed to store settings section {}: {}", section, e)
+        }
+    }
+
+    /// Writes every section to [SettingsFile::path]
+    pub(crate) fn save(&mut self) {
+        self.dirty = false;
+        let result = ron::ser::to_string_pretty(&self.sections, PrettyConfig::default())
+            .map_err(|e| e.to_string())
+            .and_then(|ron| fs::write(&self.path, ron).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            error!("failed to write settings to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+pub trait AddSetting {
+    /// Registers the resource `T` as a setting stored in `section` of the settings file. If the
+    /// section is there, it replaces the current value of `T` (inserted if missing), and any
+    /// later change to `T` is written back
+    fn add_setting<T: Resource + Default + Serialize + DeserializeOwned>(&mut self, section: &'static str) -> &mut Self;
+}
+
+impl AddSetting for App {
+    fn add_setting<T: Resource + Default + Serialize + DeserializeOwned>(&mut self, section: &'static str) -> &mut Self {
+        let stored = self.world
+            .get_resource::<SettingsFile>()
+            .and_then(|file| file.get::<T>(section));
+        if let Some(stored) = stored {
+            self.insert_resource(stored);
+        } else {
+            self.init_resource::<T>();
+        }
+        self.add_system_to_stage(CoreStage::Last, move |setting: Res<T>, file: Option<ResMut<SettingsFile>>, mut seen: Local<bool>| {
+            // the first run only sees the setting being inserted
+            if !*seen {
+                *seen = true;
+                return;
+            }
+            if let (true, Some(mut file)) = (setting.is_changed(), file) {
+                file.set(section, &*setting);
+            }
+        })
+    }
+}
+
+fn write_settings(file: Option<ResMut<SettingsFile>>) {
+    let Some(mut file) = file else {
+        return;
+    };
+    if file.dirty {
+        file.save();
+    }
+}
+
+/// Stores `setting` in a settings file of its own under the temp directory and reads it back, the
+/// way a setting goes from one run to the next
+#[cfg(test)]
+pub fn round_trip<T: Serialize + DeserializeOwned>(name: &str, setting: &T) -> Option<T> {
+    let path = std::env::temp_dir().join(format!("bevy_playground_{}_{}.ron", name, std::process::id()));
+    let mut file = SettingsFile::load(path.clone());
+    file.set(name, setting);
+    file.save();
+    let stored = SettingsFile::load(path.clone()).get::<T>(name);
+    let _ = fs::remove_file(path);
+    stored
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use super::round_trip;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum Mode {
+        Off,
+        Key(u32)
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Setting {
+        mode: Mode,
+        key: Mode,
+        fps: Option<f32>
+    }
+
+    #[test]
+    fn enums_survive_the_file() {
+        let setting = Setting {
+            mode: Mode::Off,
+            key: Mode::Key(5),
+            fps: Some(60.0)
+        };
+        assert_eq!(round_trip("enums", &setting), Some(setting));
+    }
+}
@@ -0,0 +1,247 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::input::Input;
+use bevy::math::Vec3;
+use bevy::pbr::StandardMaterial;
+use bevy::prelude::{shape, Color, Commands, Component, Entity, EventReader, FromWorld, IntoSystemDescriptor, Mesh, Query, Res, ResMut, Resource, Transform, With, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{ActiveEvents, Ccd, Collider, ColliderMassProperties, CollisionEvent, ExternalImpulse, RigidBody, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::cursor_grab::CursorGrab;
+use crate::free_control::ActiveControl;
+use crate::game_state::running;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::pool::{PhysicsPool, Pooled};
+use crate::ui_mode::UiMode;
+
+/// Shoots small spheres out of the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T], mostly for
+/// throwing a lot of fast bodies at the fixed timestep physics. Projectiles despawn after
+/// [ShooterConfig::lifetime] (back into the [PhysicsPool]), and can give whatever they hit first an extra push. This plugin can
+/// be initialized in two ways:
+///
+/// * No default bindings [ShooterPlugin::new]
+/// * Right click fires [ShooterPlugin::default]
+///
+/// Firing needs the cursor grabbed, so clicking in [UiMode] doesn't shoot.
+pub struct ShooterPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<ShooterControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> ShooterPlugin<T> {
+    /// Creates a new `ShooterPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("shooter"),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: ShooterControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for ShooterPlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::MouseButton::Right, ShooterControls::Fire)
+    }
+}
+
+impl <T: Component> Plugin for ShooterPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ShooterConfig>() {
+            app.insert_resource(ShooterConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<ProjectileAssets>()
+            .init_resource::<PhysicsPool>()
+            .add_system(fire::<T>.with_run_criteria(running))
+            .add_system(push_hit_bodies)
+            .add_system(expire_projectiles);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum ShooterControls {
+    Fire
+}
+
+#[derive(Resource, Clone)]
+pub struct ShooterConfig {
+    /// Launch speed in units per second
+    pub speed: f32,
+    pub radius: f32,
+    pub mass: f32,
+    /// Seconds before a projectile despawns
+    pub lifetime: f32,
+    /// How far in front of the shooter projectiles spawn, so they don't start inside its collider
+    pub muzzle_distance: f32,
+    /// Impulse along the projectile's flight given to the first dynamic body it hits, on top of
+    /// the collision itself, `None` leaves it to the collision
+    pub hit_impulse: Option<f32>,
+    pub color: Color
+}
+
+impl Default for ShooterConfig {
+    fn default() -> Self {
+        Self {
+            speed: 40.0,
+            radius: 0.1,
+            mass: 0.2,
+            lifetime: 5.0,
+            muzzle_distance: 1.0,
+            hit_impulse: Some(2.0),
+            color: Color::rgb(1.0, 0.8, 0.2)
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    /// Seconds since it was fired
+    pub age: f32,
+    /// Normalized direction it was fired in
+    pub direction: Vec3,
+    /// Whether it already gave [ShooterConfig::hit_impulse] to something
+    pub hit: bool
+}
+
+/// Shared mesh and material, rebuilt when [ShooterConfig] changes the look of projectiles
+#[derive(Resource)]
+struct ProjectileAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    radius: f32,
+    color: Color
+}
+
+impl FromWorld for ProjectileAssets {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.resource::<ShooterConfig>().clone();
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(projectile_mesh(config.radius));
+        let material = world.resource_mut::<Assets<StandardMaterial>>().add(config.color.into());
+        Self {
+            mesh,
+            material,
+            radius: config.radius,
+            color: config.color
+        }
+    }
+}
+
+fn projectile_mesh(radius: f32) -> Mesh {
+    shape::Icosphere { radius, subdivisions: 2 }.into()
+}
+
+fn fire<T: Component>(
+    mut commands: Commands,
+    binds: Res<Input<ShooterControls>>,
+    config: Res<ShooterConfig>,
+    cursor_grab: Res<CursorGrab>,
+    ui_mode: Option<Res<UiMode>>,
+    active: Res<ActiveControl<T>>,
+    mut assets: ResMut<ProjectileAssets>,
+    mut pool: ResMut<PhysicsPool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    shooters: Query<&Transform, With<T>>
+) {
+    if !binds.just_pressed(ShooterControls::Fire) || cursor_grab.is_inactive() || ui_mode.map_or(false, |ui_mode| ui_mode.active) {
+        return;
+    }
+    let Some(shooter) = active.entity.and_then(|entity| shooters.get(entity).ok()) else {
+        return;
+    };
+
+    if assets.radius != config.radius {
+        assets.mesh = meshes.add(projectile_mesh(config.radius));
+        assets.radius = config.radius;
+    }
+    if assets.color != config.color {
+        assets.material = materials.add(config.color.into());
+        assets.color = config.color;
+    }
+
+    let direction = shooter.forward();
+    let transform = Transform::from_translation(shooter.translation + direction * config.muzzle_distance);
+    pool.acquire(&mut commands, assets.mesh.clone(), assets.material.clone(), transform)
+        .insert((
+            RigidBody::Dynamic,
+            Collider::ball(config.radius),
+            ColliderMassProperties::Mass(config.mass),
+            Velocity::linear(direction * config.speed),
+            // small and fast, without ccd they'd tunnel through thin colliders
+            Ccd::enabled(),
+            ActiveEvents::COLLISION_EVENTS,
+            Projectile { age: 0.0, direction, hit: false }
+        ));
+}
+
+fn push_hit_bodies(
+    mut commands: Commands,
+    config: Res<ShooterConfig>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut projectiles: Query<&mut Projectile>,
+    mut bodies: Query<(&RigidBody, Option<&mut ExternalImpulse>)>
+) {
+    let Some(strength) = config.hit_impulse else {
+        collisions.clear();
+        return;
+    };
+    for collision in collisions.iter() {
+        let CollisionEvent::Started(a, b, _) = *collision else {
+            continue;
+        };
+        for (projectile, other) in [(a, b), (b, a)] {
+            let Ok(mut projectile) = projectiles.get_mut(projectile) else {
+                continue;
+            };
+            if projectile.hit {
+                continue;
+            }
+            let Ok((body, impulse)) = bodies.get_mut(other) else {
+                continue;
+            };
+            if *body != RigidBody::Dynamic {
+                continue;
+            }
+            projectile.hit = true;
+            let push = projectile.direction * strength;
+            match impulse {
+                Some(mut impulse) => impulse.impulse += push,
+                None => {
+                    commands.entity(other).insert(ExternalImpulse { impulse: push, ..default() });
+                }
+            }
+        }
+    }
+}
+
+fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<ShooterConfig>,
+    mut pool: ResMut<PhysicsPool>,
+    mut projectiles: Query<(Entity, &mut Projectile, Option<&Pooled>)>
+) {
+    for (entity, mut projectile, pooled) in &mut projectiles {
+        projectile.age += time.delta_seconds();
+        if projectile.age < config.lifetime {
+            continue;
+        }
+        match pooled {
+            Some(pooled) => {
+                pool.release(&mut commands, entity, pooled);
+                commands.entity(entity).remove::<Projectile>();
+            }
+            None => commands.entity(entity).despawn_recursive()
+        }
+    }
+}
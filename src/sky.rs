@@ -0,0 +1,175 @@
+use std::f32::consts::TAU;
+use bevy::app::{App, Plugin};
+use bevy::input::Input;
+use bevy::math::Quat;
+use bevy::pbr::{AmbientLight, DirectionalLight, DirectionalLightBundle};
+use bevy::prelude::{Commands, Component, Query, Res, ResMut, Resource, Transform, With};
+use bevy::time::Time;
+use bevy::utils::default;
+use serde::{Deserialize, Serialize};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// Spawns a sun (a [DirectionalLight] tagged with [Sun]) and moves it across the sky over the
+/// course of a day, dimming it and the ambient light as it sets. This plugin can be initialized
+/// in two ways:
+///
+/// * No default bindings [SkyPlugin::new]
+/// * period to speed time up, comma to slow it down, P to pause [SkyPlugin::default]
+///
+/// Time of day advances with Bevy's [Time], so it's driven by the fixed timestep whenever the
+/// [FixedTimePlugin](crate::fixed_time::FixedTimePlugin) is in use. The [TimeOfDay] resource
+/// holds the current time and the length of a day.
+pub struct SkyPlugin {
+    key_bindings: KeyBindingPlugin<SkyControls>
+}
+
+impl SkyPlugin {
+    /// Creates a new `SkyPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: SkyControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for SkyPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(Period, SkyControls::Faster)
+            .bind(Comma, SkyControls::Slower)
+            .bind(P, SkyControls::Pause)
+    }
+}
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<TimeOfDay>() {
+            app.insert_resource(TimeOfDay::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_startup_system(spawn_sun)
+            .add_system(sky_controls)
+            .add_system(advance_time_of_day)
+            .add_system(move_sun);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum SkyControls {
+    /// Doubles the speed time of day advances at
+    Faster,
+    /// Halves the speed time of day advances at
+    Slower,
+    /// Toggles whether time of day advances at all
+    Pause
+}
+
+/// Marks the [DirectionalLight] moved by [SkyPlugin]
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Resource)]
+pub struct TimeOfDay {
+    /// Current time as a fraction of the day, 0.0 is midnight and 0.5 is noon
+    pub time: f32,
+    /// Length of a day in seconds, at a speed of 1.0
+    pub day_length: f32,
+    pub speed: f32,
+    pub max_speed: f32,
+    pub paused: bool,
+
+    /// Illuminance of the sun when directly overhead
+    pub max_illuminance: f32,
+    /// Ambient brightness at noon and at night
+    pub day_ambient: f32,
+    pub night_ambient: f32,
+    /// Rotation around the y axis of the path the sun takes, 0.0 rises in the east (+x)
+    pub azimuth: f32
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            time: 0.35,
+            day_length: 120.0,
+            speed: 1.0,
+            max_speed: 64.0,
+            paused: false,
+
+            max_illuminance: 1000.0,
+            day_ambient: 0.3,
+            night_ambient: 0.02,
+            azimuth: 0.0
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Angle of the sun above the horizon, in radians
+    pub fn elevation(&self) -> f32 {
+        (self.time - 0.25) * TAU
+    }
+
+    /// How much of the sun's light currently reaches the ground, from 0.0 to 1.0
+    pub fn daylight(&self) -> f32 {
+        self.elevation().sin().max(0.0)
+    }
+}
+
+fn spawn_sun(mut commands: Commands) {
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        ..default()
+    })
+        .insert(Sun);
+}
+
+fn sky_controls(binds: Res<Input<SkyControls>>, mut time_of_day: ResMut<TimeOfDay>) {
+    if binds.just_pressed(SkyControls::Faster) {
+        time_of_day.speed = (time_of_day.speed * 2.0).min(time_of_day.max_speed);
+    }
+    if binds.just_pressed(SkyControls::Slower) {
+        time_of_day.speed = (time_of_day.speed / 2.0).max(1.0 / time_of_day.max_speed);
+    }
+    if binds.just_pressed(SkyControls::Pause) {
+        time_of_day.paused = !time_of_day.paused;
+    }
+}
+
+fn advance_time_of_day(time: Res<Time>, mut time_of_day: ResMut<TimeOfDay>) {
+    if !time_of_day.paused {
+        let delta = time.delta_seconds() * time_of_day.speed / time_of_day.day_length;
+        time_of_day.time = (time_of_day.time + delta).rem_euclid(1.0);
+    }
+}
+
+fn move_sun(
+    time_of_day: Res<TimeOfDay>,
+    mut ambient: ResMut<AmbientLight>,
+    mut sun: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>
+) {
+    if !time_of_day.is_changed() {
+        return;
+    }
+
+    let daylight = time_of_day.daylight();
+    for (mut transform, mut light) in &mut sun {
+        // the light shines along its local -z, which gets pitched down by the sun's elevation
+        // and then swung around to rise in the east
+        transform.rotation = Quat::from_rotation_y(time_of_day.azimuth + TAU / 4.0)
+            * Quat::from_rotation_x(-time_of_day.elevation());
+        light.illuminance = time_of_day.max_illuminance * daylight;
+    }
+    ambient.brightness = time_of_day.night_ambient + (time_of_day.day_ambient - time_of_day.night_ambient) * daylight;
+}
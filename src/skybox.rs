@@ -0,0 +1,180 @@
+use std::marker::PhantomData;
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::asset::{Assets, AssetServer, Handle};
+use bevy::log::info;
+use bevy::math::Vec3;
+use bevy::pbr::{AmbientLight, NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Color, Commands, Component, IntoSystemDescriptor, Mesh, Query, Res, ResMut, Resource, Transform, Visibility, With, Without};
+use bevy::transform::TransformSystem;
+use bevy::utils::default;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::free_control::ActiveControl;
+use crate::game_state::LoadingAssets;
+
+/// Surrounds the entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T] with a sky dome
+/// textured with an equirectangular image (such as an `.hdr` panorama), in place of the plain
+/// clear color, and tints the [AmbientLight] to go with it.
+///
+/// The skyboxes to choose from are in [SkyboxConfig], the `skybox` console command lists them or
+/// switches to the one named. Bevy doesn't do image based lighting yet, so the environment
+/// lighting is only the ambient color of the skybox, the brightness is left to the
+/// [SkyPlugin](crate::sky::SkyPlugin).
+///
+/// No panoramas ship with the playground, so there are no skyboxes by default and the dome stays
+/// hidden, leaving the plain clear color. To add some, put the images under the assets directory
+/// and insert a [SkyboxConfig] listing them before adding this plugin:
+///
+/// ```ignore
+/// app.insert_resource(SkyboxConfig {
+///     skyboxes: vec![Skybox {
+///         name: "day".to_string(),
+///         path: "skyboxes/day.hdr".to_string(),
+///         ambient: Color::rgb(0.75, 0.85, 1.0)
+///     }],
+///     ..default()
+/// });
+/// ```
+pub struct SkyboxPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for SkyboxPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for SkyboxPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<SkyboxConfig>() {
+            app.insert_resource(SkyboxConfig::default());
+        }
+        app
+            .add_startup_system(spawn_skybox)
+            .add_system(switch_skybox)
+            .add_system_to_stage(CoreStage::PostUpdate, follow_skybox::<T>.before(TransformSystem::TransformPropagate))
+            .add_console_command("skybox", "lists the skyboxes, or switches to the one named", |world, args| {
+                let mut config = world.resource_mut::<SkyboxConfig>();
+                let line = match args.first() {
+                    None if config.skyboxes.is_empty() => "no skyboxes, they're added through SkyboxConfig".to_string(),
+                    None => {
+                        let names = config.skyboxes.iter().map(|skybox| skybox.name.as_str()).collect::<Vec<_>>();
+                        format!("skyboxes: {}", names.join(", "))
+                    }
+                    Some(name) => match config.skyboxes.iter().position(|skybox| skybox.name == *name) {
+                        Some(index) => {
+                            config.current = index;
+                            format!("switched to skybox {}", name)
+                        }
+                        None => format!("unknown skybox `{}`", name)
+                    }
+                };
+                console_print(world, line);
+            });
+    }
+}
+
+#[derive(Clone)]
+pub struct Skybox {
+    pub name: String,
+    /// Path of the equirectangular image, relative to the assets directory
+    pub path: String,
+    /// Color of the ambient light while this skybox is in use
+    pub ambient: Color
+}
+
+#[derive(Resource, Clone)]
+pub struct SkyboxConfig {
+    pub skyboxes: Vec<Skybox>,
+    /// Index into `skyboxes` of the one in use, changing it switches skyboxes. Without a skybox
+    /// there the dome is hidden
+    pub current: usize,
+    /// Radius of the sky dome, this has to fit within the far plane of the camera
+    pub radius: f32
+}
+
+impl Default for SkyboxConfig {
+    fn default() -> Self {
+        Self {
+            skyboxes: Vec::new(),
+            current: 0,
+            radius: 500.0
+        }
+    }
+}
+
+#[derive(Component)]
+struct SkyDome;
+
+fn spawn_skybox(
+    mut commands: Commands,
+    config: Res<SkyboxConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(shape::UVSphere { radius: config.radius, sectors: 64, stacks: 32 }.into()),
+        material: materials.add(StandardMaterial {
+            unlit: true,
+            // seen from the inside
+            cull_mode: None,
+            ..default()
+        }),
+        // mirrored, otherwise the image would be flipped from the inside
+        transform: Transform::from_scale(Vec3::new(-1.0, 1.0, 1.0)),
+        // until there's a skybox to show
+        visibility: Visibility { is_visible: false },
+        ..default()
+    })
+        .insert((SkyDome, NotShadowCaster));
+}
+
+fn switch_skybox(
+    config: Res<SkyboxConfig>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ambient: ResMut<AmbientLight>,
+    loading: Option<ResMut<LoadingAssets>>,
+    mut domes: Query<(&Handle<StandardMaterial>, &mut Visibility), With<SkyDome>>
+) {
+    if !config.is_changed() {
+        return;
+    }
+    let skybox = config.skyboxes.get(config.current);
+    for (_, mut visibility) in &mut domes {
+        visibility.is_visible = skybox.is_some();
+    }
+    let Some(skybox) = skybox else {
+        return;
+    };
+    let texture = asset_server.load(skybox.path.as_str());
+    if let Some(mut loading) = loading {
+        loading.track(texture.clone_untyped());
+    }
+    for (handle, _) in &domes {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color_texture = Some(texture.clone());
+        }
+    }
+    ambient.color = skybox.ambient;
+    info!("using skybox {}", skybox.name);
+}
+
+fn follow_skybox<T: Component>(
+    active: Res<ActiveControl<T>>,
+    controlled: Query<&Transform, (With<T>, Without<SkyDome>)>,
+    mut domes: Query<&mut Transform, With<SkyDome>>
+) {
+    let Some(followed) = active.entity.and_then(|entity| controlled.get(entity).ok()) else {
+        return;
+    };
+    let translation = followed.translation;
+    for mut transform in &mut domes {
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
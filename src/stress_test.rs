@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::{BuildChildren, DespawnRecursiveExt};
+use bevy::log::info;
+use bevy::math::{IVec2, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Color, Commands, Component, Entity, Mesh, Res, ResMut, Resource, SpatialBundle, Transform, With};
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, RigidBody};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::lod::Lod;
+
+/// Spawns a lot of static cubes for finding where rendering (and optionally rapier) gives up, with
+/// the `stress_test <count> [individual|merged|physics]` console command, `stress_test clear`
+/// removes them again. The cubes are laid out on a grid split into chunks of
+/// [StressTestConfig::chunk_size] by [StressTestConfig::chunk_size] cubes, and
+/// [StressTestConfig::chunks_per_frame] chunks are spawned each frame, so starting a test doesn't
+/// stall on one huge frame.
+///
+/// How the cubes are drawn is up to the [StressTestMode]. Every cube shares one mesh and one
+/// material either way, which is what Bevy needs to batch them well. Bevy doesn't do GPU
+/// instancing on its own yet, merging each chunk into a single mesh is the closest thing to it
+/// here: one entity and one draw per chunk. Cubes (or merged chunks) far enough from the camera
+/// are hidden by the [LodPlugin](crate::lod::LodPlugin), see
+/// [GraphicsSettings::lod_hide](crate::graphics::GraphicsSettings::lod_hide).
+pub struct StressTestPlugin;
+
+impl Plugin for StressTestPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<StressTestConfig>() {
+            app.insert_resource(StressTestConfig::default());
+        }
+        app
+            .init_resource::<StressTest>()
+            .add_system(spawn_stress_chunks)
+            .add_console_command("stress_test", "spawns the given number of cubes, as individual entities, merged chunks or physics bodies, or clears them", |world, args| {
+                if args.first().copied() == Some("clear") {
+                    let chunks = world
+                        .query_filtered::<Entity, With<StressTestChunk>>()
+                        .iter(world)
+                        .collect::<Vec<_>>();
+                    for chunk in chunks {
+                        world.entity_mut(chunk).despawn_recursive();
+                    }
+                    world.resource_mut::<StressTest>().pending.clear();
+                    console_print(world, "cleared the stress test");
+                    return;
+                }
+                let count = args.first().and_then(|count| count.parse::<usize>().ok());
+                let mode = match args.get(1).copied() {
+                    None | Some("individual") => Some(StressTestMode::Individual),
+                    Some("merged") => Some(StressTestMode::Merged),
+                    Some("physics") => Some(StressTestMode::Physics),
+                    Some(_) => None
+                };
+                let (Some(count), Some(mode)) = (count, mode) else {
+                    console_print(world, "usage: stress_test <count|clear> [individual|merged|physics]");
+                    return;
+                };
+                let config = world.resource::<StressTestConfig>().clone();
+                let mut test = world.resource_mut::<StressTest>();
+                test.queue(&config, count, mode);
+                let chunks = test.pending.len();
+                console_print(world, format!("spawning {} cubes in {} chunks", count, chunks));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct StressTestConfig {
+    /// Cubes along each side of a chunk
+    pub chunk_size: usize,
+    pub chunks_per_frame: usize,
+    pub cube_size: f32,
+    /// Distance between the centers of neighbouring cubes
+    pub spacing: f32,
+    /// Where the corner of the grid is
+    pub origin: Vec3,
+    pub color: Color
+}
+
+impl Default for StressTestConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 32,
+            chunks_per_frame: 4,
+            cube_size: 0.5,
+            spacing: 1.0,
+            origin: Vec3::new(20.0, 0.25, 20.0),
+            color: Color::rgb(0.7, 0.4, 0.8)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StressTestMode {
+    /// An entity for each cube
+    Individual,
+    /// A single mesh for each chunk
+    Merged,
+    /// An entity with a fixed rigid body and collider for each cube
+    Physics
+}
+
+/// Chunks waiting to be spawned
+#[derive(Resource, Default)]
+pub struct StressTest {
+    pending: VecDeque<PendingChunk>,
+    assets: Option<(Handle<Mesh>, Handle<StandardMaterial>)>
+}
+
+struct PendingChunk {
+    chunk: IVec2,
+    /// Cubes in the chunk, filled row by row
+    count: usize,
+    mode: StressTestMode
+}
+
+impl StressTest {
+    /// Queues `count` cubes, filling the chunks of a square grid of them row by row
+    pub fn queue(&mut self, config: &StressTestConfig, count: usize, mode: StressTestMode) {
+        let per_chunk = config.chunk_size * config.chunk_size;
+        let chunks = (count + per_chunk - 1) / per_chunk;
+        let side = (chunks as f32).sqrt().ceil().max(1.0) as usize;
+        for index in 0..chunks {
+            let remaining = count - index * per_chunk;
+            self.pending.push_back(PendingChunk {
+                chunk: IVec2::new((index % side) as i32, (index / side) as i32),
+                count: remaining.min(per_chunk),
+                mode
+            });
+        }
+    }
+}
+
+/// The root of every chunk, the cubes are its children unless it's merged
+#[derive(Component)]
+pub struct StressTestChunk;
+
+fn spawn_stress_chunks(
+    mut commands: Commands,
+    config: Res<StressTestConfig>,
+    mut test: ResMut<StressTest>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    if test.pending.is_empty() {
+        return;
+    }
+    let (mesh, material) = test.assets.get_or_insert_with(|| (
+        meshes.add(shape::Cube { size: config.cube_size }.into()),
+        materials.add(config.color.into())
+    )).clone();
+
+    let chunk_extent = config.chunk_size as f32 * config.spacing;
+    for _ in 0..config.chunks_per_frame {
+        let Some(pending) = test.pending.pop_front() else {
+            break;
+        };
+        let corner = config.origin + Vec3::new(pending.chunk.x as f32, 0.0, pending.chunk.y as f32) * chunk_extent;
+        let offsets = (0..pending.count)
+            .map(|index| Vec3::new((index % config.chunk_size) as f32, 0.0, (index / config.chunk_size) as f32) * config.spacing)
+            .collect::<Vec<_>>();
+        match pending.mode {
+            StressTestMode::Merged => {
+                let merged = meshes.add(merge_cubes(config.cube_size, &offsets));
+                commands.spawn((
+                    PbrBundle {
+                        mesh: merged.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(corner),
+                        ..default()
+                    },
+                    Lod::new(merged),
+                    StressTestChunk
+                ));
+            }
+            StressTestMode::Individual | StressTestMode::Physics => {
+                let physics = pending.mode == StressTestMode::Physics;
+                commands.spawn((SpatialBundle::from_transform(Transform::from_translation(corner)), StressTestChunk))
+                    .with_children(|chunk| {
+                        for offset in offsets {
+                            let mut cube = chunk.spawn((
+                                PbrBundle {
+                                    mesh: mesh.clone(),
+                                    material: material.clone(),
+                                    transform: Transform::from_translation(offset),
+                                    ..default()
+                                },
+                                Lod::new(mesh.clone())
+                            ));
+                            if physics {
+                                let half = config.cube_size / 2.0;
+                                cube.insert((RigidBody::Fixed, Collider::cuboid(half, half, half)));
+                            }
+                        }
+                    });
+            }
+        }
+    }
+    if test.pending.is_empty() {
+        info!("stress test done spawning");
+    }
+}
+
+/// One mesh with a cube at each offset
+fn merge_cubes(size: f32, offsets: &[Vec3]) -> Mesh {
+    let cube = Mesh::from(shape::Cube { size });
+    let (Some(VertexAttributeValues::Float32x3(positions)), Some(VertexAttributeValues::Float32x3(normals)), Some(VertexAttributeValues::Float32x2(uvs)), Some(Indices::U32(indices))) = (
+        cube.attribute(Mesh::ATTRIBUTE_POSITION),
+        cube.attribute(Mesh::ATTRIBUTE_NORMAL),
+        cube.attribute(Mesh::ATTRIBUTE_UV_0),
+        cube.indices()
+    ) else {
+        unreachable!("shape::Cube always has positions, normals, uvs and u32 indices");
+    };
+
+    let mut merged_positions = Vec::with_capacity(positions.len() * offsets.len());
+    let mut merged_normals = Vec::with_capacity(normals.len() * offsets.len());
+    let mut merged_uvs = Vec::with_capacity(uvs.len() * offsets.len());
+    let mut merged_indices = Vec::with_capacity(indices.len() * offsets.len());
+    for offset in offsets {
+        let base = merged_positions.len() as u32;
+        merged_positions.extend(positions.iter().map(|position| (Vec3::from(*position) + *offset).to_array()));
+        merged_normals.extend_from_slice(normals);
+        merged_uvs.extend_from_slice(uvs);
+        merged_indices.extend(indices.iter().map(|index| base + index));
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, merged_positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, merged_normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, merged_uvs);
+    mesh.set_indices(Some(Indices::U32(merged_indices)));
+    mesh
+}
@@ -0,0 +1,228 @@
+use bevy::app::{App, CoreStage, Plugin};
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::{Changed, Commands, Component, Entity, Query, ReflectComponent, RemovedComponents, ResMut, Resource, World};
+use bevy::reflect::Reflect;
+use bevy::utils::HashMap;
+use bevy_rapier3d::prelude::RigidBody;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::edit_history::EditCommands;
+use crate::object_inspector::{Frozen, Inspected};
+use crate::picking::PickTarget;
+use crate::selection::Selection;
+
+/// String tags on entities, so console commands can act on whole groups of them at once. Tagged
+/// entities are kept in the [TagIndex], which is brought up to date at the end of every frame.
+///
+/// The `tag` and `untag` console commands add and remove a tag, `tags` lists every tag in use, and
+/// `despawn`, `freeze` and `unfreeze` do what the
+/// [ObjectInspectorPlugin](crate::object_inspector::ObjectInspectorPlugin) buttons do. Each of
+/// them takes `tag:<name>` arguments for the entities with those tags, and without any goes for
+/// the [Selection], the [Inspected] entity or the [PickTarget], in that order. The inspector panel
+/// shows the tags of what it inspects.
+///
+/// [Tags] are reflected, so they go into save files with the rest of a [Saved](crate::save::Saved)
+/// entity.
+pub struct TagPlugin;
+
+impl Plugin for TagPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<Tags>()
+            .register_type::<Vec<String>>()
+            .init_resource::<TagIndex>()
+            // last, so it sees the tags removed by every other stage
+            .add_system_to_stage(CoreStage::Last, index_tags)
+            .add_console_command("tag", "tags the targets, `tag:<name>` arguments or the selection", |world, args| {
+                let Some(tag) = args.first().filter(|tag| !tag.starts_with(TARGET_PREFIX)) else {
+                    console_print(world, "usage: tag <name> [tag:<name>...]");
+                    return;
+                };
+                let targets = command_targets(world, &args[1..]);
+                for entity in &targets {
+                    let Some(mut entity) = world.get_entity_mut(*entity) else {
+                        continue;
+                    };
+                    match entity.get_mut::<Tags>() {
+                        Some(mut tags) => tags.insert(tag),
+                        None => {
+                            entity.insert(Tags(vec![tag.to_string()]));
+                        }
+                    }
+                }
+                if !targets.is_empty() {
+                    // so the tag can be used right away, before the index catches up
+                    world.resource_mut::<TagIndex>().insert(tag, &targets);
+                }
+                console_print(world, format!("tagged {} with `{}`", targets.len(), tag));
+            })
+            .add_console_command("untag", "removes a tag from the targets, `tag:<name>` arguments or the selection", |world, args| {
+                let Some(tag) = args.first() else {
+                    console_print(world, "usage: untag <name> [tag:<name>...]");
+                    return;
+                };
+                let targets = command_targets(world, &args[1..]);
+                let mut removed = 0;
+                for entity in targets {
+                    if let Some(mut tags) = world.get_mut::<Tags>(entity) {
+                        if tags.remove(tag) {
+                            removed += 1;
+                        }
+                    }
+                }
+                console_print(world, format!("untagged {}", removed));
+            })
+            .add_console_command("tags", "lists every tag and how many entities have it", |world, _| {
+                let mut tags = world.resource::<TagIndex>().entities.iter()
+                    .map(|(tag, entities)| (tag.clone(), entities.len()))
+                    .collect::<Vec<_>>();
+                tags.sort();
+                if tags.is_empty() {
+                    console_print(world, "nothing is tagged");
+                }
+                for (tag, count) in tags {
+                    console_print(world, format!("{}: {}", tag, count));
+                }
+            })
+            .add_console_command("despawn", "despawns the targets, `tag:<name>` arguments or the selection", |world, args| {
+                let targets = command_targets(world, args);
+                let count = targets.len();
+                let mut queue = CommandQueue::default();
+                Commands::new(&mut queue, world).despawn_recorded_group(targets);
+                queue.apply(world);
+                console_print(world, format!("despawned {}", count));
+            })
+            .add_console_command("freeze", "freezes the bodies of the targets, `tag:<name>` arguments or the selection", |world, args| {
+                let targets = command_targets(world, args);
+                let frozen = targets.into_iter().filter(|entity| freeze(world, *entity)).count();
+                console_print(world, format!("froze {}", frozen));
+            })
+            .add_console_command("unfreeze", "unfreezes the bodies of the targets, `tag:<name>` arguments or the selection", |world, args| {
+                let targets = command_targets(world, args);
+                let unfrozen = targets.into_iter().filter(|entity| unfreeze(world, *entity)).count();
+                console_print(world, format!("unfroze {}", unfrozen));
+            });
+    }
+}
+
+/// Console arguments starting with this name a tag to act on
+pub const TARGET_PREFIX: &str = "tag:";
+
+/// Tags of an entity, in the order they were added
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|t| t == tag)
+    }
+
+    pub fn insert(&mut self, tag: &str) {
+        if !self.contains(tag) {
+            self.0.push(tag.to_string());
+        }
+    }
+
+    /// Whether it had the tag
+    pub fn remove(&mut self, tag: &str) -> bool {
+        let len = self.0.len();
+        self.0.retain(|t| t != tag);
+        self.0.len() != len
+    }
+}
+
+/// Every tagged entity by tag, as of the end of the last frame and the `tag` console commands run
+/// since
+#[derive(Resource, Default)]
+pub struct TagIndex {
+    entities: HashMap<String, Vec<Entity>>
+}
+
+impl TagIndex {
+    /// The entities with `tag`, which might include ones untagged or despawned since the end of the
+    /// last frame
+    pub fn get(&self, tag: &str) -> &[Entity] {
+        self.entities.get(tag).map_or(&[], |entities| entities.as_slice())
+    }
+
+    fn insert(&mut self, tag: &str, entities: &[Entity]) {
+        let tagged = self.entities.entry(tag.to_string()).or_default();
+        for entity in entities {
+            if !tagged.contains(entity) {
+                tagged.push(*entity);
+            }
+        }
+    }
+}
+
+/// The entities a console command acts on, those with the tags of any `tag:<name>` arguments, or
+/// without any the [Selection], the [Inspected] entity or the [PickTarget]
+pub fn command_targets(world: &mut World, args: &[&str]) -> Vec<Entity> {
+    let tags = args.iter()
+        .filter_map(|arg| arg.strip_prefix(TARGET_PREFIX))
+        .collect::<Vec<_>>();
+    if !tags.is_empty() {
+        let mut entities = Vec::new();
+        for tag in tags {
+            let tagged = world.resource::<TagIndex>().get(tag).to_vec();
+            for entity in tagged {
+                // untagged or despawned since the end of the last frame
+                let still_tagged = world.get::<Tags>(entity).map_or(false, |tags| tags.contains(tag));
+                if still_tagged && !entities.contains(&entity) {
+                    entities.push(entity);
+                }
+            }
+        }
+        return entities;
+    }
+    let selected = world.get_resource::<Selection>()
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| selection.iter().collect::<Vec<_>>());
+    selected
+        .or_else(|| world.get_resource::<Inspected>().and_then(|inspected| inspected.0).map(|entity| vec![entity]))
+        .or_else(|| world.get_resource::<PickTarget>().and_then(|target| target.entity).map(|entity| vec![entity]))
+        .unwrap_or_default()
+}
+
+/// Makes the body of `entity` [RigidBody::Fixed] like the inspector does, whether it was moving
+pub fn freeze(world: &mut World, entity: Entity) -> bool {
+    let Some(mut entity) = world.get_entity_mut(entity) else {
+        return false;
+    };
+    match entity.get::<RigidBody>().copied() {
+        Some(body) if body != RigidBody::Fixed && !entity.contains::<Frozen>() => {
+            entity.insert((RigidBody::Fixed, Frozen(body)));
+            true
+        }
+        _ => false
+    }
+}
+
+/// Gives `entity` back the body it had before it was frozen, whether it was frozen
+pub fn unfreeze(world: &mut World, entity: Entity) -> bool {
+    let Some(mut entity) = world.get_entity_mut(entity) else {
+        return false;
+    };
+    let Some(Frozen(body)) = entity.remove::<Frozen>() else {
+        return false;
+    };
+    entity.insert(body);
+    true
+}
+
+fn index_tags(
+    mut index: ResMut<TagIndex>,
+    changed: Query<(), Changed<Tags>>,
+    removed: RemovedComponents<Tags>,
+    tags: Query<(Entity, &Tags)>
+) {
+    if changed.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+    index.entities.clear();
+    for (entity, tags) in &tags {
+        for tag in &tags.0 {
+            index.entities.entry(tag.clone()).or_default().push(entity);
+        }
+    }
+}
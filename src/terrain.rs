@@ -0,0 +1,422 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::{AddAsset, AssetEvent, AssetLoader, Assets, AssetServer, Handle, LoadContext, LoadedAsset};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::math::{DVec2, DVec3, IVec2, Vec2, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{Color, Commands, Component, Entity, EventReader, FromWorld, Mesh, Query, Res, ResMut, Resource, Transform, With, World};
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::utils::{default, BoxedFuture, HashMap, HashSet};
+use bevy_rapier3d::prelude::{Collider, RigidBody};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use crate::floating_origin::FloatingOrigin;
+use crate::game_state::LoadingAssets;
+
+/// Generates noise driven terrain in square chunks, streaming them in and out around all
+/// entities with the provided marker [T]. Each chunk gets a render mesh and a matching rapier
+/// heightfield collider.
+///
+/// The [TerrainConfig] resource controls the shape of the terrain and how far it streams, it's
+/// only read when chunks are generated so changing it only affects chunks spawned afterwards.
+/// [TerrainConfig::enabled] is the exception, turning the terrain off unloads it right away.
+///
+/// Chunk coordinates are absolute, so with the
+/// [FloatingOriginPlugin](crate::floating_origin::FloatingOriginPlugin) the terrain stays the
+/// same wherever the origin ends up.
+///
+/// Instead of noise, the heights can come from a grayscale [Heightmap] image
+/// (`.heightmap.png` or `.heightmap.exr`) given by [TerrainConfig::heightmap], which is loaded
+/// on startup. Chunks wait for it to load, and are all generated again if it changes while
+/// running (with `watch_for_changes` enabled on Bevy's `AssetPlugin`).
+pub struct TerrainPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for TerrainPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for TerrainPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<TerrainConfig>() {
+            app.insert_resource(TerrainConfig::default());
+        }
+        app
+            .add_asset::<Heightmap>()
+            .init_asset_loader::<HeightmapLoader>()
+            .init_resource::<TerrainChunks>()
+            .init_resource::<TerrainMaterial>()
+            .add_startup_system(load_heightmap)
+            .add_system(stream_terrain::<T>);
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct TerrainConfig {
+    /// Turning it off unloads every chunk, until it's turned on again
+    pub enabled: bool,
+    pub seed: u32,
+    /// Width and depth of a chunk in world units
+    pub chunk_size: f32,
+    /// Number of quads along each side of a chunk
+    pub resolution: usize,
+    /// Height of the terrain where the noise is 0
+    pub base_height: f32,
+    /// How far the terrain reaches above and below `base_height`
+    pub amplitude: f32,
+    pub frequency: f64,
+    pub octaves: usize,
+    /// Chunks within this many chunks of a streaming entity are kept loaded
+    pub view_distance: i32,
+    /// Upper limit on chunks generated in a single frame, keeps flying fast from causing hitches
+    pub chunks_per_frame: usize,
+    /// A [Heightmap] to take heights from instead of noise, relative to the assets directory.
+    /// Only read on startup
+    pub heightmap: Option<String>,
+    /// How far above `base_height` white is on the heightmap, black being at `base_height`
+    pub heightmap_scale: f32,
+    /// Distance between the heightmap's pixels in world units, the heightmap is centered on the
+    /// origin and its edges carry on past it
+    pub heightmap_spacing: f32
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            seed: 0,
+            chunk_size: 32.0,
+            resolution: 32,
+            base_height: -10.0,
+            amplitude: 8.0,
+            frequency: 0.01,
+            octaves: 4,
+            view_distance: 4,
+            chunks_per_frame: 2,
+            heightmap: None,
+            heightmap_scale: 40.0,
+            heightmap_spacing: 1.0
+        }
+    }
+}
+
+impl TerrainConfig {
+    fn noise(&self) -> Fbm<Perlin> {
+        Fbm::<Perlin>::new(self.seed)
+            .set_frequency(self.frequency)
+            .set_octaves(self.octaves)
+    }
+
+    /// The chunk containing the provided world position
+    pub fn chunk_at(&self, translation: Vec3) -> IVec2 {
+        (Vec2::new(translation.x, translation.z) / self.chunk_size).floor().as_ivec2()
+    }
+}
+
+/// All currently loaded chunks, keyed by chunk coordinate
+#[derive(Resource, Default)]
+pub struct TerrainChunks {
+    pub loaded: HashMap<IVec2, Entity>
+}
+
+#[derive(Component)]
+pub struct TerrainChunk {
+    pub coord: IVec2
+}
+
+#[derive(Resource)]
+struct TerrainMaterial(Handle<StandardMaterial>);
+
+impl FromWorld for TerrainMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        TerrainMaterial(materials.add(StandardMaterial {
+            base_color: Color::rgb(0.35, 0.5, 0.3),
+            perceptual_roughness: 0.9,
+            ..default()
+        }))
+    }
+}
+
+/// Heights of a chunk sampled on a `(resolution + 1)²` grid, in rapier's heightfield layout
+/// (column major, rows along z and columns along x)
+pub struct ChunkHeights {
+    pub heights: Vec<f32>,
+    pub samples: usize
+}
+
+impl ChunkHeights {
+    pub fn generate(config: &TerrainConfig, coord: IVec2) -> Self {
+        let noise = config.noise();
+        Self::sample(config, coord, |x, z| config.base_height + noise.get([x, z]) as f32 * config.amplitude)
+    }
+
+    pub fn from_heightmap(config: &TerrainConfig, heightmap: &Heightmap, coord: IVec2) -> Self {
+        let spacing = config.heightmap_spacing as f64;
+        // the middle pixel at the origin
+        let center = DVec2::new(heightmap.width as f64, heightmap.depth as f64) / 2.0;
+        Self::sample(config, coord, |x, z| {
+            let pixel = DVec2::new(x, z) / spacing + center;
+            config.base_height + heightmap.sample(pixel.as_vec2()) * config.heightmap_scale
+        })
+    }
+
+    /// Heights from `height` at each sample's world x and z
+    fn sample(config: &TerrainConfig, coord: IVec2, height: impl Fn(f64, f64) -> f32) -> Self {
+        let samples = config.resolution + 1;
+        let step = config.chunk_size / config.resolution as f32;
+        // in f64, far from the origin f32 would no longer line up with the neighbouring chunks
+        let origin = coord.as_dvec2() * config.chunk_size as f64;
+
+        let mut heights = Vec::with_capacity(samples * samples);
+        for col in 0..samples {
+            for row in 0..samples {
+                let x = origin.x + (col as f32 * step) as f64;
+                let z = origin.y + (row as f32 * step) as f64;
+                heights.push(height(x, z));
+            }
+        }
+
+        Self {
+            heights,
+            samples
+        }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.heights[row + col * self.samples]
+    }
+
+    /// Builds a mesh centered on the chunk, which is how rapier lays out heightfields
+    pub fn mesh(&self, chunk_size: f32) -> Mesh {
+        let n = self.samples;
+        let step = chunk_size / (n - 1) as f32;
+        let half = chunk_size / 2.0;
+
+        let mut positions = Vec::with_capacity(n * n);
+        let mut normals = Vec::with_capacity(n * n);
+        let mut uvs = Vec::with_capacity(n * n);
+        for row in 0..n {
+            for col in 0..n {
+                positions.push([col as f32 * step - half, self.get(row, col), row as f32 * step - half]);
+
+                // central differences, clamped at the chunk's edges
+                let left = self.get(row, col.saturating_sub(1));
+                let right = self.get(row, (col + 1).min(n - 1));
+                let back = self.get(row.saturating_sub(1), col);
+                let front = self.get((row + 1).min(n - 1), col);
+                let normal = Vec3::new(left - right, 2.0 * step, back - front).normalize();
+                normals.push(normal.to_array());
+
+                uvs.push([col as f32 / (n - 1) as f32, row as f32 / (n - 1) as f32]);
+            }
+        }
+
+        let mut indices = Vec::with_capacity((n - 1) * (n - 1) * 6);
+        for row in 0..n as u32 - 1 {
+            for col in 0..n as u32 - 1 {
+                let i = row * n as u32 + col;
+                let below = i + n as u32;
+                indices.extend_from_slice(&[i, below, i + 1, i + 1, below, below + 1]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+
+    pub fn collider(self, chunk_size: f32) -> Collider {
+        let n = self.samples;
+        Collider::heightfield(self.heights, n, n, Vec3::new(chunk_size, 1.0, chunk_size))
+    }
+}
+
+/// Grayscale heights, from 0 for black to 1 for white
+#[derive(TypeUuid)]
+#[uuid = "3c8e1f60-7b2d-4a95-8d04-e6f19a2b5c71"]
+pub struct Heightmap {
+    /// Pixels along x
+    pub width: usize,
+    /// Pixels along z
+    pub depth: usize,
+    /// Row by row, rows along x
+    pub heights: Vec<f32>
+}
+
+impl Heightmap {
+    /// The height at `pixel` (which can be between pixels), clamped to the edges
+    pub fn sample(&self, pixel: Vec2) -> f32 {
+        let max = Vec2::new(self.width as f32 - 1.0, self.depth as f32 - 1.0);
+        let pixel = pixel.clamp(Vec2::ZERO, max.max(Vec2::ZERO));
+        let low = pixel.floor();
+        let fraction = pixel - low;
+        let get = |x: f32, z: f32| {
+            let x = (x as usize).min(self.width - 1);
+            let z = (z as usize).min(self.depth - 1);
+            self.heights[x + z * self.width]
+        };
+        let back = get(low.x, low.y) * (1.0 - fraction.x) + get(low.x + 1.0, low.y) * fraction.x;
+        let front = get(low.x, low.y + 1.0) * (1.0 - fraction.x) + get(low.x + 1.0, low.y + 1.0) * fraction.x;
+        back * (1.0 - fraction.y) + front * fraction.y
+    }
+}
+
+#[derive(Default)]
+pub struct HeightmapLoader;
+
+impl AssetLoader for HeightmapLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            // 32 bit floats keep the precision of 16 bit pngs and exrs, where 8 bits would step
+            let image = image::load_from_memory(bytes)?.to_luma32f();
+            let (width, depth) = image.dimensions();
+            if width == 0 || depth == 0 {
+                return Err(bevy::asset::Error::msg("the heightmap is empty"));
+            }
+            load_context.set_default_asset(LoadedAsset::new(Heightmap {
+                width: width as usize,
+                depth: depth as usize,
+                heights: image.into_raw()
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["heightmap.png", "heightmap.exr"]
+    }
+}
+
+/// The [Heightmap] given by [TerrainConfig::heightmap]
+#[derive(Resource)]
+pub struct TerrainHeightmap(pub Handle<Heightmap>);
+
+fn load_heightmap(
+    mut commands: Commands,
+    config: Res<TerrainConfig>,
+    asset_server: Res<AssetServer>,
+    loading: Option<ResMut<LoadingAssets>>
+) {
+    let Some(path) = &config.heightmap else {
+        return;
+    };
+    let handle = asset_server.load(path.as_str());
+    if let Some(mut loading) = loading {
+        loading.track(handle.clone_untyped());
+    }
+    commands.insert_resource(TerrainHeightmap(handle));
+}
+
+fn stream_terrain<T: Component>(
+    mut commands: Commands,
+    config: Res<TerrainConfig>,
+    material: Res<TerrainMaterial>,
+    mut chunks: ResMut<TerrainChunks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    heightmap: Option<Res<TerrainHeightmap>>,
+    heightmaps: Res<Assets<Heightmap>>,
+    mut heightmap_events: EventReader<AssetEvent<Heightmap>>,
+    origin: Option<Res<FloatingOrigin>>,
+    viewers: Query<&Transform, With<T>>
+) {
+    if !config.enabled {
+        // checked first so the chunks aren't marked as changed every frame
+        if !chunks.loaded.is_empty() {
+            for (_, entity) in chunks.loaded.drain() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        return;
+    }
+    let heightmap = match &heightmap {
+        Some(handle) => {
+            let changed = heightmap_events.iter().any(|event| match event {
+                AssetEvent::Modified { handle: modified } => *modified == handle.0,
+                _ => false
+            });
+            if changed {
+                for (_, entity) in chunks.loaded.drain() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            // nothing to generate from until it's loaded
+            let Some(heightmap) = heightmaps.get(&handle.0) else {
+                return;
+            };
+            Some(heightmap)
+        }
+        None => {
+            heightmap_events.clear();
+            None
+        }
+    };
+
+    let offset = origin.map_or(DVec3::ZERO, |origin| origin.offset);
+    let chunk_at = |transform: &Transform| config.chunk_at((transform.translation.as_dvec3() + offset).as_vec3());
+
+    let mut wanted = HashSet::new();
+    for transform in &viewers {
+        let center = chunk_at(transform);
+        for x in -config.view_distance..=config.view_distance {
+            for z in -config.view_distance..=config.view_distance {
+                wanted.insert(center + IVec2::new(x, z));
+            }
+        }
+    }
+
+    // chunks one past the view distance are kept, so hovering around a chunk border doesn't
+    // keep regenerating the same chunks
+    let keep = |coord: &IVec2| {
+        viewers.iter().any(|transform| {
+            let distance = (*coord - chunk_at(transform)).abs();
+            distance.max_element() <= config.view_distance + 1
+        })
+    };
+    chunks.loaded.retain(|coord, entity| {
+        let retain = keep(coord);
+        if !retain {
+            commands.entity(*entity).despawn_recursive();
+        }
+        retain
+    });
+
+    let mut missing = wanted
+        .into_iter()
+        .filter(|coord| !chunks.loaded.contains_key(coord))
+        .collect::<Vec<_>>();
+    // nearest first, so the chunks under the viewers show up before the distant ones
+    missing.sort_by_key(|coord| {
+        viewers
+            .iter()
+            .map(|transform| (*coord - chunk_at(transform)).abs().max_element())
+            .min()
+            .unwrap_or(0)
+    });
+
+    for coord in missing.into_iter().take(config.chunks_per_frame) {
+        let heights = match heightmap {
+            Some(heightmap) => ChunkHeights::from_heightmap(&config, heightmap, coord),
+            None => ChunkHeights::generate(&config, coord)
+        };
+        let center = ((coord.as_dvec2() + 0.5) * config.chunk_size as f64 - DVec2::new(offset.x, offset.z)).as_vec2();
+        let entity = commands.spawn(PbrBundle {
+            mesh: meshes.add(heights.mesh(config.chunk_size)),
+            material: material.0.clone(),
+            transform: Transform::from_xyz(center.x, -offset.y as f32, center.y),
+            ..default()
+        })
+            .insert((TerrainChunk { coord }, RigidBody::Fixed, heights.collider(config.chunk_size)))
+            .id();
+        chunks.loaded.insert(coord, entity);
+    }
+}
@@ -0,0 +1,207 @@
+use bevy::app::App;
+use bevy::ecs::event::Events;
+use bevy::input::{ButtonState, InputPlugin};
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButtonInput, MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::input::touch::{TouchInput, TouchPhase, Touches};
+use bevy::math::Vec2;
+use bevy::window::{Window, WindowDescriptor, WindowFocused, WindowId, Windows};
+use bevy::MinimalPlugins;
+use crate::keybind::{RawInput, TouchRegion, WheelDirection};
+
+/// Width and height of the primary window of [headless_app], in logical pixels
+pub const WINDOW_SIZE: Vec2 = Vec2::new(800.0, 600.0);
+
+/// An app with Bevy's core, time and input plugins and a focused primary window that nothing
+/// draws to, for adding the plugins under test to
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins)
+        .add_plugin(InputPlugin)
+        .add_event::<WindowFocused>();
+    let mut window = Window::new(
+        WindowId::primary(),
+        &WindowDescriptor::default(),
+        WINDOW_SIZE.x as u32,
+        WINDOW_SIZE.y as u32,
+        1.0,
+        None,
+        None
+    );
+    window.update_focused_status_from_backend(true);
+    let mut windows = Windows::default();
+    windows.add(window);
+    app.insert_resource(windows);
+    app
+}
+
+/// Scripted input for driving an [App] without winit, so systems depending on
+/// [KeyBindingPlugin](crate::keybind::KeyBindingPlugin) can be tested headlessly. Inputs are sent
+/// as the same events winit would send, so they go through Bevy's input handling and
+/// [map_keybinds](crate::keybind::map_keybinds) exactly like real input.
+///
+/// Inputs are queued into the current frame, [MockInput::wait] moves on to later frames:
+///
+/// ```ignore
+/// MockInput::new()
+///     .press(KeyCode::W)
+///     .wait(10)
+///     .release(KeyCode::W)
+///     .wait(1)
+///     .run(&mut app);
+/// ```
+///
+/// [RawInput::Touch] presses start a touch a quarter of the way across the primary window, on the
+/// side of its [TouchRegion], and [MockInput::touch_drag] drags them from there. [headless_app]
+/// makes an app with everything this needs.
+#[derive(Clone)]
+pub struct MockInput {
+    frames: Vec<Vec<MockEvent>>
+}
+
+#[derive(Copy, Clone)]
+enum MockEvent {
+    Press(RawInput),
+    Release(RawInput),
+    MouseMotion(Vec2),
+    TouchDrag(TouchRegion, Vec2)
+}
+
+// manually implemented, there always has to be a current frame to push onto
+
+impl Default for MockInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockInput {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![Vec::new()]
+        }
+    }
+
+    fn push(mut self, event: MockEvent) -> Self {
+        self.frames.last_mut().unwrap().push(event);
+        self
+    }
+
+    /// Presses the provided `input` in the current frame
+    pub fn press(self, input: impl Into<RawInput>) -> Self {
+        self.push(MockEvent::Press(input.into()))
+    }
+
+    /// Releases the provided `input` in the current frame
+    pub fn release(self, input: impl Into<RawInput>) -> Self {
+        self.push(MockEvent::Release(input.into()))
+    }
+
+    /// Presses the provided `input` in the current frame and releases it in the next one
+    pub fn tap(self, input: impl Into<RawInput>) -> Self {
+        let input = input.into();
+        self.press(input).wait(1).release(input)
+    }
+
+    /// Moves the mouse by `delta` in the current frame
+    pub fn mouse_motion(self, delta: Vec2) -> Self {
+        self.push(MockEvent::MouseMotion(delta))
+    }
+
+    /// Drags the touch pressing `region` by `delta` in the current frame, it has to have been
+    /// pressed in an earlier frame
+    pub fn touch_drag(self, region: TouchRegion, delta: Vec2) -> Self {
+        self.push(MockEvent::TouchDrag(region, delta))
+    }
+
+    /// Moves on by the provided number of `frames`, inputs afterwards are sent that many
+    /// updates later
+    pub fn wait(mut self, frames: usize) -> Self {
+        for _ in 0..frames {
+            self.frames.push(Vec::new());
+        }
+        self
+    }
+
+    /// Sends the scripted inputs to `app`, calling [App::update] once for every frame
+    pub fn run(self, app: &mut App) {
+        for frame in self.frames {
+            for event in frame {
+                match event {
+                    MockEvent::Press(input) => send_raw_input(app, input, ButtonState::Pressed),
+                    MockEvent::Release(input) => send_raw_input(app, input, ButtonState::Released),
+                    MockEvent::MouseMotion(delta) => {
+                        app.world.resource_mut::<Events<MouseMotion>>().send(MouseMotion { delta });
+                    }
+                    MockEvent::TouchDrag(region, delta) => {
+                        let start = touch_start(app, region);
+                        let position = app.world
+                            .resource::<Touches>()
+                            .get_pressed(region as u64)
+                            .map_or(start, |touch| touch.position());
+                        app.world.resource_mut::<Events<TouchInput>>().send(TouchInput {
+                            phase: TouchPhase::Moved,
+                            position: position + delta,
+                            force: None,
+                            id: region as u64
+                        });
+                    }
+                }
+            }
+            app.update();
+        }
+    }
+}
+
+fn send_raw_input(app: &mut App, input: RawInput, state: ButtonState) {
+    match input {
+        RawInput::KeyCode(key_code) => {
+            app.world.resource_mut::<Events<KeyboardInput>>().send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state
+            });
+        }
+        RawInput::MouseButton(button) => {
+            app.world.resource_mut::<Events<MouseButtonInput>>().send(MouseButtonInput {
+                button,
+                state
+            });
+        }
+        RawInput::Touch(region) => {
+            let position = touch_start(app, region);
+            app.world.resource_mut::<Events<TouchInput>>().send(TouchInput {
+                phase: if state == ButtonState::Pressed { TouchPhase::Started } else { TouchPhase::Ended },
+                position,
+                force: None,
+                // one finger for each region, so releasing a region lifts the finger pressing it
+                id: region as u64
+            });
+        }
+        // releasing happens on its own the frame after scrolling stops
+        RawInput::MouseWheel(_) if state == ButtonState::Released => {}
+        RawInput::MouseWheel(direction) => {
+            app.world.resource_mut::<Events<MouseWheel>>().send(MouseWheel {
+                unit: MouseScrollUnit::Line,
+                x: 0.0,
+                y: if direction == WheelDirection::Up { 1.0 } else { -1.0 }
+            });
+        }
+    }
+}
+
+/// Where touches pressing `region` start, a quarter of the way across the primary window
+fn touch_start(app: &App, region: TouchRegion) -> Vec2 {
+    let width = app.world
+        .get_resource::<Windows>()
+        .and_then(|windows| windows.get_primary())
+        .map_or(0.0, |window| window.width());
+    // the middle counts as the right half, the left one is kept clear of it even without a window
+    // to be half of
+    let x = match region {
+        TouchRegion::Left => width * 0.25 - 1.0,
+        TouchRegion::Right => width * 0.75
+    };
+    Vec2::new(x, 0.0)
+}
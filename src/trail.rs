@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+use bevy::app::{App, Plugin};
+use bevy::asset::{Assets, Handle};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::math::Vec3;
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{Added, AlphaMode, Color, Commands, Component, Entity, EventReader, GlobalTransform, Local, Mesh, Query, Res, ResMut, Resource, Transform, With, World};
+use bevy::utils::default;
+use crate::console::{console_print, AddConsoleCommand};
+use crate::debug_draw::{line_mesh, unlit};
+use crate::fixed_time::AddTickSystem;
+use crate::floating_origin::{add_origin_shifted, OriginShifted};
+use crate::picking::PickTarget;
+use crate::selection::Selection;
+
+/// Draws a trail behind every entity with a [Trail], fading out towards its oldest end, for seeing
+/// the paths of projectiles, vehicles and anything else moving around. A point is added every
+/// [TrailConfig::sample_every] ticks of [FixedTime](crate::fixed_time::FixedTime), keeping the
+/// newest [TrailConfig::max_points]. Once the entity is gone (or loses its [Trail]) the trail
+/// shrinks away from its oldest end, as if it was still being added to.
+///
+/// Also registers the `trail` console command, toggling a [Trail] on the [Selection], or on the
+/// [PickTarget] without one.
+pub struct TrailPlugin;
+
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<TrailConfig>() {
+            app.insert_resource(TrailConfig::default());
+        }
+        add_origin_shifted(app);
+        app
+            .add_system(spawn_trails)
+            .add_system(shift_trails)
+            .add_tick_system(sample_trails)
+            .add_console_command("trail", "toggles a trail on the selection, or what's under the crosshair", |world, _| {
+                let (added, removed) = toggle_trails(world);
+                console_print(world, format!("added {} trails, removed {}", added, removed));
+            });
+    }
+}
+
+/// Gives an entity a trail
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Trail {
+    /// The color at the newest end, fading out to nothing at the oldest
+    pub color: Color
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Self {
+            color: Color::ORANGE
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct TrailConfig {
+    /// How many points each trail keeps
+    pub max_points: usize,
+    /// How many ticks go by between points, 1 adds one every tick
+    pub sample_every: u32
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self {
+            max_points: 120,
+            sample_every: 2
+        }
+    }
+}
+
+/// The line drawn behind `owner`, in world space
+#[derive(Component)]
+struct TrailLine {
+    owner: Entity,
+    color: Color,
+    mesh: Handle<Mesh>,
+    /// Oldest first
+    points: VecDeque<Vec3>
+}
+
+fn spawn_trails(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    trails: Query<(Entity, &Trail, &GlobalTransform), Added<Trail>>
+) {
+    for (owner, trail, transform) in &trails {
+        let mesh = meshes.add(line_mesh(std::iter::empty()));
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                // the fading is in the vertex colors, which the material is multiplied by
+                material: materials.add(StandardMaterial {
+                    alpha_mode: AlphaMode::Blend,
+                    ..unlit(Color::WHITE)
+                }),
+                ..default()
+            },
+            NotShadowCaster,
+            TrailLine {
+                owner,
+                color: trail.color,
+                mesh,
+                points: VecDeque::from([transform.translation()])
+            }
+        ));
+    }
+}
+
+/// Moves the points along with the world when the origin moves, the lines themselves are roots
+/// that were moved as well, and they're put back at the origin so the points stay in world space
+fn shift_trails(mut shifted: EventReader<OriginShifted>, mut lines: Query<(&mut TrailLine, &mut Transform)>) {
+    for OriginShifted { shift } in shifted.iter() {
+        for (mut line, mut transform) in &mut lines {
+            for point in &mut line.points {
+                *point += *shift;
+            }
+            transform.translation = Vec3::ZERO;
+        }
+    }
+}
+
+fn sample_trails(
+    mut commands: Commands,
+    config: Res<TrailConfig>,
+    mut ticks: Local<u32>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    owners: Query<&GlobalTransform, With<Trail>>,
+    mut lines: Query<(Entity, &mut TrailLine)>
+) {
+    *ticks += 1;
+    if *ticks < config.sample_every.max(1) {
+        return;
+    }
+    *ticks = 0;
+
+    for (entity, mut line) in &mut lines {
+        match owners.get(line.owner) {
+            Ok(transform) => line.points.push_back(transform.translation()),
+            Err(_) if line.points.len() <= 1 => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+            Err(_) => {
+                line.points.pop_front();
+            }
+        }
+        while line.points.len() > config.max_points.max(1) {
+            line.points.pop_front();
+        }
+
+        let Some(mesh) = meshes.get_mut(&line.mesh) else {
+            continue;
+        };
+        let segments = line.points.iter().zip(line.points.iter().skip(1));
+        *mesh = line_mesh(segments.map(|(a, b)| (*a, *b)));
+        let count = line.points.len().max(2) as f32 - 1.0;
+        let [r, g, b, a] = line.color.as_rgba_f32();
+        // each segment's ends fade along with where they are in the trail
+        let colors = (1..line.points.len())
+            .flat_map(|i| [i - 1, i])
+            .map(|i| [r, g, b, a * i as f32 / count])
+            .collect::<Vec<_>>();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    }
+}
+
+/// Toggles a [Trail] on the [Selection], or the [PickTarget] without one, returning how many were
+/// added and removed
+pub fn toggle_trails(world: &mut World) -> (usize, usize) {
+    let selected = world.get_resource::<Selection>()
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| selection.iter().collect::<Vec<_>>());
+    let entities = selected
+        .or_else(|| world.get_resource::<PickTarget>().and_then(|target| target.entity).map(|entity| vec![entity]))
+        .unwrap_or_default();
+    let (mut added, mut removed) = (0, 0);
+    for entity in entities {
+        let Some(mut entity) = world.get_entity_mut(entity) else {
+            continue;
+        };
+        if entity.contains::<Trail>() {
+            entity.remove::<Trail>();
+            removed += 1;
+        } else {
+            entity.insert(Trail::default());
+            added += 1;
+        }
+    }
+    (added, removed)
+}
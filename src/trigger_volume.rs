@@ -0,0 +1,245 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::math::Vec3;
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, AlphaMode, Color, Component, Entity, EventWriter, GlobalTransform, IntoSystemDescriptor, Local, Mesh, Query, Res, ResMut, Resource, Transform, With, World};
+use bevy::utils::{default, HashMap};
+use bevy_rapier3d::plugin::{RapierConfiguration, TimestepMode};
+use bevy_rapier3d::prelude::{Collider, QueryFilter, RapierContext, Sensor};
+use crate::free_control::ActiveControl;
+
+/// Tagged [TriggerVolume]s sending [TriggerEnter] and [TriggerExit] events as activators move in
+/// and out of them. The entity controlled through
+/// [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T] is always an
+/// activator, anything else can be made one with [TriggerActivator]. Activators are checked by
+/// their position rather than their collider, so they don't need one.
+///
+/// While the controlled entity is inside a volume with a [TriggerEffect], the effect is applied
+/// to rapier, and undone when it leaves. A demo with a low gravity zone and a slow motion zone is
+/// spawned at [TriggerVolumeConfig::demo_point] on startup, if it's set.
+pub struct TriggerVolumePlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for TriggerVolumePlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for TriggerVolumePlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<TriggerVolumeConfig>() {
+            app.insert_resource(TriggerVolumeConfig::default());
+        }
+        app
+            .init_resource::<TriggerOccupancy>()
+            .add_event::<TriggerEnter>()
+            .add_event::<TriggerExit>()
+            .add_startup_system(|world: &mut World| {
+                if let Some(point) = world.resource::<TriggerVolumeConfig>().demo_point {
+                    spawn_trigger_demo(world, point);
+                }
+            })
+            .add_system(detect_triggers::<T>)
+            .add_system(apply_trigger_effects::<T>.after(detect_triggers::<T>));
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct TriggerVolumeConfig {
+    pub demo_point: Option<Vec3>
+}
+
+impl Default for TriggerVolumeConfig {
+    fn default() -> Self {
+        Self {
+            demo_point: Some(Vec3::new(0.0, 0.0, -12.0))
+        }
+    }
+}
+
+/// A sensor collider that sends trigger events, the tag tells volumes apart in them
+#[derive(Component, Clone)]
+pub struct TriggerVolume {
+    pub tag: String
+}
+
+impl TriggerVolume {
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into()
+        }
+    }
+}
+
+/// Makes trigger volumes react to the entity
+#[derive(Component)]
+pub struct TriggerActivator;
+
+/// Applied while the controlled entity is inside the [TriggerVolume]
+#[derive(Component, Copy, Clone, Debug)]
+pub enum TriggerEffect {
+    /// Replaces rapier's gravity
+    Gravity(Vec3),
+    /// Multiplies rapier's time scale, only with a variable [TimestepMode]
+    TimeScale(f32)
+}
+
+pub struct TriggerEnter {
+    pub activator: Entity,
+    pub volume: Entity,
+    pub tag: String
+}
+
+/// Also sent when the volume or the activator is despawned while inside
+pub struct TriggerExit {
+    pub activator: Entity,
+    pub volume: Entity,
+    pub tag: String
+}
+
+/// Which activators are inside which volumes, with the volume's tag
+#[derive(Resource, Default)]
+pub struct TriggerOccupancy {
+    inside: HashMap<(Entity, Entity), String>
+}
+
+impl TriggerOccupancy {
+    pub fn contains(&self, activator: Entity, volume: Entity) -> bool {
+        self.inside.contains_key(&(activator, volume))
+    }
+
+    /// The volumes `activator` is inside
+    pub fn volumes(&self, activator: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.inside
+            .keys()
+            .filter(move |(inside, _)| *inside == activator)
+            .map(|(_, volume)| *volume)
+    }
+}
+
+/// Spawns a low gravity zone and a slow motion zone next to each other around `point`
+pub fn spawn_trigger_demo(world: &mut World, point: Vec3) {
+    let half_extents = Vec3::new(3.0, 3.0, 3.0);
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape::Box::new(half_extents.x * 2.0, half_extents.y * 2.0, half_extents.z * 2.0).into());
+    let zones = [
+        ("low_gravity", TriggerEffect::Gravity(Vec3::new(0.0, -1.6, 0.0)), Color::rgba(0.3, 0.5, 1.0, 0.15), -4.0),
+        ("slow_motion", TriggerEffect::TimeScale(0.25), Color::rgba(1.0, 0.6, 0.2, 0.15), 4.0)
+    ];
+    for (tag, effect, color, x) in zones {
+        let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+            base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        world.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material,
+                transform: Transform::from_translation(point + Vec3::new(x, half_extents.y, 0.0)),
+                ..default()
+            },
+            NotShadowCaster,
+            Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            Sensor,
+            TriggerVolume::new(tag),
+            effect
+        ));
+    }
+}
+
+fn detect_triggers<T: Component>(
+    active: Res<ActiveControl<T>>,
+    rapier_context: Res<RapierContext>,
+    mut occupancy: ResMut<TriggerOccupancy>,
+    mut enters: EventWriter<TriggerEnter>,
+    mut exits: EventWriter<TriggerExit>,
+    activators: Query<(Entity, &GlobalTransform), With<TriggerActivator>>,
+    controlled: Query<&GlobalTransform, With<T>>,
+    volumes: Query<&TriggerVolume>
+) {
+    let controlled = active.entity.and_then(|entity| Some((entity, controlled.get(entity).ok()?)));
+    let mut inside = HashMap::default();
+    for (activator, transform) in activators.iter().chain(controlled) {
+        let filter = QueryFilter::default().exclude_solids();
+        rapier_context.intersections_with_point(transform.translation(), filter, |volume| {
+            if let Ok(trigger) = volumes.get(volume) {
+                inside.insert((activator, volume), trigger.tag.clone());
+            }
+            true
+        });
+    }
+
+    for ((activator, volume), tag) in &occupancy.inside {
+        if !inside.contains_key(&(*activator, *volume)) {
+            exits.send(TriggerExit { activator: *activator, volume: *volume, tag: tag.clone() });
+        }
+    }
+    for ((activator, volume), tag) in &inside {
+        if !occupancy.inside.contains_key(&(*activator, *volume)) {
+            enters.send(TriggerEnter { activator: *activator, volume: *volume, tag: tag.clone() });
+        }
+    }
+    occupancy.inside = inside;
+}
+
+/// Rapier's gravity and time scale from before any effect was applied
+#[derive(Copy, Clone)]
+struct EffectBase {
+    gravity: Vec3,
+    time_scale: Option<f32>
+}
+
+fn apply_trigger_effects<T: Component>(
+    active: Res<ActiveControl<T>>,
+    occupancy: Res<TriggerOccupancy>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    effects: Query<&TriggerEffect>,
+    mut base: Local<Option<EffectBase>>
+) {
+    let mut applied = active.entity
+        .map(|entity| occupancy.volumes(entity).filter_map(|volume| effects.get(volume).ok()).copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+    if applied.is_empty() && base.is_none() {
+        return;
+    }
+    // the same order every frame, so overlapping zones don't flicker between each other
+    applied.sort_by_key(|effect| matches!(effect, TriggerEffect::TimeScale(_)));
+
+    let current_time_scale = match rapier_config.timestep_mode {
+        TimestepMode::Variable { time_scale, .. } | TimestepMode::Interpolated { time_scale, .. } => Some(time_scale),
+        TimestepMode::Fixed { .. } => None
+    };
+    let start = *base.get_or_insert(EffectBase {
+        gravity: rapier_config.gravity,
+        time_scale: current_time_scale
+    });
+    let mut gravity = start.gravity;
+    let mut time_scale = start.time_scale;
+    for effect in &applied {
+        match effect {
+            TriggerEffect::Gravity(value) => gravity = *value,
+            TriggerEffect::TimeScale(scale) => time_scale = time_scale.map(|time_scale| time_scale * scale)
+        }
+    }
+    if applied.is_empty() {
+        *base = None;
+    }
+
+    if rapier_config.gravity != gravity {
+        rapier_config.gravity = gravity;
+    }
+    if let (Some(time_scale), Some(current)) = (time_scale, current_time_scale) {
+        if current != time_scale {
+            match &mut rapier_config.timestep_mode {
+                TimestepMode::Variable { time_scale: value, .. } | TimestepMode::Interpolated { time_scale: value, .. } => *value = time_scale,
+                TimestepMode::Fixed { .. } => {}
+            }
+        }
+    }
+}
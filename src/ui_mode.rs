@@ -0,0 +1,131 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::ecs::event::Events;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::math::{Vec2, Vec3};
+use bevy::prelude::{Camera, Component, EventWriter, GlobalTransform, IntoSystemDescriptor, Query, Res, ResMut, Resource, With};
+use bevy::utils::default;
+use bevy::window::Windows;
+use serde::{Deserialize, Serialize};
+use crate::cursor_grab::{cursor_grab, CursorGrab};
+use crate::free_control::ActiveControl;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+
+/// A mode with a visible cursor for menus and inspecting objects, while it's active the camera
+/// controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with marker [T]
+/// doesn't rotate, and [CursorRay] events are sent for wherever the cursor points instead.
+/// This plugin can be initialized in two ways:
+///
+/// * No default bindings [UiModePlugin::new]
+/// * F1 toggles the mode [UiModePlugin::default]
+///
+/// Entering the mode releases the cursor through [CursorGrab], and keeps
+/// [FreeControls::Lock](crate::free_control::FreeControls::Lock) from grabbing it again, leaving
+/// the mode grabs it again.
+pub struct UiModePlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<UiModeControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> UiModePlugin<T> {
+    /// Creates a new `UiModePlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default(),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: UiModeControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for UiModePlugin<T> {
+    fn default() -> Self {
+        Self::new().bind(bevy::prelude::KeyCode::F1, UiModeControls::Toggle)
+    }
+}
+
+impl <T: Component> Plugin for UiModePlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<UiMode>() {
+            app.insert_resource(UiMode::default());
+        }
+        if !app.world.contains_resource::<Events<CursorRay>>() {
+            app.add_event::<CursorRay>();
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_system(ui_mode_controls.before(cursor_grab))
+            .add_system(send_cursor_rays::<T>);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum UiModeControls {
+    Toggle
+}
+
+#[derive(Resource, Default)]
+pub struct UiMode {
+    pub active: bool
+}
+
+/// Sent every frame [UiMode] is active and the cursor is inside the primary window
+#[derive(Copy, Clone, Debug)]
+pub struct CursorRay {
+    /// The cursor position in logical pixels, from the bottom left of the window
+    pub cursor: Vec2,
+    /// Where the ray starts, on the near plane of the camera
+    pub origin: Vec3,
+    pub direction: Vec3
+}
+
+fn ui_mode_controls(binds: Res<Input<UiModeControls>>, mut ui_mode: ResMut<UiMode>, mut cursor_grab: ResMut<CursorGrab>) {
+    if binds.just_pressed(UiModeControls::Toggle) {
+        ui_mode.active = !ui_mode.active;
+        info!("ui mode {}", if ui_mode.active { "entered" } else { "left" });
+        if ui_mode.active {
+            cursor_grab.deactivate();
+        } else {
+            cursor_grab.activate();
+        }
+    }
+}
+
+fn send_cursor_rays<T: Component>(
+    ui_mode: Res<UiMode>,
+    windows: Res<Windows>,
+    active: Res<ActiveControl<T>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<T>>,
+    mut rays: EventWriter<CursorRay>
+) {
+    if !ui_mode.active {
+        return;
+    }
+    // todo needs to handle multiple windows, going to wait until Bevy updates to having Windows as Entities
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let Some((camera, camera_transform)) = active.entity.and_then(|entity| cameras.get(entity).ok()) else {
+        return;
+    };
+
+    let ndc = cursor / Vec2::new(window.width(), window.height()) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    // Bevy uses reversed z, the near plane is at 1.0
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+
+    rays.send(CursorRay {
+        cursor,
+        origin: near,
+        direction: (far - near).normalize()
+    });
+}
@@ -0,0 +1,422 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::asset::Assets;
+use bevy::hierarchy::{BuildChildren, Children};
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::math::{Quat, Vec3};
+use bevy::pbr::{PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, Color, Commands, Component, Entity, IntoSystemDescriptor, Local, Mesh, Query, Res, ResMut, Resource, Transform, With, Without, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::prelude::{Collider, ColliderMassProperties, ExternalForce, QueryFilter, RapierContext, RigidBody, Velocity};
+use serde::{Deserialize, Serialize};
+use crate::camera_follow::{behind, frame_delta, CameraFollowSettings, FollowSpring};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::RenderTime;
+use crate::free_control::{ActiveControl, FreeControlSuspended};
+use crate::game_state::running;
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::minimap::MinimapIcon;
+
+/// A drivable car with raycast suspension, a box shaped chassis body held up by a spring at each
+/// of its [Wheel]s, which are rays rather than colliders. Grip, engine and brake forces are
+/// applied where the wheels touch the ground. This plugin can be initialized in two ways:
+///
+/// * No default bindings [VehicleControlPlugin::new]
+/// * F gets in or out of the nearest vehicle, the arrow keys accelerate, brake (reversing once
+///  stopped) and steer [VehicleControlPlugin::default]
+///
+/// Getting in suspends the free controls of the entity controlled with marker [T] (see
+/// [FreeControlSuspended]) and has it follow the vehicle from behind instead (smoothed as the
+/// [CameraFollowPlugin](crate::camera_follow::CameraFollowPlugin) says), getting out hands it
+/// back. One vehicle is spawned at [VehicleConfig::spawn_point] on startup, and the `vehicle`
+/// console command spawns another in front of the camera.
+pub struct VehicleControlPlugin<T: Component> {
+    key_bindings: KeyBindingPlugin<VehicleControls>,
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> VehicleControlPlugin<T> {
+    /// Creates a new `VehicleControlPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default().with_preset_section("vehicle"),
+            __phantom: default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: VehicleControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl <T: Component> Default for VehicleControlPlugin<T> {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(F, VehicleControls::ToggleDriving)
+            .bind(Up, VehicleControls::Throttle)
+            .bind(Down, VehicleControls::Brake)
+            .bind(Left, VehicleControls::SteerLeft)
+            .bind(Right, VehicleControls::SteerRight)
+    }
+}
+
+impl <T: Component> Plugin for VehicleControlPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<VehicleConfig>() {
+            app.insert_resource(VehicleConfig::default());
+        }
+        app
+            .add_plugin(self.key_bindings.clone())
+            .init_resource::<VehicleDriving>()
+            .add_startup_system(|world: &mut World| {
+                let spawn_point = world.resource::<VehicleConfig>().spawn_point;
+                if let Some(spawn_point) = spawn_point {
+                    spawn_vehicle(world, Transform::from_translation(spawn_point));
+                }
+            })
+            .add_system(toggle_driving::<T>.with_run_criteria(running))
+            .add_system(vehicle_controls.with_run_criteria(running).after(toggle_driving::<T>))
+            .add_system(drive_vehicles.after(vehicle_controls))
+            .add_system(follow_vehicle::<T>.after(drive_vehicles))
+            .add_console_command("vehicle", "spawns a vehicle in front of the camera", |world, _| {
+                let in_front = world
+                    .resource::<ActiveControl<T>>()
+                    .entity
+                    .and_then(|entity| world.get::<Transform>(entity))
+                    .map(|transform| {
+                        let forward = Vec3::new(transform.forward().x, 0.0, transform.forward().z).normalize_or_zero();
+                        Transform::from_translation(transform.translation + forward * 8.0)
+                            .looking_at(transform.translation + forward * 16.0, Vec3::Y)
+                    });
+                match in_front {
+                    Some(transform) => {
+                        spawn_vehicle(world, transform);
+                    }
+                    None => console_print(world, "nothing controlled to spawn in front of")
+                }
+            });
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum VehicleControls {
+    /// Gets in the nearest vehicle within [VehicleConfig::enter_distance], or out of the current one
+    ToggleDriving,
+    Throttle,
+    /// Brakes while moving forward, reverses otherwise
+    Brake,
+    SteerLeft,
+    SteerRight
+}
+
+#[derive(Resource, Clone)]
+pub struct VehicleConfig {
+    /// Where a vehicle is spawned on startup, if anywhere
+    pub spawn_point: Option<Vec3>,
+    /// Half extents of the chassis
+    pub chassis_size: Vec3,
+    pub mass: f32,
+    pub wheel_radius: f32,
+    /// Length of the suspension when it's neither compressed nor extended
+    pub rest_length: f32,
+    /// Spring force per unit of compression
+    pub stiffness: f32,
+    /// Force per unit per second of compression
+    pub damping: f32,
+    /// Sideways force per unit per second of a wheel sliding sideways
+    pub grip: f32,
+    /// Forward force split between the driven wheels at full throttle
+    pub engine_force: f32,
+    pub brake_force: f32,
+    /// Steering angle at full lock, in radians
+    pub max_steer: f32,
+    /// How quickly the wheels turn to full lock (and back), in radians per second
+    pub steer_speed: f32,
+    /// How close the camera has to be to get in
+    pub enter_distance: f32,
+    /// Where the camera sits while driving, relative to the vehicle's heading
+    pub camera_offset: Vec3
+}
+
+impl Default for VehicleConfig {
+    fn default() -> Self {
+        Self {
+            spawn_point: Some(Vec3::new(8.0, 2.0, 0.0)),
+            chassis_size: Vec3::new(0.9, 0.25, 2.0),
+            mass: 400.0,
+            wheel_radius: 0.35,
+            rest_length: 0.5,
+            stiffness: 20000.0,
+            damping: 2000.0,
+            grip: 1500.0,
+            engine_force: 3000.0,
+            brake_force: 5000.0,
+            max_steer: 0.5,
+            steer_speed: 2.0,
+            enter_distance: 8.0,
+            camera_offset: Vec3::new(0.0, 3.0, 8.0)
+        }
+    }
+}
+
+/// The vehicle being driven, if any
+#[derive(Resource, Default)]
+pub struct VehicleDriving {
+    pub vehicle: Option<Entity>
+}
+
+/// The chassis of a vehicle, its [Wheel]s are children of it
+#[derive(Component, Default)]
+pub struct Vehicle {
+    /// -1 to 1, negative reverses
+    pub throttle: f32,
+    pub braking: bool,
+    /// Current steering angle, positive is left
+    pub steer: f32
+}
+
+#[derive(Component)]
+pub struct Wheel {
+    /// Where the suspension attaches to the chassis, in the chassis' space
+    pub mount: Vec3,
+    pub steered: bool,
+    pub driven: bool,
+    compression: f32
+}
+
+/// Spawns a vehicle shaped by [VehicleConfig] at `transform`
+pub fn spawn_vehicle(world: &mut World, transform: Transform) -> Entity {
+    let config = world.resource::<VehicleConfig>().clone();
+    let size = config.chassis_size;
+    let (chassis_mesh, wheel_mesh) = {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let chassis = meshes.add(shape::Box::new(size.x * 2.0, size.y * 2.0, size.z * 2.0).into());
+        let wheel = meshes.add(shape::UVSphere { radius: config.wheel_radius, sectors: 16, stacks: 8 }.into());
+        (chassis, wheel)
+    };
+    let (chassis_material, wheel_material) = {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        (materials.add(Color::rgb(0.8, 0.15, 0.1).into()), materials.add(Color::rgb(0.1, 0.1, 0.1).into()))
+    };
+
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)];
+    world.spawn((
+        PbrBundle {
+            mesh: chassis_mesh,
+            material: chassis_material,
+            transform,
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(size.x, size.y, size.z),
+        ColliderMassProperties::Mass(config.mass),
+        Velocity::zero(),
+        ExternalForce::default(),
+        MinimapIcon { color: Color::rgb(0.9, 0.2, 0.15) },
+        Vehicle::default()
+    ))
+        .with_children(|chassis| {
+            for (x, z) in corners {
+                let mount = Vec3::new(x * size.x, -size.y, z * size.z);
+                // -z is forward, so the front wheels steer and the rear ones drive
+                let front = z < 0.0;
+                chassis.spawn((
+                    PbrBundle {
+                        mesh: wheel_mesh.clone(),
+                        material: wheel_material.clone(),
+                        transform: Transform::from_translation(mount - Vec3::Y * config.rest_length),
+                        ..default()
+                    },
+                    Wheel {
+                        mount,
+                        steered: front,
+                        driven: !front,
+                        compression: 0.0
+                    }
+                ));
+            }
+        })
+        .id()
+}
+
+fn toggle_driving<T: Component>(
+    mut commands: Commands,
+    binds: Res<Input<VehicleControls>>,
+    config: Res<VehicleConfig>,
+    active: Res<ActiveControl<T>>,
+    mut driving: ResMut<VehicleDriving>,
+    drivers: Query<&Transform, With<T>>,
+    vehicles: Query<(Entity, &Transform), With<Vehicle>>
+) {
+    let Some(driver) = active.entity else {
+        return;
+    };
+    // the vehicle being deleted gets the driver out too
+    let gone = driving.vehicle.map_or(false, |vehicle| !vehicles.contains(vehicle));
+    if !binds.just_pressed(VehicleControls::ToggleDriving) && !gone {
+        return;
+    }
+
+    if driving.vehicle.take().is_some() {
+        commands.entity(driver).remove::<FreeControlSuspended>();
+        info!("got out of the vehicle");
+        return;
+    }
+    let Ok(driver_transform) = drivers.get(driver) else {
+        return;
+    };
+    let nearest = vehicles
+        .iter()
+        .map(|(vehicle, transform)| (vehicle, transform.translation.distance(driver_transform.translation)))
+        .filter(|(_, distance)| *distance <= config.enter_distance)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((vehicle, _)) = nearest {
+        driving.vehicle = Some(vehicle);
+        commands.entity(driver).insert(FreeControlSuspended);
+        info!("got in a vehicle");
+    }
+}
+
+fn vehicle_controls(
+    time: Res<Time>,
+    binds: Res<Input<VehicleControls>>,
+    config: Res<VehicleConfig>,
+    driving: Res<VehicleDriving>,
+    mut vehicles: Query<(Entity, &mut Vehicle, &Transform, &Velocity)>
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut vehicle, transform, velocity) in &mut vehicles {
+        // vehicles nobody is in roll to a stop with their wheels straightened out
+        let driven = driving.vehicle == Some(entity);
+        let pressed = |control| driven && binds.pressed(control);
+
+        let forward_speed = velocity.linvel.dot(transform.forward());
+        vehicle.throttle = 0.0;
+        vehicle.braking = !driven;
+        if pressed(VehicleControls::Throttle) {
+            vehicle.throttle = 1.0;
+        } else if pressed(VehicleControls::Brake) {
+            // pressed while (nearly) stopped, reverse instead
+            if forward_speed > 0.5 {
+                vehicle.braking = true;
+            } else {
+                vehicle.throttle = -0.5;
+            }
+        }
+
+        let target = match (pressed(VehicleControls::SteerLeft), pressed(VehicleControls::SteerRight)) {
+            (true, false) => config.max_steer,
+            (false, true) => -config.max_steer,
+            _ => 0.0
+        };
+        let step = config.steer_speed * delta;
+        vehicle.steer += (target - vehicle.steer).clamp(-step, step);
+    }
+}
+
+fn drive_vehicles(
+    time: Res<Time>,
+    config: Res<VehicleConfig>,
+    rapier_context: Res<RapierContext>,
+    mut vehicles: Query<(Entity, &Vehicle, &Transform, &Velocity, &mut ExternalForce, &Children)>,
+    mut wheels: Query<(&mut Wheel, &mut Transform), Without<Vehicle>>
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+    for (entity, vehicle, transform, velocity, mut external_force, children) in &mut vehicles {
+        let up = transform.up();
+        let driven_wheels = children
+            .iter()
+            .filter(|child| wheels.get(**child).map_or(false, |(wheel, _)| wheel.driven))
+            .count()
+            .max(1);
+        let filter = QueryFilter::default().exclude_sensors().exclude_rigid_body(entity);
+        let mut force = Vec3::ZERO;
+        let mut torque = Vec3::ZERO;
+
+        for child in children {
+            let Ok((mut wheel, mut wheel_transform)) = wheels.get_mut(*child) else {
+                continue;
+            };
+            let steer = if wheel.steered { vehicle.steer } else { 0.0 };
+            wheel_transform.rotation = Quat::from_rotation_y(steer);
+
+            let origin = transform.transform_point(wheel.mount);
+            let reach = config.rest_length + config.wheel_radius;
+            let hit = rapier_context.cast_ray(origin, -up, reach, true, filter);
+            let Some((_, toi)) = hit else {
+                wheel.compression = 0.0;
+                wheel_transform.translation = wheel.mount - Vec3::Y * config.rest_length;
+                continue;
+            };
+            let length = (toi - config.wheel_radius).max(0.0);
+            wheel_transform.translation = wheel.mount - Vec3::Y * length;
+
+            let compression = config.rest_length - length;
+            let compression_speed = (compression - wheel.compression) / delta;
+            wheel.compression = compression;
+            // springs only push, a wheel leaving the ground doesn't pull the chassis down
+            let spring = (config.stiffness * compression + config.damping * compression_speed).max(0.0);
+
+            let contact = origin - up * toi;
+            let wheel_rotation = transform.rotation * Quat::from_rotation_y(steer);
+            let forward = wheel_rotation * -Vec3::Z;
+            let right = wheel_rotation * Vec3::X;
+            let point_velocity = velocity.linvel + velocity.angvel.cross(contact - transform.translation);
+
+            let mut wheel_force = up * spring;
+            wheel_force -= right * point_velocity.dot(right) * config.grip / 4.0;
+            if wheel.driven {
+                wheel_force += forward * vehicle.throttle * config.engine_force / driven_wheels as f32;
+            }
+            if vehicle.braking {
+                let rolling = point_velocity.dot(forward);
+                // scaled down near a standstill, so braking doesn't rock back and forth
+                wheel_force -= forward * rolling.clamp(-1.0, 1.0) * config.brake_force / 4.0;
+            }
+
+            force += wheel_force;
+            torque += (contact - transform.translation).cross(wheel_force);
+        }
+
+        external_force.force = force;
+        external_force.torque = torque;
+    }
+}
+
+fn follow_vehicle<T: Component>(
+    time: Res<Time>,
+    render_time: Option<Res<RenderTime>>,
+    config: Res<VehicleConfig>,
+    follow_settings: Option<Res<CameraFollowSettings>>,
+    driving: Res<VehicleDriving>,
+    active: Res<ActiveControl<T>>,
+    vehicles: Query<&Transform, (With<Vehicle>, Without<T>)>,
+    mut drivers: Query<&mut Transform, With<T>>,
+    mut spring: Local<(Option<Entity>, FollowSpring)>
+) {
+    let (Some(vehicle_entity), Some(driver)) = (driving.vehicle, active.entity) else {
+        return;
+    };
+    let (Ok(vehicle), Ok(mut driver)) = (vehicles.get(vehicle_entity), drivers.get_mut(driver)) else {
+        return;
+    };
+    let (mut position, mut look_at) = behind(vehicle, config.camera_offset);
+    if let Some(settings) = follow_settings {
+        // starting over in every vehicle gotten in, instead of swooping over from the last one
+        if spring.0 != Some(vehicle_entity) {
+            *spring = (Some(vehicle_entity), default());
+        }
+        let delta = frame_delta(&time, render_time.as_deref());
+        (position, look_at) = spring.1.update(&settings, position, look_at, delta);
+    }
+    driver.translation = position;
+    driver.look_at(look_at, Vec3::Y);
+}
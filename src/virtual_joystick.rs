@@ -0,0 +1,110 @@
+use bevy::app::{App, Plugin};
+use bevy::hierarchy::{BuildChildren, Children};
+use bevy::input::touch::TouchInput;
+use bevy::math::Vec2;
+use bevy::prelude::{Color, Commands, Component, EventReader, NodeBundle, Query, Res, ResMut, Resource, Style, Visibility, With, Without};
+use bevy::ui::{PositionType, Size, UiRect, Val};
+use bevy::utils::default;
+use crate::keybind::{RawInputState, TouchRegion, TouchStickConfig};
+
+/// Draws the virtual joysticks of [RawAxis::TouchStickX](crate::keybind::RawAxis::TouchStickX)
+/// and [RawAxis::TouchStickY](crate::keybind::RawAxis::TouchStickY) with bevy_ui, one for each half
+/// of the window. A stick shows up where the touch started, with its knob following the touch.
+///
+/// The sticks only show on touch capable platforms, which is assumed on android and ios and
+/// otherwise detected by the first touch coming in.
+pub struct VirtualJoystickPlugin;
+
+impl Plugin for VirtualJoystickPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(VirtualJoystickEnabled(cfg!(any(target_os = "android", target_os = "ios"))))
+            .add_startup_system(spawn_joysticks)
+            .add_system(detect_touch)
+            .add_system(update_joysticks);
+    }
+}
+
+/// Whether the joysticks are shown at all, can be set manually to force them on or off
+#[derive(Resource)]
+pub struct VirtualJoystickEnabled(pub bool);
+
+#[derive(Component)]
+struct JoystickBase(TouchRegion);
+
+#[derive(Component)]
+struct JoystickKnob;
+
+const KNOB_SIZE: f32 = 40.0;
+
+fn spawn_joysticks(mut commands: Commands, config: Res<TouchStickConfig>) {
+    for region in [TouchRegion::Left, TouchRegion::Right] {
+        commands.spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Px(config.radius * 2.0), Val::Px(config.radius * 2.0)),
+                ..default()
+            },
+            background_color: Color::rgba(1.0, 1.0, 1.0, 0.15).into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+            .insert(JoystickBase(region))
+            .with_children(|base| {
+                base.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Px(KNOB_SIZE), Val::Px(KNOB_SIZE)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.5).into(),
+                    ..default()
+                })
+                    .insert(JoystickKnob);
+            });
+    }
+}
+
+fn detect_touch(mut touch_events: EventReader<TouchInput>, mut enabled: ResMut<VirtualJoystickEnabled>) {
+    if touch_events.iter().next().is_some() && !enabled.0 {
+        enabled.0 = true;
+    }
+}
+
+fn update_joysticks(
+    enabled: Res<VirtualJoystickEnabled>,
+    raw_inputs: Res<RawInputState>,
+    config: Res<TouchStickConfig>,
+    mut bases: Query<(&JoystickBase, &Children, &mut Style, &mut Visibility), Without<JoystickKnob>>,
+    mut knobs: Query<&mut Style, With<JoystickKnob>>
+) {
+    for (base, children, mut style, mut visibility) in &mut bases {
+        let stick = enabled.0
+            .then(|| raw_inputs.touch_stick(base.0))
+            .flatten();
+        if visibility.is_visible != stick.is_some() {
+            visibility.is_visible = stick.is_some();
+        }
+        let Some((start, value)) = stick else {
+            continue;
+        };
+
+        let corner = start - Vec2::splat(config.radius);
+        style.position = UiRect {
+            left: Val::Px(corner.x),
+            bottom: Val::Px(corner.y),
+            ..default()
+        };
+
+        let knob = Vec2::splat(config.radius - KNOB_SIZE / 2.0) + value * config.radius;
+        for child in children.iter() {
+            if let Ok(mut knob_style) = knobs.get_mut(*child) {
+                knob_style.position = UiRect {
+                    left: Val::Px(knob.x),
+                    bottom: Val::Px(knob.y),
+                    ..default()
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::marker::PhantomData;
+use bevy::app::{App, Plugin};
+use bevy::ecs::event::ManualEventReader;
+use bevy::asset::Assets;
+use bevy::math::Vec3;
+use bevy::pbr::{NotShadowCaster, PbrBundle, StandardMaterial};
+use bevy::prelude::{shape, AlphaMode, Color, Commands, Component, Entity, Events, GlobalTransform, IntoSystemDescriptor, Local, Mesh, Query, Res, ResMut, Resource, Transform, With, Without, World};
+use bevy::time::Time;
+use bevy::utils::default;
+use bevy_rapier3d::plugin::RapierConfiguration;
+use bevy_rapier3d::prelude::{Collider, ExternalImpulse, RigidBody, Sensor, Velocity};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::fixed_time::ticked;
+use crate::free_control::{free_controls, ActiveControl, FreeControlSuspended, FreeControlSystem, MoveIntent};
+use crate::game_state::running;
+use crate::picking::PickTarget;
+
+/// Boxes of [Water] that dynamic bodies float in. Every tick of
+/// [FixedTime](crate::fixed_time::FixedTime) each body inside is pushed up by the water it
+/// displaces and slowed down by drag, as impulses worth the tick's forces, so anything lighter
+/// than the water floats and anything heavier sinks slowly. How much of a body is under water goes
+/// by its collider's bounding sphere, and its volume by the collider's shape.
+///
+/// The entity controlled through [FreeControlPlugin](crate::free_control::FreeControlPlugin) with
+/// marker [T] isn't pushed around, it's given [Swimming] while inside, moving slower and sinking
+/// a little as the [WaterConfig] says. A pool is spawned at [WaterConfig::demo_point] on startup,
+/// if it's set, and the `water` console command spawns more where the crosshair points.
+///
+/// Water boxes are axis aligned, their rotation is ignored.
+pub struct WaterPlugin<T: Component> {
+    __phantom: PhantomData<fn(T)>
+}
+
+impl <T: Component> Default for WaterPlugin<T> {
+    fn default() -> Self {
+        Self {
+            __phantom: default()
+        }
+    }
+}
+
+impl <T: Component> Plugin for WaterPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<WaterConfig>() {
+            app.insert_resource(WaterConfig::default());
+        }
+        app
+            .add_startup_system(|world: &mut World| {
+                if let Some(point) = world.resource::<WaterConfig>().demo_point {
+                    spawn_water(world, point + Vec3::Y * 2.0, Vec3::new(5.0, 2.0, 5.0));
+                }
+            })
+            .add_system(float_bodies::<T>.with_run_criteria(ticked))
+            .add_system(update_swimming::<T>)
+            .add_system(swim::<T>.with_run_criteria(running).label(FreeControlSystem).after(free_controls::<T>))
+            .add_console_command("water", "spawns water at the crosshair, taking its half extents", |world, args| {
+                let half_extents = match args {
+                    [] => Some(Vec3::new(5.0, 2.0, 5.0)),
+                    [x, y, z] => match (x.parse(), y.parse(), z.parse()) {
+                        (Ok(x), Ok(y), Ok(z)) if x > 0.0 && y > 0.0 && z > 0.0 => Some(Vec3::new(x, y, z)),
+                        _ => None
+                    },
+                    _ => None
+                };
+                let Some(half_extents) = half_extents else {
+                    console_print(world, "usage: water [half_x half_y half_z]");
+                    return;
+                };
+                let point = world.get_resource::<PickTarget>()
+                    .map(|target| match target.entity {
+                        Some(_) => target.point,
+                        None => target.ray_origin + target.ray_direction * 10.0
+                    })
+                    .unwrap_or_default();
+                // resting on whatever was pointed at
+                spawn_water(world, point + Vec3::Y * half_extents.y, half_extents);
+                console_print(world, format!("spawned water at {:.1}, {:.1}, {:.1}", point.x, point.y, point.z));
+            });
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct WaterConfig {
+    pub demo_point: Option<Vec3>,
+    /// Multiplies the controlled entity's movement while [Swimming]
+    pub swim_speed: f32,
+    /// How far the controlled entity sinks each tick while [Swimming]
+    pub swim_sink: f32
+}
+
+impl Default for WaterConfig {
+    fn default() -> Self {
+        Self {
+            demo_point: Some(Vec3::new(0.0, 0.0, 14.0)),
+            swim_speed: 0.4,
+            swim_sink: 0.02
+        }
+    }
+}
+
+/// A box of water around the entity's translation, the top face is the surface
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Water {
+    pub half_extents: Vec3,
+    /// Relative to rapier's default collider density of 1, bodies less dense than this float
+    pub density: f32,
+    /// Slows bodies of the default density down by this fraction of their velocity each second,
+    /// times how much of them is under water
+    pub linear_drag: f32,
+    /// Like [Water::linear_drag], for spinning
+    pub angular_drag: f32
+}
+
+impl Water {
+    pub fn new(half_extents: Vec3) -> Self {
+        Self {
+            half_extents,
+            density: 1.5,
+            linear_drag: 1.5,
+            angular_drag: 1.0
+        }
+    }
+
+    /// Height of the surface for water at `center`
+    pub fn surface(&self, center: Vec3) -> f32 {
+        center.y + self.half_extents.y
+    }
+
+    /// How much of a ball of `radius` at `position` is in the water at `center`, from 0 to 1 going
+    /// by the part of the ball's height that's under the surface
+    pub fn submerged(&self, center: Vec3, position: Vec3, radius: f32) -> f32 {
+        let offset = (position - center).abs();
+        if offset.x > self.half_extents.x || offset.z > self.half_extents.z || position.y + radius < center.y - self.half_extents.y {
+            return 0.0;
+        }
+        ((self.surface(center) - (position.y - radius)) / (radius * 2.0)).clamp(0.0, 1.0)
+    }
+
+    /// Whether `point` is in the water at `center`
+    pub fn contains(&self, center: Vec3, point: Vec3) -> bool {
+        let offset = (point - center).abs();
+        offset.x <= self.half_extents.x && offset.y <= self.half_extents.y && offset.z <= self.half_extents.z
+    }
+}
+
+/// Given to the controlled entity while it's in [Water], its [MoveIntent]s from
+/// [free_controls] are [Swimming::speed] times as far and sink it by [Swimming::sink] each tick
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Swimming {
+    pub speed: f32,
+    pub sink: f32
+}
+
+/// Spawns a translucent box of [Water] with its middle at `center`
+pub fn spawn_water(world: &mut World, center: Vec3, half_extents: Vec3) -> Entity {
+    let size = half_extents * 2.0;
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(shape::Box::new(size.x, size.y, size.z).into());
+    let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+        base_color: Color::rgba(0.1, 0.35, 0.6, 0.4),
+        alpha_mode: AlphaMode::Blend,
+        perceptual_roughness: 0.1,
+        ..default()
+    });
+    world.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(center),
+            ..default()
+        },
+        NotShadowCaster,
+        // a sensor, so bodies go in rather than bump into it
+        Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+        Sensor,
+        Water::new(half_extents)
+    )).id()
+}
+
+fn float_bodies<T: Component>(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    waters: Query<(&Water, &GlobalTransform)>,
+    mut bodies: Query<(Entity, &RigidBody, &Collider, &GlobalTransform, Option<&Velocity>, Option<&mut ExternalImpulse>), (Without<Water>, Without<T>)>
+) {
+    let delta = time.delta_seconds();
+    if waters.is_empty() {
+        return;
+    }
+    let gravity = rapier_config.gravity;
+    for (entity, body, collider, transform, velocity, impulse) in &mut bodies {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let position = transform.translation();
+        let radius = collider.raw.compute_local_bounding_sphere().radius().max(f32::EPSILON);
+        let Some((water, submerged)) = waters.iter()
+            .map(|(water, water_transform)| (water, water.submerged(water_transform.translation(), position, radius)))
+            .find(|(_, submerged)| *submerged > 0.0) else {
+            continue;
+        };
+        // with a density of 1 the volume is also the mass the collider gives the body
+        let volume = collider.raw.mass_properties(1.0).mass();
+        let displaced = volume * water.density * submerged;
+        let mut force = -gravity * displaced;
+        let mut torque = Vec3::ZERO;
+        if let Some(velocity) = velocity {
+            force -= velocity.linvel * water.linear_drag * volume * submerged;
+            torque -= velocity.angvel * water.angular_drag * volume * submerged;
+        }
+        let (push, spin) = (force * delta, torque * delta);
+        match impulse {
+            Some(mut impulse) => {
+                impulse.impulse += push;
+                impulse.torque_impulse += spin;
+            }
+            None => {
+                commands.entity(entity).insert(ExternalImpulse { impulse: push, torque_impulse: spin });
+            }
+        }
+    }
+}
+
+/// Rewrites the [MoveIntent]s [free_controls] sent this frame for a [Swimming] entity, sending one
+/// just to sink it when it wasn't moved
+fn swim<T: Component>(
+    active: Res<ActiveControl<T>>,
+    mut intents: ResMut<Events<MoveIntent>>,
+    mut reader: Local<ManualEventReader<MoveIntent>>,
+    swimming: Query<&Swimming, (With<T>, Without<FreeControlSuspended>)>
+) {
+    let sent = reader.iter(&intents).copied().collect::<Vec<_>>();
+    let swimmer = active.entity.and_then(|entity| swimming.get(entity).ok().map(|swimming| (entity, swimming)));
+    let Some((entity, swimming)) = swimmer else {
+        return;
+    };
+    let movement = sent.iter()
+        .filter(|intent| intent.entity == entity)
+        .map(|intent| intent.direction * intent.magnitude)
+        .sum::<Vec3>();
+    let movement = movement * swimming.speed + Vec3::NEG_Y * swimming.sink;
+
+    // the intents of earlier frames have been applied already, everything applying them runs after
+    // this
+    intents.clear();
+    for intent in sent.into_iter().filter(|intent| intent.entity != entity) {
+        intents.send(intent);
+    }
+    if movement != Vec3::ZERO {
+        intents.send(MoveIntent {
+            entity,
+            direction: movement.normalize(),
+            magnitude: movement.length()
+        });
+    }
+    // so the rewritten intents aren't read as sent next frame
+    for _ in reader.iter(&intents) {}
+}
+
+fn update_swimming<T: Component>(
+    mut commands: Commands,
+    config: Res<WaterConfig>,
+    active: Res<ActiveControl<T>>,
+    waters: Query<(&Water, &GlobalTransform)>,
+    controlled: Query<(Entity, &GlobalTransform, Option<&Swimming>), With<T>>
+) {
+    for (entity, transform, swimming) in &controlled {
+        let inside = active.entity == Some(entity) && waters.iter()
+            .any(|(water, water_transform)| water.contains(water_transform.translation(), transform.translation()));
+        match (inside, swimming) {
+            (true, None) => {
+                commands.entity(entity).insert(Swimming {
+                    speed: config.swim_speed,
+                    sink: config.swim_sink
+                });
+            }
+            (false, Some(_)) => {
+                commands.entity(entity).remove::<Swimming>();
+            }
+            _ => {}
+        }
+    }
+}
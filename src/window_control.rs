@@ -0,0 +1,461 @@
+use std::time::{Duration, Instant};
+use bevy::app::{App, Plugin};
+use bevy::ecs::event::Events;
+use bevy::input::Input;
+use bevy::log::info;
+use bevy::prelude::{EventReader, EventWriter, IntoSystemDescriptor, NonSend, Res, ResMut, Resource, World};
+use bevy::window::{MonitorSelection, PresentMode, WindowMode, WindowResized, WindowScaleFactorChanged, Windows};
+use bevy::winit::WinitWindows;
+use winit::monitor::MonitorHandle;
+use serde::{Deserialize, Serialize};
+use crate::console::{console_print, AddConsoleCommand};
+use crate::frame_limit::{FrameLimit, PresentModeSetting};
+use crate::keybind::{KeyBindingPlugin, RawInput};
+use crate::settings::AddSetting;
+
+/// Bindable controls for the primary window. This plugin can be initialized in two ways:
+///
+/// * No default bindings [WindowControlPlugin::new]
+/// * F11 toggles fullscreen, F6 cycles resolutions, F7 toggles vsync and F8 moves the window to
+///  the next monitor [WindowControlPlugin::default]
+///
+/// Vsync is the present mode of [FrameLimit], so toggling it does nothing without the
+/// [FrameLimitPlugin](crate::frame_limit::FrameLimitPlugin).
+///
+/// The resolution, decorations, resizability and scale factor of the window are kept in
+/// [WindowSettings], stored in the `window` section of the settings file (see
+/// [SettingsPlugin](crate::settings::SettingsPlugin)). The `window_size`, `window_decorations`,
+/// `window_resizable` and `window_scale` console commands change them.
+///
+/// Fullscreen goes to the monitor in [WindowSettings::fullscreen_monitor], either borderless or
+/// exclusive at [WindowSettings::video_mode], set with the `fullscreen_monitor` and
+/// `fullscreen_mode` commands (`monitors` and `video_modes` list what's available).
+///
+/// Every change sends a [WindowModeChanged] event, switching modes can drop the cursor grab on
+/// some platforms, so [CursorGrabPlugin](crate::cursor_grab::CursorGrabPlugin) uses it to grab
+/// the cursor again. Changes to the size of the window send it once the window is resized, so the
+/// cursor is centered in the new size, or after [RESIZE_TIMEOUT] for changes that turn out to
+/// leave the size as it was.
+pub struct WindowControlPlugin {
+    key_bindings: KeyBindingPlugin<WindowControls>
+}
+
+impl WindowControlPlugin {
+    /// Creates a new `WindowControlPlugin`, without any default bindings
+    pub fn new() -> Self {
+        Self {
+            key_bindings: KeyBindingPlugin::default()
+        }
+    }
+
+    pub fn bind(mut self, input: impl Into<RawInput>, bind: WindowControls) -> Self {
+        self.key_bindings = self.key_bindings.bind(input, bind);
+        self
+    }
+}
+
+impl Default for WindowControlPlugin {
+    fn default() -> Self {
+        use bevy::prelude::KeyCode::*;
+
+        Self::new()
+            .bind(F11, WindowControls::ToggleFullscreen)
+            .bind(F6, WindowControls::CycleResolution)
+            .bind(F7, WindowControls::ToggleVsync)
+            .bind(F8, WindowControls::NextMonitor)
+    }
+}
+
+impl Plugin for WindowControlPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<WindowControlConfig>() {
+            app.insert_resource(WindowControlConfig::default());
+        }
+        add_window_mode_changed(app);
+        app
+            .add_plugin(self.key_bindings.clone())
+            .add_setting::<WindowSettings>("window")
+            .init_resource::<AwaitingResize>()
+            .add_system(window_controls)
+            .add_system(apply_window_settings.after(window_controls))
+            .add_system(recenter_after_resize.after(apply_window_settings))
+            .add_console_command("window_size", "sets the window to the given width and height", |world, args| {
+                let size = match args {
+                    [width, height] => width.parse::<f32>().ok().zip(height.parse::<f32>().ok()),
+                    _ => None
+                };
+                match size {
+                    Some((width, height)) if width > 0.0 && height > 0.0 => {
+                        world.resource_mut::<WindowSettings>().resolution = Some((width, height));
+                    }
+                    _ => console_print(world, "usage: window_size <width> <height>")
+                }
+            })
+            .add_console_command("window_decorations", "toggles the window decorations, or sets them with on/off", |world, args| {
+                let decorations = world.resource::<WindowSettings>().decorations;
+                match parse_toggle(args, decorations) {
+                    Some(decorations) => world.resource_mut::<WindowSettings>().decorations = decorations,
+                    None => console_print(world, "usage: window_decorations [on|off]")
+                }
+            })
+            .add_console_command("window_resizable", "toggles whether the window is resizable, or sets it with on/off", |world, args| {
+                let resizable = world.resource::<WindowSettings>().resizable;
+                match parse_toggle(args, resizable) {
+                    Some(resizable) => world.resource_mut::<WindowSettings>().resizable = resizable,
+                    None => console_print(world, "usage: window_resizable [on|off]")
+                }
+            })
+            .add_console_command("window_scale", "overrides the scale factor, auto goes back to the monitor's", |world, args| {
+                let scale = match args.first().copied() {
+                    Some("auto") => Some(None),
+                    Some(scale) => scale.parse::<f64>().ok().filter(|scale| *scale > 0.0).map(Some),
+                    None => None
+                };
+                match scale {
+                    Some(scale) => world.resource_mut::<WindowSettings>().scale_factor_override = scale,
+                    None => console_print(world, "usage: window_scale <factor|auto>")
+                }
+            })
+            .add_console_command("monitors", "lists the monitors fullscreen can go to", |world, _| {
+                let lines = monitors(world)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, monitor)| {
+                        let size = monitor.size();
+                        format!(
+                            "{}: {} {}x{} at {}x scale",
+                            index,
+                            monitor.name().unwrap_or_default(),
+                            size.width,
+                            size.height,
+                            monitor.scale_factor()
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                for line in lines {
+                    console_print(world, line);
+                }
+            })
+            .add_console_command("video_modes", "lists the video modes of a monitor, by default the fullscreen one", |world, args| {
+                let index = args.first()
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .or(world.resource::<WindowSettings>().fullscreen_monitor)
+                    .unwrap_or(0);
+                let Some(monitor) = monitors(world).into_iter().nth(index) else {
+                    console_print(world, format!("no monitor {}, try `monitors`", index));
+                    return;
+                };
+                let mut modes = monitor
+                    .video_modes()
+                    .map(|mode| (mode.size().width, mode.size().height, mode.refresh_rate_millihertz() / 1000))
+                    .collect::<Vec<_>>();
+                modes.sort_unstable_by(|a, b| b.cmp(a));
+                modes.dedup();
+                for (width, height, refresh_rate) in modes {
+                    console_print(world, format!("{}x{} at {}hz", width, height, refresh_rate));
+                }
+            })
+            .add_console_command("fullscreen_monitor", "sets the monitor fullscreen goes to, or current for the window's", |world, args| {
+                let monitor = match args.first().copied() {
+                    Some("current") => Some(None),
+                    Some(index) => index.parse::<usize>().ok().map(Some),
+                    None => None
+                };
+                match monitor {
+                    Some(monitor) => world.resource_mut::<WindowSettings>().fullscreen_monitor = monitor,
+                    None => console_print(world, "usage: fullscreen_monitor <index|current>")
+                }
+            })
+            .add_console_command("fullscreen_mode", "switches fullscreen to borderless, or exclusive at the given resolution", |world, args| {
+                let video_mode = match args {
+                    ["borderless"] => Some(None),
+                    [width, height] => width.parse::<u32>().ok().zip(height.parse::<u32>().ok()).map(Some),
+                    _ => None
+                };
+                match video_mode {
+                    Some(video_mode) => world.resource_mut::<WindowSettings>().video_mode = video_mode,
+                    None => console_print(world, "usage: fullscreen_mode <borderless|width height>")
+                }
+            });
+    }
+}
+
+/// The monitors available to the primary window, in the order [MonitorSelection::Index] uses
+fn monitors(world: &World) -> Vec<MonitorHandle> {
+    let id = world.resource::<Windows>().primary().id();
+    world
+        .get_non_send_resource::<WinitWindows>()
+        .and_then(|winit_windows| winit_windows.get_window(id))
+        .map(|winit_window| winit_window.available_monitors().collect())
+        .unwrap_or_default()
+}
+
+/// Reads an optional on/off argument, flipping `current` when there is none
+fn parse_toggle(args: &[&str], current: bool) -> Option<bool> {
+    match args.first().copied() {
+        None => Some(!current),
+        Some("on" | "true" | "1") => Some(true),
+        Some("off" | "false" | "0") => Some(false),
+        Some(_) => None
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum WindowControls {
+    /// Switches between windowed and borderless fullscreen
+    ToggleFullscreen,
+    /// Moves on to the next of [WindowControlConfig::resolutions]
+    CycleResolution,
+    ToggleVsync,
+    /// Moves the window to the next monitor, staying fullscreen if it already was
+    NextMonitor
+}
+
+#[derive(Resource)]
+pub struct WindowControlConfig {
+    /// Logical resolutions cycled through by [WindowControls::CycleResolution]
+    pub resolutions: Vec<(f32, f32)>,
+    pub resolution_index: usize,
+    pub monitor_index: usize
+}
+
+impl Default for WindowControlConfig {
+    fn default() -> Self {
+        Self {
+            resolutions: vec![(1280.0, 720.0), (1600.0, 900.0), (1920.0, 1080.0)],
+            resolution_index: 0,
+            monitor_index: 0
+        }
+    }
+}
+
+/// Persisted properties of the primary window, applied whenever changed
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    /// Logical resolution, `None` leaves the window at whatever size it starts with
+    pub resolution: Option<(f32, f32)>,
+    pub decorations: bool,
+    pub resizable: bool,
+    /// Replaces the scale factor of the monitor
+    pub scale_factor_override: Option<f64>,
+    /// Index of the monitor fullscreen goes to, `None` stays on the monitor the window is on
+    pub fullscreen_monitor: Option<usize>,
+    /// Physical resolution of the video mode for exclusive fullscreen, `None` goes borderless
+    pub video_mode: Option<(u32, u32)>
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: None,
+            decorations: true,
+            resizable: true,
+            scale_factor_override: None,
+            fullscreen_monitor: None,
+            video_mode: None
+        }
+    }
+}
+
+/// How long [WindowModeChanged] waits on a resize after a change that might not resize the window
+/// after all, like decorations on platforms keeping the inner size, in real time
+pub const RESIZE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Set while waiting on the window to be resized after a change, along with a mode to switch to
+/// once it is
+#[derive(Resource, Default)]
+struct AwaitingResize {
+    /// When the wait started
+    since: Option<Instant>,
+    then_mode: Option<WindowMode>
+}
+
+impl AwaitingResize {
+    fn start(&mut self) {
+        self.since = Some(Instant::now());
+    }
+
+    fn is_active(&self) -> bool {
+        self.since.is_some()
+    }
+}
+
+/// Sent whenever [WindowControlPlugin] changes the primary window, or the
+/// [FrameLimitPlugin](crate::frame_limit::FrameLimitPlugin) its present mode
+#[derive(Copy, Clone, Debug)]
+pub struct WindowModeChanged {
+    pub mode: WindowMode,
+    pub present_mode: PresentMode
+}
+
+/// Adds the [WindowModeChanged] event, unless it's already there. It's listened for without the
+/// [WindowControlPlugin] as well, and adding it again would clear it before every listener saw it,
+/// since each time it's added it's also updated again every frame
+pub fn add_window_mode_changed(app: &mut App) {
+    if !app.world.contains_resource::<Events<WindowModeChanged>>() {
+        app.add_event::<WindowModeChanged>();
+    }
+}
+
+fn window_controls(
+    binds: Res<Input<WindowControls>>,
+    mut config: ResMut<WindowControlConfig>,
+    mut settings: ResMut<WindowSettings>,
+    mut windows: ResMut<Windows>,
+    winit_windows: NonSend<WinitWindows>,
+    mut awaiting_resize: ResMut<AwaitingResize>,
+    frame_limit: Option<ResMut<FrameLimit>>,
+    mut mode_changed: EventWriter<WindowModeChanged>
+) {
+    // todo needs to handle multiple windows, going to wait until Bevy updates to having Windows as Entities
+    let window = windows.primary_mut();
+    let mut changed = false;
+
+    if binds.just_pressed(WindowControls::ToggleFullscreen) {
+        if window.mode() == WindowMode::Windowed {
+            // fullscreen always takes the monitor the window is on, so it's moved there first
+            if let Some(monitor) = settings.fullscreen_monitor {
+                window.center_window(MonitorSelection::Index(monitor));
+            }
+            match settings.video_mode {
+                // bevy picks the video mode closest to the size of the window, which has to be
+                // resized first
+                Some((width, height)) if (window.physical_width(), window.physical_height()) != (width, height) => {
+                    let scale = window.scale_factor() as f32;
+                    window.set_resolution(width as f32 / scale, height as f32 / scale);
+                    awaiting_resize.start();
+                    awaiting_resize.then_mode = Some(WindowMode::SizedFullscreen);
+                }
+                Some(_) => window.set_mode(WindowMode::SizedFullscreen),
+                None => window.set_mode(WindowMode::BorderlessFullscreen)
+            }
+        } else {
+            window.set_mode(WindowMode::Windowed);
+            if let Some((width, height)) = settings.resolution {
+                window.set_resolution(width, height);
+            }
+        }
+        changed = true;
+    }
+
+    if binds.just_pressed(WindowControls::CycleResolution) && !config.resolutions.is_empty() {
+        config.resolution_index = (config.resolution_index + 1) % config.resolutions.len();
+        // applied by apply_window_settings, so it's persisted like any other change
+        settings.resolution = Some(config.resolutions[config.resolution_index]);
+    }
+
+    if binds.just_pressed(WindowControls::ToggleVsync) {
+        // applied (and persisted) by the frame limit, which sends the change
+        if let Some(mut frame_limit) = frame_limit {
+            frame_limit.present_mode = match frame_limit.present_mode {
+                PresentModeSetting::AutoNoVsync | PresentModeSetting::Immediate => PresentModeSetting::AutoVsync,
+                _ => PresentModeSetting::AutoNoVsync
+            };
+        }
+    }
+
+    if binds.just_pressed(WindowControls::NextMonitor) {
+        let monitors = winit_windows
+            .get_window(window.id())
+            .map(|winit_window| winit_window.available_monitors().count())
+            .unwrap_or(1)
+            .max(1);
+        config.monitor_index = (config.monitor_index + 1) % monitors;
+
+        // borderless fullscreen always takes the monitor the window is on, so it has to leave
+        // fullscreen to move
+        let mode = window.mode();
+        if mode != WindowMode::Windowed {
+            window.set_mode(WindowMode::Windowed);
+        }
+        window.center_window(MonitorSelection::Index(config.monitor_index));
+        if mode != WindowMode::Windowed {
+            window.set_mode(mode);
+        }
+        info!("moved window to monitor {}", config.monitor_index);
+        changed = true;
+    }
+
+    if changed && !awaiting_resize.is_active() {
+        mode_changed.send(WindowModeChanged {
+            mode: window.mode(),
+            present_mode: window.present_mode()
+        });
+    }
+}
+
+fn apply_window_settings(
+    settings: Res<WindowSettings>,
+    mut windows: ResMut<Windows>,
+    mut awaiting_resize: ResMut<AwaitingResize>,
+    mut mode_changed: EventWriter<WindowModeChanged>
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let window = windows.primary_mut();
+    let mut resized = false;
+
+    if let Some((width, height)) = settings.resolution {
+        if window.requested_width() != width || window.requested_height() != height {
+            window.set_resolution(width, height);
+            info!("resolution set to {}x{}", width, height);
+            // it's only requested, the window can already be that size
+            resized |= window.width() != width || window.height() != height;
+        }
+    }
+    if window.decorations() != settings.decorations {
+        window.set_decorations(settings.decorations);
+        resized = true;
+    }
+    let mut changed = false;
+    if window.resizable() != settings.resizable {
+        window.set_resizable(settings.resizable);
+        changed = true;
+    }
+    if window.scale_factor_override() != settings.scale_factor_override {
+        let scale_factor = window.scale_factor();
+        window.set_scale_factor_override(settings.scale_factor_override);
+        info!("scale factor set to {}", window.scale_factor());
+        // an override of the monitor's own scale factor doesn't change anything
+        resized |= window.scale_factor() != scale_factor;
+    }
+
+    if resized {
+        awaiting_resize.start();
+    } else if changed {
+        mode_changed.send(WindowModeChanged {
+            mode: window.mode(),
+            present_mode: window.present_mode()
+        });
+    }
+}
+
+fn recenter_after_resize(
+    mut resized: EventReader<WindowResized>,
+    mut scale_changed: EventReader<WindowScaleFactorChanged>,
+    mut windows: ResMut<Windows>,
+    mut awaiting_resize: ResMut<AwaitingResize>,
+    mut mode_changed: EventWriter<WindowModeChanged>
+) {
+    let resized = resized.iter().count() + scale_changed.iter().count() > 0;
+    let Some(since) = awaiting_resize.since else {
+        return;
+    };
+    if !resized && since.elapsed() < RESIZE_TIMEOUT {
+        return;
+    }
+    let window = windows.primary_mut();
+    if let Some(mode) = awaiting_resize.then_mode.take() {
+        // switching modes resizes the window again, so the cursor waits on that
+        window.set_mode(mode);
+        awaiting_resize.start();
+        return;
+    }
+    awaiting_resize.since = None;
+    mode_changed.send(WindowModeChanged {
+        mode: window.mode(),
+        present_mode: window.present_mode()
+    });
+}